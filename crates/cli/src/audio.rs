@@ -0,0 +1,221 @@
+//! # Audio
+//! エミュレーションスレッドからSDLのオーディオコールバックへサンプルを渡すロックフリーのリングバッファと、
+//! バッファの残量から出力レートを微調整する動的レート制御
+
+use sdl2::audio::AudioCallback;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// 単一プロデューサー/単一コンシューマーのリングバッファ
+struct Shared {
+    /// サンプル（f32のビット列）
+    slots: Box<[AtomicU32]>,
+    /// 次に読み出す位置（単調増加、`slots.len()`で剰余を取る）
+    head: AtomicUsize,
+    /// 次に書き込む位置（単調増加）
+    tail: AtomicUsize,
+}
+
+/// リングバッファの書き込み側（エミュレーションスレッド）
+pub struct Producer {
+    shared: Arc<Shared>,
+}
+
+/// リングバッファの読み出し側（オーディオスレッド）
+pub struct Consumer {
+    shared: Arc<Shared>,
+}
+
+/// 容量`capacity`サンプルのリングバッファを作成
+pub fn ring_buffer(capacity: usize) -> (Producer, Consumer) {
+    let shared = Arc::new(Shared {
+        slots: (0..capacity).map(|_| AtomicU32::new(0)).collect(),
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+    });
+    (
+        Producer {
+            shared: shared.clone(),
+        },
+        Consumer { shared },
+    )
+}
+
+impl Shared {
+    fn len(&self) -> usize {
+        let tail = self.tail.load(Ordering::Acquire);
+        let head = self.head.load(Ordering::Acquire);
+        tail.wrapping_sub(head)
+    }
+}
+
+impl Producer {
+    /// バッファに溜まっているサンプル数
+    pub fn len(&self) -> usize {
+        self.shared.len()
+    }
+
+    /// サンプルを追加し、追加できた数を返す（溢れた分は捨てる）
+    pub fn push_slice(&mut self, samples: &[f32]) -> usize {
+        let slots = &self.shared.slots;
+        let tail = self.shared.tail.load(Ordering::Relaxed);
+        let free = slots.len() - self.shared.len();
+        let count = samples.len().min(free);
+        for (i, sample) in samples[..count].iter().enumerate() {
+            slots[(tail + i) % slots.len()].store(sample.to_bits(), Ordering::Relaxed);
+        }
+        self.shared
+            .tail
+            .store(tail.wrapping_add(count), Ordering::Release);
+        count
+    }
+}
+
+impl Consumer {
+    /// `out`を埋められるだけ埋め、読み出した数を返す
+    pub fn pop_into(&mut self, out: &mut [f32]) -> usize {
+        let slots = &self.shared.slots;
+        let head = self.shared.head.load(Ordering::Relaxed);
+        let count = out.len().min(self.shared.len());
+        for (i, sample) in out[..count].iter_mut().enumerate() {
+            *sample = f32::from_bits(slots[(head + i) % slots.len()].load(Ordering::Relaxed));
+        }
+        self.shared
+            .head
+            .store(head.wrapping_add(count), Ordering::Release);
+        count
+    }
+}
+
+/// SDLのオーディオコールバック
+pub struct AudioPlayer {
+    pub consumer: Consumer,
+    /// 最後に出力したサンプル（バッファが枯渇したときに保持してプチノイズを防ぐ）
+    pub last_sample: f32,
+}
+
+impl AudioCallback for AudioPlayer {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        let count = self.consumer.pop_into(out);
+        if count > 0 {
+            self.last_sample = out[count - 1];
+        }
+        out[count..].fill(self.last_sample);
+    }
+}
+
+/// 出力レートの最大調整幅（±0.5%、音程の変化は聞き取れない程度）
+const MAX_RATE_DELTA: f64 = 0.005;
+
+/// バッファの残量を目標値に保つように出力レートの倍率を計算する
+///
+/// 残量が目標より少なければサンプルを多めに、多ければ少なめに生成する。
+pub fn rate_adjustment(buffered: usize, target: usize) -> f64 {
+    let error = (target as f64 - buffered as f64) / target as f64;
+    1.0 + MAX_RATE_DELTA * error.clamp(-1.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pop(consumer: &mut Consumer, count: usize) -> Vec<f32> {
+        let mut out = vec![0.0; count];
+        let popped = consumer.pop_into(&mut out);
+        out.truncate(popped);
+        out
+    }
+
+    #[test]
+    fn test_wraparound() {
+        let (mut producer, mut consumer) = ring_buffer(4);
+        // 読み書きの位置をスロット数の倍数をまたいで何周もさせる
+        for round in 0..10 {
+            let base = round as f32 * 3.0;
+            assert_eq!(producer.push_slice(&[base, base + 1.0, base + 2.0]), 3);
+            assert_eq!(producer.len(), 3);
+            assert_eq!(pop(&mut consumer, 2), vec![base, base + 1.0]);
+            assert_eq!(producer.len(), 1);
+            assert_eq!(pop(&mut consumer, 4), vec![base + 2.0]);
+            assert_eq!(producer.len(), 0);
+        }
+    }
+
+    #[test]
+    fn test_push_into_full_buffer_drops_excess() {
+        let (mut producer, mut consumer) = ring_buffer(4);
+        assert_eq!(producer.push_slice(&[1.0, 2.0, 3.0]), 3);
+        // 空きは1つだけなので、残りは捨てられる
+        assert_eq!(producer.push_slice(&[4.0, 5.0, 6.0]), 1);
+        assert_eq!(producer.len(), 4);
+        assert_eq!(producer.push_slice(&[7.0]), 0);
+        assert_eq!(pop(&mut consumer, 8), vec![1.0, 2.0, 3.0, 4.0]);
+
+        // 一杯の状態で位置が一周しても長さが正しい
+        assert_eq!(producer.push_slice(&[8.0, 9.0, 10.0, 11.0, 12.0]), 4);
+        assert_eq!(producer.len(), 4);
+        assert_eq!(pop(&mut consumer, 1), vec![8.0]);
+        assert_eq!(producer.len(), 3);
+        assert_eq!(producer.push_slice(&[13.0, 14.0]), 1);
+        assert_eq!(pop(&mut consumer, 8), vec![9.0, 10.0, 11.0, 13.0]);
+    }
+
+    #[test]
+    fn test_len_across_wrap() {
+        let (mut producer, mut consumer) = ring_buffer(4);
+        producer.push_slice(&[1.0, 2.0, 3.0]);
+        pop(&mut consumer, 3);
+        // 書き込み位置のスロット(6 % 4 = 2)が読み出し位置のスロット(3)より前になる
+        producer.push_slice(&[4.0, 5.0, 6.0]);
+        assert_eq!(producer.len(), 3);
+        producer.push_slice(&[7.0]);
+        assert_eq!(producer.len(), 4);
+        assert_eq!(pop(&mut consumer, 2), vec![4.0, 5.0]);
+        assert_eq!(producer.len(), 2);
+        assert_eq!(pop(&mut consumer, 8), vec![6.0, 7.0]);
+        assert_eq!(producer.len(), 0);
+    }
+
+    #[test]
+    fn test_underrun_holds_last_sample() {
+        let (mut producer, consumer) = ring_buffer(16);
+        let mut player = AudioPlayer {
+            consumer,
+            last_sample: 0.0,
+        };
+
+        // バッファが空なら初期値のまま
+        let mut out = [1.0; 3];
+        player.callback(&mut out);
+        assert_eq!(out, [0.0; 3]);
+
+        producer.push_slice(&[0.25, 0.5]);
+        let mut out = [0.0; 5];
+        player.callback(&mut out);
+        assert_eq!(out, [0.25, 0.5, 0.5, 0.5, 0.5]);
+
+        // 枯渇したまま次のコールバックが来ても最後のサンプルを保持する
+        let mut out = [0.0; 2];
+        player.callback(&mut out);
+        assert_eq!(out, [0.5, 0.5]);
+        assert_eq!(producer.len(), 0);
+    }
+
+    #[test]
+    fn test_rate_adjustment() {
+        let target = 2048;
+        assert_eq!(rate_adjustment(target, target), 1.0);
+        // 足りなければ速く、多ければ遅く生成する
+        assert!(rate_adjustment(target / 2, target) > 1.0);
+        assert!(rate_adjustment(target * 3 / 2, target) < 1.0);
+        // 空でも溢れていても調整幅に収まる
+        assert_eq!(rate_adjustment(0, target), 1.0 + MAX_RATE_DELTA);
+        assert_eq!(rate_adjustment(target * 100, target), 1.0 - MAX_RATE_DELTA);
+        for buffered in (0..=target * 4).step_by(97) {
+            let ratio = rate_adjustment(buffered, target);
+            assert!((1.0 - MAX_RATE_DELTA..=1.0 + MAX_RATE_DELTA).contains(&ratio));
+        }
+    }
+}
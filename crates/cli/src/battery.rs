@@ -0,0 +1,127 @@
+//! # Battery Save
+//! バッテリーバックアップされたPRG RAMを`<rom>.sav`ファイルに読み書きする
+
+use nes_core::Nes;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// バッテリーRAMを定期的に書き出す間隔
+const BATTERY_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// バッテリーバックアップされたPRG RAMの.savファイル
+pub struct BatterySave {
+    path: PathBuf,
+    /// 最後にファイルへ書き出した内容
+    last_saved: Option<Vec<u8>>,
+    last_flush: Instant,
+}
+
+impl BatterySave {
+    /// `<rom>.sav`があればNESに読み込む
+    pub fn load(rom_path: &Path, nes: &mut Nes) -> Self {
+        let path = rom_path.with_extension("sav");
+        let mut last_saved = None;
+        if nes.battery_ram().is_some() {
+            match std::fs::read(&path) {
+                Ok(data) => match nes.load_battery_ram(&data) {
+                    Ok(()) => {
+                        log::info!("Loaded battery save: {:?}", path);
+                        last_saved = Some(data);
+                    }
+                    Err(e) => log::warn!("Ignoring battery save {:?}: {}", path, e),
+                },
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => log::warn!("Failed to read battery save {:?}: {}", path, e),
+            }
+        }
+        Self {
+            path,
+            last_saved,
+            last_flush: Instant::now(),
+        }
+    }
+
+    /// 前回のフラッシュから一定時間が経過していれば書き出す
+    pub fn flush_periodically(&mut self, nes: &Nes) {
+        if self.last_flush.elapsed() >= BATTERY_FLUSH_INTERVAL {
+            self.flush(nes);
+        }
+    }
+
+    /// 内容が変化していれば.savファイルに書き出す
+    ///
+    /// ムービーは.savを読み込む前の電源投入時の状態から始まるので、記録・再生中は書き出さない。
+    pub fn flush(&mut self, nes: &Nes) {
+        self.last_flush = Instant::now();
+        if nes.movie().is_some() {
+            return;
+        }
+        let Some(ram) = nes.battery_ram() else {
+            return;
+        };
+        if self.last_saved.as_deref() == Some(ram.as_slice()) {
+            return;
+        }
+        // 書き込み途中で終了しても既存のセーブを壊さないよう一時ファイル経由で置き換える
+        let tmp_path = self.path.with_extension("sav.tmp");
+        let result =
+            std::fs::write(&tmp_path, &ram).and_then(|()| std::fs::rename(&tmp_path, &self.path));
+        match result {
+            Ok(()) => {
+                log::debug!("Saved battery RAM: {:?}", self.path);
+                self.last_saved = Some(ram);
+            }
+            Err(e) => log::error!("Failed to write battery save {:?}: {}", self.path, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// $6000に$42を書き込み続けるバッテリー付きのNROM
+    fn battery_rom() -> Vec<u8> {
+        let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 2, 1, 0x02, 0];
+        rom.resize(16 + 32 * 1024 + 8 * 1024, 0);
+        // LDA #$42; STA $6000; JMP $8000
+        rom[16..24].copy_from_slice(&[0xA9, 0x42, 0x8D, 0x00, 0x60, 0x4C, 0x00, 0x80]);
+        // リセットベクタ = $8000
+        rom[16 + 0x7FFD] = 0x80;
+        rom
+    }
+
+    #[test]
+    fn test_movie_does_not_overwrite_save() {
+        let dir = std::env::temp_dir().join(format!("nes_cli_battery_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let rom_path = dir.join("game.nes");
+        let sav_path = dir.join("game.sav");
+        let saved = vec![0x5A; 8192];
+        std::fs::write(&sav_path, &saved).unwrap();
+
+        let mut nes = Nes::new();
+        nes.load_rom(&battery_rom()).unwrap();
+        let mut battery = BatterySave::load(&rom_path, &mut nes);
+        assert_eq!(nes.battery_ram(), Some(saved.clone()));
+
+        // 電源投入から記録するとPRG RAMは.savを読み込む前の状態に戻る
+        nes.record_movie(true).unwrap();
+        nes.step_frame().unwrap();
+        battery.flush(&nes);
+        assert_eq!(std::fs::read(&sav_path).unwrap(), saved);
+
+        let movie = nes.stop_movie().unwrap();
+        nes.play_movie(movie).unwrap();
+        nes.step_frame().unwrap();
+        battery.flush(&nes);
+        assert_eq!(std::fs::read(&sav_path).unwrap(), saved);
+
+        // ムービーが終われば再び書き出す
+        nes.stop_movie();
+        battery.flush(&nes);
+        assert_eq!(std::fs::read(&sav_path).unwrap()[0], 0x42);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -3,15 +3,33 @@
 //! SDL2を使用したNESエミュレータのデスクトップ版フロントエンド
 
 use anyhow::Result;
-use clap::Parser;
-use nes_core::controller::Button;
-use nes_core::Nes;
-use sdl2::audio::{AudioCallback, AudioSpecDesired};
+use clap::{Parser, ValueEnum};
+use nes_core::controller::{Button, Controller};
+use nes_core::gdb::GdbStub;
+use nes_core::input::{ArkanoidPaddle, InputDevice, PowerPad, Zapper};
+use nes_core::monitor::Monitor;
+use nes_core::rewind::RewindConfig;
+use nes_core::{Nes, NesError, Region, StopReason};
+use sdl2::audio::AudioSpecDesired;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
 use sdl2::pixels::PixelFormatEnum;
+use std::io::{BufRead, Write};
+use std::net::{TcpListener, TcpStream};
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+
+mod audio;
+mod battery;
+mod movie_file;
+
+use audio::{AudioPlayer, Producer};
+use battery::BatterySave;
+
+/// オーディオバッファに保つ目標の遅延
+const AUDIO_LATENCY: Duration = Duration::from_millis(50);
 
 /// NESエミュレータ CLI
 #[derive(Parser, Debug)]
@@ -33,49 +51,325 @@ struct Args {
     /// オーディオを無効化
     #[arg(long)]
     no_audio: bool,
+
+    /// 地域（指定しない場合はROMヘッダーから自動で選ぶ）
+    #[arg(long, value_enum)]
+    region: Option<RegionArg>,
+
+    /// オーディオではなく垂直同期でフレームのペースを決める
+    #[arg(long)]
+    vsync: bool,
+
+    /// オーディオの出力サンプルレート (Hz)
+    #[arg(long, value_name = "HZ", default_value_t = nes_core::audio::DEFAULT_SAMPLE_RATE)]
+    sample_rate: u32,
+
+    /// 1スキャンライン8個のスプライト制限を無効化（ちらつきを抑える）
+    #[arg(long)]
+    no_sprite_limit: bool,
+
+    /// プレイヤー1のキー割り当て（例: "a=Z,b=X"、指定しないボタンはデフォルトのまま）
+    #[arg(long, value_name = "KEYMAP")]
+    p1_keys: Option<String>,
+
+    /// プレイヤー2のキー割り当て（デフォルト: 方向=I/K/J/L, A=H, B=G, Select=T, Start=Y）
+    #[arg(long, value_name = "KEYMAP")]
+    p2_keys: Option<String>,
+
+    /// ポート2に接続するデバイス（Zapper/Arkanoidはマウスで操作）
+    #[arg(long, value_enum, default_value = "controller")]
+    port2: Port2Device,
+
+    /// GDBリモートプロトコルのサーバーを起動するTCPポート（GDBが接続するまで待ってから開始する）
+    ///
+    /// ウィンドウのメインループで処理するので`--no-window`とは併用できない。
+    #[arg(long, value_name = "PORT", conflicts_with = "no_window")]
+    gdb: Option<u16>,
+
+    /// 巻き戻し（Backspaceを押している間）に使うメモリの上限 (MiB、0で無効)
+    #[arg(long, value_name = "MIB", default_value = "16")]
+    rewind_mb: usize,
+
+    /// 入力をムービーファイル (.fm2/.bk2) に記録する（電源投入から、終了時に書き出す）
+    #[arg(long, value_name = "MOVIE", conflicts_with = "play")]
+    record: Option<PathBuf>,
+
+    /// 記録をこのセーブステート（モニタの`save`で作成）から始め、ムービーに埋め込む
+    #[arg(long, value_name = "STATE", requires = "record")]
+    record_from_state: Option<PathBuf>,
+
+    /// ムービーファイル (.fm2/.bk2) の入力を再生する
+    #[arg(long, value_name = "MOVIE")]
+    play: Option<PathBuf>,
+
+    /// 標準入力からコマンドを受け付けるモニタ（デバッガ）を有効化（停止状態で始まる）
+    #[arg(long)]
+    monitor: bool,
+
+    /// ウィンドウを開かずにモニタだけで実行する
+    #[arg(long, requires = "monitor")]
+    no_window: bool,
+}
+
+/// ポート2に接続できるデバイス
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Port2Device {
+    Controller,
+    Zapper,
+    Arkanoid,
+    PowerPad,
+}
+
+impl Port2Device {
+    fn create(self) -> Box<dyn InputDevice> {
+        match self {
+            Port2Device::Controller => Box::new(Controller::new()),
+            Port2Device::Zapper => Box::new(Zapper::new()),
+            Port2Device::Arkanoid => Box::new(ArkanoidPaddle::new()),
+            Port2Device::PowerPad => Box::new(PowerPad::new()),
+        }
+    }
+}
+
+/// `--region`で指定できる地域
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum RegionArg {
+    Ntsc,
+    Pal,
+    Dendy,
+}
+
+impl From<RegionArg> for Region {
+    fn from(arg: RegionArg) -> Self {
+        match arg {
+            RegionArg::Ntsc => Region::Ntsc,
+            RegionArg::Pal => Region::Pal,
+            RegionArg::Dendy => Region::Dendy,
+        }
+    }
+}
+
+/// フレームを進めるペースの決め方
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Pacing {
+    /// オーディオバッファの残量が目標値まで減るのを待つ
+    Audio,
+    /// `present`が垂直同期を待つ
+    Vsync,
+    /// タイマーで1フレームの長さごとに進める
+    Timer,
 }
 
-struct AudioPlayer {
-    samples: Arc<Mutex<Vec<f32>>>,
-    position: usize,
+/// Power Padのパネル1-12に対応するキー（3行x4列）
+const POWER_PAD_KEYS: [Keycode; 12] = [
+    Keycode::U,
+    Keycode::I,
+    Keycode::O,
+    Keycode::P,
+    Keycode::J,
+    Keycode::K,
+    Keycode::L,
+    Keycode::SEMICOLON,
+    Keycode::M,
+    Keycode::COMMA,
+    Keycode::PERIOD,
+    Keycode::SLASH,
+];
+
+/// プレイヤーごとのキー割り当て
+struct Keymap {
+    keys: Vec<(Button, Keycode)>,
+}
+
+impl Keymap {
+    /// プレイヤー1のデフォルト（方向キー, A/S/D/F）
+    fn player1() -> Self {
+        Self {
+            keys: vec![
+                (Button::Up, Keycode::Up),
+                (Button::Down, Keycode::Down),
+                (Button::Left, Keycode::Left),
+                (Button::Right, Keycode::Right),
+                (Button::A, Keycode::A),
+                (Button::B, Keycode::S),
+                (Button::Select, Keycode::D),
+                (Button::Start, Keycode::F),
+            ],
+        }
+    }
+
+    /// プレイヤー2のデフォルト（I/K/J/L, H/G/T/Y）
+    fn player2() -> Self {
+        Self {
+            keys: vec![
+                (Button::Up, Keycode::I),
+                (Button::Down, Keycode::K),
+                (Button::Left, Keycode::J),
+                (Button::Right, Keycode::L),
+                (Button::A, Keycode::H),
+                (Button::B, Keycode::G),
+                (Button::Select, Keycode::T),
+                (Button::Start, Keycode::Y),
+            ],
+        }
+    }
+
+    /// "button=Key,..." 形式の指定でデフォルトの割り当てを上書き
+    fn with_overrides(mut self, spec: &str) -> Result<Self> {
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (name, key) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("invalid keymap entry: {:?}", entry))?;
+            let button = match name.trim().to_ascii_lowercase().as_str() {
+                "up" => Button::Up,
+                "down" => Button::Down,
+                "left" => Button::Left,
+                "right" => Button::Right,
+                "a" => Button::A,
+                "b" => Button::B,
+                "select" => Button::Select,
+                "start" => Button::Start,
+                other => anyhow::bail!("unknown button: {:?}", other),
+            };
+            let keycode = Keycode::from_name(key.trim())
+                .ok_or_else(|| anyhow::anyhow!("unknown key: {:?}", key))?;
+            for binding in self.keys.iter_mut().filter(|(b, _)| *b == button) {
+                binding.1 = keycode;
+            }
+        }
+        Ok(self)
+    }
+
+    fn button(&self, keycode: Keycode) -> Option<Button> {
+        self.keys
+            .iter()
+            .find(|(_, k)| *k == keycode)
+            .map(|(button, _)| *button)
+    }
+}
+
+/// ローカルのポートでGDBの接続を1つ待つ
+fn accept_gdb(port: u16) -> Result<GdbStub<TcpStream>> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    log::info!(
+        "Waiting for GDB on 127.0.0.1:{} (target remote :{})",
+        port,
+        port
+    );
+    let (stream, peer) = listener.accept()?;
+    stream.set_nonblocking(true)?;
+    stream.set_nodelay(true)?;
+    log::info!("GDB connected from {}", peer);
+    Ok(GdbStub::new(stream))
+}
+
+/// 標準入力を1行ずつ読むスレッドを起動する（入力が終わるとチャネルが閉じる）
+fn spawn_console() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
 }
 
-impl AudioCallback for AudioPlayer {
-    type Channel = f32;
+/// モニタの出力とプロンプトを表示する
+fn print_monitor(output: &str) {
+    if !output.is_empty() {
+        println!("{}", output);
+    }
+    print!("(nes) ");
+    let _ = std::io::stdout().flush();
+}
 
-    fn callback(&mut self, out: &mut [f32]) {
-        let mut samples = self.samples.lock().unwrap();
-        for sample in out.iter_mut() {
-            if self.position < samples.len() {
-                *sample = samples[self.position];
-                self.position += 1;
-            } else {
-                *sample = 0.0;
+/// ウィンドウを開かずにモニタのコマンドでエミュレーションを進める
+///
+/// 実行中はフレームのペースを取らずに全速で進める。
+fn run_headless(nes: &mut Nes, battery_save: &mut BatterySave) -> Result<()> {
+    let console = spawn_console();
+    let mut monitor = Monitor::new();
+    print_monitor("Monitor ready (type `help` for commands, `c` to run)");
+
+    while !monitor.is_quit() {
+        let line = if monitor.is_paused() {
+            match console.recv() {
+                Ok(line) => Some(line),
+                Err(_) => break,
+            }
+        } else {
+            match console.try_recv() {
+                Ok(line) => Some(line),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => break,
             }
+        };
+        if let Some(line) = line {
+            print_monitor(&monitor.execute(nes, &line));
+            continue;
         }
-        // Drain consumed samples
-        if self.position >= samples.len() {
-            samples.clear();
-            self.position = 0;
-        } else if self.position > 0 {
-            samples.drain(0..self.position);
-            self.position = 0;
+
+        let result = nes.step_frame();
+        if let Some(output) = monitor.notify(nes, &result) {
+            print_monitor(&output);
         }
+        battery_save.flush_periodically(nes);
     }
+
+    battery_save.flush(nes);
+    log::info!("Emulation stopped");
+    Ok(())
 }
 
-fn keycode_to_button(keycode: Keycode) -> Option<Button> {
-    match keycode {
-        Keycode::Up => Some(Button::Up),
-        Keycode::Down => Some(Button::Down),
-        Keycode::Left => Some(Button::Left),
-        Keycode::Right => Some(Button::Right),
-        Keycode::A => Some(Button::A),
-        Keycode::S => Some(Button::B),
-        Keycode::D => Some(Button::Select),
-        Keycode::F => Some(Button::Start),
-        _ => None,
+/// `--play`/`--record`のムービーを開始する
+fn start_movie(nes: &mut Nes, args: &Args) -> Result<()> {
+    if let Some(path) = &args.play {
+        let movie = movie_file::load(path)?;
+        log::info!("Playing movie {:?} ({} frames)", path, movie.frames.len());
+        nes.play_movie(movie)?;
+    }
+    if let Some(path) = &args.record {
+        if let Some(state) = &args.record_from_state {
+            nes.load_state(&std::fs::read(state)?)?;
+        }
+        nes.record_movie(args.record_from_state.is_none())?;
+        log::info!("Recording movie to {:?}", path);
     }
+    Ok(())
+}
+
+/// 記録したムービーを書き出し、再生したムービーのデシンクを報告する
+fn finish_movie(nes: &mut Nes, args: &Args) -> Result<()> {
+    let Some(session) = nes.movie() else {
+        return Ok(());
+    };
+    if args.play.is_some() {
+        let desyncs = session.desyncs();
+        match desyncs.first() {
+            None => log::info!("Movie played {} frames without desync", session.frame()),
+            Some(first) => log::warn!(
+                "Movie desynced at {} checkpoint(s), first at frame {}",
+                desyncs.len(),
+                first.frame
+            ),
+        }
+    }
+    if let Some(path) = &args.record {
+        let rom_name = args
+            .rom_path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default();
+        let rom_name = rom_name.to_string();
+        if let Some(movie) = nes.stop_movie() {
+            movie_file::save(path, &movie, &rom_name)?;
+            log::info!("Saved movie {:?} ({} frames)", path, movie.frames.len());
+        }
+    }
+    Ok(())
 }
 
 fn main() -> Result<()> {
@@ -87,36 +381,98 @@ fn main() -> Result<()> {
     log::info!("Loaded ROM: {:?}", args.rom_path);
 
     // NESの初期化
-    let mut nes = Nes::new();
+    let mut nes = Nes::with_sample_rate(args.sample_rate);
     nes.load_rom(&rom_data)?;
+    if let Some(region) = args.region {
+        nes.set_region(region.into());
+    }
+    if args.no_sprite_limit {
+        nes.set_sprite_limit(false);
+    }
+    let mut battery_save = BatterySave::load(&args.rom_path, &mut nes);
+    if args.rewind_mb > 0 {
+        nes.enable_rewind(RewindConfig {
+            memory_budget: args.rewind_mb * 1024 * 1024,
+            ..RewindConfig::default()
+        });
+    }
+
+    // キー割り当て（インデックス = プレイヤー番号）
+    let mut keymaps = [Keymap::player1(), Keymap::player2()];
+    if let Some(spec) = &args.p1_keys {
+        keymaps[0] = Keymap::player1().with_overrides(spec)?;
+    }
+    if let Some(spec) = &args.p2_keys {
+        keymaps[1] = Keymap::player2().with_overrides(spec)?;
+    }
+    if args.port2 != Port2Device::Controller {
+        nes.connect_input(1, args.port2.create());
+        log::info!("Port 2: {:?}", args.port2);
+    }
+
+    // ムービーの再生や記録の開始ステートの読み込みは地域を切り替えるので、フレームの長さはその後で決める
+    start_movie(&mut nes, &args)?;
+    if nes.movie().is_some() && nes.battery_ram().is_some() {
+        log::info!("Battery save is not written while a movie is active");
+    }
+    log::info!("Region: {:?}", nes.region());
+    // 地域ごとのフレームの長さ（NTSC: 約60.0988 FPS、PAL/Dendy: 約50.007 FPS）
+    let frame_duration = Duration::from_secs_f64(1.0 / nes.region().frame_rate());
+
+    if args.no_window {
+        run_headless(&mut nes, &mut battery_save)?;
+        return finish_movie(&mut nes, &args);
+    }
 
     // SDL2の初期化
     let sdl_context = sdl2::init().map_err(|e| anyhow::anyhow!(e))?;
     let video_subsystem = sdl_context.video().map_err(|e| anyhow::anyhow!(e))?;
 
     // オーディオ初期化
-    let audio_samples: Arc<Mutex<Vec<f32>>> = Arc::new(Mutex::new(Vec::new()));
+    let mut audio_out: Option<(Producer, usize)> = None;
     let _audio_device = if !args.no_audio {
         let audio_subsystem = sdl_context.audio().map_err(|e| anyhow::anyhow!(e))?;
         let desired_spec = AudioSpecDesired {
-            freq: Some(44100),
+            freq: Some(args.sample_rate as i32),
             channels: Some(1),
             samples: Some(1024),
         };
+        let mut producer = None;
         let device = audio_subsystem
-            .open_playback(None, &desired_spec, |_spec| AudioPlayer {
-                samples: audio_samples.clone(),
-                position: 0,
+            .open_playback(None, &desired_spec, |spec| {
+                // 目標の遅延とコールバック1回分の余裕を持たせた容量
+                let target = (spec.freq as f64 * AUDIO_LATENCY.as_secs_f64()) as usize;
+                let (p, consumer) = audio::ring_buffer(target * 2 + spec.samples as usize);
+                producer = Some((p, target));
+                AudioPlayer {
+                    consumer,
+                    last_sample: 0.0,
+                }
             })
             .map_err(|e| anyhow::anyhow!(e))?;
+        audio_out = producer;
+        // 要求したレートで開けなかった場合はデバイスのレートに合わせる
+        let freq = device.spec().freq as u32;
+        if freq != nes.sample_rate() {
+            nes.set_sample_rate(freq);
+        }
         device.resume();
-        log::info!("Audio initialized");
+        log::info!("Audio initialized ({} Hz)", freq);
         Some(device)
     } else {
         log::info!("Audio disabled");
         None
     };
 
+    let pacing = if args.vsync {
+        Pacing::Vsync
+    } else if audio_out.is_some() {
+        Pacing::Audio
+    } else {
+        Pacing::Timer
+    };
+    log::info!("Frame pacing: {:?}", pacing);
+
     let window_width = 256 * args.scale;
     let window_height = 240 * args.scale;
 
@@ -125,7 +481,11 @@ fn main() -> Result<()> {
         .position_centered()
         .build()?;
 
-    let mut canvas = window.into_canvas().build()?;
+    let mut canvas = if pacing == Pacing::Vsync {
+        window.into_canvas().present_vsync().build()?
+    } else {
+        window.into_canvas().build()?
+    };
     let texture_creator = canvas.texture_creator();
 
     let mut texture = texture_creator
@@ -134,7 +494,19 @@ fn main() -> Result<()> {
 
     let mut event_pump = sdl_context.event_pump().map_err(|e| anyhow::anyhow!(e))?;
 
+    let mut gdb = args.gdb.map(accept_gdb).transpose()?;
+
+    let console = args.monitor.then(spawn_console);
+    let mut monitor = Monitor::new();
+    if console.is_some() {
+        print_monitor("Monitor ready (type `help` for commands, `c` to run)");
+    }
+
     log::info!("Starting emulation...");
+    let mut next_frame = Instant::now();
+    let mut rewinding = false;
+    // KIL/JAM命令でCPUが止まった（巻き戻すまでフレームを進めない）
+    let mut jammed = false;
 
     'running: loop {
         // イベント処理
@@ -145,6 +517,14 @@ fn main() -> Result<()> {
                     keycode: Some(Keycode::Escape),
                     ..
                 } => break 'running,
+                Event::KeyDown {
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                } => rewinding = true,
+                Event::KeyUp {
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                } => rewinding = false,
                 Event::KeyDown {
                     keycode: Some(keycode),
                     ..
@@ -152,28 +532,120 @@ fn main() -> Result<()> {
                     if args.debug {
                         log::debug!("Key pressed: {:?}", keycode);
                     }
-                    if let Some(button) = keycode_to_button(keycode) {
-                        nes.cpu_state_mut().bus.controller.press(button);
+                    for (player, keymap) in keymaps.iter().enumerate() {
+                        if let Some(button) = keymap.button(keycode) {
+                            nes.press_button(player, button);
+                        }
+                    }
+                    if let Some(panel) = POWER_PAD_KEYS.iter().position(|&k| k == keycode) {
+                        nes.set_switch(1, panel, true);
                     }
                 }
                 Event::KeyUp {
                     keycode: Some(keycode),
                     ..
                 } => {
-                    if let Some(button) = keycode_to_button(keycode) {
-                        nes.cpu_state_mut().bus.controller.release(button);
+                    for (player, keymap) in keymaps.iter().enumerate() {
+                        if let Some(button) = keymap.button(keycode) {
+                            nes.release_button(player, button);
+                        }
                     }
+                    if let Some(panel) = POWER_PAD_KEYS.iter().position(|&k| k == keycode) {
+                        nes.set_switch(1, panel, false);
+                    }
+                }
+                // マウスはポート2のZapper/Arkanoidパドルを操作する
+                Event::MouseMotion { x, y, .. } => {
+                    nes.set_pointer(1, x / args.scale as i32, y / args.scale as i32);
                 }
+                Event::MouseButtonDown {
+                    mouse_btn: MouseButton::Left,
+                    ..
+                } => nes.set_trigger(1, true),
+                Event::MouseButtonUp {
+                    mouse_btn: MouseButton::Left,
+                    ..
+                } => nes.set_trigger(1, false),
                 _ => {}
             }
         }
 
-        // 1フレーム実行
-        match nes.step_frame() {
-            Ok(frame_buffer) => {
+        // GDBのパケットを処理し、停止中はフレームを進めない
+        if let Some(stub) = gdb.as_mut() {
+            if let Err(e) = stub.process(&mut nes) {
+                log::error!("GDB connection error: {}", e);
+                gdb = None;
+            } else if stub.is_detached() {
+                log::info!("GDB detached");
+                gdb = None;
+            } else if stub.is_halted() {
+                std::thread::sleep(Duration::from_millis(1));
+                continue;
+            }
+        }
+
+        // モニタのコマンドを実行し、停止中はフレームを進めない
+        if let Some(console) = &console {
+            let mut executed = false;
+            while let Ok(line) = console.try_recv() {
+                print_monitor(&monitor.execute(&mut nes, &line));
+                executed = true;
+            }
+            if monitor.is_quit() {
+                break 'running;
+            }
+            if monitor.is_paused() {
+                // ステップ実行の結果を画面に反映する
+                if executed {
+                    texture
+                        .update(None, nes.frame_buffer(), 256 * 4)
+                        .map_err(|e| anyhow::anyhow!(e))?;
+                    canvas.clear();
+                    canvas
+                        .copy(&texture, None, None)
+                        .map_err(|e| anyhow::anyhow!(e))?;
+                    canvas.present();
+                }
+                std::thread::sleep(Duration::from_millis(1));
+                continue;
+            }
+        }
+
+        // CPUが止まっている間は巻き戻し以外では進めない
+        if jammed && !rewinding {
+            std::thread::sleep(frame_duration);
+            continue;
+        }
+
+        // 1フレーム実行（巻き戻し中は1フレーム戻す）
+        let result = if rewinding {
+            match nes.rewind(1) {
+                Ok(0) => Ok(StopReason::FrameComplete),
+                result => {
+                    // 巻き戻せればCPUは止まる前の状態に戻る
+                    jammed &= result.is_err();
+                    result.map(|_| StopReason::FrameComplete)
+                }
+            }
+        } else {
+            nes.step_frame()
+        };
+        if let Some(stub) = gdb.as_mut() {
+            if let Err(e) = stub.notify(&result) {
+                log::error!("GDB connection error: {}", e);
+                gdb = None;
+            }
+        }
+        if console.is_some() {
+            if let Some(output) = monitor.notify(&nes, &result) {
+                print_monitor(&output);
+            }
+        }
+        match result {
+            Ok(_) => {
                 // フレームバッファをテクスチャに転送
                 texture
-                    .update(None, frame_buffer, 256 * 4)
+                    .update(None, nes.frame_buffer(), 256 * 4)
                     .map_err(|e| anyhow::anyhow!(e))?;
 
                 canvas.clear();
@@ -182,14 +654,22 @@ fn main() -> Result<()> {
                     .map_err(|e| anyhow::anyhow!(e))?;
                 canvas.present();
 
-                // オーディオサンプルを取得してバッファに追加
-                if !args.no_audio {
-                    let samples = nes.get_audio_samples();
-                    if !samples.is_empty() {
-                        let mut audio_buffer = audio_samples.lock().unwrap();
-                        audio_buffer.extend(samples);
-                    }
+                // オーディオサンプルをリングバッファに追加し、残量に応じて出力レートを微調整
+                if let Some((producer, target)) = audio_out.as_mut() {
+                    producer.push_slice(&nes.get_audio_samples());
+                    nes.set_audio_rate_adjustment(audio::rate_adjustment(producer.len(), *target));
+                }
+            }
+            // 止まったCPUは以降のフレームでも同じエラーを返すので、一度だけ報告して停止する
+            Err(NesError::InvalidInstruction(opcode)) => {
+                log::error!(
+                    "CPU jammed on opcode 0x{:02X}; emulation halted (hold Backspace to rewind)",
+                    opcode
+                );
+                if args.debug {
+                    break 'running;
                 }
+                jammed = true;
             }
             Err(e) => {
                 log::error!("Emulation error: {}", e);
@@ -199,10 +679,36 @@ fn main() -> Result<()> {
             }
         }
 
-        // フレームレート制限（約60 FPS）
-        std::thread::sleep(std::time::Duration::from_millis(16));
+        battery_save.flush_periodically(&nes);
+
+        // フレームレート制限
+        match pacing {
+            Pacing::Vsync => {}
+            // 巻き戻しはオーディオサンプルを生成しないので、バッファの残量ではなくタイマーで待つ
+            Pacing::Audio if rewinding => std::thread::sleep(frame_duration),
+            Pacing::Audio => {
+                if let Some((producer, target)) = &audio_out {
+                    // デバイスが止まっていても固まらないよう、最大2フレーム分だけ待つ
+                    let deadline = Instant::now() + frame_duration * 2;
+                    while producer.len() > *target && Instant::now() < deadline {
+                        std::thread::sleep(Duration::from_millis(1));
+                    }
+                }
+            }
+            Pacing::Timer => {
+                next_frame += frame_duration;
+                let now = Instant::now();
+                if next_frame > now {
+                    std::thread::sleep(next_frame - now);
+                } else if now - next_frame > frame_duration * 4 {
+                    // 大きく遅れた場合は追いつこうとせずにやり直す
+                    next_frame = now;
+                }
+            }
+        }
     }
 
+    battery_save.flush(&nes);
     log::info!("Emulation stopped");
-    Ok(())
+    finish_movie(&mut nes, &args)
 }
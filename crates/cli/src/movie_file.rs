@@ -0,0 +1,68 @@
+//! # Movie File
+//! ムービーファイルの読み書き（拡張子で形式を選ぶ）
+//!
+//! `.fm2`はテキストをそのまま、`.bk2`はzipに格納したファイル群として扱う。
+
+use anyhow::{bail, Context, Result};
+use nes_core::movie::Movie;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+/// ムービーの形式
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Fm2,
+    Bk2,
+}
+
+impl Format {
+    fn of(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default();
+        match extension.to_ascii_lowercase().as_str() {
+            "fm2" => Ok(Format::Fm2),
+            "bk2" => Ok(Format::Bk2),
+            _ => bail!("unknown movie format {:?} (expected .fm2 or .bk2)", path),
+        }
+    }
+}
+
+/// ムービーファイルを読み込む
+pub fn load(path: &Path) -> Result<Movie> {
+    let data = std::fs::read(path).with_context(|| format!("failed to read {:?}", path))?;
+    let movie = match Format::of(path)? {
+        Format::Fm2 => Movie::from_fm2(&String::from_utf8_lossy(&data))?,
+        Format::Bk2 => {
+            let mut archive = ZipArchive::new(Cursor::new(data))?;
+            let mut files = Vec::new();
+            for i in 0..archive.len() {
+                let mut file = archive.by_index(i)?;
+                let mut contents = Vec::new();
+                file.read_to_end(&mut contents)?;
+                files.push((file.name().to_string(), contents));
+            }
+            Movie::from_bk2(&files)?
+        }
+    };
+    Ok(movie)
+}
+
+/// ムービーファイルに書き出す（`rom_name`はヘッダーに記録するROMの名前）
+pub fn save(path: &Path, movie: &Movie, rom_name: &str) -> Result<()> {
+    let data = match Format::of(path)? {
+        Format::Fm2 => movie.to_fm2(rom_name).into_bytes(),
+        Format::Bk2 => {
+            let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+            for (name, contents) in movie.to_bk2(rom_name) {
+                zip.start_file(name, SimpleFileOptions::default())?;
+                zip.write_all(&contents)?;
+            }
+            zip.finish()?.into_inner()
+        }
+    };
+    std::fs::write(path, data).with_context(|| format!("failed to write {:?}", path))
+}
@@ -5,6 +5,11 @@
 //! - 1 Noise channel
 //! - 1 DMC channel
 
+use crate::audio::{self, BlipBuffer, Filter};
+use crate::region::Region;
+use crate::state::{StateReader, StateWriter};
+use crate::Result;
+
 const PULSE_DUTY_TABLE: [[u8; 8]; 4] = [
     [0, 1, 0, 0, 0, 0, 0, 0], // 12.5%
     [0, 1, 1, 0, 0, 0, 0, 0], // 25%
@@ -26,10 +31,61 @@ const NOISE_PERIOD_TABLE: [u16; 16] = [
     4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
 ];
 
+const NOISE_PERIOD_TABLE_PAL: [u16; 16] = [
+    4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778,
+];
+
 const DMC_RATE_TABLE: [u16; 16] = [
     428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
 ];
 
+const DMC_RATE_TABLE_PAL: [u16; 16] = [
+    398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50,
+];
+
+/// フレームシーケンサーのステップ位置（リセットからのCPUサイクル数）
+///
+/// 参考: https://www.nesdev.org/wiki/APU_Frame_Counter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FrameTiming {
+    step1: u32,
+    step2: u32,
+    step3: u32,
+    /// 4ステップモードのIRQが立ち始めるサイクル（ここから3サイクル連続でセットされる）
+    irq_4step: u32,
+    step4: u32,
+    period_4step: u32,
+    step5: u32,
+    period_5step: u32,
+}
+
+/// NTSC/Dendyのフレームシーケンサー
+const FRAME_TIMING_NTSC: FrameTiming = FrameTiming {
+    step1: 7457,
+    step2: 14913,
+    step3: 22371,
+    irq_4step: 29828,
+    step4: 29829,
+    period_4step: 29830,
+    step5: 37281,
+    period_5step: 37282,
+};
+
+/// PALのフレームシーケンサー
+const FRAME_TIMING_PAL: FrameTiming = FrameTiming {
+    step1: 8313,
+    step2: 16627,
+    step3: 24939,
+    irq_4step: 33252,
+    step4: 33253,
+    period_4step: 33254,
+    step5: 41565,
+    period_5step: 41566,
+};
+
+/// リサンプラーから出力サンプルを取り出す間隔（CPUサイクル）
+const BLIP_FLUSH_CLOCKS: u32 = 8192;
+
 pub struct Apu {
     // Pulse channels
     pulse1: PulseChannel,
@@ -47,8 +103,17 @@ pub struct Apu {
     // Audio output
     pub sample_buffer: Vec<f32>,
     cycles: u64,
-    samples_per_frame: usize,
-    cycle_rate: f32,
+    region: Region,
+    sample_rate: u32,
+    blip: BlipBuffer,
+    filters: [Filter; 3],
+    /// 前回`blip`に加えたミキサー出力
+    last_output: f32,
+    /// 前回`blip`を確定させてからのCPUサイクル数
+    blip_clock: u32,
+    /// 非線形ミキサーのルックアップテーブル
+    pulse_table: [f32; 31],
+    tnd_table: [f32; 203],
 }
 
 #[derive(Default)]
@@ -122,20 +187,46 @@ struct DmcChannel {
     silence: bool,
 }
 
-#[derive(Default)]
+/// フレームシーケンサーの1サイクルで発生するイベント
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct FrameEvent {
+    /// エンベロープと三角波の線形カウンタ
+    quarter: bool,
+    /// 長さカウンタとスイープ
+    half: bool,
+}
+
 struct FrameCounter {
     mode: bool, // false = 4-step, true = 5-step
     irq_inhibit: bool,
     irq_pending: bool,
-    step: u8,
-    divider: u16,
+    /// 最後にシーケンサーがリセットされてからのCPUサイクル数
+    cycle: u32,
+    /// $4017書き込みが反映されるまでの残りCPUサイクル数（0なら保留なし）
+    write_delay: u8,
+    /// 反映待ちの$4017の値
+    pending_value: u8,
+    timing: FrameTiming,
 }
 
 impl Apu {
-    pub fn new() -> Self {
-        const SAMPLE_RATE: f32 = 44100.0;
-        const CPU_FREQUENCY: f32 = 1789773.0;
-        let samples_per_frame = (SAMPLE_RATE / 60.0) as usize;
+    /// 出力サンプルレート`sample_rate` (Hz) のAPUを作成
+    pub fn new(sample_rate: u32) -> Self {
+        // https://www.nesdev.org/wiki/APU_Mixer#Lookup_Table
+        let pulse_table = std::array::from_fn(|n| {
+            if n == 0 {
+                0.0
+            } else {
+                95.52 / (8128.0 / n as f32 + 100.0)
+            }
+        });
+        let tnd_table = std::array::from_fn(|n| {
+            if n == 0 {
+                0.0
+            } else {
+                163.67 / (24329.0 / n as f32 + 100.0)
+            }
+        });
 
         Self {
             pulse1: PulseChannel::new(false),
@@ -145,56 +236,123 @@ impl Apu {
             dmc: DmcChannel::new(),
             frame_counter: FrameCounter::new(),
             status: 0,
-            sample_buffer: Vec::with_capacity(samples_per_frame),
+            sample_buffer: Vec::new(),
             cycles: 0,
-            samples_per_frame,
-            cycle_rate: CPU_FREQUENCY / SAMPLE_RATE,
+            region: Region::Ntsc,
+            sample_rate,
+            blip: BlipBuffer::new(Region::Ntsc.cpu_clock_rate(), sample_rate),
+            filters: audio::nes_filter_chain(sample_rate),
+            last_output: 0.0,
+            blip_clock: 0,
+            pulse_table,
+            tnd_table,
         }
     }
 
+    /// 出力サンプルレート (Hz)
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// 地域（CPUクロック、ノイズ/DMCの周期表、フレームシーケンサー）を設定
+    pub fn set_region(&mut self, region: Region) {
+        self.flush_samples();
+        self.region = region;
+        self.blip
+            .set_rates(region.cpu_clock_rate(), self.sample_rate as f64);
+        self.frame_counter.timing = match region {
+            Region::Pal => FRAME_TIMING_PAL,
+            Region::Ntsc | Region::Dendy => FRAME_TIMING_NTSC,
+        };
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    /// 出力サンプルレートをわずかに伸縮させる（動的レート制御用、1.0で等倍）
+    ///
+    /// フロントエンドがオーディオバッファの残量に応じて呼び出し、遅延を一定に保つ。
+    pub fn set_rate_adjustment(&mut self, factor: f64) {
+        self.flush_samples();
+        self.blip.set_rates(
+            self.region.cpu_clock_rate(),
+            self.sample_rate as f64 * factor,
+        );
+    }
+
+    /// 出力サンプルレートを変更（未取得のサンプルは破棄される）
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.blip = BlipBuffer::new(self.region.cpu_clock_rate(), sample_rate);
+        self.filters = audio::nes_filter_chain(sample_rate);
+        self.last_output = 0.0;
+        self.blip_clock = 0;
+        self.sample_buffer.clear();
+    }
+
     pub fn tick(&mut self) {
         self.cycles += 1;
 
         // Clock triangle timer every CPU cycle
         self.triangle.clock_timer();
 
+        // DMC rate table is in CPU cycles
+        self.dmc.clock_timer();
+
         // Clock other timers every other CPU cycle
-        if self.cycles % 2 == 0 {
+        if self.cycles.is_multiple_of(2) {
             self.pulse1.clock_timer();
             self.pulse2.clock_timer();
             self.noise.clock_timer();
-            self.dmc.clock_timer();
         }
 
-        // Frame counter (approximately 240Hz)
-        self.frame_counter.divider += 1;
-        if self.frame_counter.divider >= 7457 {
-            self.frame_counter.divider = 0;
-            self.clock_frame_counter();
-        }
+        let event = self.frame_counter.tick();
+        self.apply_frame_event(event);
 
-        // Generate sample
-        let sample_cycle = (self.cycles as f32 / self.cycle_rate) as usize;
-        if sample_cycle > self.sample_buffer.len() && self.sample_buffer.len() < self.samples_per_frame {
-            let sample = self.mix_output();
-            self.sample_buffer.push(sample);
+        // 振幅が変化したら帯域制限ステップとして合成
+        let output = self.mix_output();
+        if output != self.last_output {
+            self.blip
+                .add_delta(self.blip_clock, output - self.last_output);
+            self.last_output = output;
+        }
+        self.blip_clock += 1;
+        if self.blip_clock >= BLIP_FLUSH_CLOCKS {
+            self.flush_samples();
         }
     }
 
-    fn clock_frame_counter(&mut self) {
-        let step = self.frame_counter.step;
-        self.frame_counter.step = (step + 1) % if self.frame_counter.mode { 5 } else { 4 };
+    /// 確定した時間分のサンプルを読み出し、フィルタを通して`sample_buffer`に追加
+    fn flush_samples(&mut self) {
+        self.blip.end_frame(self.blip_clock);
+        self.blip_clock = 0;
+
+        let start = self.sample_buffer.len();
+        self.blip.read_samples(&mut self.sample_buffer);
+        for sample in &mut self.sample_buffer[start..] {
+            *sample = self
+                .filters
+                .iter_mut()
+                .fold(*sample, |s, filter| filter.process(s));
+        }
 
-        // Quarter frame (envelope and linear counter)
-        self.pulse1.clock_envelope();
-        self.pulse2.clock_envelope();
-        self.triangle.clock_linear_counter();
-        self.noise.clock_envelope();
+        // 取り出されないまま溜まったサンプルは古いものから捨てる
+        let max = self.sample_rate as usize;
+        if self.sample_buffer.len() > max {
+            let excess = self.sample_buffer.len() - max;
+            self.sample_buffer.drain(..excess);
+        }
+    }
 
-        // Half frame (length counter and sweep)
-        if (!self.frame_counter.mode && (step == 1 || step == 3))
-            || (self.frame_counter.mode && (step == 1 || step == 4))
-        {
+    fn apply_frame_event(&mut self, event: FrameEvent) {
+        if event.quarter {
+            self.pulse1.clock_envelope();
+            self.pulse2.clock_envelope();
+            self.triangle.clock_linear_counter();
+            self.noise.clock_envelope();
+        }
+        if event.half {
             self.pulse1.clock_length();
             self.pulse2.clock_length();
             self.triangle.clock_length();
@@ -203,41 +361,33 @@ impl Apu {
             self.pulse1.clock_sweep();
             self.pulse2.clock_sweep();
         }
-
-        // IRQ
-        if !self.frame_counter.mode && step == 3 && !self.frame_counter.irq_inhibit {
-            self.frame_counter.irq_pending = true;
-        }
     }
 
     fn mix_output(&self) -> f32 {
         let pulse1 = if self.pulse1.enabled && self.pulse1.length_counter > 0 {
-            self.pulse1.output() as f32
+            self.pulse1.output() as usize
         } else {
-            0.0
+            0
         };
         let pulse2 = if self.pulse2.enabled && self.pulse2.length_counter > 0 {
-            self.pulse2.output() as f32
+            self.pulse2.output() as usize
         } else {
-            0.0
+            0
         };
         let triangle = if self.triangle.enabled && self.triangle.length_counter > 0 {
-            self.triangle.output() as f32
+            self.triangle.output() as usize
         } else {
-            0.0
+            0
         };
         let noise = if self.noise.enabled && self.noise.length_counter > 0 {
-            self.noise.output() as f32
+            self.noise.output() as usize
         } else {
-            0.0
+            0
         };
-        let dmc = self.dmc.output_level as f32;
-
-        // Non-linear mixing approximation
-        let pulse_out = 0.00752 * (pulse1 + pulse2);
-        let tnd_out = 0.00851 * triangle + 0.00494 * noise + 0.00335 * dmc;
+        let dmc = self.dmc.output_level as usize;
 
-        pulse_out + tnd_out
+        // Non-linear mixing (lookup table)
+        self.pulse_table[pulse1 + pulse2] + self.tnd_table[3 * triangle + 2 * noise + dmc]
     }
 
     pub fn read_register(&mut self, addr: u16) -> u8 {
@@ -354,7 +504,12 @@ impl Apu {
             }
             0x400E => {
                 self.noise.mode = (value & 0x80) != 0;
-                self.noise.timer_period = NOISE_PERIOD_TABLE[(value & 0x0F) as usize];
+                let table = if self.region == Region::Pal {
+                    &NOISE_PERIOD_TABLE_PAL
+                } else {
+                    &NOISE_PERIOD_TABLE
+                };
+                self.noise.timer_period = table[(value & 0x0F) as usize];
             }
             0x400F => {
                 if self.noise.enabled {
@@ -366,7 +521,12 @@ impl Apu {
             0x4010 => {
                 self.dmc.irq_enabled = (value & 0x80) != 0;
                 self.dmc.loop_flag = (value & 0x40) != 0;
-                self.dmc.rate = DMC_RATE_TABLE[(value & 0x0F) as usize];
+                let table = if self.region == Region::Pal {
+                    &DMC_RATE_TABLE_PAL
+                } else {
+                    &DMC_RATE_TABLE
+                };
+                self.dmc.rate = table[(value & 0x0F) as usize];
                 if !self.dmc.irq_enabled {
                     self.dmc.irq_pending = false;
                 }
@@ -411,41 +571,86 @@ impl Apu {
             }
             // Frame counter
             0x4017 => {
-                self.frame_counter.mode = (value & 0x80) != 0;
-                self.frame_counter.irq_inhibit = (value & 0x40) != 0;
-                if self.frame_counter.irq_inhibit {
-                    self.frame_counter.irq_pending = false;
-                }
-                self.frame_counter.step = 0;
-                self.frame_counter.divider = 0;
-                // Immediately clock if 5-step mode
-                if self.frame_counter.mode {
-                    self.clock_frame_counter();
-                }
+                // APUサイクルの途中で書き込むと反映が1サイクル遅れる
+                let odd_cycle = !self.cycles.is_multiple_of(2);
+                self.frame_counter.write(value, odd_cycle);
             }
             _ => {}
         }
     }
 
     pub fn get_samples(&mut self) -> Vec<f32> {
-        let samples = std::mem::take(&mut self.sample_buffer);
-        self.sample_buffer = Vec::with_capacity(self.samples_per_frame);
-        samples
+        self.flush_samples();
+        std::mem::take(&mut self.sample_buffer)
     }
 
     pub fn reset(&mut self) {
         self.sample_buffer.clear();
         self.cycles = 0;
+        self.clear_output();
+    }
+
+    /// リサンプラーとフィルタの状態を初期化
+    fn clear_output(&mut self) {
+        self.blip.clear();
+        self.filters = audio::nes_filter_chain(self.sample_rate);
+        self.last_output = 0.0;
+        self.blip_clock = 0;
     }
 
     pub fn irq_pending(&self) -> bool {
         self.frame_counter.irq_pending || self.dmc.irq_pending
     }
+
+    /// DMCがサンプルバッファを満たすために読み込みたいアドレス
+    ///
+    /// `Some`の場合、Busがそのアドレスを読んで`dmc_fill_sample`に渡し、CPUをストールさせる。
+    pub fn dmc_fetch_address(&self) -> Option<u16> {
+        let dmc = &self.dmc;
+        (dmc.sample_buffer.is_none() && dmc.bytes_remaining > 0).then_some(dmc.current_address)
+    }
+
+    /// Busが読み込んだサンプルバイトをDMCのサンプルバッファに格納
+    pub fn dmc_fill_sample(&mut self, value: u8) {
+        self.dmc.fill_sample(value);
+    }
+
+    /// DMCの出力レベル (0-127)
+    pub fn dmc_output_level(&self) -> u8 {
+        self.dmc.output_level
+    }
+
+    /// 状態を保存（出力済みサンプルは含めない）
+    pub fn save_state(&self, w: &mut StateWriter) {
+        self.pulse1.save_state(w);
+        self.pulse2.save_state(w);
+        self.triangle.save_state(w);
+        self.noise.save_state(w);
+        self.dmc.save_state(w);
+        self.frame_counter.save_state(w);
+        w.write_u8(self.status);
+        w.write_u64(self.cycles);
+    }
+
+    /// 状態を復元
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.pulse1.load_state(r)?;
+        self.pulse2.load_state(r)?;
+        self.triangle.load_state(r)?;
+        self.noise.load_state(r)?;
+        self.dmc.load_state(r)?;
+        self.frame_counter.load_state(r)?;
+        self.status = r.read_u8()?;
+        self.cycles = r.read_u64()?;
+        self.sample_buffer.clear();
+        self.clear_output();
+        Ok(())
+    }
 }
 
 impl Default for Apu {
     fn default() -> Self {
-        Self::new()
+        Self::new(audio::DEFAULT_SAMPLE_RATE)
     }
 }
 
@@ -533,6 +738,49 @@ impl PulseChannel {
             self.envelope_decay
         }
     }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.enabled);
+        w.write_u8(self.duty);
+        w.write_u8(self.duty_position);
+        w.write_u8(self.length_counter);
+        w.write_bool(self.length_halt);
+        w.write_bool(self.constant_volume);
+        w.write_u8(self.volume);
+        w.write_bool(self.envelope_start);
+        w.write_u8(self.envelope_divider);
+        w.write_u8(self.envelope_decay);
+        w.write_bool(self.sweep_enabled);
+        w.write_u8(self.sweep_period);
+        w.write_bool(self.sweep_negate);
+        w.write_u8(self.sweep_shift);
+        w.write_bool(self.sweep_reload);
+        w.write_u8(self.sweep_divider);
+        w.write_u16(self.timer);
+        w.write_u16(self.timer_period);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.enabled = r.read_bool()?;
+        self.duty = r.read_u8()?;
+        self.duty_position = r.read_u8()?;
+        self.length_counter = r.read_u8()?;
+        self.length_halt = r.read_bool()?;
+        self.constant_volume = r.read_bool()?;
+        self.volume = r.read_u8()?;
+        self.envelope_start = r.read_bool()?;
+        self.envelope_divider = r.read_u8()?;
+        self.envelope_decay = r.read_u8()?;
+        self.sweep_enabled = r.read_bool()?;
+        self.sweep_period = r.read_u8()?;
+        self.sweep_negate = r.read_bool()?;
+        self.sweep_shift = r.read_u8()?;
+        self.sweep_reload = r.read_bool()?;
+        self.sweep_divider = r.read_u8()?;
+        self.timer = r.read_u16()?;
+        self.timer_period = r.read_u16()?;
+        Ok(())
+    }
 }
 
 impl TriangleChannel {
@@ -574,6 +822,31 @@ impl TriangleChannel {
         }
         TRIANGLE_SEQUENCE[self.sequence_position as usize]
     }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.enabled);
+        w.write_u8(self.length_counter);
+        w.write_bool(self.length_halt);
+        w.write_u8(self.linear_counter);
+        w.write_u8(self.linear_counter_reload);
+        w.write_bool(self.linear_counter_reload_flag);
+        w.write_u16(self.timer);
+        w.write_u16(self.timer_period);
+        w.write_u8(self.sequence_position);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.enabled = r.read_bool()?;
+        self.length_counter = r.read_u8()?;
+        self.length_halt = r.read_bool()?;
+        self.linear_counter = r.read_u8()?;
+        self.linear_counter_reload = r.read_u8()?;
+        self.linear_counter_reload_flag = r.read_bool()?;
+        self.timer = r.read_u16()?;
+        self.timer_period = r.read_u16()?;
+        self.sequence_position = r.read_u8()?;
+        Ok(())
+    }
 }
 
 impl NoiseChannel {
@@ -631,6 +904,37 @@ impl NoiseChannel {
             self.envelope_decay
         }
     }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.enabled);
+        w.write_u8(self.length_counter);
+        w.write_bool(self.length_halt);
+        w.write_bool(self.constant_volume);
+        w.write_u8(self.volume);
+        w.write_bool(self.envelope_start);
+        w.write_u8(self.envelope_divider);
+        w.write_u8(self.envelope_decay);
+        w.write_bool(self.mode);
+        w.write_u16(self.timer);
+        w.write_u16(self.timer_period);
+        w.write_u16(self.shift_register);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.enabled = r.read_bool()?;
+        self.length_counter = r.read_u8()?;
+        self.length_halt = r.read_bool()?;
+        self.constant_volume = r.read_bool()?;
+        self.volume = r.read_u8()?;
+        self.envelope_start = r.read_bool()?;
+        self.envelope_divider = r.read_u8()?;
+        self.envelope_decay = r.read_u8()?;
+        self.mode = r.read_bool()?;
+        self.timer = r.read_u16()?;
+        self.timer_period = r.read_u16()?;
+        self.shift_register = r.read_u16()?;
+        Ok(())
+    }
 }
 
 impl DmcChannel {
@@ -643,7 +947,7 @@ impl DmcChannel {
 
     fn clock_timer(&mut self) {
         if self.timer == 0 {
-            self.timer = self.rate;
+            self.timer = self.rate.saturating_sub(1);
             if !self.silence {
                 if (self.shift_register & 1) != 0 {
                     if self.output_level <= 125 {
@@ -668,10 +972,237 @@ impl DmcChannel {
             self.timer -= 1;
         }
     }
+
+    /// メモリから読み込んだサンプルバイトを格納し、アドレスと残りバイト数を進める
+    fn fill_sample(&mut self, value: u8) {
+        self.sample_buffer = Some(value);
+        // $FFFFの次は$8000に戻る
+        self.current_address = if self.current_address == 0xFFFF {
+            0x8000
+        } else {
+            self.current_address + 1
+        };
+        self.bytes_remaining = self.bytes_remaining.saturating_sub(1);
+        if self.bytes_remaining == 0 {
+            if self.loop_flag {
+                self.current_address = self.sample_address;
+                self.bytes_remaining = self.sample_length;
+            } else if self.irq_enabled {
+                self.irq_pending = true;
+            }
+        }
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.enabled);
+        w.write_bool(self.irq_enabled);
+        w.write_bool(self.irq_pending);
+        w.write_bool(self.loop_flag);
+        w.write_u16(self.rate);
+        w.write_u16(self.timer);
+        w.write_u8(self.output_level);
+        w.write_u16(self.sample_address);
+        w.write_u16(self.sample_length);
+        w.write_u16(self.current_address);
+        w.write_u16(self.bytes_remaining);
+        w.write_bool(self.sample_buffer.is_some());
+        w.write_u8(self.sample_buffer.unwrap_or(0));
+        w.write_u8(self.shift_register);
+        w.write_u8(self.bits_remaining);
+        w.write_bool(self.silence);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.enabled = r.read_bool()?;
+        self.irq_enabled = r.read_bool()?;
+        self.irq_pending = r.read_bool()?;
+        self.loop_flag = r.read_bool()?;
+        self.rate = r.read_u16()?;
+        self.timer = r.read_u16()?;
+        self.output_level = r.read_u8()?;
+        self.sample_address = r.read_u16()?;
+        self.sample_length = r.read_u16()?;
+        self.current_address = r.read_u16()?;
+        self.bytes_remaining = r.read_u16()?;
+        let has_sample = r.read_bool()?;
+        let sample = r.read_u8()?;
+        self.sample_buffer = has_sample.then_some(sample);
+        self.shift_register = r.read_u8()?;
+        self.bits_remaining = r.read_u8()?;
+        self.silence = r.read_bool()?;
+        Ok(())
+    }
 }
 
 impl FrameCounter {
     fn new() -> Self {
-        Self::default()
+        Self {
+            mode: false,
+            irq_inhibit: false,
+            irq_pending: false,
+            cycle: 0,
+            write_delay: 0,
+            pending_value: 0,
+            timing: FRAME_TIMING_NTSC,
+        }
+    }
+
+    /// $4017への書き込み
+    ///
+    /// IRQ禁止フラグはすぐに反映し、モードの切り替えとシーケンサーのリセットは
+    /// 3サイクル後（APUサイクルの途中なら4サイクル後）に反映する。
+    fn write(&mut self, value: u8, odd_cycle: bool) {
+        self.irq_inhibit = (value & 0x40) != 0;
+        if self.irq_inhibit {
+            self.irq_pending = false;
+        }
+        self.pending_value = value;
+        self.write_delay = if odd_cycle { 4 } else { 3 };
+    }
+
+    /// 1 CPUサイクル進め、このサイクルで発生するイベントを返す
+    fn tick(&mut self) -> FrameEvent {
+        if self.write_delay > 0 {
+            self.write_delay -= 1;
+            if self.write_delay == 0 {
+                self.mode = (self.pending_value & 0x80) != 0;
+                self.cycle = 0;
+                // 5ステップモードへの切り替えは即座にクォーター/ハーフフレームを発生させる
+                return FrameEvent {
+                    quarter: self.mode,
+                    half: self.mode,
+                };
+            }
+        }
+
+        self.cycle += 1;
+        let t = self.timing;
+        let quarter = self.cycle == t.step1 || self.cycle == t.step3;
+        let (half, period) = if self.mode {
+            (
+                self.cycle == t.step2 || self.cycle == t.step5,
+                t.period_5step,
+            )
+        } else {
+            if (t.irq_4step..=t.period_4step).contains(&self.cycle) && !self.irq_inhibit {
+                self.irq_pending = true;
+            }
+            (
+                self.cycle == t.step2 || self.cycle == t.step4,
+                t.period_4step,
+            )
+        };
+        if self.cycle == period {
+            self.cycle = 0;
+        }
+        FrameEvent {
+            quarter: quarter || half,
+            half,
+        }
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.mode);
+        w.write_bool(self.irq_inhibit);
+        w.write_bool(self.irq_pending);
+        w.write_u32(self.cycle);
+        w.write_u8(self.write_delay);
+        w.write_u8(self.pending_value);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.mode = r.read_bool()?;
+        self.irq_inhibit = r.read_bool()?;
+        self.irq_pending = r.read_bool()?;
+        self.cycle = r.read_u32()?;
+        self.write_delay = r.read_u8()?;
+        self.pending_value = r.read_u8()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(apu: &mut Apu, cycles: u32) {
+        for _ in 0..cycles {
+            apu.tick();
+        }
+    }
+
+    #[test]
+    fn test_frame_irq_timing() {
+        let mut apu = Apu::default();
+        apu.write_register(0x4017, 0x00);
+        // 偶数サイクルでの書き込みは3サイクル後にリセット
+        run(&mut apu, 3 + FRAME_TIMING_NTSC.irq_4step - 1);
+        assert!(!apu.irq_pending());
+        run(&mut apu, 1);
+        assert!(apu.irq_pending());
+
+        // $4015の読み取りでクリアされるが、続く2サイクルで再びセットされる
+        assert_eq!(apu.read_register(0x4015) & 0x40, 0x40);
+        assert_eq!(apu.read_register(0x4015) & 0x40, 0);
+        run(&mut apu, 1);
+        assert!(apu.irq_pending());
+        // 次の周期のIRQまではセットされない
+        run(&mut apu, 2);
+        apu.read_register(0x4015);
+        run(&mut apu, FRAME_TIMING_NTSC.irq_4step - 2);
+        assert!(!apu.irq_pending());
+
+        // IRQ禁止フラグは即座にフラグをクリアし、5ステップモードではIRQは発生しない
+        run(&mut apu, 1);
+        assert!(apu.irq_pending());
+        apu.write_register(0x4017, 0x40);
+        assert!(!apu.irq_pending());
+        apu.write_register(0x4017, 0x80);
+        run(&mut apu, 4);
+        apu.read_register(0x4015);
+        run(&mut apu, FRAME_TIMING_NTSC.period_5step * 2);
+        assert!(!apu.irq_pending());
+    }
+
+    #[test]
+    fn test_4017_write_jitter() {
+        for (odd, delay) in [(false, 3), (true, 4)] {
+            let mut apu = Apu::default();
+            if odd {
+                run(&mut apu, 1);
+            }
+            apu.write_register(0x4015, 0x01);
+            apu.write_register(0x4003, 0x18); // 長さカウンタ = 2
+            apu.write_register(0x4017, 0x80);
+            run(&mut apu, delay - 1);
+            assert_eq!(apu.pulse1.length_counter, 2);
+            // 5ステップモードへの切り替えで即座にハーフフレームが発生
+            run(&mut apu, 1);
+            assert_eq!(apu.pulse1.length_counter, 1);
+        }
+    }
+
+    #[test]
+    fn test_length_clock_timing() {
+        for (mode, steps) in [
+            (0x00, [FRAME_TIMING_NTSC.step2, FRAME_TIMING_NTSC.step4]),
+            (0x80, [FRAME_TIMING_NTSC.step2, FRAME_TIMING_NTSC.step5]),
+        ] {
+            let mut apu = Apu::default();
+            apu.write_register(0x4015, 0x01);
+            apu.write_register(0x4017, mode);
+            run(&mut apu, 3);
+            apu.write_register(0x4003, 0x18);
+
+            let mut clocks = Vec::new();
+            for cycle in 1..=FRAME_TIMING_NTSC.step5 {
+                let before = apu.pulse1.length_counter;
+                apu.tick();
+                if apu.pulse1.length_counter != before {
+                    clocks.push(cycle);
+                }
+            }
+            assert_eq!(clocks, steps);
+        }
     }
 }
@@ -0,0 +1,215 @@
+//! # Audio
+//! APUの出力を任意のサンプルレートに変換するための帯域制限リサンプラーとフィルタ
+//!
+//! APUはCPUクロック（NTSCでは1.789773MHz）で振幅が変化する。振幅の変化量（デルタ）を
+//! 帯域制限したステップとして出力サンプル列に合成し（blip buffer方式）、
+//! 積分して波形を得る。その後、実機のアナログ回路と同じ
+//! ハイパス (90Hz, 440Hz) / ローパス (14kHz) フィルタを通す。
+//!
+//! 参考: http://slack.net/~ant/bl-synth/, https://www.nesdev.org/wiki/APU_Mixer
+
+use std::f64::consts::PI;
+
+/// デフォルトの出力サンプルレート (Hz)
+pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+
+/// 1サンプル間の位相分解能
+const PHASES: usize = 32;
+
+/// ステップ1つあたりのカーネル幅（出力サンプル数）
+const KERNEL_WIDTH: usize = 16;
+
+/// カットオフ周波数（ナイキスト周波数に対する比）
+const CUTOFF: f64 = 0.9;
+
+/// 帯域制限ステップを合成するバッファ
+pub struct BlipBuffer {
+    /// CPUクロック1つあたりの出力サンプル数
+    samples_per_clock: f64,
+    /// 位相ごとのインパルス応答（合計は1）
+    kernels: Vec<[f32; KERNEL_WIDTH]>,
+    /// デルタを蓄積するバッファ（先頭が未読の最初のサンプル）
+    deltas: Vec<f32>,
+    /// 積分器の現在値
+    integrator: f32,
+    /// `end_frame`で確定した時間（先頭からの出力サンプル数、小数部を含む）
+    time: f64,
+}
+
+impl BlipBuffer {
+    pub fn new(clock_rate: f64, sample_rate: u32) -> Self {
+        Self {
+            samples_per_clock: sample_rate as f64 / clock_rate,
+            kernels: (0..PHASES).map(Self::kernel).collect(),
+            deltas: vec![0.0; KERNEL_WIDTH],
+            integrator: 0.0,
+            time: 0.0,
+        }
+    }
+
+    /// クロック周波数と出力サンプルレートの比を変更（蓄積済みのデルタは保持する）
+    pub fn set_rates(&mut self, clock_rate: f64, sample_rate: f64) {
+        self.samples_per_clock = sample_rate / clock_rate;
+    }
+
+    /// Blackman窓をかけたsinc関数を位相`phase / PHASES`だけずらしてサンプリング
+    fn kernel(phase: usize) -> [f32; KERNEL_WIDTH] {
+        let offset = phase as f64 / PHASES as f64;
+        let center = (KERNEL_WIDTH / 2) as f64 - 1.0;
+        let mut kernel = [0f64; KERNEL_WIDTH];
+        for (i, k) in kernel.iter_mut().enumerate() {
+            let x = i as f64 - center - offset;
+            let sinc = if x == 0.0 {
+                1.0
+            } else {
+                (PI * CUTOFF * x).sin() / (PI * CUTOFF * x)
+            };
+            let n = (x + center + 1.0) / KERNEL_WIDTH as f64;
+            let window = 0.42 - 0.5 * (2.0 * PI * n).cos() + 0.08 * (4.0 * PI * n).cos();
+            *k = sinc * window;
+        }
+        let sum: f64 = kernel.iter().sum();
+        kernel.map(|k| (k / sum) as f32)
+    }
+
+    /// 前回の`end_frame`から`clock`クロック後の時点に振幅の変化`delta`を加える
+    pub fn add_delta(&mut self, clock: u32, delta: f32) {
+        let position = self.time + clock as f64 * self.samples_per_clock;
+        let index = position as usize;
+        let phase = ((position - index as f64) * PHASES as f64) as usize;
+        if self.deltas.len() < index + KERNEL_WIDTH {
+            self.deltas.resize(index + KERNEL_WIDTH, 0.0);
+        }
+        for (d, k) in self.deltas[index..].iter_mut().zip(&self.kernels[phase]) {
+            *d += k * delta;
+        }
+    }
+
+    /// `clocks`クロック分の時間を確定させ、読み出し可能にする
+    pub fn end_frame(&mut self, clocks: u32) {
+        self.time += clocks as f64 * self.samples_per_clock;
+        let needed = self.time as usize + KERNEL_WIDTH;
+        if self.deltas.len() < needed {
+            self.deltas.resize(needed, 0.0);
+        }
+    }
+
+    /// 読み出し可能なサンプル数
+    pub fn samples_available(&self) -> usize {
+        self.time as usize
+    }
+
+    /// 確定したサンプルをすべて`out`に追加
+    pub fn read_samples(&mut self, out: &mut Vec<f32>) {
+        let count = self.samples_available();
+        for &delta in &self.deltas[..count] {
+            self.integrator += delta;
+            out.push(self.integrator);
+        }
+        self.deltas.drain(..count);
+        self.deltas.resize(self.deltas.len().max(KERNEL_WIDTH), 0.0);
+        self.time -= count as f64;
+    }
+
+    /// バッファを空にする
+    pub fn clear(&mut self) {
+        self.deltas.clear();
+        self.deltas.resize(KERNEL_WIDTH, 0.0);
+        self.integrator = 0.0;
+        self.time = 0.0;
+    }
+}
+
+/// 1次のIIRフィルタ
+#[derive(Debug, Clone, Copy)]
+pub enum Filter {
+    /// ハイパスフィルタ
+    HighPass {
+        alpha: f32,
+        prev_in: f32,
+        prev_out: f32,
+    },
+    /// ローパスフィルタ
+    LowPass { alpha: f32, prev_out: f32 },
+}
+
+impl Filter {
+    pub fn high_pass(sample_rate: u32, cutoff: f32) -> Self {
+        let rc = 1.0 / (2.0 * std::f32::consts::PI * cutoff);
+        let dt = 1.0 / sample_rate as f32;
+        Filter::HighPass {
+            alpha: rc / (rc + dt),
+            prev_in: 0.0,
+            prev_out: 0.0,
+        }
+    }
+
+    pub fn low_pass(sample_rate: u32, cutoff: f32) -> Self {
+        let rc = 1.0 / (2.0 * std::f32::consts::PI * cutoff);
+        let dt = 1.0 / sample_rate as f32;
+        Filter::LowPass {
+            alpha: dt / (rc + dt),
+            prev_out: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        match self {
+            Filter::HighPass {
+                alpha,
+                prev_in,
+                prev_out,
+            } => {
+                *prev_out = *alpha * (*prev_out + input - *prev_in);
+                *prev_in = input;
+                *prev_out
+            }
+            Filter::LowPass { alpha, prev_out } => {
+                *prev_out += *alpha * (input - *prev_out);
+                *prev_out
+            }
+        }
+    }
+}
+
+/// 実機の出力段のフィルタ列 (HP 90Hz -> HP 440Hz -> LP 14kHz)
+pub fn nes_filter_chain(sample_rate: u32) -> [Filter; 3] {
+    [
+        Filter::high_pass(sample_rate, 90.0),
+        Filter::high_pass(sample_rate, 440.0),
+        Filter::low_pass(sample_rate, 14000.0),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CPU_CLOCK_RATE: f64 = 1_789_773.0;
+
+    #[test]
+    fn test_step_settles_to_amplitude() {
+        let mut blip = BlipBuffer::new(CPU_CLOCK_RATE, DEFAULT_SAMPLE_RATE);
+        blip.add_delta(100, 0.5);
+        blip.end_frame(29781);
+        let mut out = Vec::new();
+        blip.read_samples(&mut out);
+        // 1フレーム分のサンプルが得られ、ステップの後は振幅に落ち着く
+        assert_eq!(out.len(), 733);
+        assert!(out[0].abs() < 1e-3);
+        assert!(out[100..].iter().all(|s| (s - 0.5).abs() < 1e-3));
+
+        // 続きのフレームでも値が保たれる
+        blip.end_frame(29781);
+        out.clear();
+        blip.read_samples(&mut out);
+        assert!(out.iter().all(|s| (s - 0.5).abs() < 1e-3));
+    }
+
+    #[test]
+    fn test_high_pass_removes_dc() {
+        let mut filter = Filter::high_pass(DEFAULT_SAMPLE_RATE, 90.0);
+        let last = (0..44100).map(|_| filter.process(1.0)).last().unwrap();
+        assert!(last.abs() < 1e-3);
+    }
+}
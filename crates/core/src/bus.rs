@@ -4,18 +4,75 @@
 use crate::apu::Apu;
 use crate::ppu::Ppu;
 use crate::cartridge::Cartridge;
-use crate::controller::Controller;
+use crate::debugger::{Access, AccessWatch, AddressSpace};
+use crate::input::InputPorts;
+use crate::region::Region;
+use crate::state::{StateReader, StateWriter};
+use crate::{NesError, Result};
 use std::cell::RefCell;
 use std::rc::Rc;
 
+/// DMCのサンプル読み込みでCPUが停止するサイクル数
+const DMC_STALL_CYCLES: usize = 4;
+
+/// 命令の最後にCPUがポーリングする割り込み信号
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct InterruptPoll {
+    /// NMIの立ち上がりエッジを検出済み（処理されるまで保持）
+    pub nmi: bool,
+    /// IRQ線のレベル（APUとマッパーのOR、Iフラグは含まない）
+    pub irq: bool,
+}
+
+/// CPUの単体テスト用のメモリ
+///
+/// バスに取り付けると$0000-$FFFFのすべてをこの64KBのRAMとして読み書きし、
+/// サイクルごとのアクセスを順に記録する（PPUやAPUのレジスタ、カートリッジには届かない）。
+#[derive(Debug, Clone)]
+pub struct FlatMemory {
+    pub data: Vec<u8>,
+    /// (アドレス, 値, `Access::Read`/`Access::Write`)
+    pub accesses: Vec<(u16, u8, Access)>,
+}
+
+impl FlatMemory {
+    pub fn new() -> Self {
+        Self {
+            data: vec![0; 0x10000],
+            accesses: Vec::new(),
+        }
+    }
+}
+
+impl Default for FlatMemory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct Bus {
     pub ram: [u8; 2048],
     pub ppu: Ppu,
     pub apu: Apu,
     pub cartridge: Option<Rc<RefCell<Cartridge>>>,
-    pub controller: Controller,
+    pub input: InputPorts,
     pub cycles: u64,
+    /// デバッガ用のアクセス記録（CPUアドレス空間）
+    pub watch: AccessWatch,
+    /// CPUの単体テスト用のメモリ（`Some`ならメモリマップの代わりに使う）
+    pub flat_memory: Option<FlatMemory>,
     cpu_stall_cycles: usize,
+    region: Region,
+    /// PPUドットの端数（PALではCPU 5サイクルでPPU 16ドット）
+    ppu_dot_phase: u32,
+    /// 前のサイクルのNMI線のレベル（エッジ検出用）
+    nmi_line: bool,
+    /// NMIのエッジを検出し、まだ処理されていない
+    nmi_pending: bool,
+    /// 直前のサイクルの終わりの割り込み信号
+    poll: InterruptPoll,
+    /// 処理したNMIの数（デバッガ用、セーブステートには含めない）
+    nmi_count: u64,
 }
 
 impl Bus {
@@ -23,14 +80,61 @@ impl Bus {
         Bus {
             ram: [0; 2048],
             ppu: Ppu::new(),
-            apu: Apu::new(),
+            apu: Apu::default(),
             cartridge: None,
-            controller: Controller::new(),
+            input: InputPorts::new(),
             cycles: 0,
+            watch: AccessWatch::new(AddressSpace::Cpu),
+            flat_memory: None,
             cpu_stall_cycles: 0,
+            region: Region::Ntsc,
+            ppu_dot_phase: 0,
+            nmi_line: false,
+            nmi_pending: false,
+            poll: InterruptPoll::default(),
+            nmi_count: 0,
         }
     }
 
+    /// 地域を設定し、PPUとAPUのタイミングを切り替える
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.ppu_dot_phase = 0;
+        self.ppu.set_region(region);
+        self.apu.set_region(region);
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    /// IRQ線のレベル（レベルトリガー: 発生源がフラグをクリアするまでアサートされ続ける）
+    pub fn irq_line(&self) -> bool {
+        self.apu.irq_pending()
+            || self
+                .cartridge
+                .as_ref()
+                .is_some_and(|c| c.borrow().irq_pending())
+    }
+
+    /// 直前のサイクルの終わりの割り込み信号
+    ///
+    /// 命令の最後のサイクルの後に呼ぶと、最後から2番目のサイクルの終わりの状態が得られる。
+    pub fn interrupt_poll(&self) -> InterruptPoll {
+        self.poll
+    }
+
+    /// 検出済みのNMIを処理した
+    pub fn acknowledge_nmi(&mut self) {
+        self.nmi_pending = false;
+        self.nmi_count += 1;
+    }
+
+    /// これまでに処理したNMIの数
+    pub fn nmi_count(&self) -> u64 {
+        self.nmi_count
+    }
+
     pub fn reset_cpu_stall_cycles(&mut self) -> usize {
         let c = self.cpu_stall_cycles;
         self.cpu_stall_cycles = 0;
@@ -38,14 +142,19 @@ impl Bus {
     }
 
     fn unclocked_read_byte(&mut self, address: u16) -> u8 {
-        match address {
+        if let Some(flat) = &mut self.flat_memory {
+            let value = flat.data[address as usize];
+            flat.accesses.push((address, value, Access::Read));
+            return value;
+        }
+        let value = match address {
             0x0000..=0x1FFF => self.ram[address as usize % 0x0800],
             0x2000..=0x3FFF => self.ppu.read_register(address),
             0x4000..=0x4013 => 0, // APU write-only registers
             0x4014 => 0,         // OAM DMA
             0x4015 => self.apu.read_register(address),
-            0x4016 => self.controller.read(),
-            0x4017 => 0, // Controller 2 (not implemented)
+            0x4016 => self.input.read(0, &self.ppu),
+            0x4017 => self.input.read(1, &self.ppu),
             0x4018..=0x401F => 0, // APU/IO test registers
             _ => {
                 // 0x4020..=0xFFFF
@@ -55,17 +164,25 @@ impl Bus {
                     (address >> 8) as u8
                 }
             }
-        }
+        };
+        self.watch.record(Access::Read, address, value);
+        value
     }
 
     fn unclocked_write_byte(&mut self, address: u16, value: u8) {
+        if let Some(flat) = &mut self.flat_memory {
+            flat.data[address as usize] = value;
+            flat.accesses.push((address, value, Access::Write));
+            return;
+        }
+        self.watch.record(Access::Write, address, value);
         match address {
             0x0000..=0x1FFF => self.ram[address as usize % 0x0800] = value,
             0x2000..=0x3FFF => self.ppu.write_register(address, value),
             0x4000..=0x4013 => self.apu.write_register(address, value),
             0x4014 => self.oam_dma(value as u16),
             0x4015 => self.apu.write_register(address, value),
-            0x4016 => self.controller.write(value),
+            0x4016 => self.input.write(value),
             0x4017 => self.apu.write_register(address, value),
             0x4018..=0x401F => {} // APU/IO test registers (ignored)
             _ => {
@@ -105,21 +222,98 @@ impl Bus {
     }
 
     pub fn tick(&mut self) {
+        // 前のサイクルの終わりの割り込み信号を取り込む（このサイクルでの変化は次のサイクルまで見えない）
+        let nmi_line = self.ppu.nmi_line();
+        if nmi_line && !self.nmi_line {
+            self.nmi_pending = true;
+        }
+        self.nmi_line = nmi_line;
+        self.poll = InterruptPoll {
+            nmi: self.nmi_pending,
+            irq: self.irq_line(),
+        };
+
         self.cycles += 1;
 
-        // Tick PPU 3 times for every CPU cycle
-        for _ in 0..3 {
+        // Tick PPU 3 times for every CPU cycle (3.2 times on PAL)
+        let (dots, cycles) = self.region.ppu_dots_per_cpu_cycle();
+        self.ppu_dot_phase += dots;
+        while self.ppu_dot_phase >= cycles {
+            self.ppu_dot_phase -= cycles;
             self.ppu.tick();
         }
 
         // Tick APU once per CPU cycle
         self.apu.tick();
+
+        // DMCのサンプル読み込み（CPUは4サイクル停止する）
+        if let Some(address) = self.apu.dmc_fetch_address() {
+            let value = self.unclocked_read_byte(address);
+            self.apu.dmc_fill_sample(value);
+            self.cpu_stall_cycles += DMC_STALL_CYCLES;
+        }
+
+        if let Some(ref c) = self.cartridge {
+            c.borrow_mut().notify_cpu_cycle();
+        }
     }
 
-    pub fn load_rom_from_memory(&mut self, data: &[u8]) {
-        let c = Rc::new(RefCell::new(Cartridge::new(data)));
+    pub fn load_rom_from_memory(&mut self, data: &[u8]) -> Result<()> {
+        let c = Rc::new(RefCell::new(Cartridge::from_bytes(data)?));
         self.ppu.set_cartridge(c.clone());
         self.cartridge = Some(c);
+        Ok(())
+    }
+
+    /// バスと接続された全デバイスの状態を保存
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_u8(match self.region {
+            Region::Ntsc => 0,
+            Region::Pal => 1,
+            Region::Dendy => 2,
+        });
+        w.write_bytes(&self.ram);
+        w.write_u64(self.cycles);
+        w.write_u32(self.cpu_stall_cycles as u32);
+        w.write_u8(self.ppu_dot_phase as u8);
+        w.write_bool(self.nmi_line);
+        w.write_bool(self.nmi_pending);
+        w.write_bool(self.poll.nmi);
+        w.write_bool(self.poll.irq);
+        self.input.save_state(w);
+        self.ppu.save_state(w);
+        self.apu.save_state(w);
+        if let Some(ref c) = self.cartridge {
+            c.borrow().save_state(w);
+        }
+    }
+
+    /// バスと接続された全デバイスの状態を復元
+    ///
+    /// 地域はステートを保存したときのものに切り替わる（スキャンラインやドットの位相がその地域のものなので）。
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        let region = match r.read_u8()? {
+            0 => Region::Ntsc,
+            1 => Region::Pal,
+            2 => Region::Dendy,
+            other => return Err(NesError::InvalidState(format!("unknown region {}", other))),
+        };
+        self.set_region(region);
+        r.read_bytes_into(&mut self.ram)?;
+        self.cycles = r.read_u64()?;
+        self.cpu_stall_cycles = r.read_u32()? as usize;
+        self.ppu_dot_phase = r.read_u8()? as u32;
+        self.nmi_line = r.read_bool()?;
+        self.nmi_pending = r.read_bool()?;
+        self.poll.nmi = r.read_bool()?;
+        self.poll.irq = r.read_bool()?;
+        self.input.load_state(r)?;
+        self.ppu.load_state(r)?;
+        self.apu.load_state(r)?;
+        if let Some(ref c) = self.cartridge {
+            c.borrow_mut().load_state(r)?;
+        }
+        Ok(())
     }
 
     pub fn reset(&mut self) {
@@ -1,770 +1,272 @@
 //! # Cartridge
 //! Based on https://github.com/starrhorne/nes-rust
 
+use crate::mapper::{CartridgeMemory, Mapper, MapperRegistry};
+use crate::rom::RomHeader;
+use crate::state::{self, StateReader, StateWriter};
+use crate::{NesError, Result};
+
 pub struct Cartridge {
-    prg_rom: Vec<u8>,
-    chr_rom: Vec<u8>,
-    prg_ram: Vec<u8>,
-    chr_ram: Vec<u8>,
-    mapper: u8,
-    mirroring: Mirroring,
-    // Mapper 2 (UxROM) state
-    prg_bank: u8,
-    // Mapper 3 (CNROM) state
-    chr_bank: u8,
-    // Mapper 1 (MMC1) state
-    mmc1_shift_register: u8,
-    mmc1_shift_count: u8,
-    mmc1_control: u8,
-    mmc1_chr_bank_0: u8,
-    mmc1_chr_bank_1: u8,
-    mmc1_prg_bank: u8,
-    // MMC3 (Mapper 4) state
-    registers: [u8; 8],
-    register_select: u8,
-    prg_bank_mode: bool,
-    chr_bank_mode: bool,
-    // Mapper 4 IRQ state
-    irq_counter: u8,
-    irq_reload: u8,
-    irq_pending: bool,
-    irq_enabled: bool,
-    irq_reload_flag: bool,
+    header: RomHeader,
+    mapper: Box<dyn Mapper>,
+    // ROMファイル全体のCRC32（セーブステートの照合用）
+    checksum: u32,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Mirroring {
     Horizontal,
     Vertical,
     SingleScreenLower,
     SingleScreenUpper,
+    /// カートリッジ上の追加VRAMで4つのネームテーブルを独立させる
+    FourScreen,
 }
 
-impl Cartridge {
-    pub fn new(data: &[u8]) -> Self {
-        if data.len() < 16 {
-            panic!("ROM too small");
+impl Mirroring {
+    pub(crate) fn from_u8(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(Mirroring::Horizontal),
+            1 => Ok(Mirroring::Vertical),
+            2 => Ok(Mirroring::SingleScreenLower),
+            3 => Ok(Mirroring::SingleScreenUpper),
+            4 => Ok(Mirroring::FourScreen),
+            _ => Err(NesError::InvalidState(format!(
+                "invalid mirroring value: {}",
+                value
+            ))),
         }
+    }
+}
 
-        if &data[0..4] != b"NES\x1A" {
-            panic!("Invalid iNES header");
-        }
+impl Cartridge {
+    /// iNES / NES 2.0 形式のROMイメージからカートリッジを生成
+    ///
+    /// ヘッダーが不正な場合やデータが途中で切れている場合は`NesError::InvalidRom`、
+    /// 未対応のマッパーの場合は`NesError::UnsupportedMapper`を返す。
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        Self::from_bytes_with_registry(data, &MapperRegistry::default())
+    }
 
-        let prg_rom_size = data[4] as usize * 16384;
-        let chr_rom_size = data[5] as usize * 8192;
-        let flags6 = data[6];
-        let flags7 = data[7];
+    /// 指定したマッパーレジストリを使ってカートリッジを生成
+    pub fn from_bytes_with_registry(data: &[u8], registry: &MapperRegistry) -> Result<Self> {
+        let header = RomHeader::parse(data)?;
 
-        let mapper = (flags7 & 0xF0) | (flags6 >> 4);
-        let mirroring = if (flags6 & 0x01) != 0 {
-            Mirroring::Vertical
-        } else {
-            Mirroring::Horizontal
-        };
+        if !registry.is_supported(header.mapper) {
+            return Err(NesError::UnsupportedMapper(header.mapper));
+        }
+        if header.prg_rom_size == 0 {
+            return Err(NesError::InvalidRom("PRG ROM size is zero".to_string()));
+        }
 
-        let has_trainer = (flags6 & 0x04) != 0;
-        let prg_start = 16 + if has_trainer { 512 } else { 0 };
-        let chr_start = prg_start + prg_rom_size;
+        let prg_start = header.prg_rom_offset();
+        let chr_start = header.chr_rom_offset();
+        let chr_end = chr_start.saturating_add(header.chr_rom_size);
+        if data.len() < chr_end {
+            return Err(NesError::InvalidRom(format!(
+                "file truncated: expected at least {} bytes, found {}",
+                chr_end,
+                data.len()
+            )));
+        }
 
-        let prg_rom = data[prg_start..prg_start + prg_rom_size].to_vec();
-        let chr_rom = if chr_rom_size > 0 {
-            data[chr_start..chr_start + chr_rom_size].to_vec()
-        } else {
-            vec![]
-        };
-        let chr_ram = if chr_rom_size == 0 {
-            vec![0; 8192]
+        let chr_rom = data[chr_start..chr_end].to_vec();
+        let chr_ram = if chr_rom.is_empty() {
+            vec![0; header.total_chr_ram_size().max(8192)]
         } else {
-            vec![]
+            vec![0; header.total_chr_ram_size()]
         };
-
-        Cartridge {
-            prg_rom,
+        let memory = CartridgeMemory {
+            prg_rom: data[prg_start..chr_start].to_vec(),
             chr_rom,
-            prg_ram: vec![0; 8192], // 8KB PRG RAM
+            prg_ram: vec![0; header.total_prg_ram_size()],
             chr_ram,
+        };
+
+        let mapper = registry.create(&header, memory)?;
+
+        Ok(Cartridge {
+            header,
             mapper,
-            mirroring,
-            // Mapper 2
-            prg_bank: 0,
-            // Mapper 3
-            chr_bank: 0,
-            // Mapper 1 (MMC1)
-            mmc1_shift_register: 0,
-            mmc1_shift_count: 0,
-            mmc1_control: 0x0C, // PRG ROM mode 3, CHR ROM mode 0
-            mmc1_chr_bank_0: 0,
-            mmc1_chr_bank_1: 0,
-            mmc1_prg_bank: 0,
-            // Mapper 4 (MMC3)
-            registers: [0, 0, 0, 0, 0, 0, 0, 0],
-            register_select: 0,
-            prg_bank_mode: false,
-            chr_bank_mode: false,
-            // Mapper 4 IRQ
-            irq_counter: 0,
-            irq_reload: 0,
-            irq_pending: false,
-            irq_enabled: false,
-            irq_reload_flag: false,
-        }
+            checksum: state::crc32(data),
+        })
+    }
+
+    /// 解析済みのROMヘッダー
+    pub fn header(&self) -> &RomHeader {
+        &self.header
     }
 
     pub fn read_prg_byte(&self, addr: u16) -> u8 {
-        match self.mapper {
-            0 => self.mapper0_read_prg(addr),
-            1 => self.mapper1_read_prg(addr),
-            2 => self.mapper2_read_prg(addr),
-            3 => self.mapper3_read_prg(addr),
-            4 => self.mapper4_read_prg(addr),
-            7 => self.mapper7_read_prg(addr),
-            66 => self.mapper66_read_prg(addr),
-            _ => {
-                log::warn!("Unsupported mapper {}", self.mapper);
-                0
-            }
-        }
+        self.mapper.read_prg(addr)
     }
 
     pub fn write_prg_byte(&mut self, addr: u16, value: u8) {
-        match self.mapper {
-            0 => self.mapper0_write_prg(addr, value),
-            1 => self.mapper1_write_prg(addr, value),
-            2 => self.mapper2_write_prg(addr, value),
-            3 => self.mapper3_write_prg(addr, value),
-            4 => self.mapper4_write_prg(addr, value),
-            7 => self.mapper7_write_prg(addr, value),
-            66 => self.mapper66_write_prg(addr, value),
-            _ => {}
-        }
+        self.mapper.write_prg(addr, value);
     }
 
     pub fn read_chr_byte(&self, addr: u16) -> u8 {
-        match self.mapper {
-            0 => self.mapper0_read_chr(addr),
-            1 => self.mapper1_read_chr(addr),
-            2 => self.mapper2_read_chr(addr),
-            3 => self.mapper3_read_chr(addr),
-            4 => self.mapper4_read_chr(addr),
-            7 => self.mapper7_read_chr(addr),
-            66 => self.mapper66_read_chr(addr),
-            _ => 0,
-        }
+        self.mapper.read_chr(addr)
     }
 
     pub fn write_chr_byte(&mut self, addr: u16, value: u8) {
-        match self.mapper {
-            0 => self.mapper0_write_chr(addr, value),
-            1 => self.mapper1_write_chr(addr, value),
-            2 => self.mapper2_write_chr(addr, value),
-            3 => self.mapper3_write_chr(addr, value),
-            4 => self.mapper4_write_chr(addr, value),
-            7 => self.mapper7_write_chr(addr, value),
-            66 => self.mapper66_write_chr(addr, value),
-            _ => {}
-        }
+        self.mapper.write_chr(addr, value);
     }
 
     pub fn mirroring(&self) -> Mirroring {
-        self.mirroring
+        self.mapper.mirroring()
     }
 
-    // Mapper 0 (NROM)
-    fn mapper0_read_prg(&self, addr: u16) -> u8 {
-        match addr {
-            0x6000..=0x7FFF => {
-                let index = (addr - 0x6000) as usize;
-                self.prg_ram.get(index).copied().unwrap_or(0)
-            }
-            0x8000..=0xFFFF => {
-                let index = if self.prg_rom.len() == 16384 {
-                    (addr & 0x3FFF) as usize
-                } else {
-                    (addr & 0x7FFF) as usize
-                };
-                self.prg_rom.get(index).copied().unwrap_or(0)
-            }
-            _ => 0,
-        }
+    /// PPUのアドレスバスの通知（MMC3のIRQカウンタなど）
+    pub fn notify_ppu_address(&mut self, addr: u16) {
+        self.mapper.notify_ppu_address(addr);
     }
 
-    fn mapper0_write_prg(&mut self, addr: u16, value: u8) {
-        if (0x6000..=0x7FFF).contains(&addr) {
-            let index = (addr - 0x6000) as usize;
-            if index < self.prg_ram.len() {
-                self.prg_ram[index] = value;
-            }
-        }
-    }
-
-    fn mapper0_read_chr(&self, addr: u16) -> u8 {
-        let index = (addr & 0x1FFF) as usize;
-        if !self.chr_rom.is_empty() {
-            self.chr_rom.get(index).copied().unwrap_or(0)
-        } else {
-            self.chr_ram.get(index).copied().unwrap_or(0)
-        }
-    }
-
-    fn mapper0_write_chr(&mut self, addr: u16, value: u8) {
-        let index = (addr & 0x1FFF) as usize;
-        if self.chr_rom.is_empty() && index < self.chr_ram.len() {
-            self.chr_ram[index] = value;
-        }
-    }
-
-    // Mapper 4 (MMC3)
-    fn mapper4_read_prg(&self, addr: u16) -> u8 {
-        match addr {
-            0x6000..=0x7FFF => {
-                let index = (addr - 0x6000) as usize;
-                self.prg_ram.get(index).copied().unwrap_or(0)
-            }
-            0x8000..=0x9FFF => {
-                let bank = if self.prg_bank_mode {
-                    (self.prg_rom.len() / 8192).saturating_sub(2)
-                } else {
-                    self.registers[6] as usize
-                };
-                let offset = (addr - 0x8000) as usize;
-                let index = (bank * 8192 + offset) % self.prg_rom.len().max(1);
-                self.prg_rom.get(index).copied().unwrap_or(0)
-            }
-            0xA000..=0xBFFF => {
-                let bank = self.registers[7] as usize;
-                let offset = (addr - 0xA000) as usize;
-                let index = (bank * 8192 + offset) % self.prg_rom.len().max(1);
-                self.prg_rom.get(index).copied().unwrap_or(0)
-            }
-            0xC000..=0xDFFF => {
-                let bank = if self.prg_bank_mode {
-                    self.registers[6] as usize
-                } else {
-                    (self.prg_rom.len() / 8192).saturating_sub(2)
-                };
-                let offset = (addr - 0xC000) as usize;
-                let index = (bank * 8192 + offset) % self.prg_rom.len().max(1);
-                self.prg_rom.get(index).copied().unwrap_or(0)
-            }
-            0xE000..=0xFFFF => {
-                let bank = (self.prg_rom.len() / 8192).saturating_sub(1);
-                let offset = (addr - 0xE000) as usize;
-                let index = (bank * 8192 + offset) % self.prg_rom.len().max(1);
-                self.prg_rom.get(index).copied().unwrap_or(0)
-            }
-            _ => 0,
-        }
-    }
-
-    fn mapper4_write_prg(&mut self, addr: u16, value: u8) {
-        match addr {
-            0x6000..=0x7FFF => {
-                let index = (addr - 0x6000) as usize;
-                if index < self.prg_ram.len() {
-                    self.prg_ram[index] = value;
-                }
-            }
-            0x8000..=0x9FFF => {
-                if addr & 1 == 0 {
-                    // Bank select ($8000-$9FFE, even)
-                    self.register_select = value & 0x07;
-                    self.prg_bank_mode = (value & 0x40) != 0;
-                    self.chr_bank_mode = (value & 0x80) != 0;
-                } else {
-                    // Bank data ($8001-$9FFF, odd)
-                    let index = self.register_select as usize;
-                    if index < 8 {
-                        self.registers[index] = value;
-                    }
-                }
-            }
-            0xA000..=0xBFFF => {
-                if addr & 1 == 0 {
-                    // Mirroring ($A000-$BFFE, even)
-                    self.mirroring = if (value & 0x01) != 0 {
-                        Mirroring::Horizontal
-                    } else {
-                        Mirroring::Vertical
-                    };
-                }
-                // PRG RAM protect ($A001-$BFFF, odd) - ignored for now
-            }
-            0xC000..=0xDFFF => {
-                if addr & 1 == 0 {
-                    // IRQ latch ($C000-$DFFE, even)
-                    self.irq_reload = value;
-                } else {
-                    // IRQ reload ($C001-$DFFF, odd)
-                    self.irq_reload_flag = true;
-                    self.irq_counter = 0;
-                }
-            }
-            0xE000..=0xFFFF => {
-                if addr & 1 == 0 {
-                    // IRQ disable ($E000-$FFFE, even)
-                    self.irq_enabled = false;
-                    self.irq_pending = false;
-                } else {
-                    // IRQ enable ($E001-$FFFF, odd)
-                    self.irq_enabled = true;
-                }
-            }
-            _ => {}
-        }
-    }
-
-    fn mapper4_read_chr(&self, addr: u16) -> u8 {
-        if self.chr_rom.is_empty() {
-            return self.chr_ram.get((addr & 0x1FFF) as usize).copied().unwrap_or(0);
-        }
-
-        let bank = match addr {
-            0x0000..=0x03FF => {
-                if !self.chr_bank_mode {
-                    self.registers[0] & 0xFE
-                } else {
-                    self.registers[2]
-                }
-            }
-            0x0400..=0x07FF => {
-                if !self.chr_bank_mode {
-                    self.registers[0] | 0x01
-                } else {
-                    self.registers[3]
-                }
-            }
-            0x0800..=0x0BFF => {
-                if !self.chr_bank_mode {
-                    self.registers[1] & 0xFE
-                } else {
-                    self.registers[4]
-                }
-            }
-            0x0C00..=0x0FFF => {
-                if !self.chr_bank_mode {
-                    self.registers[1] | 0x01
-                } else {
-                    self.registers[5]
-                }
-            }
-            0x1000..=0x13FF => {
-                if !self.chr_bank_mode {
-                    self.registers[2]
-                } else {
-                    self.registers[0] & 0xFE
-                }
-            }
-            0x1400..=0x17FF => {
-                if !self.chr_bank_mode {
-                    self.registers[3]
-                } else {
-                    self.registers[0] | 0x01
-                }
-            }
-            0x1800..=0x1BFF => {
-                if !self.chr_bank_mode {
-                    self.registers[4]
-                } else {
-                    self.registers[1] & 0xFE
-                }
-            }
-            0x1C00..=0x1FFF => {
-                if !self.chr_bank_mode {
-                    self.registers[5]
-                } else {
-                    self.registers[1] | 0x01
-                }
-            }
-            _ => 0,
-        } as usize;
-
-        let offset = (addr & 0x03FF) as usize;
-        let index = (bank * 1024 + offset) % self.chr_rom.len().max(1);
-        self.chr_rom.get(index).copied().unwrap_or(0)
-    }
-
-    fn mapper4_write_chr(&mut self, addr: u16, value: u8) {
-        if self.chr_rom.is_empty() {
-            let index = (addr & 0x1FFF) as usize;
-            if index < self.chr_ram.len() {
-                self.chr_ram[index] = value;
-            }
-        }
-    }
-
-    // Mapper 4 IRQ
-    pub fn clock_irq(&mut self) {
-        if self.irq_counter == 0 || self.irq_reload_flag {
-            self.irq_counter = self.irq_reload;
-            self.irq_reload_flag = false;
-        } else {
-            self.irq_counter -= 1;
-        }
-
-        if self.irq_counter == 0 && self.irq_enabled {
-            self.irq_pending = true;
-        }
+    /// CPUサイクルの通知
+    pub fn notify_cpu_cycle(&mut self) {
+        self.mapper.notify_cpu_cycle();
     }
 
     pub fn irq_pending(&self) -> bool {
-        self.irq_pending
-    }
-
-    pub fn acknowledge_irq(&mut self) {
-        self.irq_pending = false;
-    }
-
-    // Mapper 2 (UxROM)
-    fn mapper2_read_prg(&self, addr: u16) -> u8 {
-        match addr {
-            0x6000..=0x7FFF => {
-                let index = (addr - 0x6000) as usize;
-                self.prg_ram.get(index).copied().unwrap_or(0)
-            }
-            0x8000..=0xBFFF => {
-                // Switchable 16KB bank
-                let bank = self.prg_bank as usize;
-                let offset = (addr - 0x8000) as usize;
-                let index = (bank * 16384 + offset) % self.prg_rom.len().max(1);
-                self.prg_rom.get(index).copied().unwrap_or(0)
-            }
-            0xC000..=0xFFFF => {
-                // Fixed to last 16KB bank
-                let last_bank = (self.prg_rom.len() / 16384).saturating_sub(1);
-                let offset = (addr - 0xC000) as usize;
-                let index = (last_bank * 16384 + offset) % self.prg_rom.len().max(1);
-                self.prg_rom.get(index).copied().unwrap_or(0)
-            }
-            _ => 0,
-        }
-    }
-
-    fn mapper2_write_prg(&mut self, addr: u16, value: u8) {
-        match addr {
-            0x6000..=0x7FFF => {
-                let index = (addr - 0x6000) as usize;
-                if index < self.prg_ram.len() {
-                    self.prg_ram[index] = value;
-                }
-            }
-            0x8000..=0xFFFF => {
-                // Bank select
-                self.prg_bank = value & 0x0F;
-            }
-            _ => {}
-        }
-    }
-
-    fn mapper2_read_chr(&self, addr: u16) -> u8 {
-        // CHR RAM only
-        let index = (addr & 0x1FFF) as usize;
-        self.chr_ram.get(index).copied().unwrap_or(0)
-    }
-
-    fn mapper2_write_chr(&mut self, addr: u16, value: u8) {
-        // CHR RAM only
-        let index = (addr & 0x1FFF) as usize;
-        if index < self.chr_ram.len() {
-            self.chr_ram[index] = value;
-        }
-    }
-
-    // Mapper 3 (CNROM)
-    fn mapper3_read_prg(&self, addr: u16) -> u8 {
-        // Same as Mapper 0
-        self.mapper0_read_prg(addr)
-    }
-
-    fn mapper3_write_prg(&mut self, addr: u16, value: u8) {
-        match addr {
-            0x6000..=0x7FFF => {
-                let index = (addr - 0x6000) as usize;
-                if index < self.prg_ram.len() {
-                    self.prg_ram[index] = value;
-                }
-            }
-            0x8000..=0xFFFF => {
-                // CHR bank select
-                self.chr_bank = value & 0x03;
-            }
-            _ => {}
-        }
-    }
-
-    fn mapper3_read_chr(&self, addr: u16) -> u8 {
-        if self.chr_rom.is_empty() {
-            return self.chr_ram.get((addr & 0x1FFF) as usize).copied().unwrap_or(0);
-        }
-        let bank = self.chr_bank as usize;
-        let offset = (addr & 0x1FFF) as usize;
-        let index = (bank * 8192 + offset) % self.chr_rom.len().max(1);
-        self.chr_rom.get(index).copied().unwrap_or(0)
-    }
-
-    fn mapper3_write_chr(&mut self, addr: u16, value: u8) {
-        // CHR ROM, ignore writes (unless CHR RAM)
-        if self.chr_rom.is_empty() {
-            let index = (addr & 0x1FFF) as usize;
-            if index < self.chr_ram.len() {
-                self.chr_ram[index] = value;
-            }
-        }
-    }
-
-    // Mapper 1 (MMC1)
-    fn mapper1_read_prg(&self, addr: u16) -> u8 {
-        match addr {
-            0x6000..=0x7FFF => {
-                let index = (addr - 0x6000) as usize;
-                self.prg_ram.get(index).copied().unwrap_or(0)
-            }
-            0x8000..=0xBFFF => {
-                let prg_mode = (self.mmc1_control >> 2) & 0x03;
-                let bank = match prg_mode {
-                    0 | 1 => {
-                        // 32KB mode: use lower bit of prg_bank
-                        (self.mmc1_prg_bank & 0xFE) as usize
-                    }
-                    2 => {
-                        // Fix first bank at $8000
-                        0
-                    }
-                    3 => {
-                        // Switch bank at $8000
-                        self.mmc1_prg_bank as usize
-                    }
-                    _ => 0,
-                };
-                let offset = (addr - 0x8000) as usize;
-                let index = (bank * 16384 + offset) % self.prg_rom.len().max(1);
-                self.prg_rom.get(index).copied().unwrap_or(0)
-            }
-            0xC000..=0xFFFF => {
-                let prg_mode = (self.mmc1_control >> 2) & 0x03;
-                let bank = match prg_mode {
-                    0 | 1 => {
-                        // 32KB mode: use upper bank
-                        (self.mmc1_prg_bank | 0x01) as usize
-                    }
-                    2 => {
-                        // Switch bank at $C000
-                        self.mmc1_prg_bank as usize
-                    }
-                    3 => {
-                        // Fix last bank at $C000
-                        (self.prg_rom.len() / 16384).saturating_sub(1)
-                    }
-                    _ => 0,
-                };
-                let offset = (addr - 0xC000) as usize;
-                let index = (bank * 16384 + offset) % self.prg_rom.len().max(1);
-                self.prg_rom.get(index).copied().unwrap_or(0)
-            }
-            _ => 0,
-        }
-    }
-
-    fn mapper1_write_prg(&mut self, addr: u16, value: u8) {
-        match addr {
-            0x6000..=0x7FFF => {
-                let index = (addr - 0x6000) as usize;
-                if index < self.prg_ram.len() {
-                    self.prg_ram[index] = value;
-                }
-            }
-            0x8000..=0xFFFF => {
-                if value & 0x80 != 0 {
-                    // Reset shift register
-                    self.mmc1_shift_register = 0;
-                    self.mmc1_shift_count = 0;
-                    self.mmc1_control |= 0x0C;
-                } else {
-                    // Shift in bit 0
-                    self.mmc1_shift_register |= (value & 0x01) << self.mmc1_shift_count;
-                    self.mmc1_shift_count += 1;
-
-                    if self.mmc1_shift_count == 5 {
-                        let register = (addr >> 13) & 0x03;
-                        match register {
-                            0 => {
-                                // Control register
-                                self.mmc1_control = self.mmc1_shift_register;
-                                // Update mirroring
-                                self.mirroring = match self.mmc1_control & 0x03 {
-                                    0 | 1 => Mirroring::Horizontal, // Single screen (simplified)
-                                    2 => Mirroring::Vertical,
-                                    3 => Mirroring::Horizontal,
-                                    _ => Mirroring::Horizontal,
-                                };
-                            }
-                            1 => {
-                                // CHR bank 0
-                                self.mmc1_chr_bank_0 = self.mmc1_shift_register;
-                            }
-                            2 => {
-                                // CHR bank 1
-                                self.mmc1_chr_bank_1 = self.mmc1_shift_register;
-                            }
-                            3 => {
-                                // PRG bank
-                                self.mmc1_prg_bank = self.mmc1_shift_register & 0x0F;
-                            }
-                            _ => {}
-                        }
-                        self.mmc1_shift_register = 0;
-                        self.mmc1_shift_count = 0;
-                    }
-                }
-            }
-            _ => {}
-        }
-    }
-
-    fn mapper1_read_chr(&self, addr: u16) -> u8 {
-        let chr_mode = (self.mmc1_control >> 4) & 0x01;
-        let (bank, offset) = if chr_mode == 0 {
-            // 8KB mode
-            let bank = (self.mmc1_chr_bank_0 & 0x1E) as usize;
-            let offset = (addr & 0x1FFF) as usize;
-            (bank, offset)
+        self.mapper.irq_pending()
+    }
+
+    /// バッテリーバックアップされたPRG RAM（バッテリーがなければ`None`）
+    ///
+    /// PRG RAMは揮発性の部分の後ろに不揮発性の部分が続くので、後者（`prg_nvram_size`）だけを返す。
+    pub fn battery_ram(&self) -> Option<&[u8]> {
+        let start = self.header.prg_ram_size;
+        let nvram = self
+            .mapper
+            .memory()
+            .prg_ram
+            .get(start..start + self.header.prg_nvram_size)?;
+        if self.header.has_battery && !nvram.is_empty() {
+            Some(nvram)
         } else {
-            // 4KB mode
-            if addr < 0x1000 {
-                let bank = self.mmc1_chr_bank_0 as usize;
-                let offset = (addr & 0x0FFF) as usize;
-                (bank, offset)
-            } else {
-                let bank = self.mmc1_chr_bank_1 as usize;
-                let offset = (addr & 0x0FFF) as usize;
-                (bank, offset)
-            }
-        };
-
-        if self.chr_rom.is_empty() {
-            // CHR RAM
-            let index = if chr_mode == 0 {
-                (bank * 8192 + offset) % self.chr_ram.len().max(1)
-            } else {
-                (bank * 4096 + offset) % self.chr_ram.len().max(1)
-            };
-            self.chr_ram.get(index).copied().unwrap_or(0)
-        } else {
-            // CHR ROM
-            let index = if chr_mode == 0 {
-                (bank * 8192 + offset) % self.chr_rom.len().max(1)
-            } else {
-                (bank * 4096 + offset) % self.chr_rom.len().max(1)
-            };
-            self.chr_rom.get(index).copied().unwrap_or(0)
-        }
-    }
-
-    fn mapper1_write_chr(&mut self, addr: u16, value: u8) {
-        if self.chr_rom.is_empty() {
-            let chr_mode = (self.mmc1_control >> 4) & 0x01;
-            let index = if chr_mode == 0 {
-                let bank = (self.mmc1_chr_bank_0 & 0x1E) as usize;
-                let offset = (addr & 0x1FFF) as usize;
-                (bank * 8192 + offset) % self.chr_ram.len().max(1)
-            } else if addr < 0x1000 {
-                let bank = self.mmc1_chr_bank_0 as usize;
-                let offset = (addr & 0x0FFF) as usize;
-                (bank * 4096 + offset) % self.chr_ram.len().max(1)
-            } else {
-                let bank = self.mmc1_chr_bank_1 as usize;
-                let offset = (addr & 0x0FFF) as usize;
-                (bank * 4096 + offset) % self.chr_ram.len().max(1)
-            };
-            if index < self.chr_ram.len() {
-                self.chr_ram[index] = value;
-            }
-        }
-    }
-
-    // Mapper 7 (AxROM)
-    // 32KB switchable PRG, 8KB CHR RAM, single-screen mirroring
-    fn mapper7_read_prg(&self, addr: u16) -> u8 {
-        match addr {
-            0x8000..=0xFFFF => {
-                // 32KB switchable bank
-                let bank = (self.prg_bank & 0x07) as usize;
-                let offset = (addr - 0x8000) as usize;
-                let index = (bank * 32768 + offset) % self.prg_rom.len().max(1);
-                self.prg_rom.get(index).copied().unwrap_or(0)
-            }
-            _ => 0,
+            None
         }
     }
 
-    fn mapper7_write_prg(&mut self, addr: u16, value: u8) {
-        if (0x8000..=0xFFFF).contains(&addr) {
-            // Bits 0-2: PRG bank select
-            self.prg_bank = value & 0x07;
-            // Bit 4: Nametable select (single-screen mirroring)
-            self.mirroring = if (value & 0x10) != 0 {
-                Mirroring::SingleScreenUpper
-            } else {
-                Mirroring::SingleScreenLower
-            };
+    /// バッテリーバックアップされたPRG RAMを復元
+    ///
+    /// バッテリーがない場合やサイズが一致しない場合は`NesError::InvalidBatteryRam`を返す。
+    pub fn load_battery_ram(&mut self, data: &[u8]) -> Result<()> {
+        let expected = self
+            .battery_ram()
+            .map(|ram| ram.len())
+            .ok_or_else(|| NesError::InvalidBatteryRam("cartridge has no battery".to_string()))?;
+        if data.len() != expected {
+            return Err(NesError::InvalidBatteryRam(format!(
+                "size mismatch: expected {} bytes, found {}",
+                expected,
+                data.len()
+            )));
         }
+        let start = self.header.prg_ram_size;
+        self.mapper.memory_mut().prg_ram[start..start + expected].copy_from_slice(data);
+        Ok(())
     }
 
-    fn mapper7_read_chr(&self, addr: u16) -> u8 {
-        // CHR RAM only
-        let index = (addr & 0x1FFF) as usize;
-        self.chr_ram.get(index).copied().unwrap_or(0)
+    /// ROMファイルのCRC32チェックサム
+    pub fn checksum(&self) -> u32 {
+        self.checksum
     }
 
-    fn mapper7_write_chr(&mut self, addr: u16, value: u8) {
-        // CHR RAM only
-        let index = (addr & 0x1FFF) as usize;
-        if index < self.chr_ram.len() {
-            self.chr_ram[index] = value;
-        }
+    /// マッパーのバンク/IRQ状態とPRG/CHR RAMを保存
+    pub fn save_state(&self, w: &mut StateWriter) {
+        self.mapper.save_state(w);
+        let memory = self.mapper.memory();
+        w.write_bytes(&memory.prg_ram);
+        w.write_bytes(&memory.chr_ram);
     }
 
-    // Mapper 66 (GxROM)
-    // 32KB switchable PRG, 8KB switchable CHR
-    fn mapper66_read_prg(&self, addr: u16) -> u8 {
-        match addr {
-            0x8000..=0xFFFF => {
-                // 32KB switchable bank (bits 4-5 of bank register)
-                let bank = ((self.prg_bank >> 4) & 0x03) as usize;
-                let offset = (addr - 0x8000) as usize;
-                let index = (bank * 32768 + offset) % self.prg_rom.len().max(1);
-                self.prg_rom.get(index).copied().unwrap_or(0)
-            }
-            _ => 0,
-        }
-    }
-
-    fn mapper66_write_prg(&mut self, addr: u16, value: u8) {
-        if (0x8000..=0xFFFF).contains(&addr) {
-            // Bits 4-5: PRG bank, Bits 0-1: CHR bank
-            self.prg_bank = value;
-            self.chr_bank = value & 0x03;
-        }
-    }
-
-    fn mapper66_read_chr(&self, addr: u16) -> u8 {
-        if self.chr_rom.is_empty() {
-            return self.chr_ram.get((addr & 0x1FFF) as usize).copied().unwrap_or(0);
-        }
-        // 8KB switchable bank
-        let bank = self.chr_bank as usize;
-        let offset = (addr & 0x1FFF) as usize;
-        let index = (bank * 8192 + offset) % self.chr_rom.len().max(1);
-        self.chr_rom.get(index).copied().unwrap_or(0)
+    /// マッパーのバンク/IRQ状態とPRG/CHR RAMを復元
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.mapper.load_state(r)?;
+        let memory = self.mapper.memory_mut();
+        r.read_bytes_into(&mut memory.prg_ram)?;
+        r.read_bytes_into(&mut memory.chr_ram)?;
+        Ok(())
     }
+}
 
-    fn mapper66_write_chr(&mut self, addr: u16, value: u8) {
-        // CHR ROM, ignore writes (unless CHR RAM)
-        if self.chr_rom.is_empty() {
-            let index = (addr & 0x1FFF) as usize;
-            if index < self.chr_ram.len() {
-                self.chr_ram[index] = value;
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rom(prg_banks: u8, chr_banks: u8, flags6: u8) -> Vec<u8> {
+        let mut data = vec![0x4E, 0x45, 0x53, 0x1A, prg_banks, chr_banks, flags6, 0];
+        data.resize(16, 0);
+        data.resize(
+            16 + prg_banks as usize * 16384 + chr_banks as usize * 8192,
+            0,
+        );
+        data
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let cart = Cartridge::from_bytes(&rom(2, 1, 0x01)).unwrap();
+        assert_eq!(cart.mirroring(), Mirroring::Vertical);
+        assert_eq!(cart.header().prg_rom_size, 32768);
+    }
+
+    #[test]
+    fn test_from_bytes_truncated() {
+        let mut data = rom(2, 1, 0);
+        data.truncate(data.len() - 1);
+        assert!(matches!(
+            Cartridge::from_bytes(&data),
+            Err(NesError::InvalidRom(_))
+        ));
+    }
+
+    #[test]
+    fn test_battery_ram() {
+        let mut cart = Cartridge::from_bytes(&rom(2, 1, 0)).unwrap();
+        assert!(cart.battery_ram().is_none());
+        assert!(cart.load_battery_ram(&[0; 8192]).is_err());
+
+        let mut cart = Cartridge::from_bytes(&rom(2, 1, 0x02)).unwrap();
+        let mut save = vec![0; 8192];
+        save[0x10] = 0x42;
+        cart.load_battery_ram(&save).unwrap();
+        assert_eq!(cart.read_prg_byte(0x6010), 0x42);
+        cart.write_prg_byte(0x6011, 0x43);
+        assert_eq!(cart.battery_ram().unwrap()[0x11], 0x43);
+        assert!(matches!(
+            cart.load_battery_ram(&[0; 100]),
+            Err(NesError::InvalidBatteryRam(_))
+        ));
+    }
+
+    #[test]
+    fn test_battery_ram_excludes_volatile_ram() {
+        // NES 2.0: 揮発性8KB + 不揮発性8KB (byte 10 = $77)
+        let mut data = rom(2, 1, 0x02);
+        data[7] = 0x08;
+        data[10] = 0x77;
+        let mut cart = Cartridge::from_bytes(&data).unwrap();
+        assert_eq!(cart.battery_ram().unwrap().len(), 8192);
+        assert!(cart.load_battery_ram(&[0; 16384]).is_err());
+
+        cart.load_battery_ram(&[0x42; 8192]).unwrap();
+        let prg_ram = &cart.mapper.memory().prg_ram;
+        assert!(prg_ram[..8192].iter().all(|&b| b == 0));
+        assert!(prg_ram[8192..].iter().all(|&b| b == 0x42));
+
+        // バッテリーがあっても不揮発性のRAMがなければ保存しない
+        data[10] = 0x07;
+        let cart = Cartridge::from_bytes(&data).unwrap();
+        assert!(cart.battery_ram().is_none());
+    }
+
+    #[test]
+    fn test_from_bytes_unsupported_mapper() {
+        assert!(matches!(
+            Cartridge::from_bytes(&rom(1, 1, 0x50)),
+            Err(NesError::UnsupportedMapper(5))
+        ));
     }
 }
-
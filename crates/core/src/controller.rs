@@ -2,8 +2,13 @@
 //!
 //! NESの標準コントローラー入力を管理する。
 
+use crate::input::InputDevice;
+use crate::ppu::Ppu;
+use crate::state::{StateReader, StateWriter};
+use crate::Result;
+
 /// コントローラーのボタン
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Button {
     A,
     B,
@@ -15,6 +20,20 @@ pub enum Button {
     Right,
 }
 
+impl Button {
+    /// 全ボタン（ビットの順）
+    pub const ALL: [Button; 8] = [
+        Button::A,
+        Button::B,
+        Button::Select,
+        Button::Start,
+        Button::Up,
+        Button::Down,
+        Button::Left,
+        Button::Right,
+    ];
+}
+
 /// コントローラー
 #[derive(Debug)]
 pub struct Controller {
@@ -56,13 +75,18 @@ impl Controller {
         }
     }
 
-    /// CPU側からの読み込み（$4016）
+    /// 現在押されているボタン（ビット0=A ... ビット7=Right）
+    pub fn buttons(&self) -> u8 {
+        self.buttons
+    }
+
+    /// CPU側からの読み込み（$4016/$4017のD0）
     pub fn read(&mut self) -> u8 {
         if self.strobe {
             // ストローブモードの時は常にAボタンの状態を返す
-            (self.buttons & 0x01) | 0x40
+            self.buttons & 0x01
         } else {
-            let value = (self.shift_register & 0x01) | 0x40;
+            let value = self.shift_register & 0x01;
             self.shift_register >>= 1;
             self.shift_register |= 0x80; // パディング
             value
@@ -72,21 +96,37 @@ impl Controller {
     /// CPU側からの書き込み（$4016）
     pub fn write(&mut self, data: u8) {
         let new_strobe = (data & 0x01) != 0;
-        
+
         // ストローブの立ち下がりエッジでシフトレジスタをロード
         if self.strobe && !new_strobe {
             self.shift_register = self.buttons;
         }
-        
+
         self.strobe = new_strobe;
-        
+
         // ストローブがセットされている間は常にリロード
         if self.strobe {
             self.shift_register = self.buttons;
         }
     }
 
-    fn button_to_bit(button: Button) -> u8 {
+    /// 状態を保存
+    ///
+    /// ボタンの押下状態はフロントエンドからの入力なので含めない
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_u8(self.shift_register);
+        w.write_bool(self.strobe);
+    }
+
+    /// 状態を復元
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.shift_register = r.read_u8()?;
+        self.strobe = r.read_bool()?;
+        Ok(())
+    }
+
+    /// `buttons()`の中でボタンが占めるビット
+    pub fn button_to_bit(button: Button) -> u8 {
         match button {
             Button::A => 0b0000_0001,      // Bit 0
             Button::B => 0b0000_0010,      // Bit 1
@@ -100,6 +140,32 @@ impl Controller {
     }
 }
 
+impl InputDevice for Controller {
+    fn read(&mut self, _ppu: &Ppu) -> u8 {
+        Controller::read(self)
+    }
+
+    fn write(&mut self, value: u8) {
+        Controller::write(self, value);
+    }
+
+    fn set_button(&mut self, _slot: usize, button: Button, pressed: bool) {
+        Controller::set_button(self, button, pressed);
+    }
+
+    fn buttons(&self, _slot: usize) -> u8 {
+        Controller::buttons(self)
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        Controller::save_state(self, w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        Controller::load_state(self, r)
+    }
+}
+
 impl Default for Controller {
     fn default() -> Self {
         Self::new()
@@ -1,7 +1,8 @@
 //! # 6502 CPU Implementation
 //! Based on https://github.com/starrhorne/nes-rust
 
-use crate::bus::Bus;
+use crate::bus::{Bus, InterruptPoll};
+use crate::state::{StateReader, StateWriter};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 enum Flag {
@@ -15,7 +16,7 @@ enum Flag {
     Negative   = 0b10000000,
 }
 
-#[allow(dead_code)]
+#[allow(dead_code, clippy::enum_variant_names)]
 #[derive(Debug, Copy, Clone, PartialEq)]
 enum Mode {
     Immediate,
@@ -34,6 +35,19 @@ enum Mode {
     NoMode,
 }
 
+/// KIL/JAM命令（実機ではCPUが停止する）に出会ったときの動作
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum InvalidOpcodePolicy {
+    /// `NesError::InvalidInstruction`を返して停止する
+    #[default]
+    Halt,
+    /// 警告を出して2サイクルのNOPとして実行を続ける
+    Continue,
+}
+
+/// XAA/LXAの不安定な定数（チップや温度で変わる。SingleStepTestsと同じ値を使う）
+const UNSTABLE_MAGIC: u8 = 0xEE;
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Interrupt {
     Nmi,
@@ -50,6 +64,9 @@ pub struct Cpu {
     pub x: u8,
     pub y: u8,
     pub p: u8,
+    invalid_opcode_policy: InvalidOpcodePolicy,
+    /// ページをまたがずに分岐した命令のポーリング結果
+    branch_poll: Option<InterruptPoll>,
 }
 
 impl Cpu {
@@ -62,6 +79,8 @@ impl Cpu {
             x: 0,
             y: 0,
             p: 0,
+            invalid_opcode_policy: InvalidOpcodePolicy::default(),
+            branch_poll: None,
         }
     }
 
@@ -75,14 +94,43 @@ impl Cpu {
         self.pc
     }
 
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
     pub fn sp(&self) -> u8 {
         self.sp
     }
 
+    pub fn set_sp(&mut self, sp: u8) {
+        self.sp = sp;
+    }
+
     pub fn status(&self) -> u8 {
         self.p
     }
 
+    /// レジスタ状態を保存（バスは含まない）
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_u16(self.pc);
+        w.write_u8(self.sp);
+        w.write_u8(self.a);
+        w.write_u8(self.x);
+        w.write_u8(self.y);
+        w.write_u8(self.p);
+    }
+
+    /// レジスタ状態を復元
+    pub fn load_state(&mut self, r: &mut StateReader) -> crate::Result<()> {
+        self.pc = r.read_u16()?;
+        self.sp = r.read_u8()?;
+        self.a = r.read_u8()?;
+        self.x = r.read_u8()?;
+        self.y = r.read_u8()?;
+        self.p = r.read_u8()?;
+        Ok(())
+    }
+
     fn next_byte(&mut self) -> u8 {
         let value = self.bus.read_byte(self.pc);
         self.increment_pc();
@@ -111,6 +159,28 @@ impl Cpu {
         self.sp = self.sp.wrapping_sub(1);
     }
 
+    /// リードモディファイライト命令が結果を書く前に元の値を書き戻すサイクル
+    fn dummy_write(&mut self, address: u16, value: u8) {
+        self.bus.write_byte(address, value);
+    }
+
+    /// 値を使わない読み取りのサイクル
+    ///
+    /// 実機と同じアドレスを読むので、PPUやAPUのレジスタなら読み取りの副作用も起きる。
+    fn dummy_read(&mut self, address: u16) {
+        self.bus.read_byte(address);
+    }
+
+    /// 1バイトの命令が2サイクル目にPC（次の命令）を読むダミーリード
+    fn dummy_read_pc(&mut self) {
+        self.dummy_read(self.pc);
+    }
+
+    /// スタックポインタが指すアドレスのダミーリード
+    fn dummy_read_stack(&mut self) {
+        self.dummy_read(0x100 + self.sp as u16);
+    }
+
     fn pop_word(&mut self) -> u16 {
         let lo = self.pop_byte() as u16;
         let hi = self.pop_byte() as u16;
@@ -164,35 +234,31 @@ impl Cpu {
             }
             Mode::ZeroPage => self.next_byte() as u16,
             Mode::ZeroPageX => {
-                self.bus.tick();
-                low_byte(offset(self.next_byte(), self.x))
+                let base = self.next_byte();
+                self.dummy_read(base as u16);
+                low_byte(offset(base, self.x))
             }
             Mode::ZeroPageY => {
-                self.bus.tick();
-                low_byte(offset(self.next_byte(), self.y))
+                let base = self.next_byte();
+                self.dummy_read(base as u16);
+                low_byte(offset(base, self.y))
             }
             Mode::Absolute => self.next_word(),
             Mode::AbsoluteX => {
                 let base = self.next_word();
-                if cross(base, self.x) {
-                    self.bus.tick();
-                };
-                offset(base, self.x)
+                self.indexed_address(base, self.x, false)
             }
             Mode::AbsoluteXForceTick => {
-                self.bus.tick();
-                offset(self.next_word(), self.x)
+                let base = self.next_word();
+                self.indexed_address(base, self.x, true)
             }
             Mode::AbsoluteY => {
                 let base = self.next_word();
-                if cross(base, self.y) {
-                    self.bus.tick();
-                }
-                offset(base, self.y)
+                self.indexed_address(base, self.y, false)
             }
             Mode::AbsoluteYForceTick => {
-                self.bus.tick();
-                offset(self.next_word(), self.y)
+                let base = self.next_word();
+                self.indexed_address(base, self.y, true)
             }
             Mode::Indirect => {
                 let i = self.next_word();
@@ -200,98 +266,132 @@ impl Cpu {
                     .read_noncontinuous_word(i, high_byte(i) | low_byte(i + 1))
             }
             Mode::IndirectX => {
-                self.bus.tick();
-                let i = offset(self.next_byte(), self.x);
+                let pointer = self.next_byte();
+                self.dummy_read(pointer as u16);
+                let i = offset(pointer, self.x);
                 self.bus
                     .read_noncontinuous_word(low_byte(i), low_byte(i + 1))
             }
             Mode::IndirectY => {
                 let i = self.next_byte();
-                let base = self.bus.read_noncontinuous_word(i, low_byte(i + 1));
-                if cross(base, self.y) {
-                    self.bus.tick();
-                }
-                offset(base, self.y)
+                let base = self.bus.read_noncontinuous_word(i, low_byte(i as u16 + 1));
+                self.indexed_address(base, self.y, false)
             }
             Mode::IndirectYForceTick => {
                 let i = self.next_byte();
-                let base = self.bus.read_noncontinuous_word(i, low_byte(i + 1));
-                self.bus.tick();
-                offset(base, self.y)
+                let base = self.bus.read_noncontinuous_word(i, low_byte(i as u16 + 1));
+                self.indexed_address(base, self.y, true)
             }
             Mode::NoMode => panic!("Mode::NoMode should never be used to read from memory"),
         }
     }
 
+    /// インデックスを足したアドレス
+    ///
+    /// ページをまたぐ場合（書き込みとリードモディファイライトでは常に）、上位バイトを
+    /// 繰り上げる前のアドレスをダミーリードする。
+    fn indexed_address(&mut self, base: u16, index: u8, force: bool) -> u16 {
+        let address = offset(base, index);
+        if force || cross(base, index) {
+            self.dummy_read(high_byte(base) | low_byte(address));
+        }
+        address
+    }
+
     fn read_operand(&mut self, mode: Mode) -> u8 {
         let address = self.operand_address(mode);
         self.bus.read_byte(address)
     }
 
+    /// 割り込みを発生させる（IRQはIフラグが立っていれば無視される）
     pub fn interrupt(&mut self, kind: Interrupt) {
-        // IRQ is ignored if I flag is set
-        if kind == Interrupt::Irq && self.get_flag(Flag::IrqDisable) {
-            return;
-        }
-
-        if kind != Interrupt::Break && kind != Interrupt::Reset {
-            self.bus.tick();
-        }
-
         match kind {
             Interrupt::Reset => {
                 // Reset does NOT push to stack, just performs dummy reads
                 // and decrements SP by 3
                 self.sp = self.sp.wrapping_sub(3);
+                self.set_flag(Flag::IrqDisable, true);
+                self.bus.tick();
+                self.bus.tick();
+                self.bus.tick();
+                self.pc = self.bus.read_word(0xFFFCu16);
             }
-            Interrupt::Break | Interrupt::Nmi | Interrupt::Irq => {
-                self.push_word(self.pc);
-                let mut status = self.p | Flag::Push as u8;
-                if kind == Interrupt::Break {
-                    status |= Flag::Break as u8;
-                } else {
-                    status &= !(Flag::Break as u8);
-                }
-                self.push_byte(status);
-            }
+            Interrupt::Irq if self.get_flag(Flag::IrqDisable) => {}
+            Interrupt::Nmi | Interrupt::Irq | Interrupt::Break => self.interrupt_sequence(kind),
         }
+    }
 
-        self.set_flag(Flag::IrqDisable, true);
-
-        let vector = match kind {
-            Interrupt::Nmi => 0xFFFA,
-            Interrupt::Reset => 0xFFFC,
-            Interrupt::Irq | Interrupt::Break => 0xFFFE,
-        };
+    /// BRK/IRQ/NMIの7サイクルのシーケンス
+    ///
+    /// ベクタはPをプッシュした時点で決まる。それまでにNMIが検出されていれば、
+    /// BRKやIRQのシーケンスでもNMIのベクタへ飛ぶ（NMIハイジャック）。
+    fn interrupt_sequence(&mut self, kind: Interrupt) {
+        if kind != Interrupt::Break {
+            // 命令の代わりにPCから2回ダミーリードする
+            self.bus.read_byte(self.pc);
+            self.bus.read_byte(self.pc);
+        }
 
-        if kind == Interrupt::Reset {
-            self.bus.tick();
-            self.bus.tick();
-            self.bus.tick();
+        self.push_word(self.pc);
+        let mut status = self.p | Flag::Push as u8;
+        if kind == Interrupt::Break {
+            status |= Flag::Break as u8;
+        } else {
+            status &= !(Flag::Break as u8);
         }
+        self.push_byte(status);
 
-        self.pc = self.bus.read_word(vector as u16);
+        let nmi = kind == Interrupt::Nmi || self.bus.interrupt_poll().nmi;
+        if nmi {
+            self.bus.acknowledge_nmi();
+        }
+        self.set_flag(Flag::IrqDisable, true);
+        self.pc = self.bus.read_word(if nmi { 0xFFFAu16 } else { 0xFFFEu16 });
     }
 
+    /// 1命令実行
+    ///
+    /// KIL/JAM命令に出会うと、`InvalidOpcodePolicy::Halt`ではPCをその命令に留めたまま
+    /// `NesError::InvalidInstruction`を返す（以降の`step`も同じエラーを返し続ける）。
+    ///
+    /// 命令の最後から2番目のサイクルの終わりの時点で割り込み信号をポーリングし、
+    /// NMIのエッジか（Iフラグが立っていなければ）IRQが検出されていれば、続けて割り込みシーケンスを実行する。
     pub fn step(&mut self) -> crate::Result<()> {
-        let pc = self.pc;
         let opcode = self.next_byte();
-        
-        // nestestデバッグ用ログ（特定のPCでのみ出力）
-        if pc == 0xC66E {
-            log::info!("nestest: Reached success address $C66E");
+        // CLI/SEI/PLPはIフラグを最後のサイクルで変えるので、ポーリングには実行前の値が使われる
+        let irq_inhibit = self.get_flag(Flag::IrqDisable);
+        self.branch_poll = None;
+        self.execute_instruction(opcode)?;
+
+        // BRKのシーケンスの後はポーリングせず、ハンドラの最初の命令を必ず実行する
+        if opcode == 0x00 {
+            return Ok(());
+        }
+        let poll = self.branch_poll.take().unwrap_or(self.bus.interrupt_poll());
+        // RTIはPを途中のサイクルで復元するので、新しいIフラグが直ちに効く
+        let irq_inhibit = if opcode == 0x40 {
+            self.get_flag(Flag::IrqDisable)
+        } else {
+            irq_inhibit
+        };
+        if poll.nmi {
+            self.interrupt_sequence(Interrupt::Nmi);
+        } else if poll.irq && !irq_inhibit {
+            self.interrupt_sequence(Interrupt::Irq);
         }
-        
-        self.execute_instruction(opcode);
         Ok(())
     }
 
-    pub fn execute_next_instruction(&mut self) {
-        let opcode = self.next_byte();
-        self.execute_instruction(opcode);
+    /// KIL/JAM命令に出会ったときの動作
+    pub fn invalid_opcode_policy(&self) -> InvalidOpcodePolicy {
+        self.invalid_opcode_policy
+    }
+
+    pub fn set_invalid_opcode_policy(&mut self, policy: InvalidOpcodePolicy) {
+        self.invalid_opcode_policy = policy;
     }
 
-    fn execute_instruction(&mut self, opcode: u8) {
+    fn execute_instruction(&mut self, opcode: u8) -> crate::Result<()> {
         match opcode {
             // Loads
             0xa1 => self.lda(Mode::IndirectX),
@@ -516,61 +616,80 @@ impl Cpu {
             0xc7 => self.dcp(Mode::ZeroPage),
             0xd7 => self.dcp(Mode::ZeroPageX),
             0xcf => self.dcp(Mode::Absolute),
-            0xdf => self.dcp(Mode::AbsoluteX),
-            0xdb => self.dcp(Mode::AbsoluteY),
+            0xdf => self.dcp(Mode::AbsoluteXForceTick),
+            0xdb => self.dcp(Mode::AbsoluteYForceTick),
             0xc3 => self.dcp(Mode::IndirectX),
-            0xd3 => self.dcp(Mode::IndirectY),
+            0xd3 => self.dcp(Mode::IndirectYForceTick),
             
             // ISB/ISC - Increment then Subtract with Carry (illegal opcode)
             0xe7 => self.isb(Mode::ZeroPage),
             0xf7 => self.isb(Mode::ZeroPageX),
             0xef => self.isb(Mode::Absolute),
-            0xff => self.isb(Mode::AbsoluteX),
-            0xfb => self.isb(Mode::AbsoluteY),
+            0xff => self.isb(Mode::AbsoluteXForceTick),
+            0xfb => self.isb(Mode::AbsoluteYForceTick),
             0xe3 => self.isb(Mode::IndirectX),
-            0xf3 => self.isb(Mode::IndirectY),
+            0xf3 => self.isb(Mode::IndirectYForceTick),
             
             // SLO - Shift Left then OR (illegal opcode)
             0x07 => self.slo(Mode::ZeroPage),
             0x17 => self.slo(Mode::ZeroPageX),
             0x0f => self.slo(Mode::Absolute),
-            0x1f => self.slo(Mode::AbsoluteX),
-            0x1b => self.slo(Mode::AbsoluteY),
+            0x1f => self.slo(Mode::AbsoluteXForceTick),
+            0x1b => self.slo(Mode::AbsoluteYForceTick),
             0x03 => self.slo(Mode::IndirectX),
-            0x13 => self.slo(Mode::IndirectY),
+            0x13 => self.slo(Mode::IndirectYForceTick),
             
             // RLA - Rotate Left then AND (illegal opcode)
             0x27 => self.rla(Mode::ZeroPage),
             0x37 => self.rla(Mode::ZeroPageX),
             0x2f => self.rla(Mode::Absolute),
-            0x3f => self.rla(Mode::AbsoluteX),
-            0x3b => self.rla(Mode::AbsoluteY),
+            0x3f => self.rla(Mode::AbsoluteXForceTick),
+            0x3b => self.rla(Mode::AbsoluteYForceTick),
             0x23 => self.rla(Mode::IndirectX),
-            0x33 => self.rla(Mode::IndirectY),
+            0x33 => self.rla(Mode::IndirectYForceTick),
             
             // SRE - Shift Right then EOR (illegal opcode)
             0x47 => self.sre(Mode::ZeroPage),
             0x57 => self.sre(Mode::ZeroPageX),
             0x4f => self.sre(Mode::Absolute),
-            0x5f => self.sre(Mode::AbsoluteX),
-            0x5b => self.sre(Mode::AbsoluteY),
+            0x5f => self.sre(Mode::AbsoluteXForceTick),
+            0x5b => self.sre(Mode::AbsoluteYForceTick),
             0x43 => self.sre(Mode::IndirectX),
-            0x53 => self.sre(Mode::IndirectY),
+            0x53 => self.sre(Mode::IndirectYForceTick),
             
             // RRA - Rotate Right then Add with Carry (illegal opcode)
             0x67 => self.rra(Mode::ZeroPage),
             0x77 => self.rra(Mode::ZeroPageX),
             0x6f => self.rra(Mode::Absolute),
-            0x7f => self.rra(Mode::AbsoluteX),
-            0x7b => self.rra(Mode::AbsoluteY),
+            0x7f => self.rra(Mode::AbsoluteXForceTick),
+            0x7b => self.rra(Mode::AbsoluteYForceTick),
             0x63 => self.rra(Mode::IndirectX),
-            0x73 => self.rra(Mode::IndirectY),
-
-            _ => {
-                log::warn!("Unimplemented instruction: 0x{:02X} at PC: 0x{:04X}", opcode, self.pc - 1);
-                self.nop();
+            0x73 => self.rra(Mode::IndirectYForceTick),
+
+            // Immediate operations (illegal opcodes)
+            0x0b | 0x2b => self.anc(),
+            0x4b => self.alr(),
+            0x6b => self.arr(),
+            0x8b => self.xaa(),
+            0xab => self.lxa(),
+            0xcb => self.axs(),
+
+            // LAS - AND memory with SP, load into A, X and SP (illegal opcode)
+            0xbb => self.las(),
+
+            // SHA/SHX/SHY/TAS - Store register AND (high byte + 1) (illegal opcodes)
+            0x93 => self.sha_indirect_y(),
+            0x9f => self.sha_absolute_y(),
+            0x9e => self.shx(),
+            0x9c => self.shy(),
+            0x9b => self.tas(),
+
+            // KIL/JAM - Halt the CPU (illegal opcodes)
+            0x02 | 0x12 | 0x22 | 0x32 | 0x42 | 0x52 | 0x62 | 0x72 | 0x92 | 0xb2 | 0xd2 | 0xf2 => {
+                return self.jam(opcode);
             }
         }
+        Ok(())
     }
 
     // Instruction implementations
@@ -682,7 +801,7 @@ impl Cpu {
         let operand = self.bus.read_byte(address);
         let carry = if self.get_flag(Flag::Carry) { 1 } else { 0 };
         let result = (operand << 1) | carry;
-        self.bus.tick();
+        self.dummy_write(address, operand);
         self.set_flag(Flag::Carry, operand & 0b1000_0000 != 0);
         self.set_flags_zero_negative(result);
         self.bus.write_byte(address, result);
@@ -691,7 +810,7 @@ impl Cpu {
     fn rol_a(&mut self) {
         let carry = if self.get_flag(Flag::Carry) { 1 } else { 0 };
         let result = (self.a << 1) | carry;
-        self.bus.tick();
+        self.dummy_read_pc();
         self.set_flag(Flag::Carry, self.a & 0b1000_0000 != 0);
         self.set_flags_zero_negative(result);
         self.a = result;
@@ -702,7 +821,7 @@ impl Cpu {
         let operand = self.bus.read_byte(address);
         let carry = if self.get_flag(Flag::Carry) { 0b1000_0000 } else { 0 };
         let result = (operand >> 1) | carry;
-        self.bus.tick();
+        self.dummy_write(address, operand);
         self.set_flag(Flag::Carry, operand & 0b0000_0001 != 0);
         self.set_flags_zero_negative(result);
         self.bus.write_byte(address, result);
@@ -711,7 +830,7 @@ impl Cpu {
     fn ror_a(&mut self) {
         let carry = if self.get_flag(Flag::Carry) { 0b1000_0000 } else { 0 };
         let result = (self.a >> 1) | carry;
-        self.bus.tick();
+        self.dummy_read_pc();
         self.set_flag(Flag::Carry, self.a & 0b0000_0001 != 0);
         self.set_flags_zero_negative(result);
         self.a = result;
@@ -721,7 +840,7 @@ impl Cpu {
         let address = self.operand_address(mode);
         let operand = self.bus.read_byte(address);
         let result = operand << 1;
-        self.bus.tick();
+        self.dummy_write(address, operand);
         self.set_flag(Flag::Carry, operand & 0b1000_0000 != 0);
         self.set_flags_zero_negative(result);
         self.bus.write_byte(address, result);
@@ -729,7 +848,7 @@ impl Cpu {
 
     fn asl_a(&mut self) {
         let result = self.a << 1;
-        self.bus.tick();
+        self.dummy_read_pc();
         self.set_flag(Flag::Carry, self.a & 0b1000_0000 != 0);
         self.set_flags_zero_negative(result);
         self.a = result;
@@ -739,7 +858,7 @@ impl Cpu {
         let address = self.operand_address(mode);
         let operand = self.bus.read_byte(address);
         let result = operand >> 1;
-        self.bus.tick();
+        self.dummy_write(address, operand);
         self.set_flag(Flag::Carry, operand & 0b0000_0001 != 0);
         self.set_flags_zero_negative(result);
         self.bus.write_byte(address, result);
@@ -747,7 +866,7 @@ impl Cpu {
 
     fn lsr_a(&mut self) {
         let result = self.a >> 1;
-        self.bus.tick();
+        self.dummy_read_pc();
         self.set_flag(Flag::Carry, self.a & 0b0000_0001 != 0);
         self.set_flags_zero_negative(result);
         self.a = result;
@@ -757,7 +876,7 @@ impl Cpu {
         let address = self.operand_address(mode);
         let operand = self.bus.read_byte(address);
         let result = operand.wrapping_add(1);
-        self.bus.tick();
+        self.dummy_write(address, operand);
         self.set_flags_zero_negative(result);
         self.bus.write_byte(address, result);
     }
@@ -766,121 +885,126 @@ impl Cpu {
         let address = self.operand_address(mode);
         let operand = self.bus.read_byte(address);
         let result = operand.wrapping_sub(1);
-        self.bus.tick();
+        self.dummy_write(address, operand);
         self.set_flags_zero_negative(result);
         self.bus.write_byte(address, result);
     }
 
     fn inx(&mut self) {
         let result = self.x.wrapping_add(1);
-        self.bus.tick();
+        self.dummy_read_pc();
         self.set_flags_zero_negative(result);
         self.x = result;
     }
 
     fn dex(&mut self) {
         let result = self.x.wrapping_sub(1);
-        self.bus.tick();
+        self.dummy_read_pc();
         self.set_flags_zero_negative(result);
         self.x = result;
     }
 
     fn iny(&mut self) {
         let result = self.y.wrapping_add(1);
-        self.bus.tick();
+        self.dummy_read_pc();
         self.set_flags_zero_negative(result);
         self.y = result;
     }
 
     fn dey(&mut self) {
         let result = self.y.wrapping_sub(1);
-        self.bus.tick();
+        self.dummy_read_pc();
         self.set_flags_zero_negative(result);
         self.y = result;
     }
 
     fn tax(&mut self) {
         let result = self.a;
-        self.bus.tick();
+        self.dummy_read_pc();
         self.set_flags_zero_negative(result);
         self.x = result;
     }
 
     fn tay(&mut self) {
         let result = self.a;
-        self.bus.tick();
+        self.dummy_read_pc();
         self.set_flags_zero_negative(result);
         self.y = result;
     }
 
     fn txa(&mut self) {
         let result = self.x;
-        self.bus.tick();
+        self.dummy_read_pc();
         self.set_flags_zero_negative(result);
         self.a = result;
     }
 
     fn tya(&mut self) {
         let result = self.y;
-        self.bus.tick();
+        self.dummy_read_pc();
         self.set_flags_zero_negative(result);
         self.a = result;
     }
 
     fn txs(&mut self) {
         self.sp = self.x;
-        self.bus.tick();
+        self.dummy_read_pc();
     }
 
     fn tsx(&mut self) {
         let result = self.sp;
-        self.bus.tick();
+        self.dummy_read_pc();
         self.set_flags_zero_negative(result);
         self.x = result;
     }
 
     fn clc(&mut self) {
         self.set_flag(Flag::Carry, false);
-        self.bus.tick();
+        self.dummy_read_pc();
     }
 
     fn sec(&mut self) {
         self.set_flag(Flag::Carry, true);
-        self.bus.tick();
+        self.dummy_read_pc();
     }
 
     fn cli(&mut self) {
         self.set_flag(Flag::IrqDisable, false);
-        self.bus.tick();
+        self.dummy_read_pc();
     }
 
     fn sei(&mut self) {
         self.set_flag(Flag::IrqDisable, true);
-        self.bus.tick();
+        self.dummy_read_pc();
     }
 
     fn clv(&mut self) {
         self.set_flag(Flag::Overflow, false);
-        self.bus.tick();
+        self.dummy_read_pc();
     }
 
     fn cld(&mut self) {
         self.set_flag(Flag::Decimal, false);
-        self.bus.tick();
+        self.dummy_read_pc();
     }
 
     fn sed(&mut self) {
         self.set_flag(Flag::Decimal, true);
-        self.bus.tick();
+        self.dummy_read_pc();
     }
 
     fn branch(&mut self, condition: bool) {
         let offset = self.next_byte() as i8;
         if condition {
-            self.bus.tick();
+            // ページをまたがない分岐は、オペランドを読む前の1回しかポーリングしない
+            let poll = self.bus.interrupt_poll();
+            self.dummy_read_pc();
             let target = self.pc.wrapping_add(offset as u16);
-            if cross(self.pc, offset as u8) {
-                self.bus.tick();
+            if high_byte(target) != high_byte(self.pc) {
+                // 上位バイトを直す前のアドレスを読む
+                self.dummy_read(high_byte(self.pc) | low_byte(target));
+            } else {
+                self.branch_poll = Some(poll);
             }
             self.pc = target;
         }
@@ -931,95 +1055,92 @@ impl Cpu {
     }
 
     fn jsr(&mut self) {
-        let target_address = self.operand_address(Mode::Absolute);
-        let return_address = self.pc - 1;
-        self.bus.tick();
-        self.push_word(return_address);
-        self.pc = target_address;
+        // 上位バイトは戻りアドレス（JSRの最後のバイト）をプッシュした後に読む
+        let lo = self.next_byte() as u16;
+        self.dummy_read_stack();
+        self.push_word(self.pc);
+        let hi = self.bus.read_byte(self.pc) as u16;
+        self.pc = (hi << 8) | lo;
     }
 
     fn rts(&mut self) {
-        self.bus.tick();
-        self.bus.tick();
-        self.pc = self.pop_word() + 1;
-        self.bus.tick();
+        self.dummy_read_pc();
+        self.dummy_read_stack();
+        self.pc = self.pop_word();
+        self.dummy_read(self.pc);
+        self.increment_pc();
     }
 
     fn brk(&mut self) {
-        self.pc += 1;
+        self.next_byte(); // Padding byte
         self.interrupt(Interrupt::Break);
     }
 
     fn rti(&mut self) {
-        self.p = self.pop_byte();
+        self.dummy_read_pc();
+        self.dummy_read_stack();
+        self.p = self.pop_byte() & !(Flag::Break as u8) | Flag::Push as u8;
         self.pc = self.pop_word();
     }
 
     fn pha(&mut self) {
-        self.bus.tick();
+        self.dummy_read_pc();
         let a = self.a;
         self.push_byte(a);
     }
 
     fn pla(&mut self) {
-        self.bus.tick();
-        self.bus.tick();
+        self.dummy_read_pc();
+        self.dummy_read_stack();
         let result = self.pop_byte();
         self.set_flags_zero_negative(result);
         self.a = result;
     }
 
     fn php(&mut self) {
-        self.bus.tick();
+        self.dummy_read_pc();
         let p = self.p | Flag::Break as u8 | Flag::Push as u8;
         self.push_byte(p);
     }
 
     fn plp(&mut self) {
-        self.bus.tick();
-        self.bus.tick();
+        self.dummy_read_pc();
+        self.dummy_read_stack();
         self.p = self.pop_byte() & !(Flag::Break as u8) | Flag::Push as u8;
     }
 
     fn nop(&mut self) {
-        self.bus.tick();
+        self.dummy_read_pc();
     }
 
-    // Illegal/Undocumented NOP variants
+    // Illegal/Undocumented NOP variants (operands are read and discarded)
     fn nop_immediate(&mut self) {
-        self.next_byte(); // Read and discard immediate value
-        self.bus.tick();
+        self.next_byte();
     }
 
     fn nop_zero_page(&mut self) {
-        self.next_byte(); // Read and discard zero page address
-        self.bus.tick();
-        self.bus.tick();
+        let addr = self.next_byte();
+        self.bus.read_byte(addr as u16);
     }
 
     fn nop_zero_page_x(&mut self) {
         let addr = self.next_byte();
-        self.bus.tick();
-        self.bus.tick();
-        self.bus.read_byte(addr.wrapping_add(self.x) as u16); // Dummy read
+        self.bus.read_byte(addr as u16); // Dummy read before indexing
+        self.bus.read_byte(addr.wrapping_add(self.x) as u16);
     }
 
     fn nop_absolute(&mut self) {
-        self.next_word(); // Read and discard absolute address
-        self.bus.tick();
-        self.bus.tick();
-        self.bus.tick();
+        let addr = self.next_word();
+        self.bus.read_byte(addr);
     }
 
     fn nop_absolute_x(&mut self) {
         let addr = self.next_word();
-        let final_addr = addr.wrapping_add(self.x as u16);
+        let final_addr = offset(addr, self.x);
         if cross(addr, self.x) {
-            self.bus.tick();
+            self.bus.read_byte(high_byte(addr) | low_byte(final_addr)); // Dummy read
         }
-        self.bus.read_byte(final_addr); // Dummy read
-        self.bus.tick();
-        self.bus.tick();
+        self.bus.read_byte(final_addr);
     }
     
     // LAX - Load A and X (illegal opcode)
@@ -1042,7 +1163,7 @@ impl Cpu {
         let address = self.operand_address(mode);
         let operand = self.bus.read_byte(address);
         let result = operand.wrapping_sub(1);
-        self.bus.tick();
+        self.dummy_write(address, operand);
         self.bus.write_byte(address, result);
         
         // Compare with A
@@ -1055,7 +1176,7 @@ impl Cpu {
         let address = self.operand_address(mode);
         let operand = self.bus.read_byte(address);
         let result = operand.wrapping_add(1);
-        self.bus.tick();
+        self.dummy_write(address, operand);
         self.bus.write_byte(address, result);
         
         // SBC with result
@@ -1072,7 +1193,7 @@ impl Cpu {
         let address = self.operand_address(mode);
         let operand = self.bus.read_byte(address);
         let result = operand << 1;
-        self.bus.tick();
+        self.dummy_write(address, operand);
         self.set_flag(Flag::Carry, operand & 0b1000_0000 != 0);
         self.bus.write_byte(address, result);
         
@@ -1088,7 +1209,7 @@ impl Cpu {
         let operand = self.bus.read_byte(address);
         let carry = if self.get_flag(Flag::Carry) { 1 } else { 0 };
         let result = (operand << 1) | carry;
-        self.bus.tick();
+        self.dummy_write(address, operand);
         self.set_flag(Flag::Carry, operand & 0b1000_0000 != 0);
         self.bus.write_byte(address, result);
         
@@ -1103,7 +1224,7 @@ impl Cpu {
         let address = self.operand_address(mode);
         let operand = self.bus.read_byte(address);
         let result = operand >> 1;
-        self.bus.tick();
+        self.dummy_write(address, operand);
         self.set_flag(Flag::Carry, operand & 0b0000_0001 != 0);
         self.bus.write_byte(address, result);
         
@@ -1119,7 +1240,7 @@ impl Cpu {
         let operand = self.bus.read_byte(address);
         let carry = if self.get_flag(Flag::Carry) { 0b1000_0000 } else { 0 };
         let result = (operand >> 1) | carry;
-        self.bus.tick();
+        self.dummy_write(address, operand);
         self.set_flag(Flag::Carry, operand & 0b0000_0001 != 0);
         self.bus.write_byte(address, result);
         
@@ -1130,6 +1251,132 @@ impl Cpu {
         self.set_flags_zero_negative(result_adc as u8);
         self.a = result_adc as u8;
     }
+
+    // ANC - AND immediate, then copy N into C (illegal opcode)
+    fn anc(&mut self) {
+        self.and(Mode::Immediate);
+        let negative = self.get_flag(Flag::Negative);
+        self.set_flag(Flag::Carry, negative);
+    }
+
+    // ALR - AND immediate, then LSR A (illegal opcode)
+    fn alr(&mut self) {
+        let value = self.a & self.read_operand(Mode::Immediate);
+        self.set_flag(Flag::Carry, value & 0b0000_0001 != 0);
+        let result = value >> 1;
+        self.set_flags_zero_negative(result);
+        self.a = result;
+    }
+
+    // ARR - AND immediate, then ROR A; C and V come from bits 6 and 5 (illegal opcode)
+    fn arr(&mut self) {
+        let value = self.a & self.read_operand(Mode::Immediate);
+        let result = (value >> 1) | (self.carry() << 7);
+        self.set_flags_zero_negative(result);
+        self.set_flag(Flag::Carry, result & 0b0100_0000 != 0);
+        self.set_flag(Flag::Overflow, ((result >> 6) ^ (result >> 5)) & 1 != 0);
+        self.a = result;
+    }
+
+    // XAA/ANE - A = (A | magic) & X & immediate (illegal, unstable opcode)
+    fn xaa(&mut self) {
+        let result = (self.a | UNSTABLE_MAGIC) & self.x & self.read_operand(Mode::Immediate);
+        self.set_flags_zero_negative(result);
+        self.a = result;
+    }
+
+    // LXA/LAX immediate - A = X = (A | magic) & immediate (illegal, unstable opcode)
+    fn lxa(&mut self) {
+        let result = (self.a | UNSTABLE_MAGIC) & self.read_operand(Mode::Immediate);
+        self.set_flags_zero_negative(result);
+        self.a = result;
+        self.x = result;
+    }
+
+    // AXS/SBX - X = (A & X) - immediate, without borrow (illegal opcode)
+    fn axs(&mut self) {
+        let operand = self.read_operand(Mode::Immediate);
+        let value = self.a & self.x;
+        self.set_flag(Flag::Carry, value >= operand);
+        let result = value.wrapping_sub(operand);
+        self.set_flags_zero_negative(result);
+        self.x = result;
+    }
+
+    // LAS - A = X = SP = memory & SP (illegal opcode)
+    fn las(&mut self) {
+        let result = self.read_operand(Mode::AbsoluteY) & self.sp;
+        self.set_flags_zero_negative(result);
+        self.a = result;
+        self.x = result;
+        self.sp = result;
+    }
+
+    /// SHA/SHX/SHY/TASの書き込み
+    ///
+    /// 書き込む値は`value & (ベースアドレスの上位バイト + 1)`。ページをまたぐと、
+    /// 書き込み先の上位バイトもその値に置き換わる。
+    fn store_and_high(&mut self, base: u16, index: u8, value: u8) {
+        let address = offset(base, index);
+        self.bus.read_byte(high_byte(base) | low_byte(address)); // Dummy read
+        let result = value & ((base >> 8) as u8).wrapping_add(1);
+        let address = if cross(base, index) {
+            (result as u16) << 8 | low_byte(address)
+        } else {
+            address
+        };
+        self.bus.write_byte(address, result);
+    }
+
+    fn sha_indirect_y(&mut self) {
+        let i = self.next_byte();
+        let base = self.bus.read_noncontinuous_word(i, low_byte(i as u16 + 1));
+        self.store_and_high(base, self.y, self.a & self.x);
+    }
+
+    fn sha_absolute_y(&mut self) {
+        let base = self.next_word();
+        self.store_and_high(base, self.y, self.a & self.x);
+    }
+
+    fn shx(&mut self) {
+        let base = self.next_word();
+        self.store_and_high(base, self.y, self.x);
+    }
+
+    fn shy(&mut self) {
+        let base = self.next_word();
+        self.store_and_high(base, self.x, self.y);
+    }
+
+    // TAS/SHS - SP = A & X, then store like SHA (illegal opcode)
+    fn tas(&mut self) {
+        let base = self.next_word();
+        self.sp = self.a & self.x;
+        self.store_and_high(base, self.y, self.sp);
+    }
+
+    // KIL/JAM - the CPU stops fetching instructions (illegal opcode)
+    fn jam(&mut self, opcode: u8) -> crate::Result<()> {
+        let pc = self.pc.wrapping_sub(1);
+        match self.invalid_opcode_policy {
+            InvalidOpcodePolicy::Halt => {
+                self.pc = pc;
+                Err(crate::NesError::InvalidInstruction(opcode))
+            }
+            InvalidOpcodePolicy::Continue => {
+                log::warn!("JAM instruction 0x{:02X} at PC: 0x{:04X}", opcode, pc);
+                self.nop();
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 fn cross(base: u16, offset: u8) -> bool {
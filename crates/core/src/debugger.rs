@@ -0,0 +1,933 @@
+//! # Debugger
+//! ブレークポイント・ウォッチポイント・条件式による実行の停止
+//!
+//! メモリのウォッチポイントはバス（CPUアドレス空間）とPPUの$2007（PPUアドレス空間）で
+//! 実際に起きたアクセスを`AccessWatch`に記録し、命令の実行後に条件式を評価して停止する。
+//! PCブレークポイントは命令の実行前に評価する。
+//!
+//! 条件式の例:
+//! ```text
+//! A == $20 && [$0300] > 5
+//! value & %1000_0000 != 0 || scanline >= 240
+//! ```
+//! 使える値は`A` `X` `Y` `P` `SP` `PC` `SCANLINE` `DOT` `FRAME` `CYCLES`、
+//! アクセス中のアドレスと値の`ADDR` `VALUE`、CPUメモリの`[式]`（大文字小文字は区別しない）。
+//! 演算子の優先順位は低い順に `||`, `&&`, 比較, `|`, `^`, `&`, `+ -`, 単項 `! - ~`。
+
+use crate::{Nes, NesError, Result};
+use std::fmt;
+
+/// アドレス空間
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AddressSpace {
+    /// CPUのアドレス空間 ($0000-$FFFF)
+    Cpu,
+    /// PPUのアドレス空間 ($0000-$3FFF、CPUからの$2007経由のアクセス）
+    Ppu,
+}
+
+impl AddressSpace {
+    /// ミラーを取り除いたアドレス（ブレークポイントの範囲はこのアドレスで指定する）
+    pub fn canonical(self, address: u16) -> u16 {
+        match self {
+            AddressSpace::Cpu => match address {
+                0x0000..=0x1FFF => address & 0x07FF,
+                0x2000..=0x3FFF => 0x2000 | (address & 0x0007),
+                _ => address,
+            },
+            AddressSpace::Ppu => match address & 0x3FFF {
+                address @ 0x3000..=0x3EFF => address - 0x1000,
+                address @ 0x3F00..=0x3FFF => 0x3F00 | (address & 0x001F),
+                address => address,
+            },
+        }
+    }
+}
+
+/// メモリアクセスの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Access {
+    Read,
+    Write,
+    /// 命令のフェッチ（PCブレークポイント）
+    Execute,
+}
+
+/// 実際に起きたメモリアクセス
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAccess {
+    pub space: AddressSpace,
+    pub access: Access,
+    /// アクセスしたアドレス（ミラーを含む）
+    pub address: u16,
+    /// 読み取った値または書き込んだ値
+    pub value: u8,
+}
+
+/// `AccessWatch`が記録するアドレス範囲
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct WatchRange {
+    start: u16,
+    end: u16,
+    read: bool,
+    write: bool,
+}
+
+/// バスに取り付けるアクセスの記録器
+///
+/// 監視範囲に一致したアクセスだけを記録し、`Nes`が命令ごとに取り出して評価する。
+#[derive(Debug)]
+pub struct AccessWatch {
+    space: AddressSpace,
+    ranges: Vec<WatchRange>,
+    /// 範囲に関係なく書き込みの有無を記録する（メモリエディタのウォッチポイント用）
+    track_writes: bool,
+    written: bool,
+    hits: Vec<MemoryAccess>,
+}
+
+impl AccessWatch {
+    pub fn new(space: AddressSpace) -> Self {
+        Self {
+            space,
+            ranges: Vec::new(),
+            track_writes: false,
+            written: false,
+            hits: Vec::new(),
+        }
+    }
+
+    /// アクセスを記録する（監視範囲外なら何もしない）
+    #[inline]
+    pub fn record(&mut self, access: Access, address: u16, value: u8) {
+        if self.ranges.is_empty() && !self.track_writes {
+            return;
+        }
+        self.written |= access == Access::Write;
+        let canonical = self.space.canonical(address);
+        let watched = self.ranges.iter().any(|r| {
+            (r.start..=r.end).contains(&canonical)
+                && match access {
+                    Access::Read => r.read,
+                    Access::Write => r.write,
+                    Access::Execute => false,
+                }
+        });
+        if watched {
+            self.hits.push(MemoryAccess {
+                space: self.space,
+                access,
+                address,
+                value,
+            });
+        }
+    }
+
+    /// 記録したアクセスを取り出す
+    pub fn take_hits(&mut self) -> Vec<MemoryAccess> {
+        std::mem::take(&mut self.hits)
+    }
+
+    /// 前回の呼び出しから書き込みがあったか
+    pub fn take_written(&mut self) -> bool {
+        std::mem::take(&mut self.written)
+    }
+
+    fn set_ranges(&mut self, ranges: Vec<WatchRange>) {
+        self.ranges = ranges;
+        self.hits.clear();
+    }
+
+    pub(crate) fn set_track_writes(&mut self, enabled: bool) {
+        self.track_writes = enabled;
+        self.written = false;
+    }
+}
+
+/// ブレークポイント（PCブレークポイントとメモリのウォッチポイント）
+#[derive(Debug, Clone, PartialEq)]
+pub struct Breakpoint {
+    /// `Debugger::add_breakpoint`で割り当てられるID
+    pub id: u32,
+    pub space: AddressSpace,
+    /// 範囲の先頭と末尾（両端を含む、ミラーを取り除いたアドレス）
+    pub start: u16,
+    pub end: u16,
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+    /// 停止する条件（`None`なら常に停止）
+    pub condition: Option<Condition>,
+    /// この回数目以降のヒットで停止する（1なら毎回）
+    pub hit_target: u64,
+    /// 条件を満たした回数
+    pub hits: u64,
+    pub enabled: bool,
+}
+
+impl Breakpoint {
+    fn new(space: AddressSpace, start: u16, end: u16) -> Self {
+        Self {
+            id: 0,
+            space,
+            start: start.min(end),
+            end: start.max(end),
+            read: false,
+            write: false,
+            execute: false,
+            condition: None,
+            hit_target: 1,
+            hits: 0,
+            enabled: true,
+        }
+    }
+
+    /// PCが`address`に達したら命令の実行前に停止する
+    pub fn execute(address: u16) -> Self {
+        Self {
+            execute: true,
+            ..Self::new(AddressSpace::Cpu, address, address)
+        }
+    }
+
+    /// 範囲内の読み取りで停止する
+    pub fn read(space: AddressSpace, start: u16, end: u16) -> Self {
+        Self {
+            read: true,
+            ..Self::new(space, start, end)
+        }
+    }
+
+    /// 範囲内の書き込みで停止する
+    pub fn write(space: AddressSpace, start: u16, end: u16) -> Self {
+        Self {
+            write: true,
+            ..Self::new(space, start, end)
+        }
+    }
+
+    /// 範囲内の読み取りと書き込みで停止する
+    pub fn access(space: AddressSpace, start: u16, end: u16) -> Self {
+        Self {
+            read: true,
+            write: true,
+            ..Self::new(space, start, end)
+        }
+    }
+
+    /// 条件式を設定する
+    pub fn with_condition(mut self, expression: &str) -> Result<Self> {
+        self.condition = Some(Condition::parse(expression)?);
+        Ok(self)
+    }
+
+    /// `count`回目以降のヒットで停止するようにする
+    pub fn with_hit_target(mut self, count: u64) -> Self {
+        self.hit_target = count.max(1);
+        self
+    }
+
+    fn matches(&self, space: AddressSpace, access: Access, address: u16) -> bool {
+        let kind = match access {
+            Access::Read => self.read,
+            Access::Write => self.write,
+            Access::Execute => self.execute,
+        };
+        self.enabled
+            && kind
+            && self.space == space
+            && (self.start..=self.end).contains(&space.canonical(address))
+    }
+}
+
+/// `run_until`で停止する目標
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunTarget {
+    /// NMIハンドラの先頭
+    Nmi,
+    /// PPUが指定のスキャンラインに入った直後の命令境界
+    Scanline(u16),
+    /// PPUのフレームが切り替わった直後の命令境界
+    FrameEnd,
+}
+
+/// 実行が止まった理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// 1フレーム分のサイクルを実行した
+    FrameComplete,
+    /// ブレークポイントで停止した（メモリのウォッチポイントならそのアクセス）
+    Breakpoint {
+        id: u32,
+        access: Option<MemoryAccess>,
+    },
+    /// `run_until`の目標に達した
+    Target(RunTarget),
+}
+
+/// 命令の実行前の位置（`RunTarget`の判定に使う）
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Position {
+    scanline: u16,
+    frame: u64,
+    nmi_count: u64,
+}
+
+impl Position {
+    pub(crate) fn of(nes: &Nes) -> Self {
+        Self {
+            scanline: nes.cpu.bus.ppu.scanline(),
+            frame: nes.cpu.bus.ppu.frame(),
+            nmi_count: nes.cpu.bus.nmi_count(),
+        }
+    }
+}
+
+/// ブレークポイントと実行目標の管理
+#[derive(Debug)]
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    next_id: u32,
+    run_target: Option<RunTarget>,
+    /// PCブレークポイントで停止したアドレス（再開時に同じ場所で止まらないようにする）
+    resume_pc: Option<u16>,
+    /// ブレークポイントが変更され、バスの監視範囲を更新する必要がある
+    dirty: bool,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self {
+            breakpoints: Vec::new(),
+            next_id: 1,
+            run_target: None,
+            resume_pc: None,
+            dirty: false,
+        }
+    }
+
+    /// ブレークポイントを追加してIDを返す
+    pub fn add_breakpoint(&mut self, mut breakpoint: Breakpoint) -> u32 {
+        breakpoint.id = self.next_id;
+        self.next_id += 1;
+        self.breakpoints.push(breakpoint);
+        self.dirty = true;
+        self.next_id - 1
+    }
+
+    /// ブレークポイントを削除
+    pub fn remove_breakpoint(&mut self, id: u32) -> bool {
+        let len = self.breakpoints.len();
+        self.breakpoints.retain(|b| b.id != id);
+        self.dirty = true;
+        self.breakpoints.len() != len
+    }
+
+    /// ブレークポイントをすべて削除
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+        self.dirty = true;
+    }
+
+    /// ブレークポイントの有効/無効を切り替える
+    pub fn set_enabled(&mut self, id: u32, enabled: bool) -> bool {
+        self.dirty = true;
+        match self.breakpoints.iter_mut().find(|b| b.id == id) {
+            Some(b) => {
+                b.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn breakpoint(&self, id: u32) -> Option<&Breakpoint> {
+        self.breakpoints.iter().find(|b| b.id == id)
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// ブレークポイントか実行目標があるか
+    pub fn is_active(&self) -> bool {
+        !self.breakpoints.is_empty() || self.run_target.is_some()
+    }
+
+    /// 次に停止する目標を設定（到達すると解除される）
+    pub fn set_run_target(&mut self, target: Option<RunTarget>) {
+        self.run_target = target;
+    }
+
+    pub fn run_target(&self) -> Option<RunTarget> {
+        self.run_target
+    }
+
+    /// ブレークポイントが変更されていればCPUとPPUの監視範囲を更新する
+    pub(crate) fn sync(&mut self, cpu: &mut AccessWatch, ppu: &mut AccessWatch) {
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+        let ranges = |space| {
+            self.breakpoints
+                .iter()
+                .filter(|b| b.enabled && b.space == space && (b.read || b.write))
+                .map(|b| WatchRange {
+                    start: b.start,
+                    end: b.end,
+                    read: b.read,
+                    write: b.write,
+                })
+                .collect()
+        };
+        cpu.set_ranges(ranges(AddressSpace::Cpu));
+        ppu.set_ranges(ranges(AddressSpace::Ppu));
+    }
+
+    /// 命令の実行前にPCブレークポイントを評価する
+    pub(crate) fn check_execute(&mut self, nes: &Nes) -> Option<StopReason> {
+        let pc = nes.cpu.pc();
+        if self.resume_pc.take() == Some(pc) {
+            return None;
+        }
+        let access = MemoryAccess {
+            space: AddressSpace::Cpu,
+            access: Access::Execute,
+            address: pc,
+            value: nes.peek_memory(pc),
+        };
+        let id = self.hit(nes, &access)?;
+        self.resume_pc = Some(pc);
+        Some(StopReason::Breakpoint { id, access: None })
+    }
+
+    /// 命令の実行後に、記録されたアクセスと実行目標を評価する
+    pub(crate) fn check_after(
+        &mut self,
+        nes: &Nes,
+        accesses: &[MemoryAccess],
+        before: Position,
+    ) -> Option<StopReason> {
+        let mut reason = None;
+        for access in accesses {
+            // 同じ命令の残りのアクセスもヒット回数に数える
+            if let Some(id) = self.hit(nes, access) {
+                reason.get_or_insert(StopReason::Breakpoint {
+                    id,
+                    access: Some(*access),
+                });
+            }
+        }
+        if reason.is_some() {
+            return reason;
+        }
+
+        let now = Position::of(nes);
+        let reached = match self.run_target? {
+            RunTarget::Nmi => now.nmi_count != before.nmi_count,
+            RunTarget::Scanline(line) => now.scanline == line && before.scanline != line,
+            RunTarget::FrameEnd => now.frame != before.frame,
+        };
+        if reached {
+            self.run_target.take().map(StopReason::Target)
+        } else {
+            None
+        }
+    }
+
+    /// `access`に一致するブレークポイントのヒット回数を数え、停止するもののIDを返す
+    fn hit(&mut self, nes: &Nes, access: &MemoryAccess) -> Option<u32> {
+        let mut stop = None;
+        for b in self
+            .breakpoints
+            .iter_mut()
+            .filter(|b| b.matches(access.space, access.access, access.address))
+        {
+            if b.condition
+                .as_ref()
+                .is_none_or(|c| c.evaluate(nes, Some(access)) != 0)
+            {
+                b.hits += 1;
+                if b.hits >= b.hit_target {
+                    stop.get_or_insert(b.id);
+                }
+            }
+        }
+        stop
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 条件式の値として参照できるもの
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Variable {
+    A,
+    X,
+    Y,
+    P,
+    Sp,
+    Pc,
+    Scanline,
+    Dot,
+    Frame,
+    Cycles,
+    Value,
+    Address,
+}
+
+impl Variable {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_uppercase().as_str() {
+            "A" => Variable::A,
+            "X" => Variable::X,
+            "Y" => Variable::Y,
+            "P" => Variable::P,
+            "SP" => Variable::Sp,
+            "PC" => Variable::Pc,
+            "SCANLINE" => Variable::Scanline,
+            "DOT" => Variable::Dot,
+            "FRAME" => Variable::Frame,
+            "CYCLES" => Variable::Cycles,
+            "VALUE" => Variable::Value,
+            "ADDR" => Variable::Address,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UnaryOp {
+    Not,
+    Negate,
+    Complement,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Add,
+    Sub,
+}
+
+impl BinaryOp {
+    /// 演算子と優先順位（大きいほど強く結合する）
+    const TABLE: [(&'static str, BinaryOp, u8); 13] = [
+        ("||", BinaryOp::Or, 1),
+        ("&&", BinaryOp::And, 2),
+        ("==", BinaryOp::Eq, 3),
+        ("!=", BinaryOp::Ne, 3),
+        ("<=", BinaryOp::Le, 3),
+        (">=", BinaryOp::Ge, 3),
+        ("<", BinaryOp::Lt, 3),
+        (">", BinaryOp::Gt, 3),
+        ("|", BinaryOp::BitOr, 4),
+        ("^", BinaryOp::BitXor, 5),
+        ("&", BinaryOp::BitAnd, 6),
+        ("+", BinaryOp::Add, 7),
+        ("-", BinaryOp::Sub, 7),
+    ];
+
+    fn apply(self, lhs: i64, rhs: i64) -> i64 {
+        match self {
+            BinaryOp::Or => (lhs != 0 || rhs != 0) as i64,
+            BinaryOp::And => (lhs != 0 && rhs != 0) as i64,
+            BinaryOp::Eq => (lhs == rhs) as i64,
+            BinaryOp::Ne => (lhs != rhs) as i64,
+            BinaryOp::Lt => (lhs < rhs) as i64,
+            BinaryOp::Le => (lhs <= rhs) as i64,
+            BinaryOp::Gt => (lhs > rhs) as i64,
+            BinaryOp::Ge => (lhs >= rhs) as i64,
+            BinaryOp::BitOr => lhs | rhs,
+            BinaryOp::BitXor => lhs ^ rhs,
+            BinaryOp::BitAnd => lhs & rhs,
+            BinaryOp::Add => lhs.wrapping_add(rhs),
+            BinaryOp::Sub => lhs.wrapping_sub(rhs),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(i64),
+    Variable(Variable),
+    /// CPUメモリの値 `[式]`（副作用のない読み取り）
+    Memory(Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn evaluate(&self, nes: &Nes, access: Option<&MemoryAccess>) -> i64 {
+        match self {
+            Expr::Number(n) => *n,
+            Expr::Variable(v) => {
+                let cpu = &nes.cpu;
+                match v {
+                    Variable::A => cpu.a as i64,
+                    Variable::X => cpu.x as i64,
+                    Variable::Y => cpu.y as i64,
+                    Variable::P => cpu.p as i64,
+                    Variable::Sp => cpu.sp() as i64,
+                    Variable::Pc => cpu.pc() as i64,
+                    Variable::Scanline => cpu.bus.ppu.scanline() as i64,
+                    Variable::Dot => cpu.bus.ppu.cycle() as i64,
+                    Variable::Frame => cpu.bus.ppu.frame() as i64,
+                    Variable::Cycles => cpu.bus.cycles as i64,
+                    Variable::Value => access.map_or(0, |a| a.value as i64),
+                    Variable::Address => access.map_or(0, |a| a.address as i64),
+                }
+            }
+            Expr::Memory(address) => nes.peek_memory(address.evaluate(nes, access) as u16) as i64,
+            Expr::Unary(op, operand) => {
+                let value = operand.evaluate(nes, access);
+                match op {
+                    UnaryOp::Not => (value == 0) as i64,
+                    UnaryOp::Negate => value.wrapping_neg(),
+                    UnaryOp::Complement => !value,
+                }
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = lhs.evaluate(nes, access);
+                // && と || は短絡評価する
+                match op {
+                    BinaryOp::And if lhs == 0 => 0,
+                    BinaryOp::Or if lhs != 0 => 1,
+                    _ => op.apply(lhs, rhs.evaluate(nes, access)),
+                }
+            }
+        }
+    }
+}
+
+/// ブレークポイントの条件式
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    source: String,
+    expr: Expr,
+}
+
+impl Condition {
+    /// 条件式を解析する
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut parser = Parser {
+            source,
+            tokens: tokenize(source)?,
+            pos: 0,
+        };
+        let expr = parser.expression(0)?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(parser.error(&format!("unexpected '{}'", token)));
+        }
+        Ok(Self {
+            source: source.trim().to_string(),
+            expr,
+        })
+    }
+
+    /// 式の値（比較と論理演算は0または1）
+    pub fn evaluate(&self, nes: &Nes, access: Option<&MemoryAccess>) -> i64 {
+        self.expr.evaluate(nes, access)
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// 数値（`$FF` `0xFF` `%1010` `255`）、名前、演算子・括弧に分割する
+fn tokenize(source: &str) -> Result<Vec<String>> {
+    const SYMBOLS: [&str; 19] = [
+        "||", "&&", "==", "!=", "<=", ">=", "<", ">", "|", "^", "&", "+", "-", "!", "~", "(", ")",
+        "[", "]",
+    ];
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+    while !rest.is_empty() {
+        if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(*s)) {
+            tokens.push(symbol.to_string());
+            rest = &rest[symbol.len()..];
+        } else {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '$' || c == '%' || c == '_'))
+                .unwrap_or(rest.len());
+            if len == 0 {
+                return Err(NesError::InvalidExpression(format!(
+                    "unexpected character '{}' in '{}'",
+                    rest.chars().next().unwrap_or_default(),
+                    source
+                )));
+            }
+            tokens.push(rest[..len].to_string());
+            rest = &rest[len..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+fn parse_number(token: &str) -> Option<i64> {
+    let token = token.replace('_', "");
+    let (digits, radix) = if let Some(hex) = token.strip_prefix('$') {
+        (hex, 16)
+    } else if let Some(hex) = token
+        .strip_prefix("0x")
+        .or_else(|| token.strip_prefix("0X"))
+    {
+        (hex, 16)
+    } else if let Some(bin) = token.strip_prefix('%') {
+        (bin, 2)
+    } else {
+        (token.as_str(), 10)
+    };
+    i64::from_str_radix(digits, radix).ok()
+}
+
+/// 優先順位法による再帰下降パーサー
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> NesError {
+        NesError::InvalidExpression(format!("{} in '{}'", message, self.source))
+    }
+
+    fn next(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.pos)?.clone();
+        self.pos += 1;
+        Some(token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<()> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            _ => Err(self.error(&format!("expected '{}'", expected))),
+        }
+    }
+
+    /// 優先順位が`min_precedence`より高い二項演算子をまとめる
+    fn expression(&mut self, min_precedence: u8) -> Result<Expr> {
+        let mut lhs = self.unary()?;
+        while let Some(&(_, op, precedence)) = self
+            .tokens
+            .get(self.pos)
+            .and_then(|t| BinaryOp::TABLE.iter().find(|(symbol, _, _)| symbol == t))
+        {
+            if precedence <= min_precedence {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.expression(precedence)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        let token = self
+            .next()
+            .ok_or_else(|| self.error("unexpected end of expression"))?;
+        let unary = |op, parser: &mut Self| Ok(Expr::Unary(op, Box::new(parser.unary()?)));
+        match token.as_str() {
+            "!" => unary(UnaryOp::Not, self),
+            "-" => unary(UnaryOp::Negate, self),
+            "~" => unary(UnaryOp::Complement, self),
+            "(" => {
+                let expr = self.expression(0)?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            "[" => {
+                let expr = self.expression(0)?;
+                self.expect("]")?;
+                Ok(Expr::Memory(Box::new(expr)))
+            }
+            _ => {
+                if let Some(variable) = Variable::from_name(&token) {
+                    Ok(Expr::Variable(variable))
+                } else if let Some(number) = parse_number(&token) {
+                    Ok(Expr::Number(number))
+                } else {
+                    Err(self.error(&format!("unknown value '{}'", token)))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_rom::{self, COUNTER};
+
+    #[test]
+    fn test_condition_evaluation() {
+        let mut nes = test_rom::nes(COUNTER);
+        nes.cpu.a = 0x20;
+        nes.poke_memory(0x0300, 6);
+        let eval = |source: &str| Condition::parse(source).unwrap().evaluate(&nes, None);
+
+        assert_eq!(eval("A == $20 && [$0300] > 5"), 1);
+        assert_eq!(eval("a == 0x20 && [$300] > 6"), 0);
+        assert_eq!(eval("[$0300] & %0110 == 6"), 1);
+        assert_eq!(eval("1 + 2 - 3 || !0"), 1);
+        assert_eq!(eval("-(A + 1)"), -0x21);
+        assert_eq!(eval("[$2FF + 1] ^ ~0"), !6);
+        assert!(Condition::parse("A ==").is_err());
+        assert!(Condition::parse("(A").is_err());
+        assert!(Condition::parse("B == 1").is_err());
+        assert!(Condition::parse("A # 1").is_err());
+        assert_eq!(Condition::parse(" A==1 ").unwrap().to_string(), "A==1");
+    }
+
+    #[test]
+    fn test_canonical_address() {
+        assert_eq!(AddressSpace::Cpu.canonical(0x1B00), 0x0300);
+        assert_eq!(AddressSpace::Cpu.canonical(0x3FFA), 0x2002);
+        assert_eq!(AddressSpace::Ppu.canonical(0x3400), 0x2400);
+        assert_eq!(AddressSpace::Ppu.canonical(0x3F3F), 0x3F1F);
+    }
+
+    #[test]
+    fn test_execute_breakpoint_with_hit_target() {
+        let mut nes = test_rom::nes(COUNTER);
+        let id = nes
+            .debugger_mut()
+            .add_breakpoint(Breakpoint::execute(0x8003).with_hit_target(3));
+
+        let reason = nes.step_frame().unwrap();
+        assert_eq!(reason, StopReason::Breakpoint { id, access: None });
+        assert_eq!(nes.cpu.pc(), 0x8003);
+        assert_eq!(nes.peek_memory(0x0300), 3);
+
+        // 再開すると同じ場所では止まらず、次のループで止まる
+        assert_eq!(
+            nes.step_frame().unwrap(),
+            StopReason::Breakpoint { id, access: None }
+        );
+        assert_eq!(nes.peek_memory(0x0300), 4);
+        assert_eq!(nes.debugger().breakpoint(id).unwrap().hits, 4);
+    }
+
+    #[test]
+    fn test_conditional_write_watchpoint() {
+        let mut nes = test_rom::nes(COUNTER);
+        // ミラーへのアクセスも正規のアドレスで一致する
+        let breakpoint = Breakpoint::write(AddressSpace::Cpu, 0x0300, 0x0300)
+            .with_condition("value == 10 && addr == $0300")
+            .unwrap();
+        let id = nes.debugger_mut().add_breakpoint(breakpoint);
+
+        // INCは変更前の値を書き戻してから新しい値を書き込む
+        let reason = nes.step_frame().unwrap();
+        let access = MemoryAccess {
+            space: AddressSpace::Cpu,
+            access: Access::Write,
+            address: 0x0300,
+            value: 10,
+        };
+        assert_eq!(
+            reason,
+            StopReason::Breakpoint {
+                id,
+                access: Some(access)
+            }
+        );
+        assert_eq!(nes.cpu.pc(), 0x8003);
+
+        nes.debugger_mut().set_enabled(id, false);
+        assert_eq!(nes.step_frame().unwrap(), StopReason::FrameComplete);
+    }
+
+    #[test]
+    fn test_ppu_watchpoint() {
+        let mut nes = test_rom::nes(&[
+            0xA9, 0x21, 0x8D, 0x06, 0x20, // LDA #$21; STA $2006
+            0xA9, 0x00, 0x8D, 0x06, 0x20, // LDA #$00; STA $2006
+            0xA9, 0x55, 0x8D, 0x07, 0x20, // LDA #$55; STA $2007
+            0x4C, 0x0F, 0x80, // JMP *
+        ]);
+        let id =
+            nes.debugger_mut()
+                .add_breakpoint(Breakpoint::write(AddressSpace::Ppu, 0x2000, 0x2FFF));
+
+        let reason = nes.step_frame().unwrap();
+        let access = MemoryAccess {
+            space: AddressSpace::Ppu,
+            access: Access::Write,
+            address: 0x2100,
+            value: 0x55,
+        };
+        assert_eq!(
+            reason,
+            StopReason::Breakpoint {
+                id,
+                access: Some(access)
+            }
+        );
+    }
+
+    #[test]
+    fn test_memory_editor_watchpoint_follows_bus_writes() {
+        use crate::memory_editor::MemoryRegion;
+
+        let mut nes = test_rom::nes(COUNTER);
+        nes.poke_memory(0x0300, 0x40);
+        let id = nes.add_watchpoint(MemoryRegion::Ram, 0x0300, "counter");
+        for _ in 0..3 {
+            nes.step().unwrap();
+        }
+        let watchpoint = nes.watchpoints().into_iter().find(|w| w.id == id).unwrap();
+        assert_eq!(watchpoint.last_value, 0x41);
+        assert_eq!(watchpoint.changes, 1);
+
+        assert!(nes.remove_watchpoint(id));
+        assert!(nes.watchpoints().is_empty());
+    }
+
+    #[test]
+    fn test_run_until() {
+        let mut nes = test_rom::nes(COUNTER);
+        let target = RunTarget::Scanline(100);
+        assert_eq!(
+            nes.run_until(target, 2).unwrap(),
+            StopReason::Target(target)
+        );
+        assert_eq!(nes.ppu_state().scanline(), 100);
+        assert_eq!(nes.debugger().run_target(), None);
+
+        let frame = nes.ppu_state().frame();
+        let target = RunTarget::FrameEnd;
+        assert_eq!(
+            nes.run_until(target, 2).unwrap(),
+            StopReason::Target(target)
+        );
+        assert_eq!(nes.ppu_state().frame(), frame + 1);
+
+        // NMIが無効なので到達しない
+        assert_eq!(
+            nes.run_until(RunTarget::Nmi, 2).unwrap(),
+            StopReason::FrameComplete
+        );
+    }
+}
@@ -9,7 +9,7 @@ pub enum NesError {
     InvalidRom(String),
 
     #[error("Unsupported mapper: {0}")]
-    UnsupportedMapper(u8),
+    UnsupportedMapper(u16),
 
     #[error("Invalid CPU instruction: {0:#04x}")]
     InvalidInstruction(u8),
@@ -17,6 +17,23 @@ pub enum NesError {
     #[error("Memory access violation at address: {0:#06x}")]
     MemoryAccessViolation(u16),
 
+    #[error("Invalid save state: {0}")]
+    InvalidState(String),
+
+    #[error(
+        "Save state ROM mismatch: state is for {expected:#010x}, loaded ROM is {actual:#010x}"
+    )]
+    StateRomMismatch { expected: u32, actual: u32 },
+
+    #[error("Invalid battery save: {0}")]
+    InvalidBatteryRam(String),
+
+    #[error("Invalid debugger expression: {0}")]
+    InvalidExpression(String),
+
+    #[error("Invalid movie: {0}")]
+    InvalidMovie(String),
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
@@ -0,0 +1,496 @@
+//! # GDB Remote Serial Protocol
+//! GDBのリモートシリアルプロトコル（RSP）のサブセットでCPUをデバッグするスタブ
+//!
+//! 通信路（TCPなど）はフロントエンドが用意し、ノンブロッキングのストリームとして渡す。
+//! レジスタの番号と`g`パケットの並び:
+//! ```text
+//! 0: A, 1: X, 2: Y, 3: P, 4: SP (各8ビット), 5: PC (16ビット、リトルエンディアン)
+//! ```
+//! 対応するパケット: `?` `g` `G` `p` `P` `m` `M` `Z0`-`Z4` `z0`-`z4` `s` `c` `D` `k`、
+//! `qSupported` `QStartNoAckMode` `qAttached` `qC` `qfThreadInfo` `qsThreadInfo` `H`、割り込み (0x03)。
+//! メモリは`Nes::peek_memory`/`poke_memory`でアクセスするため、副作用のあるレジスタは読めない。
+//!
+//! 参考: https://sourceware.org/gdb/current/onlinedocs/gdb.html/Remote-Protocol.html
+
+use crate::debugger::{AddressSpace, Breakpoint, StopReason};
+use crate::{Nes, NesError, Result};
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read, Write};
+
+/// `m`パケットで一度に読めるバイト数の上限
+const MAX_MEMORY_READ: usize = 0x800;
+
+/// 停止の原因となったシグナル
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGABRT: u8 = 6;
+
+/// GDBのリモートスタブ
+pub struct GdbStub<S: Read + Write> {
+    stream: S,
+    /// 受信途中のデータ
+    input: Vec<u8>,
+    halted: bool,
+    detached: bool,
+    /// `QStartNoAckMode`で確認応答（+/-）を省略する
+    no_ack: bool,
+    /// 最後に送ったパケット（`-`を受け取ったら再送する）
+    last_packet: Vec<u8>,
+    /// 最後の停止理由（`?`への応答）
+    last_stop: String,
+    /// (Zパケットの種類, アドレス) -> デバッガのブレークポイントID
+    breakpoints: HashMap<(u8, u16), u32>,
+}
+
+impl<S: Read + Write> GdbStub<S> {
+    /// 接続直後のスタブ（GDBの接続時の作法に従って停止状態で始まる）
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            input: Vec::new(),
+            halted: true,
+            detached: false,
+            no_ack: false,
+            last_packet: Vec::new(),
+            last_stop: format!("S{:02x}", SIGTRAP),
+            breakpoints: HashMap::new(),
+        }
+    }
+
+    /// GDBが停止を指示している（フロントエンドはフレームを進めない）
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// GDBが切断した
+    pub fn is_detached(&self) -> bool {
+        self.detached
+    }
+
+    /// 受信したパケットをすべて処理する
+    pub fn process(&mut self, nes: &mut Nes) -> Result<()> {
+        let mut buf = [0u8; 1024];
+        while !self.detached {
+            match self.stream.read(&mut buf) {
+                Ok(0) => self.detach(nes),
+                Ok(n) => {
+                    self.input.extend_from_slice(&buf[..n]);
+                    self.handle_input(nes)?;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// `step_frame`の結果を伝える（停止した場合はGDBに停止理由を送る）
+    pub fn notify(&mut self, result: &Result<StopReason>) -> Result<()> {
+        let reply = match result {
+            Ok(StopReason::FrameComplete) => return Ok(()),
+            Ok(StopReason::Breakpoint {
+                id,
+                access: Some(access),
+            }) => {
+                let kind = match self.breakpoints.iter().find(|(_, b)| *b == id) {
+                    Some(((4, _), _)) => "awatch",
+                    Some(((3, _), _)) => "rwatch",
+                    _ => "watch",
+                };
+                format!("T{:02x}{}:{:04x};", SIGTRAP, kind, access.address)
+            }
+            Ok(_) => format!("S{:02x}", SIGTRAP),
+            Err(NesError::InvalidInstruction(_)) => format!("S{:02x}", SIGILL),
+            Err(_) => format!("S{:02x}", SIGABRT),
+        };
+        self.stop(&reply)
+    }
+
+    fn stop(&mut self, reply: &str) -> Result<()> {
+        self.halted = true;
+        self.last_stop = reply.to_string();
+        self.send(reply)
+    }
+
+    /// 切断し、GDBが設定したブレークポイントを取り除いて実行を再開する
+    fn detach(&mut self, nes: &mut Nes) {
+        for (_, id) in self.breakpoints.drain() {
+            nes.debugger_mut().remove_breakpoint(id);
+        }
+        self.halted = false;
+        self.detached = true;
+    }
+
+    /// `$data#checksum`形式のパケットと確認応答、割り込みを処理する
+    fn handle_input(&mut self, nes: &mut Nes) -> Result<()> {
+        while let Some(&first) = self.input.first() {
+            match first {
+                b'$' => {
+                    let Some(hash) = self.input.iter().position(|&b| b == b'#') else {
+                        return Ok(());
+                    };
+                    if self.input.len() < hash + 3 {
+                        return Ok(());
+                    }
+                    let packet: Vec<u8> = self.input.drain(..hash + 3).collect();
+                    let data = &packet[1..hash];
+                    let valid = std::str::from_utf8(&packet[hash + 1..])
+                        .ok()
+                        .and_then(|s| u8::from_str_radix(s, 16).ok())
+                        == Some(checksum(data));
+                    if !self.no_ack {
+                        self.stream.write_all(if valid { b"+" } else { b"-" })?;
+                    }
+                    if valid {
+                        let data = String::from_utf8_lossy(data).into_owned();
+                        if let Some(reply) = self.handle_packet(nes, &data)? {
+                            self.send(&reply)?;
+                        }
+                    }
+                }
+                b'-' => {
+                    self.input.remove(0);
+                    let packet = self.last_packet.clone();
+                    self.stream.write_all(&packet)?;
+                }
+                0x03 => {
+                    self.input.remove(0);
+                    if !self.halted {
+                        self.stop(&format!("S{:02x}", SIGINT))?;
+                    }
+                }
+                // '+'と不正なバイトは読み捨てる
+                _ => {
+                    self.input.remove(0);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, data: &str) -> Result<()> {
+        let packet = format!("${}#{:02x}", data, checksum(data.as_bytes()));
+        self.stream.write_all(packet.as_bytes())?;
+        self.stream.flush()?;
+        self.last_packet = packet.into_bytes();
+        Ok(())
+    }
+
+    /// パケットを処理して応答を返す（`None`なら停止するまで応答しない）
+    fn handle_packet(&mut self, nes: &mut Nes, packet: &str) -> Result<Option<String>> {
+        let (command, args) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+        let reply = match command {
+            "?" => self.last_stop.clone(),
+            "g" => {
+                let cpu = nes.cpu_state();
+                let pc = cpu.pc().to_le_bytes();
+                hex(&[cpu.a, cpu.x, cpu.y, cpu.p, cpu.sp(), pc[0], pc[1]])
+            }
+            "G" => match parse_hex_bytes(args) {
+                Some(bytes) if bytes.len() == 7 => {
+                    for (register, &value) in bytes[..5].iter().enumerate() {
+                        set_register(nes, register, value as u16);
+                    }
+                    set_register(nes, 5, u16::from_le_bytes([bytes[5], bytes[6]]));
+                    "OK".to_string()
+                }
+                _ => error(),
+            },
+            "p" => match parse_hex(args).and_then(|r| register(nes, r as usize)) {
+                Some(bytes) => hex(&bytes),
+                None => error(),
+            },
+            "P" => {
+                let write = args.split_once('=').and_then(|(register, value)| {
+                    let register = parse_hex(register)? as usize;
+                    let bytes = parse_hex_bytes(value)?;
+                    let value = match bytes.as_slice() {
+                        [value] if register < 5 => *value as u16,
+                        [low, high] if register == 5 => u16::from_le_bytes([*low, *high]),
+                        _ => return None,
+                    };
+                    set_register(nes, register, value);
+                    Some(())
+                });
+                write.map_or_else(error, |()| "OK".to_string())
+            }
+            "m" => match parse_address_length(args) {
+                Some((address, length)) => {
+                    hex(&nes.read_memory_range(address, length.min(MAX_MEMORY_READ)))
+                }
+                None => error(),
+            },
+            "M" => {
+                let write = args.split_once(':').and_then(|(range, data)| {
+                    let (address, length) = parse_address_length(range)?;
+                    let bytes = parse_hex_bytes(data).filter(|b| b.len() == length)?;
+                    for (i, &value) in bytes.iter().enumerate() {
+                        nes.poke_memory(address.wrapping_add(i as u16), value);
+                    }
+                    Some(())
+                });
+                write.map_or_else(error, |()| "OK".to_string())
+            }
+            "Z" | "z" => self.handle_breakpoint(nes, command == "Z", args),
+            "s" => {
+                if let Some(address) = parse_hex(args) {
+                    nes.cpu_state_mut().set_pc(address as u16);
+                }
+                let signal = match nes.step() {
+                    Ok(_) => SIGTRAP,
+                    Err(NesError::InvalidInstruction(_)) => SIGILL,
+                    Err(e) => return Err(e),
+                };
+                self.last_stop = format!("S{:02x}", signal);
+                self.last_stop.clone()
+            }
+            "c" => {
+                if let Some(address) = parse_hex(args) {
+                    nes.cpu_state_mut().set_pc(address as u16);
+                }
+                self.halted = false;
+                return Ok(None);
+            }
+            "D" => {
+                self.detach(nes);
+                "OK".to_string()
+            }
+            "k" => {
+                self.detach(nes);
+                return Ok(None);
+            }
+            "H" => "OK".to_string(),
+            _ => match packet {
+                _ if packet.starts_with("qSupported") => {
+                    "PacketSize=1000;QStartNoAckMode+".to_string()
+                }
+                "QStartNoAckMode" => {
+                    self.send("OK")?;
+                    self.no_ack = true;
+                    return Ok(None);
+                }
+                "qAttached" => "1".to_string(),
+                "qC" => "QC1".to_string(),
+                "qfThreadInfo" => "m1".to_string(),
+                "qsThreadInfo" => "l".to_string(),
+                // 未対応のパケットには空の応答を返す
+                _ => String::new(),
+            },
+        };
+        Ok(Some(reply))
+    }
+
+    /// `Z type,addr,kind` / `z type,addr,kind`（0/1: 実行, 2: 書き込み, 3: 読み取り, 4: アクセス）
+    fn handle_breakpoint(&mut self, nes: &mut Nes, insert: bool, args: &str) -> String {
+        let mut parts = args.split(',');
+        let (Some(kind), Some(address), Some(length)) = (
+            parts.next().and_then(|k| k.parse::<u8>().ok()),
+            parts.next().and_then(parse_hex),
+            parts.next().and_then(parse_hex),
+        ) else {
+            return error();
+        };
+        let address = address as u16;
+        let end = address.wrapping_add((length as u16).max(1) - 1);
+        let breakpoint = match kind {
+            0 | 1 => Breakpoint::execute(address),
+            2 => Breakpoint::write(AddressSpace::Cpu, address, end),
+            3 => Breakpoint::read(AddressSpace::Cpu, address, end),
+            4 => Breakpoint::access(AddressSpace::Cpu, address, end),
+            _ => return String::new(),
+        };
+
+        let key = (kind, address);
+        if insert {
+            self.breakpoints
+                .entry(key)
+                .or_insert_with(|| nes.debugger_mut().add_breakpoint(breakpoint));
+        } else if let Some(id) = self.breakpoints.remove(&key) {
+            nes.debugger_mut().remove_breakpoint(id);
+        }
+        "OK".to_string()
+    }
+}
+
+/// レジスタの値（リトルエンディアンのバイト列）
+fn register(nes: &Nes, register: usize) -> Option<Vec<u8>> {
+    let cpu = nes.cpu_state();
+    Some(match register {
+        0 => vec![cpu.a],
+        1 => vec![cpu.x],
+        2 => vec![cpu.y],
+        3 => vec![cpu.p],
+        4 => vec![cpu.sp()],
+        5 => cpu.pc().to_le_bytes().to_vec(),
+        _ => return None,
+    })
+}
+
+fn set_register(nes: &mut Nes, register: usize, value: u16) {
+    let cpu = nes.cpu_state_mut();
+    match register {
+        0 => cpu.a = value as u8,
+        1 => cpu.x = value as u8,
+        2 => cpu.y = value as u8,
+        3 => cpu.p = value as u8,
+        4 => cpu.set_sp(value as u8),
+        5 => cpu.set_pc(value),
+        _ => {}
+    }
+}
+
+fn error() -> String {
+    "E01".to_string()
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn parse_hex(s: &str) -> Option<u32> {
+    u32::from_str_radix(s, 16).ok()
+}
+
+fn parse_hex_bytes(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// `addr,length`
+fn parse_address_length(s: &str) -> Option<(u16, usize)> {
+    let (address, length) = s.split_once(',')?;
+    Some((parse_hex(address)? as u16, parse_hex(length)? as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_rom::{self, COUNTER};
+    use std::io::BufReader;
+    use std::net::{TcpListener, TcpStream};
+
+    /// スクリプト化したGDBクライアント
+    struct Client {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+    }
+
+    impl Client {
+        fn read_byte(&mut self) -> u8 {
+            let mut byte = [0u8];
+            self.reader.read_exact(&mut byte).unwrap();
+            byte[0]
+        }
+
+        /// パケットを1つ受信して確認応答を返す
+        fn receive(&mut self) -> String {
+            while self.read_byte() != b'$' {}
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte() {
+                    b'#' => break,
+                    b => data.push(b),
+                }
+            }
+            let sum = [self.read_byte(), self.read_byte()];
+            assert_eq!(
+                u8::from_str_radix(std::str::from_utf8(&sum).unwrap(), 16).unwrap(),
+                checksum(&data)
+            );
+            self.writer.write_all(b"+").unwrap();
+            String::from_utf8(data).unwrap()
+        }
+
+        fn send(&mut self, data: &str) {
+            write!(self.writer, "${}#{:02x}", data, checksum(data.as_bytes())).unwrap();
+            assert_eq!(self.read_byte(), b'+');
+        }
+
+        fn request(&mut self, data: &str) -> String {
+            self.send(data);
+            self.receive()
+        }
+    }
+
+    #[test]
+    fn test_scripted_session() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let client = std::thread::spawn(move || {
+            let stream = TcpStream::connect(address).unwrap();
+            let mut gdb = Client {
+                reader: BufReader::new(stream.try_clone().unwrap()),
+                writer: stream,
+            };
+            assert!(gdb.request("qSupported:swbreak+").contains("PacketSize"));
+            assert_eq!(gdb.request("?"), "S05");
+            assert_eq!(gdb.request("vMustReplyEmpty"), "");
+
+            // レジスタ
+            assert_eq!(gdb.request("P0=42"), "OK");
+            assert_eq!(gdb.request("p0"), "42");
+            assert_eq!(gdb.request("G01020324fd0080"), "OK");
+            assert_eq!(gdb.request("g"), "01020324fd0080");
+            assert_eq!(gdb.request("p5"), "0080");
+            assert_eq!(gdb.request("p9"), "E01");
+
+            // メモリ
+            assert_eq!(gdb.request("M300,2:0a0b"), "OK");
+            assert_eq!(gdb.request("m300,2"), "0a0b");
+
+            // ブレークポイントで停止するまで実行
+            assert_eq!(gdb.request("Z0,8003,1"), "OK");
+            assert_eq!(gdb.request("c"), "S05");
+            assert_eq!(gdb.request("p5"), "0380");
+            assert_eq!(gdb.request("m300,1"), "0b");
+            assert_eq!(gdb.request("z0,8003,1"), "OK");
+
+            // 書き込みウォッチポイント
+            assert_eq!(gdb.request("Z2,300,1"), "OK");
+            assert_eq!(gdb.request("c"), "T05watch:0300;");
+            assert_eq!(gdb.request("z2,300,1"), "OK");
+
+            // ステップ実行
+            assert_eq!(gdb.request("s"), "S05");
+            assert_eq!(gdb.request("p5"), "0580");
+
+            // 確認応答の省略と割り込み
+            assert_eq!(gdb.request("QStartNoAckMode"), "OK");
+            write!(gdb.writer, "$c#{:02x}", checksum(b"c")).unwrap();
+            gdb.writer.write_all(&[0x03]).unwrap();
+            assert_eq!(gdb.receive(), "S02");
+
+            write!(gdb.writer, "$D#{:02x}", checksum(b"D")).unwrap();
+            assert_eq!(gdb.receive(), "OK");
+        });
+
+        let mut nes = test_rom::nes(COUNTER);
+        let (stream, _) = listener.accept().unwrap();
+        stream.set_nonblocking(true).unwrap();
+        let mut stub = GdbStub::new(stream);
+        while !stub.is_detached() {
+            stub.process(&mut nes).unwrap();
+            if !stub.is_halted() && !stub.is_detached() {
+                let result = nes.step_frame();
+                stub.notify(&result).unwrap();
+            } else {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+        }
+
+        client.join().unwrap();
+        assert!(nes.debugger().breakpoints().is_empty());
+    }
+}
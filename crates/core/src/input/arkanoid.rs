@@ -0,0 +1,121 @@
+//! # Arkanoid Vaus コントローラー
+//! ポテンショメータでパドルの位置を読み取るコントローラー（NES版、ポート2に接続）
+//!
+//! 参考: https://www.nesdev.org/wiki/Arkanoid_controller
+
+use super::InputDevice;
+use crate::ppu::Ppu;
+use crate::state::{StateReader, StateWriter};
+use crate::Result;
+
+/// Arkanoid Vaus コントローラー（D3: 位置のシリアルデータ、D4: ファイアボタン）
+///
+/// ストローブで位置を8ビットラッチし、反転した値をMSBから1ビットずつ送出する。
+#[derive(Debug)]
+pub struct ArkanoidPaddle {
+    /// ポテンショメータの値
+    position: u8,
+    fire: bool,
+    shift_register: u8,
+    strobe: bool,
+}
+
+impl ArkanoidPaddle {
+    /// ポテンショメータの最小値（左端）
+    pub const MIN_POSITION: u8 = 0x62;
+    /// ポテンショメータの最大値（右端）
+    pub const MAX_POSITION: u8 = 0xF2;
+
+    pub fn new() -> Self {
+        Self {
+            position: (Self::MIN_POSITION / 2) + (Self::MAX_POSITION / 2),
+            fire: false,
+            shift_register: 0,
+            strobe: false,
+        }
+    }
+
+    /// ポテンショメータの値を直接設定
+    pub fn set_position(&mut self, position: u8) {
+        self.position = position.clamp(Self::MIN_POSITION, Self::MAX_POSITION);
+    }
+
+    pub fn position(&self) -> u8 {
+        self.position
+    }
+}
+
+impl Default for ArkanoidPaddle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputDevice for ArkanoidPaddle {
+    fn read(&mut self, _ppu: &Ppu) -> u8 {
+        let data = if self.strobe {
+            !self.position & 0x80
+        } else {
+            let bit = self.shift_register & 0x80;
+            self.shift_register <<= 1;
+            bit
+        };
+        let data = if data != 0 { 0x08 } else { 0x00 };
+        let fire = if self.fire { 0x10 } else { 0x00 };
+        data | fire
+    }
+
+    fn write(&mut self, value: u8) {
+        let strobe = value & 0x01 != 0;
+        // ストローブの立ち下がりで位置をラッチ
+        if self.strobe && !strobe {
+            self.shift_register = !self.position;
+        }
+        self.strobe = strobe;
+    }
+
+    /// 画面のX座標 (0-255) をパドルの可動範囲に割り当てる
+    fn set_pointer(&mut self, x: i32, _y: i32) {
+        let range = (Self::MAX_POSITION - Self::MIN_POSITION) as i32;
+        let x = x.clamp(0, 255);
+        self.position = Self::MIN_POSITION + (x * range / 255) as u8;
+    }
+
+    fn set_trigger(&mut self, pressed: bool) {
+        self.fire = pressed;
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u8(self.shift_register);
+        w.write_bool(self.strobe);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.shift_register = r.read_u8()?;
+        self.strobe = r.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serial_position() {
+        let ppu = Ppu::new();
+        let mut paddle = ArkanoidPaddle::new();
+        paddle.set_position(0xA5);
+        paddle.set_trigger(true);
+        paddle.write(1);
+        paddle.write(0);
+
+        let mut value = 0u8;
+        for _ in 0..8 {
+            let data = paddle.read(&ppu);
+            assert_ne!(data & 0x10, 0);
+            value = (value << 1) | (data >> 3 & 0x01);
+        }
+        assert_eq!(!value, 0xA5);
+    }
+}
@@ -0,0 +1,84 @@
+//! # Four Score
+//! NES用の4人プレイアダプタ
+
+use super::InputDevice;
+use crate::controller::{Button, Controller};
+use crate::ppu::Ppu;
+use crate::state::{StateReader, StateWriter};
+use crate::Result;
+
+/// Four Score の片側（1ポート分）
+///
+/// 24ビットのシリアル出力: パッド1 (8ビット) → パッド2 (8ビット) → 識別子 (8ビット)。
+/// ポート1ではプレイヤー1/3、ポート2ではプレイヤー2/4を読み出す。
+#[derive(Debug)]
+pub struct FourScore {
+    pads: [Controller; 2],
+    /// 識別子（MSBから順に送出される）
+    signature: u8,
+    shift_register: u32,
+    strobe: bool,
+}
+
+impl FourScore {
+    /// $4016側の識別子
+    pub const SIGNATURE_PORT1: u8 = 0x10;
+    /// $4017側の識別子
+    pub const SIGNATURE_PORT2: u8 = 0x20;
+
+    pub fn new(signature: u8) -> Self {
+        Self {
+            pads: [Controller::new(), Controller::new()],
+            signature,
+            shift_register: 0,
+            strobe: false,
+        }
+    }
+
+    fn latch(&mut self) {
+        // 識別子はMSBから送出されるのでビットを反転しておく
+        self.shift_register = self.pads[0].buttons() as u32
+            | (self.pads[1].buttons() as u32) << 8
+            | (self.signature.reverse_bits() as u32) << 16;
+    }
+}
+
+impl InputDevice for FourScore {
+    fn read(&mut self, _ppu: &Ppu) -> u8 {
+        if self.strobe {
+            return self.pads[0].buttons() & 0x01;
+        }
+        let value = (self.shift_register & 0x01) as u8;
+        // 24ビットを読み終えた後は1を返し続ける
+        self.shift_register = (self.shift_register >> 1) | 0x0080_0000;
+        value
+    }
+
+    fn write(&mut self, value: u8) {
+        self.strobe = value & 0x01 != 0;
+        if self.strobe {
+            self.latch();
+        }
+    }
+
+    fn set_button(&mut self, slot: usize, button: Button, pressed: bool) {
+        if let Some(pad) = self.pads.get_mut(slot) {
+            pad.set_button(button, pressed);
+        }
+    }
+
+    fn buttons(&self, slot: usize) -> u8 {
+        self.pads.get(slot).map_or(0, Controller::buttons)
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u32(self.shift_register);
+        w.write_bool(self.strobe);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.shift_register = r.read_u32()?;
+        self.strobe = r.read_bool()?;
+        Ok(())
+    }
+}
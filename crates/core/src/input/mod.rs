@@ -0,0 +1,267 @@
+//! # 入力デバイス
+//!
+//! コントローラーポート ($4016/$4017) と、そこに接続する入力デバイスを管理する。
+//!
+//! 各ポートには`InputDevice`を実装した任意のデバイスを接続できる。
+//! Four Score（4人用アダプタ）は両ポートにまたがるため、`InputPorts::set_four_score`で
+//! 各ポートを`FourScore`に差し替えて扱う。
+
+mod arkanoid;
+mod four_score;
+mod power_pad;
+mod zapper;
+
+pub use arkanoid::ArkanoidPaddle;
+pub use four_score::FourScore;
+pub use power_pad::PowerPad;
+pub use zapper::Zapper;
+
+use crate::controller::{Button, Controller};
+use crate::ppu::Ppu;
+use crate::state::{StateReader, StateWriter};
+use crate::Result;
+
+/// $4016/$4017読み取り時のオープンバス（上位アドレスバイトの名残）
+const OPEN_BUS: u8 = 0x40;
+
+/// コントローラーポートに接続する入力デバイス
+pub trait InputDevice {
+    /// ポートからの読み込み（D0-D4のみ有効、残りのビットは0にする）
+    ///
+    /// `ppu`はZapperのように画面の状態を参照するデバイス用。
+    fn read(&mut self, ppu: &Ppu) -> u8;
+
+    /// $4016への書き込み（ストローブ、両ポートに届く）
+    fn write(&mut self, value: u8);
+
+    /// ボタンの状態を設定（`slot`はFour Scoreのように複数のパッドを持つデバイス用）
+    fn set_button(&mut self, _slot: usize, _button: Button, _pressed: bool) {}
+
+    /// 押されているボタン（`Controller::buttons`と同じビット配置、パッドを持たないデバイスは0）
+    fn buttons(&self, _slot: usize) -> u8 {
+        0
+    }
+
+    /// 画面上のポインタ位置を設定（Zapperの照準、Arkanoidパドルの位置）
+    fn set_pointer(&mut self, _x: i32, _y: i32) {}
+
+    /// トリガー/ファイアボタンの状態を設定
+    fn set_trigger(&mut self, _pressed: bool) {}
+
+    /// 番号付きスイッチの状態を設定（Power Padの各パネルなど）
+    fn set_switch(&mut self, _index: usize, _pressed: bool) {}
+
+    /// 状態を保存（ボタンの押下状態などフロントエンドからの入力は含めない）
+    fn save_state(&self, w: &mut StateWriter);
+
+    /// 状態を復元
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()>;
+}
+
+/// 何も接続されていないポート
+#[derive(Debug, Default)]
+pub struct Unplugged;
+
+impl InputDevice for Unplugged {
+    fn read(&mut self, _ppu: &Ppu) -> u8 {
+        0
+    }
+
+    fn write(&mut self, _value: u8) {}
+
+    fn save_state(&self, _w: &mut StateWriter) {}
+
+    fn load_state(&mut self, _r: &mut StateReader) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// 2つのコントローラーポート
+pub struct InputPorts {
+    ports: [Box<dyn InputDevice>; 2],
+    four_score: bool,
+}
+
+impl InputPorts {
+    /// 両ポートに標準コントローラーを接続した状態
+    pub fn new() -> Self {
+        Self {
+            ports: [Box::new(Controller::new()), Box::new(Controller::new())],
+            four_score: false,
+        }
+    }
+
+    /// ポート (0または1) にデバイスを接続（Four Scoreは解除される）
+    pub fn connect(&mut self, port: usize, device: Box<dyn InputDevice>) {
+        if self.four_score {
+            self.four_score = false;
+            self.ports = [Box::new(Controller::new()), Box::new(Controller::new())];
+        }
+        if let Some(slot) = self.ports.get_mut(port) {
+            *slot = device;
+        }
+    }
+
+    /// Four Scoreの接続/取り外し（取り外すと標準コントローラー2つに戻る）
+    pub fn set_four_score(&mut self, enabled: bool) {
+        self.four_score = enabled;
+        self.ports = if enabled {
+            [
+                Box::new(FourScore::new(FourScore::SIGNATURE_PORT1)),
+                Box::new(FourScore::new(FourScore::SIGNATURE_PORT2)),
+            ]
+        } else {
+            [Box::new(Controller::new()), Box::new(Controller::new())]
+        };
+    }
+
+    /// Four Scoreが接続されているか
+    pub fn four_score(&self) -> bool {
+        self.four_score
+    }
+
+    /// プレイヤー (0-3) のボタンの状態を設定
+    ///
+    /// Four Score未接続時はプレイヤー1/2がそれぞれポート1/2に対応し、3/4は無視される。
+    pub fn set_button(&mut self, player: usize, button: Button, pressed: bool) {
+        let (port, slot) = self.player_slot(player);
+        if slot < 2 {
+            if let Some(device) = self.ports.get_mut(port) {
+                device.set_button(slot, button, pressed);
+            }
+        }
+    }
+
+    /// プレイヤー (0-3) の押されているボタン
+    pub fn buttons(&self, player: usize) -> u8 {
+        let (port, slot) = self.player_slot(player);
+        match self.ports.get(port) {
+            Some(device) if slot < 2 => device.buttons(slot),
+            _ => 0,
+        }
+    }
+
+    /// プレイヤー (0-3) の全ボタンの状態をビットマスクで設定
+    pub fn set_buttons(&mut self, player: usize, buttons: u8) {
+        for button in Button::ALL {
+            let pressed = buttons & Controller::button_to_bit(button) != 0;
+            self.set_button(player, button, pressed);
+        }
+    }
+
+    /// プレイヤーが接続されているポートとそのポートの中の位置
+    fn player_slot(&self, player: usize) -> (usize, usize) {
+        if self.four_score {
+            (player % 2, player / 2)
+        } else {
+            (player, 0)
+        }
+    }
+
+    /// ポート (0または1) のポインタ位置を設定
+    pub fn set_pointer(&mut self, port: usize, x: i32, y: i32) {
+        if let Some(device) = self.ports.get_mut(port) {
+            device.set_pointer(x, y);
+        }
+    }
+
+    /// ポート (0または1) のトリガーの状態を設定
+    pub fn set_trigger(&mut self, port: usize, pressed: bool) {
+        if let Some(device) = self.ports.get_mut(port) {
+            device.set_trigger(pressed);
+        }
+    }
+
+    /// ポート (0または1) の番号付きスイッチの状態を設定
+    pub fn set_switch(&mut self, port: usize, index: usize, pressed: bool) {
+        if let Some(device) = self.ports.get_mut(port) {
+            device.set_switch(index, pressed);
+        }
+    }
+
+    /// $4016 (port 0) / $4017 (port 1) の読み込み
+    pub fn read(&mut self, port: usize, ppu: &Ppu) -> u8 {
+        (self.ports[port].read(ppu) & 0x1F) | OPEN_BUS
+    }
+
+    /// $4016への書き込み（ストローブは両ポートに送られる）
+    pub fn write(&mut self, value: u8) {
+        for device in self.ports.iter_mut() {
+            device.write(value);
+        }
+    }
+
+    /// 各ポートのデバイスの状態を保存
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.four_score);
+        for device in self.ports.iter() {
+            let mut sub = StateWriter::new();
+            device.save_state(&mut sub);
+            w.write_bytes(&sub.into_inner());
+        }
+    }
+
+    /// 各ポートのデバイスの状態を復元
+    ///
+    /// Four Scoreの接続状態は保存時のものに合わせる。それ以外のデバイス構成は
+    /// フロントエンドの設定なので変更しない。
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        let four_score = r.read_bool()?;
+        if four_score != self.four_score {
+            self.set_four_score(four_score);
+        }
+        for device in self.ports.iter_mut() {
+            let data = r.read_bytes()?;
+            device.load_state(&mut StateReader::new(data))?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for InputPorts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_bits(ports: &mut InputPorts, port: usize, count: usize) -> Vec<u8> {
+        let ppu = Ppu::new();
+        (0..count).map(|_| ports.read(port, &ppu) & 0x01).collect()
+    }
+
+    #[test]
+    fn test_two_controllers() {
+        let mut ports = InputPorts::new();
+        ports.set_button(0, Button::A, true);
+        ports.set_button(1, Button::Start, true);
+        ports.write(1);
+        ports.write(0);
+        assert_eq!(read_bits(&mut ports, 0, 8), [1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(read_bits(&mut ports, 1, 8), [0, 0, 0, 1, 0, 0, 0, 0]);
+        assert_eq!(ports.read(0, &Ppu::new()), 0x41);
+    }
+
+    #[test]
+    fn test_four_score() {
+        let mut ports = InputPorts::new();
+        ports.set_four_score(true);
+        ports.set_button(2, Button::B, true);
+        ports.set_button(3, Button::Right, true);
+        ports.write(1);
+        ports.write(0);
+
+        let port1 = read_bits(&mut ports, 0, 24);
+        assert_eq!(port1[..8], [0; 8]);
+        assert_eq!(port1[8..16], [0, 1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(port1[16..], [0, 0, 0, 1, 0, 0, 0, 0]);
+
+        let port2 = read_bits(&mut ports, 1, 24);
+        assert_eq!(port2[8..16], [0, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(port2[16..], [0, 0, 1, 0, 0, 0, 0, 0]);
+        assert_eq!(ports.read(1, &Ppu::new()) & 0x01, 1);
+    }
+}
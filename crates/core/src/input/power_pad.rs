@@ -0,0 +1,112 @@
+//! # Power Pad
+//! 12枚のパネルを踏んで操作するマット型コントローラー（ポート2に接続）
+//!
+//! 参考: https://www.nesdev.org/wiki/Power_Pad
+
+use super::InputDevice;
+use crate::ppu::Ppu;
+use crate::state::{StateReader, StateWriter};
+use crate::Result;
+
+/// D3から送出されるパネル番号（1始まり）の順序
+const D3_ORDER: [usize; 8] = [2, 1, 5, 9, 6, 10, 11, 7];
+
+/// D4から送出されるパネル番号（1始まり）の順序
+const D4_ORDER: [usize; 4] = [4, 3, 12, 8];
+
+/// Power Pad（D3/D4の2本のシリアル出力、押されているパネルは1）
+#[derive(Debug, Default)]
+pub struct PowerPad {
+    /// 各パネルの状態（ビット0=パネル1 ... ビット11=パネル12）
+    panels: u16,
+    shift_d3: u16,
+    shift_d4: u16,
+    strobe: bool,
+}
+
+impl PowerPad {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn latch(&mut self) {
+        let serialize = |order: &[usize]| {
+            order
+                .iter()
+                .enumerate()
+                .filter(|(_, &panel)| self.panels & (1 << (panel - 1)) != 0)
+                .fold(0u16, |acc, (i, _)| acc | (1 << i))
+        };
+        // 全ビット送出後は1を返し続ける
+        self.shift_d3 = serialize(&D3_ORDER) | 0xFF00;
+        self.shift_d4 = serialize(&D4_ORDER) | 0xFFF0;
+    }
+}
+
+impl InputDevice for PowerPad {
+    fn read(&mut self, _ppu: &Ppu) -> u8 {
+        if self.strobe {
+            self.latch();
+        }
+        let d3 = ((self.shift_d3 & 0x01) as u8) << 3;
+        let d4 = ((self.shift_d4 & 0x01) as u8) << 4;
+        if !self.strobe {
+            self.shift_d3 = (self.shift_d3 >> 1) | 0x8000;
+            self.shift_d4 = (self.shift_d4 >> 1) | 0x8000;
+        }
+        d3 | d4
+    }
+
+    fn write(&mut self, value: u8) {
+        self.strobe = value & 0x01 != 0;
+        if self.strobe {
+            self.latch();
+        }
+    }
+
+    /// パネル (0-11、パネル1-12に対応) の状態を設定
+    fn set_switch(&mut self, index: usize, pressed: bool) {
+        if index < 12 {
+            if pressed {
+                self.panels |= 1 << index;
+            } else {
+                self.panels &= !(1 << index);
+            }
+        }
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u16(self.shift_d3);
+        w.write_u16(self.shift_d4);
+        w.write_bool(self.strobe);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.shift_d3 = r.read_u16()?;
+        self.shift_d4 = r.read_u16()?;
+        self.strobe = r.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serial_order() {
+        let ppu = Ppu::new();
+        let mut pad = PowerPad::new();
+        // パネル1と12
+        pad.set_switch(0, true);
+        pad.set_switch(11, true);
+        pad.write(1);
+        pad.write(0);
+
+        let reads: Vec<u8> = (0..9).map(|_| pad.read(&ppu)).collect();
+        let d3: Vec<u8> = reads.iter().map(|v| v >> 3 & 1).collect();
+        let d4: Vec<u8> = reads.iter().map(|v| v >> 4 & 1).collect();
+        assert_eq!(d3, [0, 1, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(d4, [0, 0, 1, 0, 1, 1, 1, 1, 1]);
+    }
+}
@@ -0,0 +1,129 @@
+//! # Zapper
+//! 光線銃。照準先のピクセルが明るく、かつ走査線がその位置を通過した直後なら光を検出する。
+//!
+//! 参考: https://www.nesdev.org/wiki/Zapper
+
+use super::InputDevice;
+use crate::ppu::Ppu;
+use crate::state::{StateReader, StateWriter};
+use crate::Result;
+
+/// 光を検出したとみなす明るさ（輝度 0-255）
+const LIGHT_THRESHOLD: u32 = 0x80;
+
+/// 走査線が通過してから光を検出し続けるスキャンライン数（フォトダイオードの残光）
+const LIGHT_SCANLINES: i32 = 26;
+
+/// 照準の周囲で光を検出するピクセル範囲（半径）
+const SENSE_RADIUS: i32 = 1;
+
+/// Zapper（D3: 光センサ（0=検出）、D4: トリガー（1=引いている））
+#[derive(Debug)]
+pub struct Zapper {
+    /// 照準の画面座標（画面外なら光は検出されない）
+    x: i32,
+    y: i32,
+    trigger: bool,
+}
+
+impl Zapper {
+    pub fn new() -> Self {
+        Self {
+            x: -1,
+            y: -1,
+            trigger: false,
+        }
+    }
+
+    /// 照準先で光を検出しているか
+    pub fn detects_light(&self, ppu: &Ppu) -> bool {
+        self.senses_light(
+            ppu.frame_buffer(),
+            ppu.scanline() as i32,
+            ppu.cycle() as i32 - 1,
+        )
+    }
+
+    /// RGBAフレームバッファと走査線の位置（スキャンライン、ドット）から光の検出を判定
+    fn senses_light(&self, frame: &[u8], scanline: i32, dot: i32) -> bool {
+        for y in (self.y - SENSE_RADIUS)..=(self.y + SENSE_RADIUS) {
+            // 走査線がまだこの行に達していない、または通過してから時間が経ちすぎている
+            let elapsed = scanline - y;
+            if !(0..LIGHT_SCANLINES).contains(&elapsed) || !(0..240).contains(&y) {
+                continue;
+            }
+            for x in (self.x - SENSE_RADIUS)..=(self.x + SENSE_RADIUS) {
+                if !(0..256).contains(&x) || (elapsed == 0 && dot <= x) {
+                    continue;
+                }
+                let idx = ((y * 256 + x) * 4) as usize;
+                let [r, g, b] = [frame[idx], frame[idx + 1], frame[idx + 2]].map(u32::from);
+                if (r * 299 + g * 587 + b * 114) / 1000 >= LIGHT_THRESHOLD {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+impl Default for Zapper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputDevice for Zapper {
+    fn read(&mut self, ppu: &Ppu) -> u8 {
+        let light = if self.detects_light(ppu) { 0x00 } else { 0x08 };
+        let trigger = if self.trigger { 0x10 } else { 0x00 };
+        light | trigger
+    }
+
+    fn write(&mut self, _value: u8) {}
+
+    fn set_pointer(&mut self, x: i32, y: i32) {
+        self.x = x;
+        self.y = y;
+    }
+
+    fn set_trigger(&mut self, pressed: bool) {
+        self.trigger = pressed;
+    }
+
+    fn save_state(&self, _w: &mut StateWriter) {}
+
+    fn load_state(&mut self, _r: &mut StateReader) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_light_sensing() {
+        let mut frame = vec![0u8; 256 * 240 * 4];
+        for y in 100..110 {
+            for x in 50..60 {
+                let idx = (y * 256 + x) * 4;
+                frame[idx..idx + 4].copy_from_slice(&[0xEC, 0xEE, 0xEC, 0xFF]);
+            }
+        }
+
+        let mut zapper = Zapper::new();
+        zapper.set_pointer(55, 105);
+        // 走査線が照準に達する前 / 直後 / 残光が消えた後
+        assert!(!zapper.senses_light(&frame, 90, 0));
+        assert!(zapper.senses_light(&frame, 106, 0));
+        assert!(!zapper.senses_light(&frame, 140, 0));
+
+        // 暗い場所を狙っている
+        zapper.set_pointer(10, 105);
+        assert!(!zapper.senses_light(&frame, 106, 0));
+
+        zapper.set_trigger(true);
+        assert_eq!(zapper.read(&Ppu::new()), 0x18);
+    }
+}
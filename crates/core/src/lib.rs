@@ -2,19 +2,55 @@
 //! Based on https://github.com/starrhorne/nes-rust
 
 pub mod apu;
+pub mod audio;
 pub mod cpu;
 pub mod ppu;
 pub mod bus;
 pub mod cartridge;
 pub mod controller;
+pub mod debugger;
 pub mod error;
+pub mod gdb;
+pub mod input;
+pub mod mapper;
 pub mod memory_editor;
+pub mod monitor;
+pub mod movie;
+pub mod region;
+pub mod rewind;
+pub mod rom;
+pub mod state;
+pub mod trace;
 
+#[cfg(test)]
+pub(crate) mod test_rom;
+
+pub use debugger::{Debugger, StopReason};
 pub use error::{NesError, Result};
+pub use region::Region;
+
+use debugger::{Position, RunTarget};
+use memory_editor::{MemoryEditor, MemoryRegion, Watchpoint};
+use movie::{Movie, MovieMode, MovieSession, MovieStart};
+use rewind::{RewindBuffer, RewindConfig};
+use std::io::Write;
 
 /// NESエミュレータのメインインスタンス
 pub struct Nes {
     cpu: cpu::Cpu,
+    /// 命令トレースの出力先
+    tracer: Option<Box<dyn Write>>,
+    debugger: Debugger,
+    /// バスへの書き込みで値を更新するウォッチポイント
+    memory_editor: MemoryEditor,
+    /// 途中で停止したフレームの終わりのサイクル
+    frame_end: Option<u64>,
+    /// フレームごとのステート（巻き戻しが有効な場合）
+    rewind: Option<RewindBuffer>,
+    /// 記録・再生中のムービー
+    movie: Option<MovieSession>,
+    /// ROMをロードした直後のステート（電源投入から始まるムービー用）
+    power_on_state: Option<Vec<u8>>,
 }
 
 impl Nes {
@@ -22,61 +58,410 @@ impl Nes {
     pub fn new() -> Self {
         Self {
             cpu: cpu::Cpu::new(),
+            tracer: None,
+            debugger: Debugger::new(),
+            memory_editor: MemoryEditor::new(),
+            frame_end: None,
+            rewind: None,
+            movie: None,
+            power_on_state: None,
         }
     }
 
+    /// 出力サンプルレート`sample_rate` (Hz) のNESインスタンスを作成
+    pub fn with_sample_rate(sample_rate: u32) -> Self {
+        let mut nes = Self::new();
+        nes.set_sample_rate(sample_rate);
+        nes
+    }
+
     /// ROMをロード
+    ///
+    /// 地域はヘッダーのタイミング情報から自動で選ばれる（変更するにはロード後に`set_region`を呼ぶ）。
     pub fn load_rom(&mut self, rom_data: &[u8]) -> Result<()> {
-        self.cpu.bus.load_rom_from_memory(rom_data);
+        self.cpu.bus.load_rom_from_memory(rom_data)?;
+        let timing = self
+            .cpu
+            .bus
+            .cartridge
+            .as_ref()
+            .map(|c| c.borrow().header().timing);
+        if let Some(timing) = timing {
+            self.set_region(Region::from_timing(timing));
+        }
         self.cpu.reset();
+        self.frame_end = None;
+        if let Some(rewind) = self.rewind.as_mut() {
+            rewind.clear();
+        }
+        self.movie = None;
+        self.power_on_state = Some(self.save_state());
         Ok(())
     }
 
-    /// システムをリセット
+    /// 地域（NTSC/PAL/Dendy）
+    pub fn region(&self) -> Region {
+        self.cpu.bus.region()
+    }
+
+    /// 地域を設定（CPU/PPUのクロック比、スキャンライン数、APUの周期表が切り替わる）
+    pub fn set_region(&mut self, region: Region) {
+        self.cpu.bus.set_region(region);
+    }
+
+    /// システムをリセット（ムービーの記録中は次のフレームにリセットを記録する）
     pub fn reset(&mut self) {
         self.cpu.reset();
+        self.frame_end = None;
+        if let Some(movie) = self.movie.as_mut() {
+            movie.request_reset();
+        }
     }
 
-    /// 1フレーム実行（約29780.5サイクル）
-    pub fn step_frame(&mut self) -> Result<&[u8]> {
-        const CYCLES_PER_FRAME: u64 = 29781;
-        let target = self.cpu.bus.cycles + CYCLES_PER_FRAME;
+    /// 1フレーム実行（NTSCでは約29780.5サイクル）
+    ///
+    /// ブレークポイントや`run_until`の目標で止まった場合はその理由を返し、次の呼び出しは
+    /// 止まった位置から同じフレームの終わりまで実行する。画面は`frame_buffer`で取得する。
+    pub fn step_frame(&mut self) -> Result<StopReason> {
+        let target = match self.frame_end {
+            Some(target) => target,
+            None => {
+                self.start_movie_frame();
+                self.cpu.bus.cycles + self.region().cpu_cycles_per_frame().round() as u64
+            }
+        };
+        self.frame_end = Some(target);
 
         while self.cpu.bus.cycles < target {
-            // Handle OAM DMA stall cycles
-            let stall_cycles = self.cpu.bus.reset_cpu_stall_cycles();
-            for _ in 0..stall_cycles {
-                self.cpu.bus.tick();
+            self.tick_stall_cycles();
+            if let Some(reason) = self.execute_instruction(true)? {
+                return Ok(reason);
             }
+        }
 
-            self.cpu.step()?;
-
-            // nestestの成功アドレスをチェック
-            if self.cpu.pc() == 0xC66E {
-                log::info!("nestest PASSED at PC=$C66E");
+        self.frame_end = None;
+        if let Some(movie) = self.movie.as_mut() {
+            movie.end_frame(&self.cpu.bus.ram);
+        }
+        if self.rewind.is_some() {
+            let state = self.save_state();
+            if let Some(rewind) = self.rewind.as_mut() {
+                rewind.push(state);
             }
+        }
+        Ok(StopReason::FrameComplete)
+    }
 
-            // Check for NMI
-            if self.cpu.bus.ppu.nmi {
-                self.cpu.interrupt(cpu::Interrupt::Nmi);
-                self.cpu.bus.ppu.nmi = false;
-            }
+    /// 現在のフレームバッファ (256x240 RGBA)
+    pub fn frame_buffer(&self) -> &[u8] {
+        self.cpu.bus.ppu.frame_buffer()
+    }
 
-            // Check for IRQ from mapper
-            if let Some(ref c) = self.cpu.bus.cartridge {
-                if c.borrow().irq_pending() {
-                    c.borrow_mut().acknowledge_irq();
-                    self.cpu.interrupt(cpu::Interrupt::Irq);
-                }
+    /// OAM DMAとDMCの読み込みでCPUが停止するサイクルを進める
+    fn tick_stall_cycles(&mut self) {
+        let stall_cycles = self.cpu.bus.reset_cpu_stall_cycles();
+        for _ in 0..stall_cycles {
+            self.cpu.bus.tick();
+        }
+    }
+
+    /// 1命令実行し、デバッガが停止を求めればその理由を返す
+    ///
+    /// `check_execute`が真ならPCブレークポイントを命令の実行前に評価する。
+    fn execute_instruction(&mut self, check_execute: bool) -> Result<Option<StopReason>> {
+        self.debugger
+            .sync(&mut self.cpu.bus.watch, &mut self.cpu.bus.ppu.watch);
+        if check_execute && self.debugger.is_active() {
+            let mut debugger = std::mem::take(&mut self.debugger);
+            let reason = debugger.check_execute(self);
+            self.debugger = debugger;
+            if reason.is_some() {
+                return Ok(reason);
             }
+        }
+
+        // NMI/IRQはCPUが命令の終わりにポーリングして処理する
+        let before = Position::of(self);
+        self.write_trace()?;
+        self.cpu.step()?;
+
+        if self.cpu.bus.watch.take_written() {
+            self.refresh_watchpoints();
+        }
+        let mut accesses = self.cpu.bus.watch.take_hits();
+        accesses.append(&mut self.cpu.bus.ppu.watch.take_hits());
+        if accesses.is_empty() && self.debugger.run_target().is_none() {
+            return Ok(None);
+        }
+        let mut debugger = std::mem::take(&mut self.debugger);
+        let reason = debugger.check_after(self, &accesses, before);
+        self.debugger = debugger;
+        Ok(reason)
+    }
 
-            // Check for IRQ from APU
-            if self.cpu.bus.apu.irq_pending() {
-                self.cpu.interrupt(cpu::Interrupt::Irq);
+    /// マシン全体の状態をスナップショットとして保存
+    ///
+    /// CPU・RAM・PPU・APU・コントローラー・マッパー/カートリッジRAMを含む。
+    /// ヘッダーにはフォーマットバージョンとROMのCRC32を記録する。
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = state::StateWriter::new();
+        w.write_bytes(state::STATE_MAGIC);
+        w.write_u32(state::STATE_VERSION);
+        w.write_u32(self.rom_checksum().unwrap_or(0));
+        self.cpu.save_state(&mut w);
+        self.cpu.bus.save_state(&mut w);
+        w.into_inner()
+    }
+
+    /// スナップショットからマシン全体の状態を復元
+    ///
+    /// バージョンやROMが一致しない場合は`NesError`を返し、現在の状態は変更しない。
+    pub fn load_state(&mut self, data: &[u8]) -> Result<()> {
+        let mut r = state::StateReader::new(data);
+        if r.read_bytes()? != state::STATE_MAGIC {
+            return Err(NesError::InvalidState("bad magic".to_string()));
+        }
+        let version = r.read_u32()?;
+        if version != state::STATE_VERSION {
+            return Err(NesError::InvalidState(format!(
+                "unsupported version {} (expected {})",
+                version,
+                state::STATE_VERSION
+            )));
+        }
+        let expected = r.read_u32()?;
+        let actual = self
+            .rom_checksum()
+            .ok_or_else(|| NesError::InvalidState("no ROM loaded".to_string()))?;
+        if expected != actual {
+            return Err(NesError::StateRomMismatch { expected, actual });
+        }
+
+        // 途中で失敗した場合に備えて現在の状態を退避しておく
+        let backup = self.save_state();
+        let result = self.load_state_body(&mut r);
+        self.frame_end = None;
+        if result.is_err() {
+            let mut r = state::StateReader::new(&backup);
+            r.read_bytes()?;
+            r.read_u32()?;
+            r.read_u32()?;
+            self.load_state_body(&mut r)?;
+        }
+        result
+    }
+
+    fn load_state_body(&mut self, r: &mut state::StateReader) -> Result<()> {
+        self.cpu.load_state(r)?;
+        self.cpu.bus.load_state(r)?;
+        if !r.is_empty() {
+            return Err(NesError::InvalidState("trailing data".to_string()));
+        }
+        Ok(())
+    }
+
+    // ========== ムービー API ==========
+
+    /// 入力の記録を開始
+    ///
+    /// `from_power_on`なら電源投入直後の状態に戻してから、そうでなければ現在の状態を
+    /// セーブステートとして埋め込んで記録を始める。
+    pub fn record_movie(&mut self, from_power_on: bool) -> Result<()> {
+        let start = if from_power_on {
+            self.load_power_on_state()?;
+            MovieStart::PowerOn
+        } else {
+            MovieStart::SaveState(self.save_state())
+        };
+        let mut movie = Movie::new(start);
+        movie.region = self.region();
+        movie.four_score = self.cpu.bus.input.four_score();
+        self.frame_end = None;
+        self.movie = Some(MovieSession::new(movie, MovieMode::Recording));
+        Ok(())
+    }
+
+    /// ムービーの開始状態に戻して再生を始める（再生中はフロントエンドからの入力を上書きする）
+    pub fn play_movie(&mut self, movie: Movie) -> Result<()> {
+        self.set_region(movie.region);
+        self.cpu.bus.input.set_four_score(movie.four_score);
+        match &movie.start {
+            MovieStart::PowerOn => self.load_power_on_state()?,
+            MovieStart::SaveState(data) => self.load_state(data)?,
+        }
+        self.frame_end = None;
+        self.movie = Some(MovieSession::new(movie, MovieMode::Playing));
+        Ok(())
+    }
+
+    /// 記録・再生を終了してムービーを返す
+    pub fn stop_movie(&mut self) -> Option<Movie> {
+        self.movie.take().map(MovieSession::into_movie)
+    }
+
+    pub fn movie(&self) -> Option<&MovieSession> {
+        self.movie.as_ref()
+    }
+
+    fn load_power_on_state(&mut self) -> Result<()> {
+        let state = self
+            .power_on_state
+            .clone()
+            .ok_or_else(|| NesError::InvalidMovie("no ROM loaded".to_string()))?;
+        self.load_state(&state)
+    }
+
+    /// フレームの開始時に入力を記録するか、ムービーの入力を適用する
+    fn start_movie_frame(&mut self) {
+        let Some(movie) = self.movie.as_mut() else {
+            return;
+        };
+        let input = &self.cpu.bus.input;
+        let pads = std::array::from_fn(|player| input.buttons(player));
+        if let Some(frame) = movie.start_frame(pads) {
+            if frame.reset {
+                self.cpu.reset();
+            }
+            for (player, &buttons) in frame.pads.iter().enumerate() {
+                self.cpu.bus.input.set_buttons(player, buttons);
             }
         }
+    }
 
-        Ok(self.cpu.bus.ppu.frame_buffer())
+    // ========== 巻き戻し API ==========
+
+    /// 巻き戻しを有効化（以降、フレームを最後まで実行するたびにステートを保存する）
+    pub fn enable_rewind(&mut self, config: RewindConfig) {
+        self.rewind = Some(RewindBuffer::new(config));
+    }
+
+    /// 巻き戻しを無効化して保存したステートを捨てる
+    pub fn disable_rewind(&mut self) {
+        self.rewind = None;
+    }
+
+    pub fn rewind_buffer(&self) -> Option<&RewindBuffer> {
+        self.rewind.as_ref()
+    }
+
+    /// `frames`フレーム前の状態に戻し、実際に戻ったフレーム数を返す
+    ///
+    /// ステートに画面は含まれないので、1つ前のフレームから1フレーム実行して画面を描き直してから
+    /// 目標のステートを読み込む。描き直しで出たオーディオサンプルはステートの読み込みで捨てられるので、
+    /// 巻き戻し中は音が出ない（フロントエンドはオーディオ以外でフレームのペースを取る）。
+    pub fn rewind(&mut self, frames: usize) -> Result<usize> {
+        let Some(mut buffer) = self.rewind.take() else {
+            return Ok(0);
+        };
+        let mut rewound = 0;
+        while rewound < frames && buffer.len() > 1 {
+            buffer.pop();
+            rewound += 1;
+        }
+        // 描き直しのフレームはムービーに記録しない
+        let mut movie = self.movie.take();
+        let result = self.restore_rewind_frame(&buffer, rewound);
+        if let Some(movie) = movie.as_mut() {
+            movie.rewind(rewound);
+        }
+        self.movie = movie;
+        self.rewind = Some(buffer);
+        result.map(|()| rewound)
+    }
+
+    fn restore_rewind_frame(&mut self, buffer: &RewindBuffer, rewound: usize) -> Result<()> {
+        let Some(target) = buffer.latest().filter(|_| rewound > 0) else {
+            return Ok(());
+        };
+        if let Some(previous) = buffer.len().checked_sub(2).and_then(|i| buffer.get(i)) {
+            self.load_state(&previous)?;
+            while self.step_frame()? != StopReason::FrameComplete {}
+        }
+        self.load_state(&target)
+    }
+
+    /// ロード中のROMのヘッダー情報
+    pub fn rom_header(&self) -> Option<rom::RomHeader> {
+        self.cpu
+            .bus
+            .cartridge
+            .as_ref()
+            .map(|c| c.borrow().header().clone())
+    }
+
+    /// ロード中のROMのCRC32
+    pub fn rom_checksum(&self) -> Option<u32> {
+        self.cpu
+            .bus
+            .cartridge
+            .as_ref()
+            .map(|c| c.borrow().checksum())
+    }
+
+    /// 1スキャンライン8個のスプライト制限の有効/無効を切り替える（無効にするとちらつきがなくなる）
+    pub fn set_sprite_limit(&mut self, enabled: bool) {
+        self.cpu.bus.ppu.set_sprite_limit(enabled);
+    }
+
+    /// プレイヤー (0-3) のボタンを押す
+    ///
+    /// プレイヤー1/2はポート1/2、プレイヤー3/4はFour Score接続時のみ有効。
+    pub fn press_button(&mut self, player: usize, button: controller::Button) {
+        self.cpu.bus.input.set_button(player, button, true);
+    }
+
+    /// プレイヤー (0-3) のボタンを離す
+    pub fn release_button(&mut self, player: usize, button: controller::Button) {
+        self.cpu.bus.input.set_button(player, button, false);
+    }
+
+    /// プレイヤー (0-3) のボタンの状態を設定
+    pub fn set_button(&mut self, player: usize, button: controller::Button, pressed: bool) {
+        self.cpu.bus.input.set_button(player, button, pressed);
+    }
+
+    /// ポート (0または1) に入力デバイスを接続
+    pub fn connect_input(&mut self, port: usize, device: Box<dyn input::InputDevice>) {
+        self.cpu.bus.input.connect(port, device);
+    }
+
+    /// ポート (0または1) のポインタ位置を画面座標で設定（Zapper、Arkanoidパドル）
+    pub fn set_pointer(&mut self, port: usize, x: i32, y: i32) {
+        self.cpu.bus.input.set_pointer(port, x, y);
+    }
+
+    /// ポート (0または1) のトリガー/ファイアボタンの状態を設定
+    pub fn set_trigger(&mut self, port: usize, pressed: bool) {
+        self.cpu.bus.input.set_trigger(port, pressed);
+    }
+
+    /// ポート (0または1) の番号付きスイッチの状態を設定（Power Padのパネル）
+    pub fn set_switch(&mut self, port: usize, index: usize, pressed: bool) {
+        self.cpu.bus.input.set_switch(port, index, pressed);
+    }
+
+    /// Four Score（4人用アダプタ）の接続/取り外し
+    pub fn set_four_score(&mut self, enabled: bool) {
+        self.cpu.bus.input.set_four_score(enabled);
+    }
+
+    /// バッテリーバックアップされたPRG RAMの内容（.savファイルとして保存する）
+    ///
+    /// ROMが未ロード、またはカートリッジにバッテリーがない場合は`None`。
+    pub fn battery_ram(&self) -> Option<Vec<u8>> {
+        self.cpu
+            .bus
+            .cartridge
+            .as_ref()
+            .and_then(|c| c.borrow().battery_ram().map(|ram| ram.to_vec()))
+    }
+
+    /// .savファイルの内容をバッテリーバックアップされたPRG RAMに読み込む
+    pub fn load_battery_ram(&mut self, data: &[u8]) -> Result<()> {
+        match self.cpu.bus.cartridge {
+            Some(ref c) => c.borrow_mut().load_battery_ram(data),
+            None => Err(NesError::InvalidBatteryRam("no ROM loaded".to_string())),
+        }
     }
 
     /// オーディオサンプルを取得
@@ -84,37 +469,71 @@ impl Nes {
         self.cpu.bus.apu.get_samples()
     }
 
-    /// 1CPUサイクル実行（デバッグ用）
+    /// オーディオの出力サンプルレート (Hz)
+    pub fn sample_rate(&self) -> u32 {
+        self.cpu.bus.apu.sample_rate()
+    }
+
+    /// オーディオの出力サンプルレートを変更
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.cpu.bus.apu.set_sample_rate(sample_rate);
+    }
+
+    /// オーディオの出力サンプルレートをわずかに伸縮させる（1.0で等倍）
+    pub fn set_audio_rate_adjustment(&mut self, factor: f64) {
+        self.cpu.bus.apu.set_rate_adjustment(factor);
+    }
+
+    /// 1命令実行し、かかったサイクル数を返す（デバッグ用）
+    ///
+    /// ブレークポイントでは止まらないが、ヒット回数とウォッチポイントの値は更新する。
     pub fn step(&mut self) -> Result<u32> {
-        // Handle OAM DMA stall cycles
-        let stall_cycles = self.cpu.bus.reset_cpu_stall_cycles();
-        for _ in 0..stall_cycles {
-            self.cpu.bus.tick();
-        }
+        self.tick_stall_cycles();
 
         let start_cycles = self.cpu.bus.cycles;
-        self.cpu.step()?;
+        self.execute_instruction(false)?;
         let elapsed = (self.cpu.bus.cycles - start_cycles) as u32;
 
-        if self.cpu.bus.ppu.nmi {
-            self.cpu.interrupt(cpu::Interrupt::Nmi);
-            self.cpu.bus.ppu.nmi = false;
-        }
+        Ok(elapsed)
+    }
 
-        // Check for IRQ from mapper
-        if let Some(ref c) = self.cpu.bus.cartridge {
-            if c.borrow().irq_pending() {
-                c.borrow_mut().acknowledge_irq();
-                self.cpu.interrupt(cpu::Interrupt::Irq);
+    /// KIL/JAM命令に出会ったときに停止するか続行するか
+    pub fn set_invalid_opcode_policy(&mut self, policy: cpu::InvalidOpcodePolicy) {
+        self.cpu.set_invalid_opcode_policy(policy);
+    }
+
+    /// 現在のPCの命令をnestest.log形式の1行にする
+    pub fn trace_line(&self) -> String {
+        trace::trace_line(self)
+    }
+
+    /// 命令トレースの出力先を設定（`None`で停止）
+    ///
+    /// 設定中は`step`/`step_frame`が命令を実行する直前にnestest.log形式の行を書き込む。
+    pub fn set_tracer(&mut self, tracer: Option<Box<dyn Write>>) {
+        self.tracer = tracer;
+    }
+
+    fn write_trace(&mut self) -> Result<()> {
+        if self.tracer.is_some() {
+            let line = self.trace_line();
+            if let Some(ref mut tracer) = self.tracer {
+                writeln!(tracer, "{}", line)?;
             }
         }
+        Ok(())
+    }
 
-        // Check for IRQ from APU
-        if self.cpu.bus.apu.irq_pending() {
-            self.cpu.interrupt(cpu::Interrupt::Irq);
+    /// nestestの自動実行モードを開始する
+    ///
+    /// ROMのロード直後に呼ぶ。PCを$C000にしてnestest.logの初期状態（P=$24, SP=$FD, CYC=7）に揃える。
+    pub fn start_nestest_automation(&mut self) {
+        while self.cpu.bus.cycles < 7 {
+            self.cpu.bus.tick();
         }
-
-        Ok(elapsed)
+        self.cpu.set_pc(trace::NESTEST_START);
+        self.cpu.set_sp(0xFD);
+        self.cpu.p = 0x24;
     }
 
     /// CPU状態の取得（デバッグ用）
@@ -132,6 +551,81 @@ impl Nes {
         &self.cpu.bus.ppu
     }
 
+    // ========== デバッガ API ==========
+
+    pub fn debugger(&self) -> &Debugger {
+        &self.debugger
+    }
+
+    /// ブレークポイントの追加・削除（変更は次の命令の実行時にバスへ反映される）
+    pub fn debugger_mut(&mut self) -> &mut Debugger {
+        &mut self.debugger
+    }
+
+    /// `target`に達するかブレークポイントで止まるまで実行（`max_frames`フレームで打ち切る）
+    ///
+    /// 目標に達しなかった場合、目標は解除される。
+    pub fn run_until(&mut self, target: RunTarget, max_frames: u32) -> Result<StopReason> {
+        self.debugger.set_run_target(Some(target));
+        let mut reason = StopReason::FrameComplete;
+        for _ in 0..max_frames {
+            reason = self.step_frame()?;
+            if reason != StopReason::FrameComplete {
+                break;
+            }
+        }
+        if !matches!(reason, StopReason::Target(_)) {
+            self.debugger.set_run_target(None);
+        }
+        Ok(reason)
+    }
+
+    /// メモリエディタのウォッチポイントを追加（値はバスへの書き込みのたびに更新される）
+    pub fn add_watchpoint(&mut self, region: MemoryRegion, address: u16, label: &str) -> u32 {
+        let value = self.peek_region(region, address);
+        let id = self
+            .memory_editor
+            .add_watchpoint(region, address, label, value);
+        self.cpu.bus.watch.set_track_writes(true);
+        id
+    }
+
+    /// メモリエディタのウォッチポイントを削除
+    pub fn remove_watchpoint(&mut self, id: u32) -> bool {
+        let removed = self.memory_editor.remove_watchpoint(id);
+        self.cpu
+            .bus
+            .watch
+            .set_track_writes(self.memory_editor.has_watchpoints());
+        removed
+    }
+
+    /// メモリエディタのウォッチポイントをすべて取得
+    pub fn watchpoints(&self) -> Vec<&Watchpoint> {
+        self.memory_editor.get_watchpoints()
+    }
+
+    /// PPUへの書き込みやバンク切り替えも含め、すべての変化はCPUのバス書き込みから起きる
+    fn refresh_watchpoints(&mut self) {
+        let mut editor = std::mem::take(&mut self.memory_editor);
+        editor.refresh_watchpoints(|region, address| self.peek_region(region, address));
+        self.memory_editor = editor;
+    }
+
+    /// メモリ領域内のアドレスの値を副作用なしで読み取る
+    pub fn peek_region(&self, region: MemoryRegion, address: u16) -> u8 {
+        let renderer = &self.cpu.bus.ppu.renderer;
+        match region {
+            MemoryRegion::Ram => self.cpu.bus.ram[(address & 0x07FF) as usize],
+            MemoryRegion::Vram => renderer.vram[(address & 0x07FF) as usize],
+            MemoryRegion::Oam => renderer.oam[(address & 0x00FF) as usize],
+            MemoryRegion::Palette => renderer.palette[(address & 0x001F) as usize],
+            MemoryRegion::PrgRom => self.peek_memory(0x8000 | (address & 0x7FFF)),
+            MemoryRegion::PrgRam => self.peek_memory(0x6000 | (address & 0x1FFF)),
+            MemoryRegion::Chr => self.read_chr(address & 0x1FFF),
+        }
+    }
+
     // ========== メモリエディタ API ==========
 
     /// CPU RAMを読み取り (0x0000-0x07FF, 2KB)
@@ -148,7 +642,7 @@ impl Nes {
     /// CPU RAMを一括書き込み
     pub fn write_ram_range(&mut self, start: u16, data: &[u8]) {
         for (i, &value) in data.iter().enumerate() {
-            let addr = ((start as usize + i) & 0x07FF) as usize;
+            let addr = (start as usize + i) & 0x07FF;
             self.cpu.bus.ram[addr] = value;
         }
     }
@@ -269,7 +763,7 @@ impl Nes {
     /// メモリダンプを16進数文字列で取得
     pub fn hex_dump(&self, start: u16, length: usize) -> String {
         let memory = self.read_memory_range(start, length);
-        memory_editor::MemoryEditor::hex_dump(&memory, 0, memory.len())
+        memory_editor::MemoryEditor::hex_dump_at(&memory, 0, memory.len(), start)
     }
 
     /// 逆アセンブル
@@ -310,10 +804,531 @@ impl Default for Nes {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_rom::nrom;
+
+    /// $8000から`INX; STX $00; JMP $8000`を繰り返すNROMイメージ
+    fn test_rom(tag: u8) -> Vec<u8> {
+        nrom(&[0xE8, 0x86, 0x00, 0x4C, 0x00, 0x80], &[tag])
+    }
+
+    /// DMCを設定して$C000からのサンプルを再生し、無限ループするプログラム
+    fn dmc_program(flags: u8, length: u8) -> Vec<u8> {
+        vec![
+            0xA9, flags, 0x8D, 0x10, 0x40, // LDA #flags; STA $4010
+            0xA9, 0x00, 0x8D, 0x12, 0x40, // LDA #$00; STA $4012 ($C000)
+            0xA9, length, 0x8D, 0x13, 0x40, // LDA #length; STA $4013
+            0xA9, 0x10, 0x8D, 0x11, 0x40, // LDA #$10; STA $4011
+            0x8D, 0x15, 0x40, // STA $4015 (DMC有効)
+            0x4C, 0x17, 0x80, // JMP *
+        ]
+    }
+
+    #[test]
+    fn test_dmc_plays_sample_from_prg() {
+        // 全ビット1 (出力+2) のサンプル1バイト
+        let mut nes = Nes::new();
+        nes.load_rom(&nrom(&dmc_program(0x0F, 0x00), &[0xFF]))
+            .unwrap();
+        nes.step_frame().unwrap();
+        assert_eq!(nes.cpu.bus.apu.dmc_output_level(), 0x10 + 16);
+        assert_eq!(nes.cpu.bus.apu.read_register(0x4015) & 0x10, 0);
+
+        // 全ビット0 (出力-2) のサンプル
+        let mut nes = Nes::new();
+        nes.load_rom(&nrom(&dmc_program(0x0F, 0x00), &[0x00]))
+            .unwrap();
+        nes.step_frame().unwrap();
+        assert_eq!(nes.cpu.bus.apu.dmc_output_level(), 0x10 - 16);
+    }
+
+    #[test]
+    fn test_dmc_fetch_stalls_cpu() {
+        let mut nes = Nes::new();
+        nes.load_rom(&nrom(&dmc_program(0x0F, 0x00), &[0xFF]))
+            .unwrap();
+        let mut stalled = 0;
+        for _ in 0..20 {
+            nes.step().unwrap();
+            stalled += nes.cpu.bus.reset_cpu_stall_cycles();
+        }
+        assert_eq!(stalled, 4);
+    }
+
+    #[test]
+    fn test_dmc_irq_and_loop() {
+        // IRQ有効・ループなし: 17バイト再生後にIRQ
+        let mut nes = Nes::new();
+        nes.load_rom(&nrom(&dmc_program(0x8F, 0x01), &[0x55; 17]))
+            .unwrap();
+        nes.step_frame().unwrap();
+        assert!(nes.cpu.bus.apu.irq_pending());
+        assert_eq!(nes.cpu.bus.apu.read_register(0x4015) & 0x90, 0x80);
+
+        // ループ: IRQは発生せず、再生が続く
+        let mut nes = Nes::new();
+        nes.load_rom(&nrom(&dmc_program(0xCF, 0x01), &[0x55; 17]))
+            .unwrap();
+        nes.step_frame().unwrap();
+        assert!(!nes.cpu.bus.apu.irq_pending());
+        assert_eq!(nes.cpu.bus.apu.read_register(0x4015) & 0x10, 0x10);
+    }
+
+    /// ちょうど`cycles`サイクル（2以上）待つコードを`program`（$8000から配置）の末尾に追加
+    fn push_delay(program: &mut Vec<u8>, mut cycles: u32) {
+        assert!(cycles != 1);
+        while cycles >= 8 {
+            // LDX #k; DEX; BNE *-1 は分岐がページをまたがなければ5k+1サイクル
+            let dex = 0x8000 + program.len() + 2;
+            if dex >> 8 != (dex + 3) >> 8 {
+                program.push(0xEA); // NOP
+                cycles -= 2;
+                continue;
+            }
+            let k = ((cycles - 3) / 5).min(255);
+            program.extend_from_slice(&[0xA2, k as u8, 0xCA, 0xD0, 0xFD]);
+            cycles -= 5 * k + 1;
+        }
+        if cycles % 2 == 1 {
+            program.extend_from_slice(&[0x24, 0x00]); // BIT $00
+            cycles -= 3;
+        }
+        program.extend(std::iter::repeat_n(0xEA, cycles as usize / 2));
+    }
+
+    /// $4017に`mode`を書き込み、書き込みからちょうど`delay`サイクル後と`delay + 4`サイクル後に
+    /// $4015を読み取った値を返す（blargg's apu_testと同じ測り方）
+    ///
+    /// `pre`は書き込みの前に、`post`（`post_cycles`サイクル）は書き込みの直後に実行する。
+    /// `odd`なら奇数のAPUサイクルで書き込む。
+    fn probe_frame_counter(
+        pre: &[u8],
+        mode: u8,
+        odd: bool,
+        post: (&[u8], u32),
+        delay: u32,
+    ) -> [u8; 2] {
+        // 書き込み前の待ち時間を2サイクルと3サイクルで試して、書き込むサイクルの偶奇を合わせる
+        for pad in [&[0xEA][..], &[0x24, 0x00]] {
+            let mut program = vec![0x78]; // SEI
+            program.extend_from_slice(pre);
+            program.extend_from_slice(pad);
+            program.extend_from_slice(&[0xA9, mode]); // LDA #mode
+            let write_pc = 0x8000 + program.len() as u16;
+            program.extend_from_slice(&[0x8D, 0x17, 0x40]); // STA $4017
+            program.extend_from_slice(post.0);
+            push_delay(&mut program, delay - post.1 - 4);
+            let read_pc = 0x8000 + program.len() as u16;
+            program.extend_from_slice(&[
+                0xAD, 0x15, 0x40, // LDA $4015
+                0xAE, 0x15, 0x40, // LDX $4015
+                0x85, 0x00, 0x86, 0x01, // STA $00; STX $01
+            ]);
+            let end_pc = 0x8000 + program.len() as u16;
+            program.extend_from_slice(&[0x4C, end_pc as u8, (end_pc >> 8) as u8]); // JMP *
+
+            let mut nes = Nes::new();
+            nes.load_rom(&nrom(&program, &[])).unwrap();
+            let mut write_cycle = None;
+            while nes.cpu.pc() != end_pc {
+                let pc = nes.cpu.pc();
+                nes.step().unwrap();
+                // 書き込みも読み取りも命令の最後のサイクル（APUのサイクル数はバスと同じ）
+                if pc == write_pc {
+                    write_cycle = Some(nes.cpu.bus.cycles);
+                } else if pc == read_pc {
+                    assert_eq!(nes.cpu.bus.cycles - write_cycle.unwrap(), delay as u64);
+                }
+            }
+            if (write_cycle.unwrap() % 2 == 1) == odd {
+                return [nes.cpu.bus.ram[0], nes.cpu.bus.ram[1]];
+            }
+        }
+        unreachable!()
+    }
+
+    /// 矩形波1を有効にして長さカウンタに2をロードする
+    const LOAD_LENGTH: &[u8] = &[
+        0xA9, 0x01, 0x8D, 0x15, 0x40, // LDA #$01; STA $4015
+        0xA9, 0x18, 0x8D, 0x03, 0x40, // LDA #$18; STA $4003
+    ];
+
+    #[test]
+    fn test_frame_irq_flag_timing() {
+        // 4ステップモードのフラグは書き込みから3サイクル（奇数サイクルなら4サイクル）後に
+        // リセットされたシーケンサーの29828-29830サイクル目にセットされる
+        for (odd, jitter) in [(false, 0), (true, 1)] {
+            let irq = |mode, delay: u32| {
+                probe_frame_counter(&[], mode, odd, (&[], 0), delay + jitter).map(|v| v & 0x40 != 0)
+            };
+            assert_eq!(irq(0x00, 29830), [false, true]);
+            // 読み取りでクリアしても、続く2サイクルで再びセットされる
+            assert_eq!(irq(0x00, 29831), [true, true]);
+            assert_eq!(irq(0x00, 29832), [true, true]);
+            // 最後にセットされるサイクルで読み取ればクリアされたままになる
+            assert_eq!(irq(0x00, 29833), [true, false]);
+            // IRQ禁止と5ステップモードではセットされない
+            assert_eq!(irq(0x40, 29831), [false, false]);
+            assert_eq!(irq(0x80, 29831), [false, false]);
+        }
+    }
+
+    #[test]
+    fn test_length_clock_timing_from_program() {
+        // 長さカウンタを1にしてから4ステップモードにする（5ステップモードへの切り替えで1回クロックされる）
+        let mut length_one = LOAD_LENGTH.to_vec();
+        length_one.extend_from_slice(&[0xA9, 0x80, 0x8D, 0x17, 0x40, 0xEA, 0xEA]); // LDA #$80; STA $4017
+        let reload: (&[u8], u32) = (&[0xA9, 0x18, 0x8D, 0x03, 0x40], 6); // LDA #$18; STA $4003
+
+        for (odd, jitter) in [(false, 0), (true, 1)] {
+            // 長さカウンタが0になる最初の読み取りのタイミング
+            let cleared_at = |pre: &[u8], mode, post, delay: u32| {
+                let status =
+                    |delay| probe_frame_counter(pre, mode, odd, post, delay + jitter)[0] & 0x01;
+                (status(delay - 1), status(delay))
+            };
+            // 4ステップモード: 長さカウンタはステップ2 (14913) とステップ4 (29829) でクロックされる
+            assert_eq!(cleared_at(&length_one, 0x00, (&[], 0), 3 + 14913), (1, 0));
+            assert_eq!(cleared_at(LOAD_LENGTH, 0x00, reload, 3 + 29829), (1, 0));
+            // 5ステップモード: 書き込み直後、ステップ2 (14913) とステップ5 (37281) でクロックされる
+            assert_eq!(cleared_at(LOAD_LENGTH, 0x80, (&[], 0), 3 + 14913), (1, 0));
+            assert_eq!(cleared_at(LOAD_LENGTH, 0x80, reload, 3 + 37281), (1, 0));
+        }
+    }
+
+    #[test]
+    fn test_audio_sample_rate() {
+        for rate in [44100, 48000] {
+            let mut nes = Nes::with_sample_rate(rate);
+            nes.load_rom(&test_rom(0)).unwrap();
+            let mut total = 0;
+            for _ in 0..20 {
+                nes.step_frame().unwrap();
+                total += nes.get_audio_samples().len();
+            }
+            // 20フレーム分のサンプルが欠けずに得られる
+            let cycles = nes.cpu.bus.cycles as f64;
+            let expected = (cycles * rate as f64 / Region::Ntsc.cpu_clock_rate()) as usize;
+            assert!(total.abs_diff(expected) <= 2, "{total} != {expected}");
+        }
+    }
+
+    #[test]
+    fn test_region_from_header() {
+        // NES 2.0ヘッダーのbyte 12 = 1 (PAL)
+        let mut rom = test_rom(0);
+        rom[7] = 0x08;
+        rom[12] = 0x01;
+        let mut nes = Nes::new();
+        nes.load_rom(&rom).unwrap();
+        assert_eq!(nes.region(), Region::Pal);
+
+        // 312ライン x 341ドット / 3.2ドット毎サイクル = 1フレーム
+        let start = nes.cpu.bus.ppu.frame();
+        for _ in 0..10 {
+            nes.step_frame().unwrap();
+        }
+        assert_eq!(nes.cpu.bus.ppu.frame() - start, 10);
+
+        // iNESヘッダーはNTSC
+        nes.load_rom(&test_rom(0)).unwrap();
+        assert_eq!(nes.region(), Region::Ntsc);
+    }
 
     #[test]
     fn test_nes_creation() {
         let nes = Nes::new();
         assert_eq!(nes.cpu.pc(), 0);
     }
+
+    #[test]
+    fn test_save_state_round_trip() {
+        let mut nes = Nes::new();
+        nes.load_rom(&test_rom(0)).unwrap();
+        nes.step_frame().unwrap();
+        let state = nes.save_state();
+        let ram = nes.read_ram()[0];
+        let cycles = nes.cpu.bus.cycles;
+
+        nes.step_frame().unwrap();
+        assert_ne!(nes.cpu.bus.cycles, cycles);
+
+        nes.load_state(&state).unwrap();
+        assert_eq!(nes.read_ram()[0], ram);
+        assert_eq!(nes.cpu.bus.cycles, cycles);
+        assert_eq!(nes.save_state(), state);
+    }
+
+    #[test]
+    fn test_save_state_restores_region() {
+        let mut nes = Nes::new();
+        nes.load_rom(&test_rom(0)).unwrap();
+        nes.set_region(Region::Pal);
+        nes.step_frame().unwrap();
+        let state = nes.save_state();
+
+        nes.set_region(Region::Ntsc);
+        nes.load_state(&state).unwrap();
+        assert_eq!(nes.region(), Region::Pal);
+        assert_eq!(nes.cpu.bus.ppu.region(), Region::Pal);
+        assert_eq!(nes.save_state(), state);
+    }
+
+    #[test]
+    fn test_rewind() {
+        let mut nes = Nes::new();
+        nes.load_rom(&test_rom(0)).unwrap();
+        nes.enable_rewind(RewindConfig {
+            memory_budget: 1 << 20,
+            keyframe_interval: 4,
+        });
+        let mut states = Vec::new();
+        for _ in 0..10 {
+            nes.step_frame().unwrap();
+            states.push(nes.save_state());
+        }
+        assert_eq!(nes.rewind_buffer().unwrap().len(), 10);
+
+        assert_eq!(nes.rewind(3).unwrap(), 3);
+        assert_eq!(nes.save_state(), states[6]);
+        assert_eq!(nes.rewind_buffer().unwrap().len(), 7);
+        // 描き直しのフレームのオーディオは残らない
+        assert!(nes.get_audio_samples().is_empty());
+
+        // 巻き戻した位置から再び記録する
+        nes.step_frame().unwrap();
+        assert_eq!(nes.save_state(), states[7]);
+
+        // 最も古いフレームより前には戻らない
+        assert_eq!(nes.rewind(100).unwrap(), 7);
+        assert_eq!(nes.save_state(), states[0]);
+        assert_eq!(nes.rewind(1).unwrap(), 0);
+
+        nes.disable_rewind();
+        assert_eq!(nes.rewind(1).unwrap(), 0);
+    }
+
+    /// Aボタンを読むたびに$00に足し込むプログラム
+    fn input_rom() -> Vec<u8> {
+        nrom(
+            &[
+                0xA9, 0x01, 0x8D, 0x16, 0x40, // LDA #1; STA $4016
+                0xA9, 0x00, 0x8D, 0x16, 0x40, // LDA #0; STA $4016
+                0xAD, 0x16, 0x40, 0x29, 0x01, // LDA $4016; AND #1
+                0x18, 0x65, 0x00, 0x85, 0x00, // CLC; ADC $00; STA $00
+                0x4C, 0x00, 0x80, // JMP $8000
+            ],
+            &[],
+        )
+    }
+
+    #[test]
+    fn test_movie_record_and_play() {
+        let mut nes = Nes::new();
+        nes.load_rom(&input_rom()).unwrap();
+        nes.step_frame().unwrap();
+        nes.record_movie(true).unwrap();
+        for frame in 0..130 {
+            nes.set_button(0, controller::Button::A, frame % 3 == 0);
+            nes.step_frame().unwrap();
+        }
+        let ram = *nes.read_ram();
+        let movie = nes.stop_movie().unwrap();
+        assert_eq!(movie.frames.len(), 130);
+        assert_eq!(movie.checkpoints.len(), 2);
+
+        // 別のインスタンスでFM2を経由して再生する
+        let mut other = Nes::new();
+        other.load_rom(&input_rom()).unwrap();
+        other
+            .play_movie(movie::Movie::from_fm2(&movie.to_fm2("test")).unwrap())
+            .unwrap();
+        for _ in 0..130 {
+            other.step_frame().unwrap();
+        }
+        assert_eq!(other.read_ram(), &ram);
+        assert!(other.movie().unwrap().desyncs().is_empty());
+        other.step_frame().unwrap();
+        assert_eq!(other.movie().unwrap().mode(), movie::MovieMode::Finished);
+
+        // RAMを書き換えるとデシンクとして検出する
+        other.play_movie(movie).unwrap();
+        for frame in 0..130 {
+            if frame == 100 {
+                other.write_ram(0x00, 0xAA);
+            }
+            other.step_frame().unwrap();
+        }
+        let desyncs = other.movie().unwrap().desyncs();
+        assert_eq!(desyncs.len(), 1);
+        assert_eq!(desyncs[0].frame, 120);
+    }
+
+    #[test]
+    fn test_movie_from_save_state_with_reset() {
+        let mut nes = Nes::new();
+        nes.load_rom(&input_rom()).unwrap();
+        nes.set_button(0, controller::Button::A, true);
+        nes.step_frame().unwrap();
+        nes.record_movie(false).unwrap();
+        nes.step_frame().unwrap();
+        nes.reset();
+        nes.step_frame().unwrap();
+        let ram = *nes.read_ram();
+        let movie = nes.stop_movie().unwrap();
+        assert!(matches!(movie.start, movie::MovieStart::SaveState(_)));
+        assert!(movie.frames[1].reset);
+
+        nes.set_button(0, controller::Button::A, false);
+        nes.write_ram(0x00, 0);
+        nes.play_movie(movie).unwrap();
+        nes.step_frame().unwrap();
+        nes.step_frame().unwrap();
+        assert_eq!(nes.read_ram(), &ram);
+    }
+
+    #[test]
+    fn test_rewind_rerecords_movie() {
+        let mut nes = Nes::new();
+        nes.load_rom(&input_rom()).unwrap();
+        nes.enable_rewind(RewindConfig::default());
+        nes.record_movie(true).unwrap();
+        for _ in 0..10 {
+            nes.step_frame().unwrap();
+        }
+        assert_eq!(nes.rewind(4).unwrap(), 4);
+        let session = nes.movie().unwrap();
+        assert_eq!(session.frame(), 6);
+        assert_eq!(session.movie().frames.len(), 6);
+        assert_eq!(session.movie().rerecord_count, 1);
+    }
+
+    #[test]
+    fn test_load_state_rejects_other_rom() {
+        let mut nes = Nes::new();
+        nes.load_rom(&test_rom(0)).unwrap();
+        let state = nes.save_state();
+
+        let mut other = Nes::new();
+        other.load_rom(&test_rom(1)).unwrap();
+        assert!(matches!(
+            other.load_state(&state),
+            Err(NesError::StateRomMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_load_state_truncated_keeps_state() {
+        let mut nes = Nes::new();
+        nes.load_rom(&test_rom(0)).unwrap();
+        nes.step_frame().unwrap();
+        let state = nes.save_state();
+        nes.step_frame().unwrap();
+        let before = nes.save_state();
+
+        assert!(nes.load_state(&state[..state.len() - 10]).is_err());
+        assert_eq!(nes.save_state(), before);
+    }
+
+    /// NMIベクタが$9000、IRQベクタが$A000のNROMイメージ
+    fn interrupt_rom() -> Vec<u8> {
+        let mut rom = nrom(&[], &[]);
+        rom[16 + 0x7FFA..16 + 0x7FFC].copy_from_slice(&[0x00, 0x90]);
+        rom[16 + 0x7FFE..16 + 0x8000].copy_from_slice(&[0x00, 0xA0]);
+        rom
+    }
+
+    /// `setup`の直後から`asserted`が真になるまでのサイクル数を数え、
+    /// その`offset`サイクル前から$0200の`code`を`steps`命令実行する
+    fn run_with_interrupt_at(
+        setup: fn(&mut Nes),
+        asserted: fn(&Nes) -> bool,
+        offset: u64,
+        code: &[u8],
+        p: u8,
+        steps: usize,
+    ) -> Nes {
+        let start = || {
+            let mut nes = Nes::new();
+            nes.load_rom(&interrupt_rom()).unwrap();
+            setup(&mut nes);
+            nes
+        };
+        let mut probe = start();
+        let mut cycles = 0;
+        while !asserted(&probe) {
+            probe.cpu.bus.tick();
+            cycles += 1;
+        }
+
+        let mut nes = start();
+        for _ in 0..cycles - offset {
+            nes.cpu.bus.tick();
+        }
+        nes.cpu.bus.ram[0x0200..0x0200 + code.len()].copy_from_slice(code);
+        nes.cpu.set_pc(0x0200);
+        nes.cpu.set_sp(0xFD);
+        nes.cpu.p = p;
+        for _ in 0..steps {
+            nes.cpu.step().unwrap();
+        }
+        nes
+    }
+
+    /// 割り込みでスタックに積まれた戻りアドレス
+    fn pushed_pc(nes: &Nes) -> u16 {
+        u16::from_le_bytes([nes.cpu.bus.ram[0x01FC], nes.cpu.bus.ram[0x01FD]])
+    }
+
+    fn enable_frame_irq(nes: &mut Nes) {
+        nes.cpu.bus.write_byte(0x4017u16, 0x00);
+    }
+
+    fn enable_nmi(nes: &mut Nes) {
+        nes.cpu.bus.write_byte(0x2000u16, 0x80);
+    }
+
+    #[test]
+    fn test_irq_polled_before_last_cycle() {
+        let irq = |nes: &Nes| nes.cpu.bus.irq_line();
+        // 3サイクル命令の2サイクル目でIRQが立つと、直後に割り込む
+        let lda = [0xA5, 0x00, 0xEA, 0xEA]; // LDA $00; NOP; NOP
+        let nes = run_with_interrupt_at(enable_frame_irq, irq, 2, &lda, 0x20, 1);
+        assert_eq!(nes.cpu.pc(), 0xA000);
+        assert_eq!(pushed_pc(&nes), 0x0202);
+
+        // ページをまたがない分岐は追加サイクルでポーリングしないため、1命令遅れる
+        let bne = [0xD0, 0x00, 0xEA, 0xEA]; // BNE +0; NOP; NOP
+        let nes = run_with_interrupt_at(enable_frame_irq, irq, 2, &bne, 0x20, 2);
+        assert_eq!(nes.cpu.pc(), 0xA000);
+        assert_eq!(pushed_pc(&nes), 0x0203);
+    }
+
+    #[test]
+    fn test_cli_delays_irq_by_one_instruction() {
+        let irq = |nes: &Nes| nes.cpu.bus.irq_line();
+        let cli = [0x58, 0xEA, 0xEA]; // CLI; NOP; NOP
+        let nes = run_with_interrupt_at(enable_frame_irq, irq, 0, &cli, 0x24, 1);
+        assert_eq!(nes.cpu.pc(), 0x0201);
+
+        let nes = run_with_interrupt_at(enable_frame_irq, irq, 0, &cli, 0x24, 2);
+        assert_eq!(nes.cpu.pc(), 0xA000);
+        assert_eq!(pushed_pc(&nes), 0x0202);
+        // IRQはレベルトリガーなので、ハンドラ中もI=1の間は保留される
+        assert!(nes.cpu.bus.irq_line());
+    }
+
+    #[test]
+    fn test_nmi_hijacks_brk() {
+        let nmi = |nes: &Nes| nes.cpu.bus.ppu.nmi_line();
+        let brk = [0x00, 0x00];
+        // BRKの4サイクル目（PCLのプッシュ）までのNMIはベクタを乗っ取る
+        let nes = run_with_interrupt_at(enable_nmi, nmi, 4, &brk, 0x20, 1);
+        assert_eq!(nes.cpu.pc(), 0x9000);
+        assert_eq!(nes.cpu.bus.ram[0x01FB] & 0x10, 0x10);
+
+        // それより後ならBRKはそのまま実行され、NMIは次の命令の後に処理される
+        let nes = run_with_interrupt_at(enable_nmi, nmi, 5, &brk, 0x20, 1);
+        assert_eq!(nes.cpu.pc(), 0xA000);
+        assert!(nes.cpu.bus.interrupt_poll().nmi);
+    }
 }
@@ -0,0 +1,83 @@
+//! # Mapper 7 (AxROM)
+//! 32KB switchable PRG, 8KB CHR RAM, single-screen mirroring
+
+use super::{CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::rom::RomHeader;
+use crate::state::{StateReader, StateWriter};
+use crate::Result;
+
+pub struct Axrom {
+    mem: CartridgeMemory,
+    mirroring: Mirroring,
+    prg_bank: u8,
+}
+
+impl Axrom {
+    pub fn new(_header: &RomHeader, mem: CartridgeMemory) -> Self {
+        Self {
+            mem,
+            mirroring: Mirroring::SingleScreenLower,
+            prg_bank: 0,
+        }
+    }
+}
+
+impl Mapper for Axrom {
+    fn read_prg(&self, addr: u16) -> u8 {
+        match addr {
+            // 32KB switchable bank
+            0x8000..=0xFFFF => self.mem.read_prg_rom(
+                0x8000,
+                (self.prg_bank & 0x07) as usize,
+                (addr - 0x8000) as usize,
+            ),
+            _ => 0,
+        }
+    }
+
+    fn write_prg(&mut self, addr: u16, value: u8) {
+        if (0x8000..=0xFFFF).contains(&addr) {
+            // Bits 0-2: PRG bank select
+            self.prg_bank = value & 0x07;
+            // Bit 4: Nametable select (single-screen mirroring)
+            self.mirroring = if (value & 0x10) != 0 {
+                Mirroring::SingleScreenUpper
+            } else {
+                Mirroring::SingleScreenLower
+            };
+        }
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        self.mem.read_chr(0x2000, 0, (addr & 0x1FFF) as usize)
+    }
+
+    fn write_chr(&mut self, addr: u16, value: u8) {
+        self.mem
+            .write_chr(0x2000, 0, (addr & 0x1FFF) as usize, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn memory(&self) -> &CartridgeMemory {
+        &self.mem
+    }
+
+    fn memory_mut(&mut self) -> &mut CartridgeMemory {
+        &mut self.mem
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u8(self.prg_bank);
+        w.write_u8(self.mirroring as u8);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.prg_bank = r.read_u8()?;
+        self.mirroring = Mirroring::from_u8(r.read_u8()?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+//! # Mapper 3 (CNROM)
+//! PRGはNROMと同じ、8KBのCHRバンク切り替え
+
+use super::{CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::rom::RomHeader;
+use crate::state::{StateReader, StateWriter};
+use crate::Result;
+
+pub struct Cnrom {
+    mem: CartridgeMemory,
+    mirroring: Mirroring,
+    chr_bank: u8,
+}
+
+impl Cnrom {
+    pub fn new(header: &RomHeader, mem: CartridgeMemory) -> Self {
+        Self {
+            mem,
+            mirroring: header.mirroring,
+            chr_bank: 0,
+        }
+    }
+}
+
+impl Mapper for Cnrom {
+    fn read_prg(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => self.mem.read_prg_ram(addr),
+            0x8000..=0xFFFF => self.mem.read_prg_rom(0x8000, 0, (addr - 0x8000) as usize),
+            _ => 0,
+        }
+    }
+
+    fn write_prg(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF => self.mem.write_prg_ram(addr, value),
+            0x8000..=0xFFFF => {
+                // CHR bank select
+                self.chr_bank = value & 0x03;
+            }
+            _ => {}
+        }
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        self.mem
+            .read_chr(0x2000, self.chr_bank as usize, (addr & 0x1FFF) as usize)
+    }
+
+    fn write_chr(&mut self, addr: u16, value: u8) {
+        // CHR ROM, ignore writes (unless CHR RAM)
+        self.mem
+            .write_chr(0x2000, 0, (addr & 0x1FFF) as usize, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn memory(&self) -> &CartridgeMemory {
+        &self.mem
+    }
+
+    fn memory_mut(&mut self) -> &mut CartridgeMemory {
+        &mut self.mem
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u8(self.chr_bank);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.chr_bank = r.read_u8()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+//! # Mapper 66 (GxROM)
+//! 32KB switchable PRG, 8KB switchable CHR
+
+use super::{CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::rom::RomHeader;
+use crate::state::{StateReader, StateWriter};
+use crate::Result;
+
+pub struct Gxrom {
+    mem: CartridgeMemory,
+    mirroring: Mirroring,
+    prg_bank: u8,
+    chr_bank: u8,
+}
+
+impl Gxrom {
+    pub fn new(header: &RomHeader, mem: CartridgeMemory) -> Self {
+        Self {
+            mem,
+            mirroring: header.mirroring,
+            prg_bank: 0,
+            chr_bank: 0,
+        }
+    }
+}
+
+impl Mapper for Gxrom {
+    fn read_prg(&self, addr: u16) -> u8 {
+        match addr {
+            // 32KB switchable bank
+            0x8000..=0xFFFF => {
+                self.mem
+                    .read_prg_rom(0x8000, self.prg_bank as usize, (addr - 0x8000) as usize)
+            }
+            _ => 0,
+        }
+    }
+
+    fn write_prg(&mut self, addr: u16, value: u8) {
+        if (0x8000..=0xFFFF).contains(&addr) {
+            // Bits 4-5: PRG bank, Bits 0-1: CHR bank
+            self.prg_bank = (value >> 4) & 0x03;
+            self.chr_bank = value & 0x03;
+        }
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        // 8KB switchable bank
+        self.mem
+            .read_chr(0x2000, self.chr_bank as usize, (addr & 0x1FFF) as usize)
+    }
+
+    fn write_chr(&mut self, addr: u16, value: u8) {
+        // CHR ROM, ignore writes (unless CHR RAM)
+        self.mem
+            .write_chr(0x2000, 0, (addr & 0x1FFF) as usize, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn memory(&self) -> &CartridgeMemory {
+        &self.mem
+    }
+
+    fn memory_mut(&mut self) -> &mut CartridgeMemory {
+        &mut self.mem
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u8(self.prg_bank);
+        w.write_u8(self.chr_bank);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.prg_bank = r.read_u8()?;
+        self.chr_bank = r.read_u8()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+//! # Mapper 1 (MMC1)
+//! シリアルポート経由で書き込む5ビットのレジスタでPRG/CHRバンクを切り替える
+//!
+//! CHR RAMを載せたSxROM基板の一部は、使わないCHRバンクレジスタの上位ビットを
+//! 256KBのPRG ROM外側バンクやPRG RAMのバンクの選択に流用する。
+//! 基板はNES 2.0のサブマッパーとPRG RAM/ROMのサイズから選ぶ。
+//! 参考: https://www.nesdev.org/wiki/MMC1
+
+use super::{CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::rom::{RomFormat, RomHeader};
+use crate::state::{StateReader, StateWriter};
+use crate::Result;
+
+/// MMC1を載せた基板の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Board {
+    /// SNROMなどの標準的な基板
+    Standard,
+    /// SEROM/SHROM/SH1ROM (サブマッパー5): 32KBのPRG ROMをバンク切り替えせずに使う
+    FixedPrg,
+    /// SOROM: 16KBのPRG RAMをCHRバンクのビット3で切り替える
+    Sorom,
+    /// SUROM: 512KBのPRG ROMをCHRバンクのビット4で256KBずつ切り替える
+    Surom,
+    /// SXROM: SUROMに加えて32KBのPRG RAMをCHRバンクのビット2-3で切り替える
+    Sxrom,
+}
+
+impl Board {
+    fn detect(header: &RomHeader) -> Self {
+        if header.format == RomFormat::Nes20 && header.submapper == 5 {
+            return Board::FixedPrg;
+        }
+        match header.total_prg_ram_size() {
+            size if size >= 32 * 1024 => Board::Sxrom,
+            size if size >= 16 * 1024 => Board::Sorom,
+            _ if header.prg_rom_size > 256 * 1024 => Board::Surom,
+            _ => Board::Standard,
+        }
+    }
+}
+
+pub struct Mmc1 {
+    mem: CartridgeMemory,
+    board: Board,
+    mirroring: Mirroring,
+    shift_register: u8,
+    shift_count: u8,
+    control: u8,
+    chr_bank_0: u8,
+    chr_bank_1: u8,
+    prg_bank: u8,
+    /// 最後にシリアルポートへ書き込んでからのCPUサイクル数
+    cycles_since_write: u8,
+}
+
+impl Mmc1 {
+    pub fn new(header: &RomHeader, mem: CartridgeMemory) -> Self {
+        Self {
+            mem,
+            board: Board::detect(header),
+            mirroring: header.mirroring,
+            shift_register: 0,
+            shift_count: 0,
+            control: 0x0C, // PRG ROM mode 3, CHR ROM mode 0
+            chr_bank_0: 0,
+            chr_bank_1: 0,
+            prg_bank: 0,
+            cycles_since_write: u8::MAX,
+        }
+    }
+
+    /// CHRアドレスを(バンクサイズ, バンク, オフセット)に変換
+    fn chr_bank(&self, addr: u16) -> (usize, usize, usize) {
+        let chr_mode = (self.control >> 4) & 0x01;
+        if chr_mode == 0 {
+            // 8KB mode
+            (
+                0x2000,
+                (self.chr_bank_0 >> 1) as usize,
+                (addr & 0x1FFF) as usize,
+            )
+        } else if addr < 0x1000 {
+            // 4KB mode
+            (0x1000, self.chr_bank_0 as usize, (addr & 0x0FFF) as usize)
+        } else {
+            (0x1000, self.chr_bank_1 as usize, (addr & 0x0FFF) as usize)
+        }
+    }
+
+    /// 256KB単位の外側バンクの先頭（16KBバンク番号）
+    ///
+    /// 4KB CHRモードでは本来PPUが最後に参照した方のレジスタが使われるが、
+    /// ゲームは両方に同じ値を書くのでCHRバンク0で代表する。
+    fn prg_outer_bank(&self) -> usize {
+        match self.board {
+            Board::Surom | Board::Sxrom => (self.chr_bank_0 & 0x10) as usize,
+            _ => 0,
+        }
+    }
+
+    /// PRG RAMのアドレスをインデックスに変換（RAMが無効ならNone）
+    fn prg_ram_index(&self, addr: u16) -> Option<usize> {
+        // MMC1B以降: PRGバンクのビット4が1ならPRG RAMを無効にする
+        if self.prg_bank & 0x10 != 0 {
+            return None;
+        }
+        let bank = match self.board {
+            Board::Sorom => (self.chr_bank_0 >> 3) & 0x01,
+            Board::Sxrom => (self.chr_bank_0 >> 2) & 0x03,
+            _ => 0,
+        };
+        let index = bank as usize * 0x2000 + (addr - 0x6000) as usize;
+        (index < self.mem.prg_ram.len()).then_some(index)
+    }
+}
+
+impl Mapper for Mmc1 {
+    fn read_prg(&self, addr: u16) -> u8 {
+        let prg_mode = match self.board {
+            Board::FixedPrg => 0,
+            _ => (self.control >> 2) & 0x03,
+        };
+        let prg_bank = match self.board {
+            Board::FixedPrg => 0,
+            _ => (self.prg_bank & 0x0F) as usize,
+        };
+        let outer = self.prg_outer_bank();
+        match addr {
+            0x6000..=0x7FFF => self.prg_ram_index(addr).map_or(0, |i| self.mem.prg_ram[i]),
+            0x8000..=0xBFFF => {
+                let bank = match prg_mode {
+                    // 32KB mode: use lower bit of prg_bank
+                    0 | 1 => prg_bank & 0x0E,
+                    // Fix first bank at $8000
+                    2 => 0,
+                    // Switch bank at $8000
+                    _ => prg_bank,
+                };
+                self.mem
+                    .read_prg_rom(0x4000, outer | bank, (addr - 0x8000) as usize)
+            }
+            0xC000..=0xFFFF => {
+                let bank = match prg_mode {
+                    // 32KB mode: use upper bank
+                    0 | 1 => prg_bank | 0x01,
+                    // Switch bank at $C000
+                    2 => prg_bank,
+                    // Fix last bank (of the 256KB outer bank) at $C000
+                    _ => self.mem.prg_rom_banks(0x4000).saturating_sub(1) & 0x0F,
+                };
+                self.mem
+                    .read_prg_rom(0x4000, outer | bank, (addr - 0xC000) as usize)
+            }
+            _ => 0,
+        }
+    }
+
+    fn write_prg(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF => {
+                if let Some(index) = self.prg_ram_index(addr) {
+                    self.mem.prg_ram[index] = value;
+                }
+            }
+            0x8000..=0xFFFF => {
+                // 連続したサイクルの書き込み（INCなどのリードモディファイライト）は2回目を無視する
+                let consecutive = self.cycles_since_write <= 1;
+                self.cycles_since_write = 0;
+                if consecutive {
+                    return;
+                }
+
+                if value & 0x80 != 0 {
+                    // Reset shift register
+                    self.shift_register = 0;
+                    self.shift_count = 0;
+                    self.control |= 0x0C;
+                    return;
+                }
+
+                // Shift in bit 0
+                self.shift_register |= (value & 0x01) << self.shift_count;
+                self.shift_count += 1;
+
+                if self.shift_count == 5 {
+                    match (addr >> 13) & 0x03 {
+                        0 => {
+                            // Control register
+                            self.control = self.shift_register;
+                            self.mirroring = match self.control & 0x03 {
+                                0 => Mirroring::SingleScreenLower,
+                                1 => Mirroring::SingleScreenUpper,
+                                2 => Mirroring::Vertical,
+                                _ => Mirroring::Horizontal,
+                            };
+                        }
+                        1 => self.chr_bank_0 = self.shift_register,
+                        2 => self.chr_bank_1 = self.shift_register,
+                        _ => self.prg_bank = self.shift_register,
+                    }
+                    self.shift_register = 0;
+                    self.shift_count = 0;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        let (size, bank, offset) = self.chr_bank(addr);
+        self.mem.read_chr(size, bank, offset)
+    }
+
+    fn write_chr(&mut self, addr: u16, value: u8) {
+        let (size, bank, offset) = self.chr_bank(addr);
+        self.mem.write_chr(size, bank, offset, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn memory(&self) -> &CartridgeMemory {
+        &self.mem
+    }
+
+    fn memory_mut(&mut self) -> &mut CartridgeMemory {
+        &mut self.mem
+    }
+
+    fn notify_cpu_cycle(&mut self) {
+        self.cycles_since_write = self.cycles_since_write.saturating_add(1);
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u8(self.mirroring as u8);
+        w.write_u8(self.shift_register);
+        w.write_u8(self.shift_count);
+        w.write_u8(self.control);
+        w.write_u8(self.chr_bank_0);
+        w.write_u8(self.chr_bank_1);
+        w.write_u8(self.prg_bank);
+        w.write_u8(self.cycles_since_write);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.mirroring = Mirroring::from_u8(r.read_u8()?)?;
+        self.shift_register = r.read_u8()?;
+        self.shift_count = r.read_u8()?;
+        self.control = r.read_u8()?;
+        self.chr_bank_0 = r.read_u8()?;
+        self.chr_bank_1 = r.read_u8()?;
+        self.prg_bank = r.read_u8()?;
+        self.cycles_since_write = r.read_u8()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,283 @@
+//! # Mapper 4 (MMC3)
+//! 8KB単位のPRGバンク、1KB/2KB単位のCHRバンク、スキャンラインIRQカウンタ
+//!
+//! IRQカウンタはPPUのアドレスバスのA12の立ち上がりで進む。A12が短時間だけ下がった場合
+//! （スプライトフェッチの間のネームテーブル読み込みなど）は数えないよう、
+//! A12がCPUの3サイクル以上Lowだったときの立ち上がりだけを数える。
+//! 参考: https://www.nesdev.org/wiki/MMC3
+
+use super::{CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::rom::{RomFormat, RomHeader};
+use crate::state::{StateReader, StateWriter};
+use crate::Result;
+
+/// A12の立ち上がりを数えるのに必要なLowの期間（CPUサイクル）
+const A12_FILTER_CYCLES: u8 = 3;
+
+/// IRQの発生条件が異なるMMC3のリビジョン
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Revision {
+    /// Sharp製 (MMC3B/C): クロック後にカウンタが0ならIRQ（ラッチ0では毎スキャンライン）
+    Sharp,
+    /// NEC製 (MMC3A、NES 2.0のサブマッパー4): デクリメントで0になるか、リロード要求で0になったときだけIRQ
+    Nec,
+}
+
+pub struct Mmc3 {
+    mem: CartridgeMemory,
+    revision: Revision,
+    mirroring: Mirroring,
+    registers: [u8; 8],
+    register_select: u8,
+    prg_bank_mode: bool,
+    chr_bank_mode: bool,
+    /// $A001 bit 7: PRG RAMの有効化
+    prg_ram_enabled: bool,
+    /// $A001 bit 6: PRG RAMの書き込み禁止
+    prg_ram_write_protect: bool,
+    // IRQ state
+    irq_counter: u8,
+    irq_reload: u8,
+    irq_pending: bool,
+    irq_enabled: bool,
+    irq_reload_flag: bool,
+    /// PPUのアドレスバスのA12
+    a12: bool,
+    /// A12がLowになってからのCPUサイクル数
+    a12_low_cycles: u8,
+}
+
+impl Mmc3 {
+    pub fn new(header: &RomHeader, mem: CartridgeMemory) -> Self {
+        Self {
+            mem,
+            revision: if header.format == RomFormat::Nes20 && header.submapper == 4 {
+                Revision::Nec
+            } else {
+                Revision::Sharp
+            },
+            mirroring: header.mirroring,
+            registers: [0; 8],
+            register_select: 0,
+            prg_bank_mode: false,
+            chr_bank_mode: false,
+            // $A001を書かないゲームがあるので電源投入時は有効にしておく
+            prg_ram_enabled: true,
+            prg_ram_write_protect: false,
+            irq_counter: 0,
+            irq_reload: 0,
+            irq_pending: false,
+            irq_enabled: false,
+            irq_reload_flag: false,
+            a12: false,
+            a12_low_cycles: 0,
+        }
+    }
+
+    /// A12の立ち上がりでIRQカウンタを進める
+    fn clock_irq_counter(&mut self) {
+        let previous = self.irq_counter;
+        let reloaded = self.irq_reload_flag;
+        if self.irq_counter == 0 || self.irq_reload_flag {
+            self.irq_counter = self.irq_reload;
+            self.irq_reload_flag = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+
+        let fire = match self.revision {
+            Revision::Sharp => self.irq_counter == 0,
+            Revision::Nec => self.irq_counter == 0 && (previous != 0 || reloaded),
+        };
+        if fire && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+
+    /// CHRアドレスに対応する1KBバンク番号
+    fn chr_bank(&self, addr: u16) -> usize {
+        // CHR A12反転モードでは$0000と$1000の役割を入れ替える
+        let addr = if self.chr_bank_mode {
+            addr ^ 0x1000
+        } else {
+            addr
+        };
+        let bank = match addr & 0x1FFF {
+            0x0000..=0x03FF => self.registers[0] & 0xFE,
+            0x0400..=0x07FF => self.registers[0] | 0x01,
+            0x0800..=0x0BFF => self.registers[1] & 0xFE,
+            0x0C00..=0x0FFF => self.registers[1] | 0x01,
+            0x1000..=0x13FF => self.registers[2],
+            0x1400..=0x17FF => self.registers[3],
+            0x1800..=0x1BFF => self.registers[4],
+            _ => self.registers[5],
+        };
+        bank as usize
+    }
+}
+
+impl Mapper for Mmc3 {
+    fn read_prg(&self, addr: u16) -> u8 {
+        let second_last = self.mem.prg_rom_banks(0x2000).saturating_sub(2);
+        let bank = match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled => return self.mem.read_prg_ram(addr),
+            0x8000..=0x9FFF => {
+                if self.prg_bank_mode {
+                    second_last
+                } else {
+                    self.registers[6] as usize
+                }
+            }
+            0xA000..=0xBFFF => self.registers[7] as usize,
+            0xC000..=0xDFFF => {
+                if self.prg_bank_mode {
+                    self.registers[6] as usize
+                } else {
+                    second_last
+                }
+            }
+            0xE000..=0xFFFF => self.mem.prg_rom_banks(0x2000).saturating_sub(1),
+            _ => return 0,
+        };
+        self.mem
+            .read_prg_rom(0x2000, bank, (addr & 0x1FFF) as usize)
+    }
+
+    fn write_prg(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled && !self.prg_ram_write_protect => {
+                self.mem.write_prg_ram(addr, value)
+            }
+            0x8000..=0x9FFF => {
+                if addr & 1 == 0 {
+                    // Bank select ($8000-$9FFE, even)
+                    self.register_select = value & 0x07;
+                    self.prg_bank_mode = (value & 0x40) != 0;
+                    self.chr_bank_mode = (value & 0x80) != 0;
+                } else {
+                    // Bank data ($8001-$9FFF, odd)
+                    self.registers[self.register_select as usize] = value;
+                }
+            }
+            0xA000..=0xBFFF => {
+                if addr & 1 == 0 {
+                    // Mirroring ($A000-$BFFE, even) - 4画面の基板では固定
+                    if self.mirroring != Mirroring::FourScreen {
+                        self.mirroring = if (value & 0x01) != 0 {
+                            Mirroring::Horizontal
+                        } else {
+                            Mirroring::Vertical
+                        };
+                    }
+                } else {
+                    // PRG RAM protect ($A001-$BFFF, odd)
+                    self.prg_ram_enabled = (value & 0x80) != 0;
+                    self.prg_ram_write_protect = (value & 0x40) != 0;
+                }
+            }
+            0xC000..=0xDFFF => {
+                if addr & 1 == 0 {
+                    // IRQ latch ($C000-$DFFE, even)
+                    self.irq_reload = value;
+                } else {
+                    // IRQ reload ($C001-$DFFF, odd)
+                    self.irq_reload_flag = true;
+                    self.irq_counter = 0;
+                }
+            }
+            0xE000..=0xFFFF => {
+                if addr & 1 == 0 {
+                    // IRQ disable ($E000-$FFFE, even)
+                    self.irq_enabled = false;
+                    self.irq_pending = false;
+                } else {
+                    // IRQ enable ($E001-$FFFF, odd)
+                    self.irq_enabled = true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        if self.mem.chr_rom.is_empty() {
+            return self.mem.read_chr(0x2000, 0, (addr & 0x1FFF) as usize);
+        }
+        self.mem
+            .read_chr(0x0400, self.chr_bank(addr), (addr & 0x03FF) as usize)
+    }
+
+    fn write_chr(&mut self, addr: u16, value: u8) {
+        self.mem
+            .write_chr(0x2000, 0, (addr & 0x1FFF) as usize, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn memory(&self) -> &CartridgeMemory {
+        &self.mem
+    }
+
+    fn memory_mut(&mut self) -> &mut CartridgeMemory {
+        &mut self.mem
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn notify_ppu_address(&mut self, addr: u16) {
+        let a12 = addr & 0x1000 != 0;
+        if a12 && !self.a12 && self.a12_low_cycles >= A12_FILTER_CYCLES {
+            self.clock_irq_counter();
+        }
+        if !a12 && self.a12 {
+            self.a12_low_cycles = 0;
+        }
+        self.a12 = a12;
+    }
+
+    fn notify_cpu_cycle(&mut self) {
+        if !self.a12 {
+            self.a12_low_cycles = self.a12_low_cycles.saturating_add(1);
+        }
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u8(self.mirroring as u8);
+        w.write_bytes(&self.registers);
+        w.write_u8(self.register_select);
+        w.write_bool(self.prg_bank_mode);
+        w.write_bool(self.chr_bank_mode);
+        w.write_bool(self.prg_ram_enabled);
+        w.write_bool(self.prg_ram_write_protect);
+        w.write_u8(self.irq_counter);
+        w.write_u8(self.irq_reload);
+        w.write_bool(self.irq_pending);
+        w.write_bool(self.irq_enabled);
+        w.write_bool(self.irq_reload_flag);
+        w.write_bool(self.a12);
+        w.write_u8(self.a12_low_cycles);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.mirroring = Mirroring::from_u8(r.read_u8()?)?;
+        r.read_bytes_into(&mut self.registers)?;
+        self.register_select = r.read_u8()?;
+        self.prg_bank_mode = r.read_bool()?;
+        self.chr_bank_mode = r.read_bool()?;
+        self.prg_ram_enabled = r.read_bool()?;
+        self.prg_ram_write_protect = r.read_bool()?;
+        self.irq_counter = r.read_u8()?;
+        self.irq_reload = r.read_u8()?;
+        self.irq_pending = r.read_bool()?;
+        self.irq_enabled = r.read_bool()?;
+        self.irq_reload_flag = r.read_bool()?;
+        self.a12 = r.read_bool()?;
+        self.a12_low_cycles = r.read_u8()?;
+        Ok(())
+    }
+}
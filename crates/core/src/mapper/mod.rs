@@ -0,0 +1,447 @@
+//! # Mapper
+//! カートリッジ基板ごとのバンク切り替えロジック
+//!
+//! 各基板は`Mapper`トレイトを実装し、`MapperRegistry`にマッパー番号で登録する。
+//! 新しい基板を追加する場合は、このモジュールにファイルを追加して
+//! `MapperRegistry::with_builtin_mappers`に登録するだけでよい。
+
+mod axrom;
+mod cnrom;
+mod gxrom;
+mod mmc1;
+mod mmc3;
+mod nrom;
+mod uxrom;
+
+pub use axrom::Axrom;
+pub use cnrom::Cnrom;
+pub use gxrom::Gxrom;
+pub use mmc1::Mmc1;
+pub use mmc3::Mmc3;
+pub use nrom::Nrom;
+pub use uxrom::Uxrom;
+
+use crate::cartridge::Mirroring;
+use crate::rom::RomHeader;
+use crate::state::{StateReader, StateWriter};
+use crate::{NesError, Result};
+use std::collections::HashMap;
+
+/// カートリッジ上のメモリ
+pub struct CartridgeMemory {
+    pub prg_rom: Vec<u8>,
+    pub chr_rom: Vec<u8>,
+    pub prg_ram: Vec<u8>,
+    pub chr_ram: Vec<u8>,
+}
+
+impl CartridgeMemory {
+    /// PRG RAM ($6000-$7FFF) を読み取り
+    pub fn read_prg_ram(&self, addr: u16) -> u8 {
+        let index = (addr - 0x6000) as usize;
+        self.prg_ram.get(index).copied().unwrap_or(0)
+    }
+
+    /// PRG RAM ($6000-$7FFF) に書き込み
+    pub fn write_prg_ram(&mut self, addr: u16, value: u8) {
+        let index = (addr - 0x6000) as usize;
+        if index < self.prg_ram.len() {
+            self.prg_ram[index] = value;
+        }
+    }
+
+    /// `bank_size`単位のPRG ROMバンクから読み取り（範囲外のバンクは折り返す）
+    pub fn read_prg_rom(&self, bank_size: usize, bank: usize, offset: usize) -> u8 {
+        let index = (bank * bank_size + offset) % self.prg_rom.len().max(1);
+        self.prg_rom.get(index).copied().unwrap_or(0)
+    }
+
+    /// PRG ROMのバンク数
+    pub fn prg_rom_banks(&self, bank_size: usize) -> usize {
+        self.prg_rom.len() / bank_size
+    }
+
+    /// `bank_size`単位のCHRバンクから読み取り（CHR ROMがなければCHR RAM）
+    pub fn read_chr(&self, bank_size: usize, bank: usize, offset: usize) -> u8 {
+        let chr = if self.chr_rom.is_empty() {
+            &self.chr_ram
+        } else {
+            &self.chr_rom
+        };
+        let index = (bank * bank_size + offset) % chr.len().max(1);
+        chr.get(index).copied().unwrap_or(0)
+    }
+
+    /// `bank_size`単位のCHRバンクに書き込み（CHR RAMの場合のみ有効）
+    pub fn write_chr(&mut self, bank_size: usize, bank: usize, offset: usize, value: u8) {
+        if self.chr_rom.is_empty() && !self.chr_ram.is_empty() {
+            let index = (bank * bank_size + offset) % self.chr_ram.len();
+            self.chr_ram[index] = value;
+        }
+    }
+}
+
+/// カートリッジ基板の共通インターフェース
+pub trait Mapper {
+    /// CPU空間 ($4020-$FFFF) の読み取り
+    fn read_prg(&self, addr: u16) -> u8;
+
+    /// CPU空間 ($4020-$FFFF) への書き込み
+    fn write_prg(&mut self, addr: u16, value: u8);
+
+    /// PPU空間 ($0000-$1FFF) の読み取り
+    fn read_chr(&self, addr: u16) -> u8;
+
+    /// PPU空間 ($0000-$1FFF) への書き込み
+    fn write_chr(&mut self, addr: u16, value: u8);
+
+    /// 現在のネームテーブルミラーリング
+    fn mirroring(&self) -> Mirroring;
+
+    /// カートリッジ上のメモリ
+    fn memory(&self) -> &CartridgeMemory;
+
+    /// カートリッジ上のメモリ（可変）
+    fn memory_mut(&mut self) -> &mut CartridgeMemory;
+
+    /// IRQ信号がアサートされているか（レベルトリガー: ゲームがレジスタ操作でクリアするまで保持する）
+    fn irq_pending(&self) -> bool {
+        false
+    }
+
+    /// PPUのアドレスバスの通知（レンダリングのフェッチと$2006/$2007のアクセス）
+    fn notify_ppu_address(&mut self, _addr: u16) {}
+
+    /// CPUサイクルごとの通知
+    fn notify_cpu_cycle(&mut self) {}
+
+    /// バンク/IRQレジスタを保存（メモリの内容はCartridge側で保存する）
+    fn save_state(&self, w: &mut StateWriter);
+
+    /// バンク/IRQレジスタを復元
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()>;
+}
+
+/// マッパーのコンストラクタ
+pub type MapperConstructor = fn(&RomHeader, CartridgeMemory) -> Box<dyn Mapper>;
+
+/// マッパー番号からコンストラクタを引くレジストリ
+#[derive(Clone)]
+pub struct MapperRegistry {
+    constructors: HashMap<u16, MapperConstructor>,
+}
+
+impl MapperRegistry {
+    /// 空のレジストリ
+    pub fn new() -> Self {
+        Self {
+            constructors: HashMap::new(),
+        }
+    }
+
+    /// 組み込みのマッパーを登録したレジストリ
+    pub fn with_builtin_mappers() -> Self {
+        let mut registry = Self::new();
+        registry.register(0, |h, m| Box::new(Nrom::new(h, m)));
+        registry.register(1, |h, m| Box::new(Mmc1::new(h, m)));
+        registry.register(2, |h, m| Box::new(Uxrom::new(h, m)));
+        registry.register(3, |h, m| Box::new(Cnrom::new(h, m)));
+        registry.register(4, |h, m| Box::new(Mmc3::new(h, m)));
+        registry.register(7, |h, m| Box::new(Axrom::new(h, m)));
+        registry.register(66, |h, m| Box::new(Gxrom::new(h, m)));
+        registry
+    }
+
+    /// マッパーを登録（同じ番号が登録済みなら置き換える）
+    pub fn register(&mut self, number: u16, constructor: MapperConstructor) {
+        self.constructors.insert(number, constructor);
+    }
+
+    /// マッパー番号が登録されているか
+    pub fn is_supported(&self, number: u16) -> bool {
+        self.constructors.contains_key(&number)
+    }
+
+    /// ヘッダーのマッパー番号に対応するマッパーを生成
+    pub fn create(&self, header: &RomHeader, memory: CartridgeMemory) -> Result<Box<dyn Mapper>> {
+        let constructor = self
+            .constructors
+            .get(&header.mapper)
+            .ok_or(NesError::UnsupportedMapper(header.mapper))?;
+        Ok(constructor(header, memory))
+    }
+}
+
+impl Default for MapperRegistry {
+    fn default() -> Self {
+        Self::with_builtin_mappers()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(mapper: u16) -> RomHeader {
+        let mut data = vec![0x4E, 0x45, 0x53, 0x1A, 8, 0, 0, 0];
+        data.resize(16, 0);
+        let mut header = RomHeader::parse(&data).unwrap();
+        header.mapper = mapper;
+        header
+    }
+
+    /// 各8KBバンクの先頭バイトにバンク番号を書いた128KBのPRG ROM
+    fn memory() -> CartridgeMemory {
+        let mut prg_rom = vec![0u8; 128 * 1024];
+        for (i, bank) in prg_rom.chunks_mut(0x2000).enumerate() {
+            bank[0] = i as u8;
+        }
+        CartridgeMemory {
+            prg_rom,
+            chr_rom: vec![],
+            prg_ram: vec![0; 8192],
+            chr_ram: vec![0; 8192],
+        }
+    }
+
+    #[test]
+    fn test_registry_rejects_unknown_mapper() {
+        let registry = MapperRegistry::default();
+        assert!(matches!(
+            registry.create(&header(5), memory()),
+            Err(NesError::UnsupportedMapper(5))
+        ));
+    }
+
+    #[test]
+    fn test_registry_custom_mapper() {
+        let mut registry = MapperRegistry::new();
+        registry.register(200, |h, m| Box::new(Nrom::new(h, m)));
+        let mapper = registry.create(&header(200), memory()).unwrap();
+        assert_eq!(mapper.read_prg(0x8000), 0);
+        assert_eq!(mapper.read_prg(0xA000), 1);
+    }
+
+    #[test]
+    fn test_uxrom_bank_switch() {
+        let mut mapper = MapperRegistry::default()
+            .create(&header(2), memory())
+            .unwrap();
+        mapper.write_prg(0x8000, 3);
+        assert_eq!(mapper.read_prg(0x8000), 6);
+        assert_eq!(mapper.read_prg(0xC000), 14);
+    }
+
+    #[test]
+    fn test_mmc3_prg_modes_and_irq() {
+        let mut mapper = MapperRegistry::default()
+            .create(&header(4), memory())
+            .unwrap();
+        mapper.write_prg(0x8000, 6);
+        mapper.write_prg(0x8001, 5);
+        assert_eq!(mapper.read_prg(0x8000), 5);
+        assert_eq!(mapper.read_prg(0xC000), 14);
+        mapper.write_prg(0x8000, 0x46);
+        assert_eq!(mapper.read_prg(0x8000), 14);
+        assert_eq!(mapper.read_prg(0xC000), 5);
+        assert_eq!(mapper.read_prg(0xE000), 15);
+
+        mapper.write_prg(0xC000, 1);
+        mapper.write_prg(0xC001, 0);
+        mapper.write_prg(0xE001, 0);
+        mmc3_a12_rise(mapper.as_mut(), 3);
+        assert!(!mapper.irq_pending());
+        mmc3_a12_rise(mapper.as_mut(), 3);
+        assert!(mapper.irq_pending());
+    }
+
+    /// A12を`low_cycles`CPUサイクルの間Lowにしてから立ち上げる
+    fn mmc3_a12_rise(mapper: &mut dyn Mapper, low_cycles: usize) {
+        mapper.notify_ppu_address(0x0FF0);
+        for _ in 0..low_cycles {
+            mapper.notify_cpu_cycle();
+        }
+        mapper.notify_ppu_address(0x1FF0);
+    }
+
+    fn mmc3_with_submapper(submapper: u8) -> Box<dyn Mapper> {
+        let mut header = header(4);
+        header.format = crate::rom::RomFormat::Nes20;
+        header.submapper = submapper;
+        MapperRegistry::default().create(&header, memory()).unwrap()
+    }
+
+    #[test]
+    fn test_mmc3_a12_filter() {
+        let mut mapper = mmc3_with_submapper(0);
+        mapper.write_prg(0xC000, 2);
+        mapper.write_prg(0xC001, 0);
+        mapper.write_prg(0xE001, 0);
+        // スプライトフェッチの間のような短いLowは数えない
+        for _ in 0..10 {
+            mmc3_a12_rise(mapper.as_mut(), 1);
+        }
+        mmc3_a12_rise(mapper.as_mut(), 3);
+        mmc3_a12_rise(mapper.as_mut(), 3);
+        assert!(!mapper.irq_pending());
+        mmc3_a12_rise(mapper.as_mut(), 3);
+        assert!(mapper.irq_pending());
+
+        // $E000でIRQを無効にするとクリアされる
+        mapper.write_prg(0xE000, 0);
+        assert!(!mapper.irq_pending());
+    }
+
+    #[test]
+    fn test_mmc3_revisions() {
+        // ラッチ0: Sharpは毎回、NECはリロード要求の直後だけIRQを発生させる
+        for (submapper, expected) in [(0, [true, true, true]), (4, [true, false, false])] {
+            let mut mapper = mmc3_with_submapper(submapper);
+            mapper.write_prg(0xC000, 0);
+            mapper.write_prg(0xC001, 0);
+            mapper.write_prg(0xE001, 0);
+            for expected in expected {
+                mmc3_a12_rise(mapper.as_mut(), 3);
+                assert_eq!(mapper.irq_pending(), expected, "submapper {}", submapper);
+                mapper.write_prg(0xE000, 0);
+                mapper.write_prg(0xE001, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mmc3_prg_ram_protect() {
+        let mut mapper = MapperRegistry::default()
+            .create(&header(4), memory())
+            .unwrap();
+        mapper.write_prg(0x6000, 0x11);
+        assert_eq!(mapper.read_prg(0x6000), 0x11);
+
+        // 書き込み禁止
+        mapper.write_prg(0xA001, 0xC0);
+        mapper.write_prg(0x6000, 0x22);
+        assert_eq!(mapper.read_prg(0x6000), 0x11);
+
+        // 無効: 読み書きとも効かない
+        mapper.write_prg(0xA001, 0x00);
+        mapper.write_prg(0x6000, 0x33);
+        assert_eq!(mapper.read_prg(0x6000), 0);
+        mapper.write_prg(0xA001, 0x80);
+        assert_eq!(mapper.read_prg(0x6000), 0x11);
+    }
+
+    #[test]
+    fn test_mmc3_four_screen() {
+        let mut header = header(4);
+        header.four_screen = true;
+        header.mirroring = Mirroring::FourScreen;
+        let mut mapper = MapperRegistry::default().create(&header, memory()).unwrap();
+        mapper.write_prg(0xA000, 0x01);
+        assert_eq!(mapper.mirroring(), Mirroring::FourScreen);
+    }
+
+    /// MMC1のシリアル書き込み（LSBから5ビット、連続サイクルにならないよう間を空ける）
+    fn mmc1_write(mapper: &mut dyn Mapper, addr: u16, value: u8) {
+        for i in 0..5 {
+            mapper.notify_cpu_cycle();
+            mapper.notify_cpu_cycle();
+            mapper.write_prg(addr, (value >> i) & 0x01);
+        }
+    }
+
+    /// 16KBのPRG ROMバンクの先頭バイトにバンク番号を書いたMMC1基板
+    fn mmc1(prg_rom_kb: usize, prg_ram_kb: usize) -> Box<dyn Mapper> {
+        let mut header = header(1);
+        header.prg_rom_size = prg_rom_kb * 1024;
+        header.prg_ram_size = prg_ram_kb * 1024;
+        let mut prg_rom = vec![0u8; prg_rom_kb * 1024];
+        for (i, bank) in prg_rom.chunks_mut(0x4000).enumerate() {
+            bank[0] = i as u8;
+        }
+        let memory = CartridgeMemory {
+            prg_rom,
+            chr_rom: vec![],
+            prg_ram: vec![0; prg_ram_kb * 1024],
+            chr_ram: vec![0; 8192],
+        };
+        MapperRegistry::default().create(&header, memory).unwrap()
+    }
+
+    #[test]
+    fn test_mmc1_mirroring_and_consecutive_writes() {
+        let mut mapper = mmc1(256, 8);
+        mmc1_write(mapper.as_mut(), 0x8000, 0x00);
+        assert_eq!(mapper.mirroring(), Mirroring::SingleScreenLower);
+        mmc1_write(mapper.as_mut(), 0x8000, 0x01);
+        assert_eq!(mapper.mirroring(), Mirroring::SingleScreenUpper);
+        mmc1_write(mapper.as_mut(), 0x8000, 0x0F);
+        assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+
+        // INCのダミーライトと本来の書き込み: 2回目は無視されるので1ビットだけ入る
+        mapper.notify_cpu_cycle();
+        mapper.notify_cpu_cycle();
+        mapper.write_prg(0xE000, 0x00);
+        mapper.notify_cpu_cycle();
+        mapper.write_prg(0xE000, 0x01);
+        for _ in 0..4 {
+            mapper.notify_cpu_cycle();
+            mapper.notify_cpu_cycle();
+            mapper.write_prg(0xE000, 0x01);
+        }
+        assert_eq!(mapper.read_prg(0x8000), 0x0E);
+    }
+
+    #[test]
+    fn test_mmc1_surom_outer_bank() {
+        let mut mapper = mmc1(512, 8);
+        mmc1_write(mapper.as_mut(), 0xE000, 3);
+        assert_eq!(mapper.read_prg(0x8000), 3);
+        assert_eq!(mapper.read_prg(0xC000), 15);
+        // CHRバンク0のビット4で後半の256KBへ
+        mmc1_write(mapper.as_mut(), 0xA000, 0x10);
+        assert_eq!(mapper.read_prg(0x8000), 19);
+        assert_eq!(mapper.read_prg(0xC000), 31);
+    }
+
+    #[test]
+    fn test_mmc1_prg_ram_banks() {
+        let mut mapper = mmc1(256, 32);
+        for bank in 0..4u8 {
+            mmc1_write(mapper.as_mut(), 0xA000, bank << 2);
+            mapper.write_prg(0x6000, 0x40 + bank);
+        }
+        for bank in 0..4u8 {
+            mmc1_write(mapper.as_mut(), 0xA000, bank << 2);
+            assert_eq!(mapper.read_prg(0x6000), 0x40 + bank);
+        }
+        assert_eq!(&mapper.memory().prg_ram[0x6000..0x6001], &[0x43]);
+
+        // PRGバンクのビット4でPRG RAMを無効にする
+        mmc1_write(mapper.as_mut(), 0xE000, 0x10);
+        mapper.write_prg(0x6000, 0xFF);
+        assert_eq!(mapper.read_prg(0x6000), 0);
+        mmc1_write(mapper.as_mut(), 0xE000, 0x00);
+        assert_eq!(mapper.read_prg(0x6000), 0x43);
+
+        // SOROMはビット3で2つのバンクを切り替える
+        let mut mapper = mmc1(256, 16);
+        mmc1_write(mapper.as_mut(), 0xA000, 0x08);
+        mapper.write_prg(0x7FFF, 0x55);
+        assert_eq!(mapper.memory().prg_ram[0x3FFF], 0x55);
+    }
+
+    #[test]
+    fn test_mapper_state_round_trip() {
+        let registry = MapperRegistry::default();
+        let mut mapper = registry.create(&header(1), memory()).unwrap();
+        mmc1_write(mapper.as_mut(), 0xE000, 1);
+        let mut w = StateWriter::new();
+        mapper.save_state(&mut w);
+        let data = w.into_inner();
+
+        let mut restored = registry.create(&header(1), memory()).unwrap();
+        restored.load_state(&mut StateReader::new(&data)).unwrap();
+        assert_eq!(restored.read_prg(0x8000), mapper.read_prg(0x8000));
+        assert_eq!(restored.read_prg(0x8000), 2);
+    }
+}
@@ -0,0 +1,66 @@
+//! # Mapper 0 (NROM)
+//! 最も単純なマッパー、バンク切り替えなし
+
+use super::{CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::rom::RomHeader;
+use crate::state::{StateReader, StateWriter};
+use crate::Result;
+
+pub struct Nrom {
+    mem: CartridgeMemory,
+    mirroring: Mirroring,
+}
+
+impl Nrom {
+    pub fn new(header: &RomHeader, mem: CartridgeMemory) -> Self {
+        Self {
+            mem,
+            mirroring: header.mirroring,
+        }
+    }
+}
+
+impl Mapper for Nrom {
+    fn read_prg(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => self.mem.read_prg_ram(addr),
+            // 16KBの場合は$C000-$FFFFにミラー
+            0x8000..=0xFFFF => self.mem.read_prg_rom(0x8000, 0, (addr - 0x8000) as usize),
+            _ => 0,
+        }
+    }
+
+    fn write_prg(&mut self, addr: u16, value: u8) {
+        if (0x6000..=0x7FFF).contains(&addr) {
+            self.mem.write_prg_ram(addr, value);
+        }
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        self.mem.read_chr(0x2000, 0, (addr & 0x1FFF) as usize)
+    }
+
+    fn write_chr(&mut self, addr: u16, value: u8) {
+        self.mem
+            .write_chr(0x2000, 0, (addr & 0x1FFF) as usize, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn memory(&self) -> &CartridgeMemory {
+        &self.mem
+    }
+
+    fn memory_mut(&mut self) -> &mut CartridgeMemory {
+        &mut self.mem
+    }
+
+    fn save_state(&self, _w: &mut StateWriter) {}
+
+    fn load_state(&mut self, _r: &mut StateReader) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+//! # Mapper 2 (UxROM)
+//! $8000-$BFFFが切り替え可能な16KBバンク、$C000-$FFFFは最終バンクに固定
+
+use super::{CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::rom::RomHeader;
+use crate::state::{StateReader, StateWriter};
+use crate::Result;
+
+pub struct Uxrom {
+    mem: CartridgeMemory,
+    mirroring: Mirroring,
+    prg_bank: u8,
+}
+
+impl Uxrom {
+    pub fn new(header: &RomHeader, mem: CartridgeMemory) -> Self {
+        Self {
+            mem,
+            mirroring: header.mirroring,
+            prg_bank: 0,
+        }
+    }
+}
+
+impl Mapper for Uxrom {
+    fn read_prg(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => self.mem.read_prg_ram(addr),
+            0x8000..=0xBFFF => {
+                // Switchable 16KB bank
+                self.mem
+                    .read_prg_rom(0x4000, self.prg_bank as usize, (addr - 0x8000) as usize)
+            }
+            0xC000..=0xFFFF => {
+                // Fixed to last 16KB bank
+                let last_bank = self.mem.prg_rom_banks(0x4000).saturating_sub(1);
+                self.mem
+                    .read_prg_rom(0x4000, last_bank, (addr - 0xC000) as usize)
+            }
+            _ => 0,
+        }
+    }
+
+    fn write_prg(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF => self.mem.write_prg_ram(addr, value),
+            0x8000..=0xFFFF => {
+                // Bank select
+                self.prg_bank = value & 0x0F;
+            }
+            _ => {}
+        }
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        self.mem.read_chr(0x2000, 0, (addr & 0x1FFF) as usize)
+    }
+
+    fn write_chr(&mut self, addr: u16, value: u8) {
+        self.mem
+            .write_chr(0x2000, 0, (addr & 0x1FFF) as usize, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn memory(&self) -> &CartridgeMemory {
+        &self.mem
+    }
+
+    fn memory_mut(&mut self) -> &mut CartridgeMemory {
+        &mut self.mem
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u8(self.prg_bank);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.prg_bank = r.read_u8()?;
+        Ok(())
+    }
+}
@@ -61,6 +61,8 @@ pub struct Watchpoint {
     pub address: u16,
     pub label: String,
     pub last_value: u8,
+    /// 値が変化した回数
+    pub changes: u64,
 }
 
 /// メモリエディタ
@@ -110,10 +112,10 @@ impl MemoryEditor {
                 SearchCondition::GreaterThan(v) => value > v,
                 SearchCondition::LessThan(v) => value < v,
                 SearchCondition::Between(lo, hi) => value >= lo && value <= hi,
-                SearchCondition::Increased => prev.map_or(false, |p| value > p),
-                SearchCondition::Decreased => prev.map_or(false, |p| value < p),
-                SearchCondition::Unchanged => prev.map_or(false, |p| value == p),
-                SearchCondition::Changed => prev.map_or(false, |p| value != p),
+                SearchCondition::Increased => prev.is_some_and(|p| value > p),
+                SearchCondition::Decreased => prev.is_some_and(|p| value < p),
+                SearchCondition::Unchanged => prev == Some(value),
+                SearchCondition::Changed => prev.is_some_and(|p| value != p),
             };
 
             if matches {
@@ -146,10 +148,10 @@ impl MemoryEditor {
                 SearchCondition::GreaterThan(v) => value > v,
                 SearchCondition::LessThan(v) => value < v,
                 SearchCondition::Between(lo, hi) => value >= lo && value <= hi,
-                SearchCondition::Increased => prev.map_or(false, |p| value > p),
-                SearchCondition::Decreased => prev.map_or(false, |p| value < p),
-                SearchCondition::Unchanged => prev.map_or(false, |p| value == p),
-                SearchCondition::Changed => prev.map_or(false, |p| value != p),
+                SearchCondition::Increased => prev.is_some_and(|p| value > p),
+                SearchCondition::Decreased => prev.is_some_and(|p| value < p),
+                SearchCondition::Unchanged => prev == Some(value),
+                SearchCondition::Changed => prev.is_some_and(|p| value != p),
             }
         });
 
@@ -176,18 +178,28 @@ impl MemoryEditor {
         &self.search_results
     }
 
-    /// ウォッチポイントを追加
-    pub fn add_watchpoint(&mut self, region: MemoryRegion, address: u16, label: &str) -> u32 {
+    /// 現在の値が`value`のアドレスにウォッチポイントを追加
+    pub fn add_watchpoint(
+        &mut self,
+        region: MemoryRegion,
+        address: u16,
+        label: &str,
+        value: u8,
+    ) -> u32 {
         let id = self.next_watchpoint_id;
         self.next_watchpoint_id += 1;
 
-        self.watchpoints.insert(id, Watchpoint {
+        self.watchpoints.insert(
             id,
-            region,
-            address,
-            label: label.to_string(),
-            last_value: 0,
-        });
+            Watchpoint {
+                id,
+                region,
+                address,
+                label: label.to_string(),
+                last_value: value,
+                changes: 0,
+            },
+        );
 
         id
     }
@@ -202,21 +214,40 @@ impl MemoryEditor {
         self.watchpoints.values().collect()
     }
 
-    /// ウォッチポイントの値を更新
-    pub fn update_watchpoint(&mut self, id: u32, value: u8) {
-        if let Some(wp) = self.watchpoints.get_mut(&id) {
-            wp.last_value = value;
+    /// ウォッチポイントがあるか
+    pub fn has_watchpoints(&self) -> bool {
+        !self.watchpoints.is_empty()
+    }
+
+    /// 各ウォッチポイントの値を`read`で読み直し、変化したもののIDを返す
+    ///
+    /// `Nes`はバスへの書き込みがあった命令の後に呼び出す。
+    pub fn refresh_watchpoints(&mut self, read: impl Fn(MemoryRegion, u16) -> u8) -> Vec<u32> {
+        let mut changed = Vec::new();
+        for wp in self.watchpoints.values_mut() {
+            let value = read(wp.region, wp.address);
+            if value != wp.last_value {
+                wp.last_value = value;
+                wp.changes += 1;
+                changed.push(wp.id);
+            }
         }
+        changed
     }
 
     /// メモリダンプを16進数文字列で取得
     pub fn hex_dump(memory: &[u8], start: usize, length: usize) -> String {
+        Self::hex_dump_at(memory, start, length, 0)
+    }
+
+    /// `memory[0]`を`base`番地として表示するメモリダンプ
+    pub fn hex_dump_at(memory: &[u8], start: usize, length: usize, base: u16) -> String {
         let mut result = String::new();
         let end = (start + length).min(memory.len());
 
         for addr in (start..end).step_by(16) {
             // アドレス
-            result.push_str(&format!("{:04X}: ", addr));
+            result.push_str(&format!("{:04X}: ", (base as usize + addr) & 0xFFFF));
 
             // 16進ダンプ
             for i in 0..16 {
@@ -248,136 +279,42 @@ impl MemoryEditor {
         result
     }
 
-    /// 逆アセンブル（簡易版）
+    /// 逆アセンブル
+    ///
+    /// `memory`は`start`番地から始まるバイト列。分岐命令の飛び先は絶対アドレスに解決する。
+    /// 末尾で命令が途切れた場合、足りないオペランドは`??`と表示する。
     pub fn disassemble(memory: &[u8], start: u16, count: usize) -> Vec<(u16, String)> {
         let mut result = Vec::new();
-        let mut pc = start as usize;
+        let mut offset = 0usize;
 
         for _ in 0..count {
-            if pc >= memory.len() {
+            let Some(&opcode) = memory.get(offset) else {
                 break;
-            }
-
-            let opcode = memory[pc];
-            let (mnemonic, size) = Self::decode_instruction(opcode);
-
-            let operand = match size {
-                1 => String::new(),
-                2 => {
-                    if pc + 1 < memory.len() {
-                        format!(" ${:02X}", memory[pc + 1])
-                    } else {
-                        " ??".to_string()
-                    }
-                }
-                3 => {
-                    if pc + 2 < memory.len() {
-                        format!(" ${:02X}{:02X}", memory[pc + 2], memory[pc + 1])
-                    } else {
-                        " ????".to_string()
-                    }
-                }
-                _ => String::new(),
             };
-
-            result.push((pc as u16, format!("{}{}", mnemonic, operand)));
-            pc += size;
+            let address = start.wrapping_add(offset as u16);
+            let op = Self::decode(opcode);
+            let size = op.mode.size();
+            let operand = &memory[offset + 1..(offset + size).min(memory.len())];
+
+            let text = if operand.len() + 1 < size {
+                format!("{} ??", op.mnemonic)
+            } else {
+                format!(
+                    "{}{}",
+                    op.mnemonic,
+                    op.mode.format_operand(address, operand)
+                )
+            };
+            result.push((address, text));
+            offset += size;
         }
 
         result
     }
 
-    fn decode_instruction(opcode: u8) -> (&'static str, usize) {
-        match opcode {
-            // Load/Store
-            0xA9 => ("LDA #", 2),
-            0xA5 => ("LDA", 2),
-            0xAD => ("LDA", 3),
-            0xA2 => ("LDX #", 2),
-            0xA6 => ("LDX", 2),
-            0xAE => ("LDX", 3),
-            0xA0 => ("LDY #", 2),
-            0xA4 => ("LDY", 2),
-            0xAC => ("LDY", 3),
-            0x85 => ("STA", 2),
-            0x8D => ("STA", 3),
-            0x86 => ("STX", 2),
-            0x8E => ("STX", 3),
-            0x84 => ("STY", 2),
-            0x8C => ("STY", 3),
-            // Arithmetic
-            0x69 => ("ADC #", 2),
-            0x65 => ("ADC", 2),
-            0x6D => ("ADC", 3),
-            0xE9 => ("SBC #", 2),
-            0xE5 => ("SBC", 2),
-            0xED => ("SBC", 3),
-            // Compare
-            0xC9 => ("CMP #", 2),
-            0xC5 => ("CMP", 2),
-            0xCD => ("CMP", 3),
-            0xE0 => ("CPX #", 2),
-            0xC0 => ("CPY #", 2),
-            // Logic
-            0x29 => ("AND #", 2),
-            0x09 => ("ORA #", 2),
-            0x49 => ("EOR #", 2),
-            // Shift
-            0x0A => ("ASL A", 1),
-            0x4A => ("LSR A", 1),
-            0x2A => ("ROL A", 1),
-            0x6A => ("ROR A", 1),
-            // Inc/Dec
-            0xE6 => ("INC", 2),
-            0xEE => ("INC", 3),
-            0xC6 => ("DEC", 2),
-            0xCE => ("DEC", 3),
-            0xE8 => ("INX", 1),
-            0xCA => ("DEX", 1),
-            0xC8 => ("INY", 1),
-            0x88 => ("DEY", 1),
-            // Transfer
-            0xAA => ("TAX", 1),
-            0xA8 => ("TAY", 1),
-            0x8A => ("TXA", 1),
-            0x98 => ("TYA", 1),
-            0x9A => ("TXS", 1),
-            0xBA => ("TSX", 1),
-            // Stack
-            0x48 => ("PHA", 1),
-            0x68 => ("PLA", 1),
-            0x08 => ("PHP", 1),
-            0x28 => ("PLP", 1),
-            // Branch
-            0x10 => ("BPL", 2),
-            0x30 => ("BMI", 2),
-            0x50 => ("BVC", 2),
-            0x70 => ("BVS", 2),
-            0x90 => ("BCC", 2),
-            0xB0 => ("BCS", 2),
-            0xD0 => ("BNE", 2),
-            0xF0 => ("BEQ", 2),
-            // Jump
-            0x4C => ("JMP", 3),
-            0x6C => ("JMP (", 3),
-            0x20 => ("JSR", 3),
-            0x60 => ("RTS", 1),
-            0x40 => ("RTI", 1),
-            // Flags
-            0x18 => ("CLC", 1),
-            0x38 => ("SEC", 1),
-            0x58 => ("CLI", 1),
-            0x78 => ("SEI", 1),
-            0xB8 => ("CLV", 1),
-            0xD8 => ("CLD", 1),
-            0xF8 => ("SED", 1),
-            // Other
-            0xEA => ("NOP", 1),
-            0x00 => ("BRK", 1),
-            0x24 => ("BIT", 2),
-            0x2C => ("BIT", 3),
-            _ => ("???", 1),
-        }
+    /// オペコードをデコード
+    pub fn decode(opcode: u8) -> Opcode {
+        OPCODES[opcode as usize]
     }
 }
 
@@ -387,6 +324,346 @@ impl Default for MemoryEditor {
     }
 }
 
+/// アドレッシングモード
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressingMode {
+    Implied,
+    Accumulator,
+    Immediate,
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    Indirect,
+    IndirectX,
+    IndirectY,
+    Relative,
+}
+
+impl AddressingMode {
+    /// オペコードを含む命令のバイト数
+    pub fn size(self) -> usize {
+        match self {
+            AddressingMode::Implied | AddressingMode::Accumulator => 1,
+            AddressingMode::Absolute
+            | AddressingMode::AbsoluteX
+            | AddressingMode::AbsoluteY
+            | AddressingMode::Indirect => 3,
+            _ => 2,
+        }
+    }
+
+    /// オペランドを`$12,X`のようなアセンブラ表記にする（先頭の空白を含む）
+    fn format_operand(self, address: u16, operand: &[u8]) -> String {
+        let byte = || operand[0];
+        let word = || u16::from_le_bytes([operand[0], operand[1]]);
+        match self {
+            AddressingMode::Implied => String::new(),
+            AddressingMode::Accumulator => " A".to_string(),
+            AddressingMode::Immediate => format!(" #${:02X}", byte()),
+            AddressingMode::ZeroPage => format!(" ${:02X}", byte()),
+            AddressingMode::ZeroPageX => format!(" ${:02X},X", byte()),
+            AddressingMode::ZeroPageY => format!(" ${:02X},Y", byte()),
+            AddressingMode::Absolute => format!(" ${:04X}", word()),
+            AddressingMode::AbsoluteX => format!(" ${:04X},X", word()),
+            AddressingMode::AbsoluteY => format!(" ${:04X},Y", word()),
+            AddressingMode::Indirect => format!(" (${:04X})", word()),
+            AddressingMode::IndirectX => format!(" (${:02X},X)", byte()),
+            AddressingMode::IndirectY => format!(" (${:02X}),Y", byte()),
+            AddressingMode::Relative => {
+                let target = address.wrapping_add(2).wrapping_add(byte() as i8 as u16);
+                format!(" ${:04X}", target)
+            }
+        }
+    }
+}
+
+/// オペコード表のエントリ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Opcode {
+    /// ニーモニック（非公式命令はnestest.logと同じ名前）
+    pub mnemonic: &'static str,
+    pub mode: AddressingMode,
+    /// 公式命令か
+    pub official: bool,
+}
+
+const fn op(mnemonic: &'static str, mode: AddressingMode, official: bool) -> Opcode {
+    Opcode {
+        mnemonic,
+        mode,
+        official,
+    }
+}
+
+/// 256個すべてのオペコード
+///
+/// 参考: https://www.nesdev.org/wiki/CPU_unofficial_opcodes
+#[rustfmt::skip]
+const OPCODES: [Opcode; 256] = {
+    use AddressingMode::*;
+    [
+    op("BRK", Implied, true), // $00
+    op("ORA", IndirectX, true), // $01
+    op("KIL", Implied, false), // $02
+    op("SLO", IndirectX, false), // $03
+    op("NOP", ZeroPage, false), // $04
+    op("ORA", ZeroPage, true), // $05
+    op("ASL", ZeroPage, true), // $06
+    op("SLO", ZeroPage, false), // $07
+    op("PHP", Implied, true), // $08
+    op("ORA", Immediate, true), // $09
+    op("ASL", Accumulator, true), // $0A
+    op("ANC", Immediate, false), // $0B
+    op("NOP", Absolute, false), // $0C
+    op("ORA", Absolute, true), // $0D
+    op("ASL", Absolute, true), // $0E
+    op("SLO", Absolute, false), // $0F
+    op("BPL", Relative, true), // $10
+    op("ORA", IndirectY, true), // $11
+    op("KIL", Implied, false), // $12
+    op("SLO", IndirectY, false), // $13
+    op("NOP", ZeroPageX, false), // $14
+    op("ORA", ZeroPageX, true), // $15
+    op("ASL", ZeroPageX, true), // $16
+    op("SLO", ZeroPageX, false), // $17
+    op("CLC", Implied, true), // $18
+    op("ORA", AbsoluteY, true), // $19
+    op("NOP", Implied, false), // $1A
+    op("SLO", AbsoluteY, false), // $1B
+    op("NOP", AbsoluteX, false), // $1C
+    op("ORA", AbsoluteX, true), // $1D
+    op("ASL", AbsoluteX, true), // $1E
+    op("SLO", AbsoluteX, false), // $1F
+    op("JSR", Absolute, true), // $20
+    op("AND", IndirectX, true), // $21
+    op("KIL", Implied, false), // $22
+    op("RLA", IndirectX, false), // $23
+    op("BIT", ZeroPage, true), // $24
+    op("AND", ZeroPage, true), // $25
+    op("ROL", ZeroPage, true), // $26
+    op("RLA", ZeroPage, false), // $27
+    op("PLP", Implied, true), // $28
+    op("AND", Immediate, true), // $29
+    op("ROL", Accumulator, true), // $2A
+    op("ANC", Immediate, false), // $2B
+    op("BIT", Absolute, true), // $2C
+    op("AND", Absolute, true), // $2D
+    op("ROL", Absolute, true), // $2E
+    op("RLA", Absolute, false), // $2F
+    op("BMI", Relative, true), // $30
+    op("AND", IndirectY, true), // $31
+    op("KIL", Implied, false), // $32
+    op("RLA", IndirectY, false), // $33
+    op("NOP", ZeroPageX, false), // $34
+    op("AND", ZeroPageX, true), // $35
+    op("ROL", ZeroPageX, true), // $36
+    op("RLA", ZeroPageX, false), // $37
+    op("SEC", Implied, true), // $38
+    op("AND", AbsoluteY, true), // $39
+    op("NOP", Implied, false), // $3A
+    op("RLA", AbsoluteY, false), // $3B
+    op("NOP", AbsoluteX, false), // $3C
+    op("AND", AbsoluteX, true), // $3D
+    op("ROL", AbsoluteX, true), // $3E
+    op("RLA", AbsoluteX, false), // $3F
+    op("RTI", Implied, true), // $40
+    op("EOR", IndirectX, true), // $41
+    op("KIL", Implied, false), // $42
+    op("SRE", IndirectX, false), // $43
+    op("NOP", ZeroPage, false), // $44
+    op("EOR", ZeroPage, true), // $45
+    op("LSR", ZeroPage, true), // $46
+    op("SRE", ZeroPage, false), // $47
+    op("PHA", Implied, true), // $48
+    op("EOR", Immediate, true), // $49
+    op("LSR", Accumulator, true), // $4A
+    op("ALR", Immediate, false), // $4B
+    op("JMP", Absolute, true), // $4C
+    op("EOR", Absolute, true), // $4D
+    op("LSR", Absolute, true), // $4E
+    op("SRE", Absolute, false), // $4F
+    op("BVC", Relative, true), // $50
+    op("EOR", IndirectY, true), // $51
+    op("KIL", Implied, false), // $52
+    op("SRE", IndirectY, false), // $53
+    op("NOP", ZeroPageX, false), // $54
+    op("EOR", ZeroPageX, true), // $55
+    op("LSR", ZeroPageX, true), // $56
+    op("SRE", ZeroPageX, false), // $57
+    op("CLI", Implied, true), // $58
+    op("EOR", AbsoluteY, true), // $59
+    op("NOP", Implied, false), // $5A
+    op("SRE", AbsoluteY, false), // $5B
+    op("NOP", AbsoluteX, false), // $5C
+    op("EOR", AbsoluteX, true), // $5D
+    op("LSR", AbsoluteX, true), // $5E
+    op("SRE", AbsoluteX, false), // $5F
+    op("RTS", Implied, true), // $60
+    op("ADC", IndirectX, true), // $61
+    op("KIL", Implied, false), // $62
+    op("RRA", IndirectX, false), // $63
+    op("NOP", ZeroPage, false), // $64
+    op("ADC", ZeroPage, true), // $65
+    op("ROR", ZeroPage, true), // $66
+    op("RRA", ZeroPage, false), // $67
+    op("PLA", Implied, true), // $68
+    op("ADC", Immediate, true), // $69
+    op("ROR", Accumulator, true), // $6A
+    op("ARR", Immediate, false), // $6B
+    op("JMP", Indirect, true), // $6C
+    op("ADC", Absolute, true), // $6D
+    op("ROR", Absolute, true), // $6E
+    op("RRA", Absolute, false), // $6F
+    op("BVS", Relative, true), // $70
+    op("ADC", IndirectY, true), // $71
+    op("KIL", Implied, false), // $72
+    op("RRA", IndirectY, false), // $73
+    op("NOP", ZeroPageX, false), // $74
+    op("ADC", ZeroPageX, true), // $75
+    op("ROR", ZeroPageX, true), // $76
+    op("RRA", ZeroPageX, false), // $77
+    op("SEI", Implied, true), // $78
+    op("ADC", AbsoluteY, true), // $79
+    op("NOP", Implied, false), // $7A
+    op("RRA", AbsoluteY, false), // $7B
+    op("NOP", AbsoluteX, false), // $7C
+    op("ADC", AbsoluteX, true), // $7D
+    op("ROR", AbsoluteX, true), // $7E
+    op("RRA", AbsoluteX, false), // $7F
+    op("NOP", Immediate, false), // $80
+    op("STA", IndirectX, true), // $81
+    op("NOP", Immediate, false), // $82
+    op("SAX", IndirectX, false), // $83
+    op("STY", ZeroPage, true), // $84
+    op("STA", ZeroPage, true), // $85
+    op("STX", ZeroPage, true), // $86
+    op("SAX", ZeroPage, false), // $87
+    op("DEY", Implied, true), // $88
+    op("NOP", Immediate, false), // $89
+    op("TXA", Implied, true), // $8A
+    op("XAA", Immediate, false), // $8B
+    op("STY", Absolute, true), // $8C
+    op("STA", Absolute, true), // $8D
+    op("STX", Absolute, true), // $8E
+    op("SAX", Absolute, false), // $8F
+    op("BCC", Relative, true), // $90
+    op("STA", IndirectY, true), // $91
+    op("KIL", Implied, false), // $92
+    op("SHA", IndirectY, false), // $93
+    op("STY", ZeroPageX, true), // $94
+    op("STA", ZeroPageX, true), // $95
+    op("STX", ZeroPageY, true), // $96
+    op("SAX", ZeroPageY, false), // $97
+    op("TYA", Implied, true), // $98
+    op("STA", AbsoluteY, true), // $99
+    op("TXS", Implied, true), // $9A
+    op("TAS", AbsoluteY, false), // $9B
+    op("SHY", AbsoluteX, false), // $9C
+    op("STA", AbsoluteX, true), // $9D
+    op("SHX", AbsoluteY, false), // $9E
+    op("SHA", AbsoluteY, false), // $9F
+    op("LDY", Immediate, true), // $A0
+    op("LDA", IndirectX, true), // $A1
+    op("LDX", Immediate, true), // $A2
+    op("LAX", IndirectX, false), // $A3
+    op("LDY", ZeroPage, true), // $A4
+    op("LDA", ZeroPage, true), // $A5
+    op("LDX", ZeroPage, true), // $A6
+    op("LAX", ZeroPage, false), // $A7
+    op("TAY", Implied, true), // $A8
+    op("LDA", Immediate, true), // $A9
+    op("TAX", Implied, true), // $AA
+    op("LAX", Immediate, false), // $AB
+    op("LDY", Absolute, true), // $AC
+    op("LDA", Absolute, true), // $AD
+    op("LDX", Absolute, true), // $AE
+    op("LAX", Absolute, false), // $AF
+    op("BCS", Relative, true), // $B0
+    op("LDA", IndirectY, true), // $B1
+    op("KIL", Implied, false), // $B2
+    op("LAX", IndirectY, false), // $B3
+    op("LDY", ZeroPageX, true), // $B4
+    op("LDA", ZeroPageX, true), // $B5
+    op("LDX", ZeroPageY, true), // $B6
+    op("LAX", ZeroPageY, false), // $B7
+    op("CLV", Implied, true), // $B8
+    op("LDA", AbsoluteY, true), // $B9
+    op("TSX", Implied, true), // $BA
+    op("LAS", AbsoluteY, false), // $BB
+    op("LDY", AbsoluteX, true), // $BC
+    op("LDA", AbsoluteX, true), // $BD
+    op("LDX", AbsoluteY, true), // $BE
+    op("LAX", AbsoluteY, false), // $BF
+    op("CPY", Immediate, true), // $C0
+    op("CMP", IndirectX, true), // $C1
+    op("NOP", Immediate, false), // $C2
+    op("DCP", IndirectX, false), // $C3
+    op("CPY", ZeroPage, true), // $C4
+    op("CMP", ZeroPage, true), // $C5
+    op("DEC", ZeroPage, true), // $C6
+    op("DCP", ZeroPage, false), // $C7
+    op("INY", Implied, true), // $C8
+    op("CMP", Immediate, true), // $C9
+    op("DEX", Implied, true), // $CA
+    op("AXS", Immediate, false), // $CB
+    op("CPY", Absolute, true), // $CC
+    op("CMP", Absolute, true), // $CD
+    op("DEC", Absolute, true), // $CE
+    op("DCP", Absolute, false), // $CF
+    op("BNE", Relative, true), // $D0
+    op("CMP", IndirectY, true), // $D1
+    op("KIL", Implied, false), // $D2
+    op("DCP", IndirectY, false), // $D3
+    op("NOP", ZeroPageX, false), // $D4
+    op("CMP", ZeroPageX, true), // $D5
+    op("DEC", ZeroPageX, true), // $D6
+    op("DCP", ZeroPageX, false), // $D7
+    op("CLD", Implied, true), // $D8
+    op("CMP", AbsoluteY, true), // $D9
+    op("NOP", Implied, false), // $DA
+    op("DCP", AbsoluteY, false), // $DB
+    op("NOP", AbsoluteX, false), // $DC
+    op("CMP", AbsoluteX, true), // $DD
+    op("DEC", AbsoluteX, true), // $DE
+    op("DCP", AbsoluteX, false), // $DF
+    op("CPX", Immediate, true), // $E0
+    op("SBC", IndirectX, true), // $E1
+    op("NOP", Immediate, false), // $E2
+    op("ISB", IndirectX, false), // $E3
+    op("CPX", ZeroPage, true), // $E4
+    op("SBC", ZeroPage, true), // $E5
+    op("INC", ZeroPage, true), // $E6
+    op("ISB", ZeroPage, false), // $E7
+    op("INX", Implied, true), // $E8
+    op("SBC", Immediate, true), // $E9
+    op("NOP", Implied, true), // $EA
+    op("SBC", Immediate, false), // $EB
+    op("CPX", Absolute, true), // $EC
+    op("SBC", Absolute, true), // $ED
+    op("INC", Absolute, true), // $EE
+    op("ISB", Absolute, false), // $EF
+    op("BEQ", Relative, true), // $F0
+    op("SBC", IndirectY, true), // $F1
+    op("KIL", Implied, false), // $F2
+    op("ISB", IndirectY, false), // $F3
+    op("NOP", ZeroPageX, false), // $F4
+    op("SBC", ZeroPageX, true), // $F5
+    op("INC", ZeroPageX, true), // $F6
+    op("ISB", ZeroPageX, false), // $F7
+    op("SED", Implied, true), // $F8
+    op("SBC", AbsoluteY, true), // $F9
+    op("NOP", Implied, false), // $FA
+    op("ISB", AbsoluteY, false), // $FB
+    op("NOP", AbsoluteX, false), // $FC
+    op("SBC", AbsoluteX, true), // $FD
+    op("INC", AbsoluteX, true), // $FE
+    op("ISB", AbsoluteX, false), // $FF
+    ]
+};
+
 /// チートコード (Game Genie形式)
 #[derive(Debug, Clone)]
 pub struct CheatCode {
@@ -443,18 +720,11 @@ impl CheatCode {
             | (chars[4] & 0x7) as u16
             | ((chars[3] & 0x8) as u16);
 
-        let value = ((chars[1] & 0x7) << 4)
-            | ((chars[0] & 0x8))
-            | (chars[0] & 0x7)
-            | ((chars[5] & 0x8));
+        let value =
+            ((chars[1] & 0x7) << 4) | (chars[0] & 0x8) | (chars[0] & 0x7) | (chars[5] & 0x8);
 
         let compare = if code.len() == 8 {
-            Some(
-                ((chars[7] & 0x7) << 4)
-                    | ((chars[6] & 0x8))
-                    | (chars[6] & 0x7)
-                    | ((chars[7] & 0x8)),
-            )
+            Some(((chars[7] & 0x7) << 4) | (chars[6] & 0x8) | (chars[6] & 0x7) | (chars[7] & 0x8))
         } else {
             None
         };
@@ -0,0 +1,440 @@
+//! # Monitor
+//! 端末から1行ずつコマンドを入力して操作するデバッガ（マシン語モニタ）
+//!
+//! 入出力はフロントエンドが受け持ち、`Monitor::execute`に1行を渡して表示する文字列を受け取る。
+//! 実行中のフロントエンドは`is_paused`でなければフレームを進め、`step_frame`の結果を`notify`に渡す。
+//!
+//! アドレスと値は16進数（`$`や`0x`は付けても付けなくてもよい）、回数は10進数で指定する。
+//! 空行は直前のコマンドを繰り返す。
+
+use crate::debugger::{AddressSpace, Breakpoint, StopReason};
+use crate::{Nes, NesError, Result};
+
+/// `m`で省略したときに表示するバイト数
+const DEFAULT_DUMP_LENGTH: usize = 64;
+
+/// `d`で省略したときに表示する命令数
+const DEFAULT_DISASSEMBLE_COUNT: usize = 10;
+
+/// これ以上のY座標のスプライトは画面外（`oam`では省略する）
+const HIDDEN_SPRITE_Y: u8 = 0xEF;
+
+const HELP: &str = "\
+s [N]               step N instructions (default 1)
+f [N]               step N frames (default 1)
+c                   continue
+pause               pause
+r                   show registers
+m ADDR [LEN]        hex dump LEN bytes of CPU memory (default 40)
+d [ADDR] [N]        disassemble N instructions (default: PC)
+b ADDR [if COND]    break on execution
+bw ADDR[-END] [if COND]  break on write
+br ADDR[-END] [if COND]  break on read
+bl                  list breakpoints
+bc ID|all           clear breakpoints
+poke ADDR VAL...    write CPU memory
+oam [all]           show sprites (hidden sprites with `all`)
+save PATH           save state to a file
+load PATH           load state from a file
+q                   quit";
+
+/// デバッガのコマンドインタプリタ
+#[derive(Debug)]
+pub struct Monitor {
+    paused: bool,
+    quit: bool,
+    /// 空行で繰り返すコマンド
+    last_command: String,
+}
+
+impl Monitor {
+    /// 停止状態で始まるモニタ（`c`で実行を始める）
+    pub fn new() -> Self {
+        Self {
+            paused: true,
+            quit: false,
+            last_command: String::new(),
+        }
+    }
+
+    /// 停止中（フロントエンドはフレームを進めない）
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// `q`が入力された
+    pub fn is_quit(&self) -> bool {
+        self.quit
+    }
+
+    /// 1行のコマンドを実行して表示する文字列を返す
+    pub fn execute(&mut self, nes: &mut Nes, line: &str) -> String {
+        let line = match line.trim() {
+            "" => self.last_command.clone(),
+            line => line.to_string(),
+        };
+        if line.is_empty() {
+            return String::new();
+        }
+        self.last_command = line.clone();
+
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default();
+        let args: Vec<&str> = words.collect();
+        self.run(nes, command, &args)
+            .unwrap_or_else(|e| format!("error: {}", e))
+    }
+
+    /// `step_frame`の結果を伝える（停止した場合は一時停止して理由を返す）
+    pub fn notify(&mut self, nes: &Nes, result: &Result<StopReason>) -> Option<String> {
+        let reason = match result {
+            Ok(StopReason::FrameComplete) => return None,
+            Ok(StopReason::Breakpoint { id, access: None }) => format!("breakpoint #{}", id),
+            Ok(StopReason::Breakpoint {
+                id,
+                access: Some(access),
+            }) => format!(
+                "breakpoint #{} ({:?} ${:04X} = ${:02X})",
+                id, access.access, access.address, access.value
+            ),
+            Ok(StopReason::Target(target)) => format!("reached {:?}", target),
+            Err(e) => format!("error: {}", e),
+        };
+        self.paused = true;
+        Some(format!("{}\n{}", reason, nes.trace_line()))
+    }
+
+    fn run(&mut self, nes: &mut Nes, command: &str, args: &[&str]) -> Result<String> {
+        match command {
+            "s" | "step" => {
+                let count = parse_count(args.first())?;
+                self.paused = true;
+                for _ in 0..count {
+                    nes.step()?;
+                }
+                Ok(nes.trace_line())
+            }
+            "f" | "frame" => {
+                let count = parse_count(args.first())?;
+                self.paused = true;
+                for _ in 0..count {
+                    let result = nes.step_frame();
+                    if let Some(message) = self.notify(nes, &result) {
+                        return Ok(message);
+                    }
+                }
+                Ok(nes.trace_line())
+            }
+            "c" | "continue" => {
+                self.paused = false;
+                Ok("running".to_string())
+            }
+            "pause" => {
+                self.paused = true;
+                Ok(nes.trace_line())
+            }
+            "r" | "regs" => Ok(nes.trace_line()),
+            "m" | "mem" => {
+                let address = parse_hex(required(args.first())?)?;
+                let length = match args.get(1) {
+                    Some(length) => parse_hex(length)? as usize,
+                    None => DEFAULT_DUMP_LENGTH,
+                };
+                Ok(nes.hex_dump(address, length).trim_end().to_string())
+            }
+            "d" | "dis" => {
+                let pc = nes.cpu_state().pc();
+                let address = match args.first() {
+                    Some(address) => parse_hex(address)?,
+                    None => pc,
+                };
+                let count = match args.get(1) {
+                    Some(count) => parse_count(Some(count))?,
+                    None => DEFAULT_DISASSEMBLE_COUNT,
+                };
+                let lines: Vec<String> = nes
+                    .disassemble(address, count)
+                    .into_iter()
+                    .map(|(address, text)| {
+                        let marker = if address == pc { '>' } else { ' ' };
+                        format!("{} {:04X}  {}", marker, address, text)
+                    })
+                    .collect();
+                Ok(lines.join("\n"))
+            }
+            "b" | "bw" | "br" => {
+                let (range, condition) = split_condition(args);
+                let (start, end) = parse_range(required(range.first())?)?;
+                let breakpoint = match command {
+                    "b" => Breakpoint::execute(start),
+                    "bw" => Breakpoint::write(AddressSpace::Cpu, start, end),
+                    _ => Breakpoint::read(AddressSpace::Cpu, start, end),
+                };
+                let breakpoint = match condition {
+                    Some(condition) => breakpoint.with_condition(&condition)?,
+                    None => breakpoint,
+                };
+                let id = nes.debugger_mut().add_breakpoint(breakpoint);
+                Ok(describe(
+                    nes.debugger()
+                        .breakpoint(id)
+                        .expect("breakpoint was just added"),
+                ))
+            }
+            "bl" => {
+                let breakpoints = nes.debugger().breakpoints();
+                if breakpoints.is_empty() {
+                    return Ok("no breakpoints".to_string());
+                }
+                Ok(breakpoints
+                    .iter()
+                    .map(describe)
+                    .collect::<Vec<_>>()
+                    .join("\n"))
+            }
+            "bc" => match required(args.first())? {
+                "all" => {
+                    nes.debugger_mut().clear_breakpoints();
+                    Ok("cleared all breakpoints".to_string())
+                }
+                id => {
+                    let id: u32 = id.parse().map_err(|_| invalid(id))?;
+                    if nes.debugger_mut().remove_breakpoint(id) {
+                        Ok(format!("cleared #{}", id))
+                    } else {
+                        Err(NesError::Other(format!("no breakpoint #{}", id)))
+                    }
+                }
+            },
+            "poke" => {
+                let address = parse_hex(required(args.first())?)?;
+                if args.len() < 2 {
+                    return Err(NesError::Other("missing value".to_string()));
+                }
+                for (i, value) in args[1..].iter().enumerate() {
+                    let value = u8::try_from(parse_hex(value)?).map_err(|_| invalid(value))?;
+                    nes.poke_memory(address.wrapping_add(i as u16), value);
+                }
+                Ok(nes.hex_dump(address, args.len() - 1).trim_end().to_string())
+            }
+            "oam" => {
+                let all = args.first() == Some(&"all");
+                let lines: Vec<String> = nes
+                    .get_all_sprites()
+                    .into_iter()
+                    .filter(|&(_, y, ..)| all || y < HIDDEN_SPRITE_Y)
+                    .map(|(index, y, tile, attr, x)| {
+                        format!(
+                            "#{:02} X:{:02X} Y:{:02X} tile:{:02X} attr:{:02X} palette:{}{}{}{}",
+                            index,
+                            x,
+                            y,
+                            tile,
+                            attr,
+                            attr & 0x03,
+                            if attr & 0x20 != 0 { " behind" } else { "" },
+                            if attr & 0x40 != 0 { " hflip" } else { "" },
+                            if attr & 0x80 != 0 { " vflip" } else { "" },
+                        )
+                    })
+                    .collect();
+                if lines.is_empty() {
+                    return Ok("no visible sprites".to_string());
+                }
+                Ok(lines.join("\n"))
+            }
+            "save" => {
+                let path = required(args.first())?;
+                std::fs::write(path, nes.save_state())?;
+                Ok(format!("saved state to {}", path))
+            }
+            "load" => {
+                let path = required(args.first())?;
+                nes.load_state(&std::fs::read(path)?)?;
+                Ok(format!("loaded state from {}\n{}", path, nes.trace_line()))
+            }
+            "q" | "quit" => {
+                self.quit = true;
+                Ok(String::new())
+            }
+            "h" | "help" | "?" => Ok(HELP.to_string()),
+            _ => Err(NesError::Other(format!(
+                "unknown command: {} (type `help`)",
+                command
+            ))),
+        }
+    }
+}
+
+impl Default for Monitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `bl`で表示するブレークポイントの1行
+fn describe(breakpoint: &Breakpoint) -> String {
+    let kind = if breakpoint.execute {
+        "exec"
+    } else if breakpoint.read && breakpoint.write {
+        "access"
+    } else if breakpoint.write {
+        "write"
+    } else {
+        "read"
+    };
+    let mut line = format!("#{} {} ${:04X}", breakpoint.id, kind, breakpoint.start);
+    if breakpoint.end != breakpoint.start {
+        line.push_str(&format!("-${:04X}", breakpoint.end));
+    }
+    if let Some(condition) = &breakpoint.condition {
+        line.push_str(&format!(" if {}", condition));
+    }
+    line.push_str(&format!(" (hits: {})", breakpoint.hits));
+    if !breakpoint.enabled {
+        line.push_str(" [disabled]");
+    }
+    line
+}
+
+/// 引数を`if`の前後で分ける
+fn split_condition<'a>(args: &'a [&'a str]) -> (&'a [&'a str], Option<String>) {
+    match args.iter().position(|&arg| arg == "if") {
+        Some(i) => (&args[..i], Some(args[i + 1..].join(" "))),
+        None => (args, None),
+    }
+}
+
+fn required<'a>(arg: Option<&&'a str>) -> Result<&'a str> {
+    arg.copied()
+        .ok_or_else(|| NesError::Other("missing argument".to_string()))
+}
+
+fn invalid(arg: &str) -> NesError {
+    NesError::Other(format!("invalid number: {}", arg))
+}
+
+/// `C000` `$C000` `0xC000`
+fn parse_hex(arg: &str) -> Result<u16> {
+    let digits = arg
+        .strip_prefix('$')
+        .or_else(|| arg.strip_prefix("0x"))
+        .unwrap_or(arg);
+    u16::from_str_radix(digits, 16).map_err(|_| invalid(arg))
+}
+
+/// `ADDR`または`START-END`
+fn parse_range(arg: &str) -> Result<(u16, u16)> {
+    match arg.split_once('-') {
+        Some((start, end)) => Ok((parse_hex(start)?, parse_hex(end)?)),
+        None => parse_hex(arg).map(|address| (address, address)),
+    }
+}
+
+/// 10進数の回数（省略時は1）
+fn parse_count(arg: Option<&&str>) -> Result<usize> {
+    match arg {
+        Some(arg) => arg.parse().map_err(|_| invalid(arg)),
+        None => Ok(1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_rom::{self, COUNTER};
+
+    #[test]
+    fn test_step_and_inspect() {
+        let mut nes = test_rom::nes(COUNTER);
+        let mut monitor = Monitor::new();
+        assert!(monitor.is_paused());
+
+        assert!(monitor.execute(&mut nes, "s 2").starts_with("8005"));
+        // 空行は直前のコマンドを繰り返す
+        assert!(monitor.execute(&mut nes, "").starts_with("8003"));
+        assert_eq!(nes.peek_memory(0x0300), 2);
+
+        assert_eq!(
+            monitor.execute(&mut nes, "poke $10 12 34"),
+            nes.hex_dump(0x10, 2).trim_end()
+        );
+        assert!(monitor
+            .execute(&mut nes, "m 10 2")
+            .starts_with("0010: 12 34"));
+        let disassembly = monitor.execute(&mut nes, "d 8000 3");
+        assert_eq!(disassembly.lines().count(), 3);
+        assert!(disassembly.lines().nth(1).unwrap().starts_with("> 8003"));
+        assert!(monitor.execute(&mut nes, "m zz").starts_with("error:"));
+        assert!(monitor.execute(&mut nes, "bogus").starts_with("error:"));
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut nes = test_rom::nes(COUNTER);
+        let mut monitor = Monitor::new();
+
+        assert_eq!(
+            monitor.execute(&mut nes, "bw 300 if value == 3"),
+            "#1 write $0300 if value == 3 (hits: 0)"
+        );
+        assert_eq!(
+            monitor.execute(&mut nes, "b $8003"),
+            "#2 exec $8003 (hits: 0)"
+        );
+        assert_eq!(monitor.execute(&mut nes, "bc 2"), "cleared #2");
+        assert!(monitor.execute(&mut nes, "bc 2").starts_with("error:"));
+
+        monitor.execute(&mut nes, "c");
+        assert!(!monitor.is_paused());
+        let result = nes.step_frame();
+        let message = monitor.notify(&nes, &result).unwrap();
+        assert!(message.starts_with("breakpoint #1 (Write $0300 = $03)"));
+        assert!(monitor.is_paused());
+        assert_eq!(
+            monitor.execute(&mut nes, "bl"),
+            "#1 write $0300 if value == 3 (hits: 1)"
+        );
+
+        monitor.execute(&mut nes, "bc all");
+        assert_eq!(monitor.execute(&mut nes, "bl"), "no breakpoints");
+        monitor.execute(&mut nes, "f");
+        assert!(nes.peek_memory(0x0300) > 3);
+    }
+
+    #[test]
+    fn test_oam_and_state() {
+        let mut nes = test_rom::nes(COUNTER);
+        let mut monitor = Monitor::new();
+        for address in 0..=255 {
+            nes.write_oam(address, 0xFF);
+        }
+        assert_eq!(monitor.execute(&mut nes, "oam"), "no visible sprites");
+        nes.write_oam(4, 0x20);
+        nes.write_oam(5, 0x01);
+        nes.write_oam(6, 0x61);
+        nes.write_oam(7, 0x40);
+        assert_eq!(
+            monitor.execute(&mut nes, "oam"),
+            "#01 X:40 Y:20 tile:01 attr:61 palette:1 behind hflip"
+        );
+        assert_eq!(monitor.execute(&mut nes, "oam all").lines().count(), 64);
+
+        let path =
+            std::env::temp_dir().join(format!("hacknes_monitor_{}.state", std::process::id()));
+        let path = path.to_str().unwrap();
+        monitor.execute(&mut nes, "s 3");
+        assert!(monitor
+            .execute(&mut nes, &format!("save {}", path))
+            .starts_with("saved"));
+        monitor.execute(&mut nes, "s 6");
+        assert!(monitor
+            .execute(&mut nes, &format!("load {}", path))
+            .starts_with("loaded"));
+        assert_eq!(nes.peek_memory(0x0300), 1);
+        std::fs::remove_file(path).unwrap();
+
+        monitor.execute(&mut nes, "q");
+        assert!(monitor.is_quit());
+    }
+}
@@ -0,0 +1,676 @@
+//! # Movie
+//! フレームごとのコントローラー入力の記録と再生
+//!
+//! ムービーは開始状態（電源投入またはセーブステート）と、フレームごとの入力の列からなる。
+//! フレームは`Nes::step_frame`の1回分で、フレームの開始時に入力を記録・適用する。
+//! 記録時は一定間隔のフレームの終わりに内部RAMのCRC32をチェックポイントとして保存し、
+//! 再生時に同じフレームで比較してずれ（デシンク）を検出する。
+//!
+//! 読み書きできる形式:
+//! - FCEUXの`.fm2`（テキスト）
+//! - BizHawkの`.bk2`の中身（`Header.txt`、`Input Log.txt`、`Comments.txt`、`Core.bin`）。
+//!   zipへの格納はフロントエンドが行う
+//!
+//! どちらの形式でも、セーブステートから始まるムービーにはこのエミュレータのセーブステートを埋め込むので、
+//! 他のエミュレータで記録したセーブステート開始のムービーは再生できない。
+//! チェックポイントはコメント（`comment checkpoint <フレーム> <CRC32>`）として保存する。
+//! どちらの形式にもDendyを表す項目がないので、Dendyのムービーは`palFlag`/`PAL`を立てたうえで
+//! `comment region dendy`（`.bk2`では`Comments.txt`の`region dendy`）を書き込む。
+//!
+//! 参考: https://fceux.com/web/help/fm2.html
+
+use crate::controller::{Button, Controller};
+use crate::state::crc32;
+use crate::{NesError, Region, Result};
+
+/// 記録できるプレイヤー数（Four Score使用時）
+pub const MAX_PLAYERS: usize = 4;
+
+/// チェックポイントを記録する間隔（フレーム数）
+pub const CHECKPOINT_INTERVAL: usize = 60;
+
+/// Dendyのムービーに書き込むコメント
+const DENDY_COMMENT: &str = "region dendy";
+
+/// `.fm2`の入力の文字（ビット7から0の順）
+const FM2_BUTTONS: &[u8; 8] = b"RLDUTSBA";
+
+/// `.bk2`の入力ログでのボタン名と文字
+const BK2_BUTTONS: [(&str, char, Button); 8] = [
+    ("Up", 'U', Button::Up),
+    ("Down", 'D', Button::Down),
+    ("Left", 'L', Button::Left),
+    ("Right", 'R', Button::Right),
+    ("Start", 'S', Button::Start),
+    ("Select", 's', Button::Select),
+    ("B", 'B', Button::B),
+    ("A", 'A', Button::A),
+];
+
+/// 1フレーム分の入力
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameInput {
+    /// プレイヤーごとのボタン（`Controller::buttons`と同じビット配置）
+    pub pads: [u8; MAX_PLAYERS],
+    /// フレームの開始時にリセットボタンを押す
+    pub reset: bool,
+}
+
+/// ムービーの開始状態
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MovieStart {
+    /// ROMをロードした直後の状態
+    PowerOn,
+    /// 埋め込まれたセーブステート（`Nes::save_state`の形式）
+    SaveState(Vec<u8>),
+}
+
+/// フレームの終わりの内部RAMのCRC32
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// 記録を始めてから終わったフレーム数
+    pub frame: usize,
+    pub ram_crc: u32,
+}
+
+/// 再生中に見つかったチェックポイントとの不一致
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Desync {
+    pub frame: usize,
+    pub expected: u32,
+    pub actual: u32,
+}
+
+/// 入力の記録
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub start: MovieStart,
+    pub region: Region,
+    pub four_score: bool,
+    pub rerecord_count: u32,
+    pub frames: Vec<FrameInput>,
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+impl Movie {
+    pub fn new(start: MovieStart) -> Self {
+        Self {
+            start,
+            region: Region::Ntsc,
+            four_score: false,
+            rerecord_count: 0,
+            frames: Vec::new(),
+            checkpoints: Vec::new(),
+        }
+    }
+
+    /// 記録しているプレイヤー数
+    fn players(&self) -> usize {
+        if self.four_score {
+            MAX_PLAYERS
+        } else {
+            2
+        }
+    }
+
+    // ========== FCEUX (.fm2) ==========
+
+    /// `.fm2`のテキストから読み込む
+    pub fn from_fm2(text: &str) -> Result<Self> {
+        let mut movie = Self::new(MovieStart::PowerOn);
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            let error = |message: &str| invalid(format!("line {}: {}", number + 1, message));
+            if let Some(fields) = line.strip_prefix('|') {
+                let mut fields = fields.split('|');
+                let commands: u32 = fields
+                    .next()
+                    .and_then(|c| c.trim().parse().ok())
+                    .ok_or_else(|| error("bad command field"))?;
+                let mut input = FrameInput {
+                    reset: commands & 0x03 != 0,
+                    ..FrameInput::default()
+                };
+                for pad in input.pads.iter_mut().take(movie.players()) {
+                    let field = fields.next().ok_or_else(|| error("missing port"))?;
+                    *pad = parse_fm2_pad(field).ok_or_else(|| error("bad port field"))?;
+                }
+                movie.frames.push(input);
+                continue;
+            }
+
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "version" if value != "3" => return Err(error("unsupported version")),
+                "palFlag" => {
+                    movie.region = if value == "1" {
+                        Region::Pal
+                    } else {
+                        Region::Ntsc
+                    };
+                }
+                "fourscore" => movie.four_score = value == "1",
+                "rerecordCount" => movie.rerecord_count = value.parse().unwrap_or(0),
+                "savestate" => {
+                    let data = decode_binary(value).ok_or_else(|| error("bad savestate"))?;
+                    movie.start = MovieStart::SaveState(data);
+                }
+                "comment" if value == DENDY_COMMENT => movie.region = Region::Dendy,
+                "comment" => {
+                    if let Some(checkpoint) = parse_checkpoint(value) {
+                        movie.checkpoints.push(checkpoint);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(movie)
+    }
+
+    /// `.fm2`のテキストに書き出す
+    pub fn to_fm2(&self, rom_name: &str) -> String {
+        let mut out = String::new();
+        out.push_str("version 3\n");
+        out.push_str("emuVersion 22020\n");
+        out.push_str(&format!("rerecordCount {}\n", self.rerecord_count));
+        out.push_str(&format!(
+            "palFlag {}\n",
+            (self.region != Region::Ntsc) as u8
+        ));
+        out.push_str(&format!("romFilename {}\n", rom_name));
+        out.push_str(&format!("fourscore {}\n", self.four_score as u8));
+        out.push_str("microphone 0\n");
+        let port = if self.four_score { 0 } else { 1 };
+        out.push_str(&format!("port0 {}\nport1 {}\nport2 0\n", port, port));
+        out.push_str("FDS 0\nNewPPU 1\n");
+        if let MovieStart::SaveState(data) = &self.start {
+            out.push_str(&format!("savestate base64:{}\n", encode_base64(data)));
+        }
+        if self.region == Region::Dendy {
+            out.push_str(&format!("comment {}\n", DENDY_COMMENT));
+        }
+        for checkpoint in &self.checkpoints {
+            out.push_str(&format!(
+                "comment checkpoint {} {:08X}\n",
+                checkpoint.frame, checkpoint.ram_crc
+            ));
+        }
+        for input in &self.frames {
+            out.push_str(&format!("|{}|", input.reset as u8));
+            for &pad in input.pads.iter().take(self.players()) {
+                for (bit, &c) in FM2_BUTTONS.iter().enumerate() {
+                    out.push(if pad & (0x80 >> bit) != 0 {
+                        c as char
+                    } else {
+                        '.'
+                    });
+                }
+                out.push('|');
+            }
+            out.push_str("|\n");
+        }
+        out
+    }
+
+    // ========== BizHawk (.bk2) ==========
+
+    /// `.bk2`に含まれるファイル（名前と内容）から読み込む
+    pub fn from_bk2(files: &[(String, Vec<u8>)]) -> Result<Self> {
+        let file = |name: &str| files.iter().find(|(n, _)| n == name).map(|(_, data)| data);
+        let text = |name: &str| file(name).map(|data| String::from_utf8_lossy(data).into_owned());
+
+        let mut movie = Self::new(MovieStart::PowerOn);
+        let mut from_savestate = false;
+        for line in text("Header.txt").unwrap_or_default().lines() {
+            let (key, value) = line.trim_end().split_once(' ').unwrap_or((line, ""));
+            match key {
+                "Platform" if value != "NES" => {
+                    return Err(invalid(format!("unsupported platform {}", value)));
+                }
+                "rerecordCount" => movie.rerecord_count = value.parse().unwrap_or(0),
+                "StartsFromSavestate" => from_savestate = value.eq_ignore_ascii_case("true"),
+                "PAL" if value.eq_ignore_ascii_case("true") => movie.region = Region::Pal,
+                _ => {}
+            }
+        }
+        if from_savestate {
+            let data = file("Core.bin").ok_or_else(|| invalid("missing Core.bin".to_string()))?;
+            movie.start = MovieStart::SaveState(data.clone());
+        }
+        for line in text("Comments.txt").unwrap_or_default().lines() {
+            if line.trim_end() == DENDY_COMMENT {
+                movie.region = Region::Dendy;
+            } else if let Some(checkpoint) =
+                line.strip_prefix("checkpoint ").and_then(parse_checkpoint)
+            {
+                movie.checkpoints.push(checkpoint);
+            }
+        }
+
+        let log =
+            text("Input Log.txt").ok_or_else(|| invalid("missing Input Log.txt".to_string()))?;
+        // LogKeyのグループ: 0番目はリセット/電源、以降はプレイヤーごとのボタン
+        let mut groups: Vec<Vec<Option<Button>>> = Vec::new();
+        for line in log.lines().map(str::trim_end) {
+            if let Some(key) = line.strip_prefix("LogKey:") {
+                groups = parse_bk2_log_key(key);
+                movie.four_score = groups.len() > 3;
+            } else if let Some(fields) = line.strip_prefix('|') {
+                if groups.is_empty() {
+                    return Err(invalid("input before LogKey".to_string()));
+                }
+                let mut input = FrameInput::default();
+                for (i, field) in fields.split('|').take(groups.len()).enumerate() {
+                    for (button, c) in groups[i].iter().zip(field.chars()) {
+                        let pressed = c != '.' && c != ' ';
+                        match (i, button) {
+                            (0, _) => input.reset |= pressed,
+                            (player, Some(button)) if pressed && player <= MAX_PLAYERS => {
+                                input.pads[player - 1] |= Controller::button_to_bit(*button);
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                movie.frames.push(input);
+            }
+        }
+        Ok(movie)
+    }
+
+    /// `.bk2`に格納するファイル（名前と内容）に書き出す
+    pub fn to_bk2(&self, rom_name: &str) -> Vec<(String, Vec<u8>)> {
+        let mut header = String::new();
+        header.push_str("MovieVersion BizHawk v2.0.0\n");
+        header.push_str("Platform NES\n");
+        header.push_str("Core NesHawk\n");
+        header.push_str(&format!("GameName {}\n", rom_name));
+        header.push_str(&format!("rerecordCount {}\n", self.rerecord_count));
+        if self.region != Region::Ntsc {
+            header.push_str("PAL True\n");
+        }
+
+        let mut log = String::from("[Input]\nLogKey:#Reset|Power|");
+        for player in 1..=self.players() {
+            for (i, (name, ..)) in BK2_BUTTONS.iter().enumerate() {
+                let separator = if i == 0 { "#" } else { "" };
+                log.push_str(&format!("{}P{} {}|", separator, player, name));
+            }
+        }
+        log.push('\n');
+        for input in &self.frames {
+            log.push_str(if input.reset { "|r.|" } else { "|..|" });
+            for &pad in input.pads.iter().take(self.players()) {
+                for &(_, c, button) in &BK2_BUTTONS {
+                    log.push(if pad & Controller::button_to_bit(button) != 0 {
+                        c
+                    } else {
+                        '.'
+                    });
+                }
+                log.push('|');
+            }
+            log.push('\n');
+        }
+        log.push_str("[/Input]\n");
+
+        let mut comments = String::new();
+        if self.region == Region::Dendy {
+            comments.push_str(&format!("{}\n", DENDY_COMMENT));
+        }
+        for c in &self.checkpoints {
+            comments.push_str(&format!("checkpoint {} {:08X}\n", c.frame, c.ram_crc));
+        }
+
+        let mut files = Vec::new();
+        if let MovieStart::SaveState(data) = &self.start {
+            header.push_str("StartsFromSavestate True\n");
+            files.push(("Core.bin".to_string(), data.clone()));
+        }
+        files.push(("Header.txt".to_string(), header.into_bytes()));
+        files.push(("Input Log.txt".to_string(), log.into_bytes()));
+        files.push(("Comments.txt".to_string(), comments.into_bytes()));
+        files.push(("Subtitles.txt".to_string(), Vec::new()));
+        files.push(("SyncSettings.json".to_string(), b"{}".to_vec()));
+        files
+    }
+}
+
+/// 記録・再生の状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovieMode {
+    Recording,
+    Playing,
+    /// 再生が最後のフレームまで終わった
+    Finished,
+}
+
+/// `Nes`が記録・再生しているムービー
+#[derive(Debug)]
+pub struct MovieSession {
+    movie: Movie,
+    mode: MovieMode,
+    /// 次に開始するフレーム
+    frame: usize,
+    desyncs: Vec<Desync>,
+    /// 記録中に`Nes::reset`が呼ばれた
+    pending_reset: bool,
+}
+
+impl MovieSession {
+    pub(crate) fn new(movie: Movie, mode: MovieMode) -> Self {
+        Self {
+            movie,
+            mode,
+            frame: 0,
+            desyncs: Vec::new(),
+            pending_reset: false,
+        }
+    }
+
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+
+    pub fn mode(&self) -> MovieMode {
+        self.mode
+    }
+
+    /// 記録・再生したフレーム数
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// 再生中に見つかったデシンク
+    pub fn desyncs(&self) -> &[Desync] {
+        &self.desyncs
+    }
+
+    pub(crate) fn into_movie(self) -> Movie {
+        self.movie
+    }
+
+    /// `frames`フレーム巻き戻す（記録中は以降の入力を捨てて撮り直す）
+    pub(crate) fn rewind(&mut self, frames: usize) {
+        if frames == 0 {
+            return;
+        }
+        self.frame = self.frame.saturating_sub(frames);
+        match self.mode {
+            MovieMode::Recording => {
+                self.movie.frames.truncate(self.frame);
+                let frame = self.frame;
+                self.movie.checkpoints.retain(|c| c.frame <= frame);
+                self.movie.rerecord_count += 1;
+            }
+            _ => {
+                self.mode = MovieMode::Playing;
+                self.desyncs.retain(|d| d.frame <= self.frame);
+            }
+        }
+    }
+
+    pub(crate) fn request_reset(&mut self) {
+        if self.mode == MovieMode::Recording {
+            self.pending_reset = true;
+        }
+    }
+
+    /// フレームの開始時に呼ぶ（記録時は`pads`を記録し、再生時は適用する入力を返す）
+    pub(crate) fn start_frame(&mut self, pads: [u8; MAX_PLAYERS]) -> Option<FrameInput> {
+        match self.mode {
+            MovieMode::Recording => {
+                self.movie.frames.push(FrameInput {
+                    pads,
+                    reset: std::mem::take(&mut self.pending_reset),
+                });
+                None
+            }
+            MovieMode::Playing => match self.movie.frames.get(self.frame) {
+                Some(input) => Some(*input),
+                None => {
+                    self.mode = MovieMode::Finished;
+                    // 押されたままのボタンを離す
+                    Some(FrameInput::default())
+                }
+            },
+            MovieMode::Finished => None,
+        }
+    }
+
+    /// フレームの終わりに呼ぶ（チェックポイントの記録・比較）
+    pub(crate) fn end_frame(&mut self, ram: &[u8]) {
+        if self.mode == MovieMode::Finished {
+            return;
+        }
+        self.frame += 1;
+        if !self.frame.is_multiple_of(CHECKPOINT_INTERVAL) {
+            return;
+        }
+        let ram_crc = crc32(ram);
+        match self.mode {
+            MovieMode::Recording => self.movie.checkpoints.push(Checkpoint {
+                frame: self.frame,
+                ram_crc,
+            }),
+            _ => {
+                let expected = self
+                    .movie
+                    .checkpoints
+                    .iter()
+                    .find(|c| c.frame == self.frame);
+                if let Some(expected) = expected.filter(|c| c.ram_crc != ram_crc) {
+                    log::warn!(
+                        "Movie desync at frame {}: RAM CRC {:08X}, expected {:08X}",
+                        self.frame,
+                        ram_crc,
+                        expected.ram_crc
+                    );
+                    self.desyncs.push(Desync {
+                        frame: self.frame,
+                        expected: expected.ram_crc,
+                        actual: ram_crc,
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn invalid(message: String) -> NesError {
+    NesError::InvalidMovie(message)
+}
+
+/// `RLDUTSBA`の位置に文字があれば押されている
+fn parse_fm2_pad(field: &str) -> Option<u8> {
+    if field.is_empty() {
+        return Some(0);
+    }
+    if field.len() != 8 {
+        return None;
+    }
+    Some(field.bytes().enumerate().fold(0, |pad, (bit, c)| {
+        if c == b'.' || c == b' ' {
+            pad
+        } else {
+            pad | (0x80 >> bit)
+        }
+    }))
+}
+
+/// `checkpoint <フレーム> <CRC32>`
+fn parse_checkpoint(text: &str) -> Option<Checkpoint> {
+    let mut words = text
+        .strip_prefix("checkpoint ")
+        .unwrap_or(text)
+        .split_whitespace();
+    Some(Checkpoint {
+        frame: words.next()?.parse().ok()?,
+        ram_crc: u32::from_str_radix(words.next()?, 16).ok()?,
+    })
+}
+
+/// `#Reset|Power|#P1 Up|P1 Down|...|`をグループごとのボタンに分ける
+fn parse_bk2_log_key(key: &str) -> Vec<Vec<Option<Button>>> {
+    let mut groups: Vec<Vec<Option<Button>>> = Vec::new();
+    for name in key.split('|').filter(|n| !n.is_empty()) {
+        if name.starts_with('#') || groups.is_empty() {
+            groups.push(Vec::new());
+        }
+        let name = name.trim_start_matches('#');
+        let button = name
+            .split_once(' ')
+            .and_then(|(_, button)| BK2_BUTTONS.iter().find(|(n, ..)| *n == button))
+            .map(|&(_, _, button)| button);
+        if let Some(group) = groups.last_mut() {
+            group.push(button);
+        }
+    }
+    groups
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn encode_base64(data: &[u8]) -> String {
+    let mut out = String::new();
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i)) as usize & 0x3F] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut n = 0u32;
+    let mut bits = 0;
+    for c in text.bytes().take_while(|&c| c != b'=') {
+        n = n << 6 | BASE64.iter().position(|&b| b == c)? as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+/// `.fm2`のバイナリ値（`base64:...`または`0x...`）
+fn decode_binary(value: &str) -> Option<Vec<u8>> {
+    if let Some(text) = value.strip_prefix("base64:") {
+        return decode_base64(text);
+    }
+    let hex = value.strip_prefix("0x")?;
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_movie() -> Movie {
+        let mut movie = Movie::new(MovieStart::SaveState(vec![0, 1, 2, 0xFE, 0xFF]));
+        movie.rerecord_count = 3;
+        movie.frames = vec![
+            FrameInput::default(),
+            FrameInput {
+                pads: [0x81, 0x40, 0, 0],
+                reset: false,
+            },
+            FrameInput {
+                pads: [0x0C, 0, 0, 0],
+                reset: true,
+            },
+        ];
+        movie.checkpoints = vec![Checkpoint {
+            frame: 60,
+            ram_crc: 0xDEAD_BEEF,
+        }];
+        movie
+    }
+
+    #[test]
+    fn test_fm2_round_trip() {
+        let movie = sample_movie();
+        let text = movie.to_fm2("game");
+        assert!(text.contains("|0|R......A|.L......||\n"));
+        assert!(text.contains("|1|....TS..|........||\n"));
+        assert_eq!(Movie::from_fm2(&text).unwrap(), movie);
+
+        let mut four_score = Movie::new(MovieStart::PowerOn);
+        four_score.four_score = true;
+        four_score.region = Region::Pal;
+        four_score.frames.push(FrameInput {
+            pads: [1, 2, 4, 8],
+            reset: false,
+        });
+        assert_eq!(
+            Movie::from_fm2(&four_score.to_fm2("game")).unwrap(),
+            four_score
+        );
+
+        let mut dendy = sample_movie();
+        dendy.region = Region::Dendy;
+        let text = dendy.to_fm2("game");
+        assert!(text.contains("palFlag 1\n"));
+        assert_eq!(Movie::from_fm2(&text).unwrap(), dendy);
+    }
+
+    #[test]
+    fn test_fm2_from_fceux() {
+        let text = "version 3\nemuVersion 22020\nrerecordCount 12\npalFlag 0\n\
+                    romFilename smb\nromChecksum base64:jjYwGG411HcjG/j9UOVM3Q==\n\
+                    guid 452DE2C3-EF43-2FA9-77AC-0677FC51543B\nfourscore 0\nport0 1\nport1 1\nport2 0\n\
+                    |0|........|........||\n|2|R..U...A|        ||\n";
+        let movie = Movie::from_fm2(text).unwrap();
+        assert_eq!(movie.start, MovieStart::PowerOn);
+        assert_eq!(movie.rerecord_count, 12);
+        assert_eq!(movie.frames.len(), 2);
+        assert_eq!(movie.frames[1].pads[0], 0x91);
+        assert!(movie.frames[1].reset);
+        assert!(Movie::from_fm2("version 2\n").is_err());
+        assert!(Movie::from_fm2("|x|........|\n").is_err());
+    }
+
+    #[test]
+    fn test_bk2_round_trip() {
+        let movie = sample_movie();
+        let files = movie.to_bk2("game");
+        let log = &files.iter().find(|(n, _)| n == "Input Log.txt").unwrap().1;
+        let log = String::from_utf8(log.clone()).unwrap();
+        assert!(log.contains("LogKey:#Reset|Power|#P1 Up|P1 Down|P1 Left|P1 Right|P1 Start|P1 Select|P1 B|P1 A|#P2 Up|"));
+        assert!(log.contains("|..|...R...A|..L.....|\n"));
+        assert!(log.contains("|r.|....Ss..|........|\n"));
+        assert_eq!(Movie::from_bk2(&files).unwrap(), movie);
+
+        for region in [Region::Pal, Region::Dendy] {
+            let mut movie = sample_movie();
+            movie.region = region;
+            assert_eq!(Movie::from_bk2(&movie.to_bk2("game")).unwrap(), movie);
+        }
+    }
+
+    #[test]
+    fn test_base64() {
+        for data in [&b""[..], b"f", b"fo", b"foo", b"foob", b"\x00\xFF\x10"] {
+            assert_eq!(decode_base64(&encode_base64(data)).unwrap(), data);
+        }
+        assert_eq!(encode_base64(b"foob"), "Zm9vYg==");
+        assert_eq!(decode_binary("0x00ff").unwrap(), vec![0x00, 0xFF]);
+    }
+}
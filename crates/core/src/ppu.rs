@@ -2,6 +2,10 @@
 //! Based on https://github.com/starrhorne/nes-rust
 
 use crate::cartridge::Cartridge;
+use crate::debugger::{Access, AccessWatch, AddressSpace};
+use crate::region::Region;
+use crate::state::{StateReader, StateWriter};
+use crate::Result;
 use std::cell::RefCell;
 use std::rc::Rc;
 
@@ -30,7 +34,11 @@ const PALETTE: [[u8; 3]; 64] = [
 pub struct Ppu {
     pub registers: Registers,
     pub renderer: Renderer,
-    pub nmi: bool,
+    /// デバッガ用のアクセス記録（CPUからの$2007経由のPPUアドレス空間へのアクセス）
+    pub watch: AccessWatch,
+    /// 1スキャンライン8個のスプライト制限（無効にするとちらつきがなくなる）
+    sprite_limit: bool,
+    region: Region,
     cartridge: Option<Rc<RefCell<Cartridge>>>,
 }
 
@@ -39,8 +47,6 @@ pub struct Registers {
     pub mask: u8,      // $2001
     pub status: u8,    // $2002
     pub oam_addr: u8,  // $2003
-    pub scroll_x: u8,  // $2005 first write
-    pub scroll_y: u8,  // $2005 second write
     pub addr: u16,     // $2006
     pub data_buffer: u8,
     pub v: u16,        // Current VRAM address (15 bits)
@@ -56,7 +62,49 @@ pub struct Renderer {
     pub frame_buffer: Vec<u8>,
     pub palette: [u8; 32],
     pub vram: [u8; 2048],
+    /// 4画面ミラーリングのカートリッジが持つ追加のネームテーブルRAM（$2800-$2FFF）
+    pub extra_vram: [u8; 2048],
     pub oam: [u8; 256],
+    // 背景フェッチパイプライン（次のタイルのラッチと16ビットシフトレジスタ）
+    bg_next_tile: u8,
+    bg_next_attr: u8,
+    bg_next_lo: u8,
+    bg_next_hi: u8,
+    bg_shift_lo: u16,
+    bg_shift_hi: u16,
+    bg_attr_shift_lo: u16,
+    bg_attr_shift_hi: u16,
+    /// 現在のスキャンラインに表示するスプライト（OAM順）
+    line_sprites: Vec<LineSprite>,
+}
+
+/// スキャンライン上のスプライト（パターンはフェッチ時に水平反転を適用済み）
+#[derive(Debug, Clone, Copy)]
+struct LineSprite {
+    x: u8,
+    attr: u8,
+    /// パターン下位のアドレス（上位は+8）
+    addr: u16,
+    lo: u8,
+    hi: u8,
+    /// OAMの先頭（スプライト0）か
+    zero: bool,
+}
+
+impl LineSprite {
+    /// フェッチしたパターンを格納する（水平反転の属性なら反転する）
+    fn set_pattern(&mut self, high: bool, data: u8) {
+        let data = if self.attr & 0x40 != 0 {
+            data.reverse_bits()
+        } else {
+            data
+        };
+        if high {
+            self.hi = data;
+        } else {
+            self.lo = data;
+        }
+    }
 }
 
 impl Ppu {
@@ -64,291 +112,412 @@ impl Ppu {
         Ppu {
             registers: Registers::new(),
             renderer: Renderer::new(),
-            nmi: false,
+            watch: AccessWatch::new(AddressSpace::Ppu),
+            sprite_limit: true,
+            region: Region::Ntsc,
             cartridge: None,
         }
     }
 
+    /// 地域（スキャンライン数とVBlankの位置）を設定
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    /// 1スキャンライン8個のスプライト制限の有効/無効を切り替える
+    ///
+    /// 無効にしても、スプライトオーバーフローフラグは実機と同じように設定される。
+    pub fn set_sprite_limit(&mut self, enabled: bool) {
+        self.sprite_limit = enabled;
+    }
+
+    pub fn sprite_limit(&self) -> bool {
+        self.sprite_limit
+    }
+
     pub fn set_cartridge(&mut self, cartridge: Rc<RefCell<Cartridge>>) {
         self.cartridge = Some(cartridge);
     }
 
     pub fn tick(&mut self) {
-        self.renderer.tick();
+        self.renderer.tick(self.region.scanlines());
 
         let scanline = self.renderer.scanline;
         let cycle = self.renderer.cycle;
-        let rendering_enabled = (self.registers.mask & 0x18) != 0;
+        let rendering_enabled = self.rendering_enabled();
+        let visible_line = scanline < 240;
+        let pre_render_line = scanline == self.region.pre_render_scanline();
+
+        if rendering_enabled && (visible_line || pre_render_line) {
+            self.tick_background(cycle, pre_render_line);
+        }
+
+        // 次のスキャンラインに表示するスプライトを決定
+        if cycle == 257 && (visible_line || pre_render_line) {
+            if rendering_enabled && visible_line {
+                self.evaluate_sprites(scanline);
+            } else {
+                self.renderer.line_sprites.clear();
+            }
+        }
+        if rendering_enabled && (visible_line || pre_render_line) && (257..=320).contains(&cycle) {
+            self.tick_sprite_fetch(cycle);
+        }
+
+        // ドットごとにピクセルを出力（ドット1-256 = X 0-255）
+        if visible_line && (1..=256).contains(&cycle) {
+            self.render_pixel(cycle - 1, scanline);
+        }
 
         // VBlank logic
-        if scanline == 241 && cycle == 1 {
+        if scanline == self.region.vblank_scanline() && cycle == 1 {
             self.registers.status |= 0x80; // Set VBlank flag
-            if self.registers.ctrl & 0x80 != 0 {
-                self.nmi = true;
-            }
         }
 
-        // Pre-render scanline (261)
-        if scanline == 261 && cycle == 1 {
+        // Pre-render scanline (NTSC: 261, PAL/Dendy: 311)
+        if pre_render_line && cycle == 1 {
             self.registers.status &= !0x80; // Clear VBlank flag
             self.registers.status &= !0x40; // Clear Sprite 0 Hit flag
             self.registers.status &= !0x20; // Clear Sprite Overflow flag
-            self.nmi = false;
         }
 
-        // Sprite 0 Hit detection - check once per scanline at a fixed cycle
-        // This simplified approach checks at cycle 2 of each visible scanline
-        if scanline < 240 && cycle == 2 && rendering_enabled {
-            self.check_sprite_0_hit_scanline(scanline);
+        // NTSCの奇数フレームではレンダリング中にプリレンダーラインの最後のドットを飛ばす
+        if pre_render_line
+            && cycle == 339
+            && rendering_enabled
+            && self.region.skips_odd_frame_dot()
+            && self.renderer.frame % 2 == 1
+        {
+            self.renderer.cycle = 340;
         }
+    }
 
-        // Render full frame at end of visible scanlines
-        if scanline == 240 && cycle == 1 {
-            self.render_frame();
-        }
+    fn rendering_enabled(&self) -> bool {
+        (self.registers.mask & 0x18) != 0
+    }
 
-        // Clock MMC3 IRQ counter at cycle 260 on visible scanlines
-        // Only clock when rendering is enabled (BG or sprites enabled)
-        if cycle == 260
-            && scanline < 240
-            && rendering_enabled
-        {
-            if let Some(ref c) = self.cartridge {
-                c.borrow_mut().clock_irq();
+    /// 背景のフェッチパイプライン（表示スキャンラインとプリレンダーライン）
+    ///
+    /// 8ドットごとにネームテーブル → 属性 → パターン下位 → パターン上位の順で読み込み、
+    /// 次のタイルの先頭でシフトレジスタにロードする。
+    /// 参考: https://www.nesdev.org/wiki/PPU_rendering
+    fn tick_background(&mut self, cycle: u16, pre_render_line: bool) {
+        if (2..=257).contains(&cycle) || (321..=337).contains(&cycle) {
+            self.shift_background();
+
+            match (cycle - 1) % 8 {
+                0 => {
+                    self.load_background_shifters();
+                    let v = self.registers.v;
+                    self.renderer.bg_next_tile = self.fetch(0x2000 | (v & 0x0FFF));
+                }
+                2 => {
+                    let v = self.registers.v;
+                    let addr = 0x23C0 | (v & 0x0C00) | ((v >> 4) & 0x38) | ((v >> 2) & 0x07);
+                    let mut attr = self.fetch(addr);
+                    // 2x2タイル単位の象限を選ぶ
+                    if v & 0x40 != 0 {
+                        attr >>= 4;
+                    }
+                    if v & 0x02 != 0 {
+                        attr >>= 2;
+                    }
+                    self.renderer.bg_next_attr = attr & 0x03;
+                }
+                4 => {
+                    let addr = self.background_pattern_addr();
+                    self.renderer.bg_next_lo = self.fetch(addr);
+                }
+                6 => {
+                    let addr = self.background_pattern_addr();
+                    self.renderer.bg_next_hi = self.fetch(addr + 8);
+                }
+                7 => self.increment_coarse_x(),
+                _ => {}
             }
         }
+
+        if cycle == 256 {
+            self.increment_y();
+        }
+
+        if cycle == 257 {
+            self.load_background_shifters();
+            // v: ....A.. ...BCDEF <- t: ....A.. ...BCDEF
+            self.registers.v = (self.registers.v & !0x041F) | (self.registers.t & 0x041F);
+        }
+
+        if pre_render_line && (280..=304).contains(&cycle) {
+            // v: GHIA.BC DEF..... <- t: GHIA.BC DEF.....
+            self.registers.v = (self.registers.v & !0x7BE0) | (self.registers.t & 0x7BE0);
+        }
     }
 
-    /// Simplified sprite 0 hit detection - checks if sprite 0 overlaps with background on this scanline
-    fn check_sprite_0_hit_scanline(&mut self, scanline: u16) {
-        // If already set, skip
-        if (self.registers.status & 0x40) != 0 {
-            return;
+    fn background_pattern_addr(&self) -> u16 {
+        let base: u16 = if self.registers.ctrl & 0x10 != 0 {
+            0x1000
+        } else {
+            0x0000
+        };
+        let fine_y = (self.registers.v >> 12) & 0x07;
+        base + (self.renderer.bg_next_tile as u16) * 16 + fine_y
+    }
+
+    fn shift_background(&mut self) {
+        let r = &mut self.renderer;
+        r.bg_shift_lo <<= 1;
+        r.bg_shift_hi <<= 1;
+        r.bg_attr_shift_lo <<= 1;
+        r.bg_attr_shift_hi <<= 1;
+    }
+
+    fn load_background_shifters(&mut self) {
+        let r = &mut self.renderer;
+        r.bg_shift_lo = (r.bg_shift_lo & 0xFF00) | r.bg_next_lo as u16;
+        r.bg_shift_hi = (r.bg_shift_hi & 0xFF00) | r.bg_next_hi as u16;
+        let attr_lo = if r.bg_next_attr & 0x01 != 0 {
+            0xFF
+        } else {
+            0x00
+        };
+        let attr_hi = if r.bg_next_attr & 0x02 != 0 {
+            0xFF
+        } else {
+            0x00
+        };
+        r.bg_attr_shift_lo = (r.bg_attr_shift_lo & 0xFF00) | attr_lo;
+        r.bg_attr_shift_hi = (r.bg_attr_shift_hi & 0xFF00) | attr_hi;
+    }
+
+    /// coarse Xをインクリメント（32で水平方向のネームテーブルを切り替え）
+    fn increment_coarse_x(&mut self) {
+        let v = &mut self.registers.v;
+        if *v & 0x001F == 31 {
+            *v &= !0x001F;
+            *v ^= 0x0400;
+        } else {
+            *v += 1;
         }
+    }
 
-        // Both BG and sprites must be enabled
-        if (self.registers.mask & 0x08) == 0 || (self.registers.mask & 0x10) == 0 {
+    /// fine Y / coarse Yをインクリメント（30行目で垂直方向のネームテーブルを切り替え）
+    fn increment_y(&mut self) {
+        let v = &mut self.registers.v;
+        if *v & 0x7000 != 0x7000 {
+            *v += 0x1000;
             return;
         }
+        *v &= !0x7000;
+        let mut coarse_y = (*v & 0x03E0) >> 5;
+        if coarse_y == 29 {
+            coarse_y = 0;
+            *v ^= 0x0800;
+        } else if coarse_y == 31 {
+            // 属性テーブル領域を指している場合はネームテーブルを切り替えずに0へ戻る
+            coarse_y = 0;
+        } else {
+            coarse_y += 1;
+        }
+        *v = (*v & !0x03E0) | (coarse_y << 5);
+    }
 
-        // Get sprite 0 info
-        let sprite_y = self.renderer.oam[0] as u16;
-        let sprite_tile = self.renderer.oam[1];
-        let sprite_attr = self.renderer.oam[2];
-        let sprite_x = self.renderer.oam[3] as u16;
+    /// 次のスキャンライン (`scanline + 1`) に表示するスプライトを選ぶ（パターンは`tick_sprite_fetch`で読み込む）
+    ///
+    /// 実機のセカンダリOAMと同様に最初の8個までを選ぶ。9個目以降の判定では
+    /// スプライト番号とバイトオフセットが同時に進むバグを再現してオーバーフローフラグを設定する。
+    /// 参考: https://www.nesdev.org/wiki/PPU_sprite_evaluation
+    fn evaluate_sprites(&mut self, scanline: u16) {
+        let sprite_height: u16 = if self.registers.ctrl & 0x20 != 0 {
+            16
+        } else {
+            8
+        };
+        let in_range = |y: u8| scanline.wrapping_sub(y as u16) < sprite_height;
+        let oam = &self.renderer.oam;
+
+        let mut selected: Vec<usize> = Vec::with_capacity(64);
+        let mut n = 0;
+        while n < 64 && selected.len() < 8 {
+            if in_range(oam[n * 4]) {
+                selected.push(n);
+            }
+            n += 1;
+        }
 
-        // Sprite 0 hit cannot occur at x=255
-        if sprite_x >= 255 {
-            return;
+        // 9個目以降: mもインクリメントされるため、Y座標以外のバイトを比較してしまう
+        let mut m = 0;
+        while n < 64 {
+            if in_range(oam[n * 4 + m]) {
+                self.registers.status |= 0x20;
+                break;
+            }
+            n += 1;
+            m = (m + 1) & 0x03;
         }
 
-        // Sprite appears on scanline (sprite_y + 1)
-        let sprite_top = sprite_y.wrapping_add(1);
-        let sprite_height: u16 = if (self.registers.ctrl & 0x20) != 0 { 16 } else { 8 };
+        if !self.sprite_limit {
+            selected = (0..64).filter(|&i| in_range(oam[i * 4])).collect();
+        }
 
-        // Check if this scanline intersects sprite 0
-        if scanline < sprite_top || scanline >= sprite_top + sprite_height {
-            return;
+        let mut sprites = std::mem::take(&mut self.renderer.line_sprites);
+        sprites.clear();
+        for i in selected {
+            let sprite_y = self.renderer.oam[i * 4] as u16;
+            let tile = self.renderer.oam[i * 4 + 1];
+            let attr = self.renderer.oam[i * 4 + 2];
+            let row = scanline.wrapping_sub(sprite_y);
+            let row = if attr & 0x80 != 0 {
+                sprite_height - 1 - row
+            } else {
+                row
+            };
+            let addr = if sprite_height == 16 {
+                // 8x16: タイル番号のbit 0でパターンテーブルを選択
+                let base: u16 = if tile & 0x01 != 0 { 0x1000 } else { 0x0000 };
+                let tile = (tile & 0xFE) as u16 + if row >= 8 { 1 } else { 0 };
+                base + tile * 16 + (row & 0x07)
+            } else {
+                let base: u16 = if self.registers.ctrl & 0x08 != 0 {
+                    0x1000
+                } else {
+                    0x0000
+                };
+                base + (tile as u16) * 16 + row
+            };
+
+            sprites.push(LineSprite {
+                x: self.renderer.oam[i * 4 + 3],
+                attr,
+                addr,
+                lo: 0,
+                hi: 0,
+                zero: i == 0,
+            });
         }
 
-        // Calculate which row of the sprite we're on
-        let sprite_row = scanline - sprite_top;
-        let flip_v = (sprite_attr & 0x80) != 0;
-        let flip_h = (sprite_attr & 0x40) != 0;
-        let row = if flip_v { sprite_height - 1 - sprite_row } else { sprite_row };
-
-        // Get sprite pattern
-        let sprite_pattern_base: u16 = if (self.registers.ctrl & 0x08) != 0 { 0x1000 } else { 0x0000 };
-        let tile_addr = sprite_pattern_base + (sprite_tile as u16) * 16 + row;
-        let sprite_plane0 = self.read_chr(tile_addr);
-        let sprite_plane1 = self.read_chr(tile_addr + 8);
-
-        // Get background pattern table
-        let bg_pattern_base: u16 = if (self.registers.ctrl & 0x10) != 0 { 0x1000 } else { 0x0000 };
-
-        // Check each pixel of sprite 0 on this scanline
-        for col in 0..8u16 {
-            let pixel_x = sprite_x + col;
-            
-            // Cannot hit at x >= 255
-            if pixel_x >= 255 {
-                continue;
-            }
+        self.renderer.line_sprites = sprites;
+    }
 
-            // Left edge clipping
-            if pixel_x < 8 {
-                if (self.registers.mask & 0x02) == 0 || (self.registers.mask & 0x04) == 0 {
-                    continue;
-                }
+    /// スプライトのフェッチ（ドット257-320、1スプライト8ドット）
+    ///
+    /// ダミーのネームテーブル2回 → パターン下位 → パターン上位の順で読み込む。
+    /// 8個に満たない分もタイル$FFを読み込むので、MMC3はこのときのA12の立ち上がりでスキャンラインを数える。
+    fn tick_sprite_fetch(&mut self, cycle: u16) {
+        let slot = ((cycle - 257) / 8) as usize;
+        let high = match (cycle - 257) % 8 {
+            0 | 2 => {
+                self.fetch(0x2000 | (self.registers.v & 0x0FFF));
+                return;
             }
+            4 => false,
+            6 => true,
+            _ => return,
+        };
 
-            // Check sprite pixel
-            let sprite_bit = if flip_h { col } else { 7 - col } as u8;
-            let sprite_pixel = ((sprite_plane0 >> sprite_bit) & 1) | (((sprite_plane1 >> sprite_bit) & 1) << 1);
-            
-            if sprite_pixel == 0 {
-                continue;
+        let addr = match self.renderer.line_sprites.get(slot) {
+            Some(sprite) => sprite.addr,
+            None if self.registers.ctrl & 0x20 != 0 => 0x1FE0,
+            None => {
+                (if self.registers.ctrl & 0x08 != 0 {
+                    0x1000
+                } else {
+                    0x0000
+                }) + 0x0FF0
             }
+        };
+        let data = self.fetch(addr + if high { 8 } else { 0 });
+        if let Some(sprite) = self.renderer.line_sprites.get_mut(slot) {
+            sprite.set_pattern(high, data);
+        }
 
-            // Check background pixel at this position
-            // For SMB, scroll is 0 when waiting for sprite 0 hit, so we use pixel_x directly
-            let bg_x = pixel_x as usize;
-            let bg_y = scanline as usize;
-            
-            let tile_x = bg_x / 8;
-            let tile_y = bg_y / 8;
-            let fine_x = bg_x % 8;
-            let fine_y = bg_y % 8;
-
-            // Get nametable address (assuming nametable 0 for simplicity, which is correct for SMB status bar)
-            let nt_addr = 0x2000 + (tile_y * 32 + tile_x) as u16;
-            let tile_num = self.read_vram(nt_addr);
-
-            // Get background tile pixel
-            let bg_tile_addr = bg_pattern_base + (tile_num as u16) * 16 + fine_y as u16;
-            let bg_plane0 = self.read_chr(bg_tile_addr);
-            let bg_plane1 = self.read_chr(bg_tile_addr + 8);
-            let bg_bit = 7 - fine_x as u8;
-            let bg_pixel = ((bg_plane0 >> bg_bit) & 1) | (((bg_plane1 >> bg_bit) & 1) << 1);
-
-            // Hit if both pixels are non-transparent
-            if bg_pixel != 0 {
-                self.registers.status |= 0x40;
-                return;
+        // スプライト制限を無効にした場合、9個目以降はバスを通さずにまとめて読み込む
+        if high && slot == 7 {
+            for i in 8..self.renderer.line_sprites.len() {
+                let addr = self.renderer.line_sprites[i].addr;
+                let (lo, hi) = (self.read_chr(addr), self.read_chr(addr + 8));
+                let sprite = &mut self.renderer.line_sprites[i];
+                sprite.set_pattern(false, lo);
+                sprite.set_pattern(true, hi);
             }
         }
     }
 
-    fn render_frame(&mut self) {
-        // Clear frame buffer with background color
-        let bg_color = self.get_palette_color(0);
-        for pixel in self.renderer.frame_buffer.chunks_mut(4) {
-            pixel[0] = bg_color[0];
-            pixel[1] = bg_color[1];
-            pixel[2] = bg_color[2];
-            pixel[3] = 255;
-        }
-
-        // Render background
-        if self.registers.mask & 0x08 != 0 {
-            self.render_background();
-        }
-
-        // Render sprites
-        if self.registers.mask & 0x10 != 0 {
-            self.render_sprites();
-        }
-    }
-
-    fn render_background(&mut self) {
-        let pattern_table_base = if self.registers.ctrl & 0x10 != 0 { 0x1000 } else { 0x0000 };
-        
-        // Get base nametable offset from PPUCTRL bits 0-1
-        // This adds 256 pixels horizontally (bit 0) or 240 pixels vertically (bit 1)
-        let base_nt_x = if (self.registers.ctrl & 0x01) != 0 { 256 } else { 0 };
-        let base_nt_y = if (self.registers.ctrl & 0x02) != 0 { 240 } else { 0 };
-
-        for y in 0..240 {
-            for x in 0..256 {
-                // Combine base nametable offset with scroll values
-                let scroll_x = (x + self.registers.scroll_x as usize + base_nt_x) % 512;
-                let scroll_y = (y + self.registers.scroll_y as usize + base_nt_y) % 480;
-
-                let tile_x = scroll_x / 8;
-                let tile_y = scroll_y / 8;
-                let pixel_x = scroll_x % 8;
-                let pixel_y = scroll_y % 8;
-
-                // Determine which nametable to use based on scroll position
-                // Nametable layout: 0 1
-                //                   2 3
-                let nt_x = tile_x / 32;  // 0 or 1
-                let nt_y = tile_y / 30;  // 0 or 1
-                let nt_offset = ((nt_x + nt_y * 2) * 0x0400) as u16;
-                let nt_addr = 0x2000 + nt_offset + ((tile_y % 30) * 32 + (tile_x % 32)) as u16;
-
-                let tile_num = self.read_vram(nt_addr);
-
-                // Get attribute
-                let attr_x = (tile_x % 32) / 4;
-                let attr_y = (tile_y % 30) / 4;
-                let attr_addr = 0x2000 + nt_offset + 0x3C0 + (attr_y * 8 + attr_x) as u16;
-                let attr_byte = self.read_vram(attr_addr);
-                
-                let shift = (((tile_y % 4) / 2) * 4 + ((tile_x % 4) / 2) * 2) as u8;
-                let palette_num = (attr_byte >> shift) & 0x03;
-
-                // Get pixel from pattern table
-                let tile_addr = pattern_table_base + (tile_num as u16) * 16 + pixel_y as u16;
-                let plane0 = self.read_chr(tile_addr);
-                let plane1 = self.read_chr(tile_addr + 8);
-
-                let bit = 7 - pixel_x;
-                let pixel_value = ((plane0 >> bit) & 1) | (((plane1 >> bit) & 1) << 1);
-
-                if pixel_value != 0 {
-                    let palette_index = (palette_num * 4 + pixel_value) as usize;
-                    let color = self.get_palette_color(palette_index as u8);
-                    let idx = (y * SCREEN_WIDTH + x) * 4;
-                    self.renderer.frame_buffer[idx] = color[0];
-                    self.renderer.frame_buffer[idx + 1] = color[1];
-                    self.renderer.frame_buffer[idx + 2] = color[2];
-                    self.renderer.frame_buffer[idx + 3] = 255;
-                }
+    /// X座標でのスプライトのピクセル（最も優先度の高い不透明なもの）
+    fn sprite_pixel(&self, x: u16) -> Option<(u8, &LineSprite)> {
+        self.renderer.line_sprites.iter().find_map(|sprite| {
+            let offset = x.wrapping_sub(sprite.x as u16);
+            if offset >= 8 {
+                return None;
             }
-        }
+            let bit = 7 - offset;
+            let pixel = ((sprite.lo >> bit) & 1) | (((sprite.hi >> bit) & 1) << 1);
+            (pixel != 0).then_some((pixel, sprite))
+        })
     }
 
-    fn render_sprites(&mut self) {
-        let sprite_size = if self.registers.ctrl & 0x20 != 0 { 16 } else { 8 };
-        let pattern_table_base = if self.registers.ctrl & 0x08 != 0 { 0x1000 } else { 0x0000 };
+    /// 1ピクセルを合成してフレームバッファに書き込む
+    fn render_pixel(&mut self, x: u16, y: u16) {
+        let mask = self.registers.mask;
+        let show_background = mask & 0x08 != 0 && (x >= 8 || mask & 0x02 != 0);
+        let show_sprites = mask & 0x10 != 0 && (x >= 8 || mask & 0x04 != 0);
+
+        let (bg_pixel, bg_palette) = if show_background {
+            let bit = 15 - self.registers.x as u16;
+            let r = &self.renderer;
+            let pixel = (((r.bg_shift_lo >> bit) & 1) | (((r.bg_shift_hi >> bit) & 1) << 1)) as u8;
+            let palette = (((r.bg_attr_shift_lo >> bit) & 1)
+                | (((r.bg_attr_shift_hi >> bit) & 1) << 1)) as u8;
+            (pixel, palette)
+        } else {
+            (0, 0)
+        };
 
-        // Render sprites in reverse order (priority)
-        for i in (0..64).rev() {
-            let sprite_y = self.renderer.oam[i * 4] as usize;
-            let tile_num = self.renderer.oam[i * 4 + 1];
-            let attributes = self.renderer.oam[i * 4 + 2];
-            let sprite_x = self.renderer.oam[i * 4 + 3] as usize;
+        let sprite = if show_sprites {
+            self.sprite_pixel(x)
+                .map(|(pixel, sprite)| (pixel, sprite.attr, sprite.zero))
+        } else {
+            None
+        };
 
-            if sprite_y >= 0xEF {
-                continue;
+        // Sprite 0 Hit: 背景とスプライト0の不透明ピクセルが重なった（X=255を除く）
+        if let Some((_, _, true)) = sprite {
+            if bg_pixel != 0 && x != 255 {
+                self.registers.status |= 0x40;
             }
+        }
 
-            let palette_num = (attributes & 0x03) + 4;
-            let flip_h = (attributes & 0x40) != 0;
-            let flip_v = (attributes & 0x80) != 0;
-
-            for py in 0..sprite_size {
-                let y = sprite_y + py + 1;
-                if y >= 240 {
-                    continue;
-                }
+        // 最も優先度の高い不透明なスプライトが背景の後ろ (attr bit 5) なら、
+        // 後続のスプライトに関係なく不透明な背景が表示される
+        let palette_index = match sprite {
+            Some((sp_pixel, attr, _)) if attr & 0x20 == 0 || bg_pixel == 0 => {
+                0x10 + (attr & 0x03) * 4 + sp_pixel
+            }
+            _ if bg_pixel != 0 => bg_palette * 4 + bg_pixel,
+            _ => 0,
+        };
 
-                let tile_y = if flip_v { sprite_size - 1 - py } else { py };
-                let tile_addr = pattern_table_base + (tile_num as u16) * 16 + tile_y as u16;
-                let plane0 = self.read_chr(tile_addr);
-                let plane1 = self.read_chr(tile_addr + 8);
+        let color = self.get_palette_color(palette_index);
+        let idx = (y as usize * SCREEN_WIDTH + x as usize) * 4;
+        self.renderer.frame_buffer[idx] = color[0];
+        self.renderer.frame_buffer[idx + 1] = color[1];
+        self.renderer.frame_buffer[idx + 2] = color[2];
+        self.renderer.frame_buffer[idx + 3] = 255;
+    }
 
-                for px in 0..8 {
-                    let x = sprite_x + px;
-                    if x >= 256 {
-                        continue;
-                    }
+    /// レンダリングによるフェッチ（アドレスバスをカートリッジに通知してから読む）
+    fn fetch(&mut self, addr: u16) -> u8 {
+        self.set_bus_address(addr);
+        self.read_vram(addr)
+    }
 
-                    let bit = if flip_h { px } else { 7 - px };
-                    let pixel_value = ((plane0 >> bit) & 1) | (((plane1 >> bit) & 1) << 1);
-
-                    if pixel_value != 0 {
-                        let palette_index = (palette_num * 4 + pixel_value) as usize;
-                        let color = self.get_palette_color(palette_index as u8);
-                        let idx = (y * SCREEN_WIDTH + x) * 4;
-                        self.renderer.frame_buffer[idx] = color[0];
-                        self.renderer.frame_buffer[idx + 1] = color[1];
-                        self.renderer.frame_buffer[idx + 2] = color[2];
-                        self.renderer.frame_buffer[idx + 3] = 255;
-                    }
-                }
-            }
+    /// PPUのアドレスバスをカートリッジに通知する（MMC3はA12の変化でIRQカウンタを進める）
+    fn set_bus_address(&mut self, addr: u16) {
+        if let Some(ref c) = self.cartridge {
+            c.borrow_mut().notify_ppu_address(addr & 0x3FFF);
         }
     }
 
@@ -372,11 +541,14 @@ impl Ppu {
             0x0000..=0x1FFF => self.read_chr(addr),
             0x2000..=0x3EFF => {
                 let mirror_addr = self.mirror_vram_addr(addr);
-                self.renderer.vram[mirror_addr]
+                match mirror_addr {
+                    0x0000..=0x07FF => self.renderer.vram[mirror_addr],
+                    _ => self.renderer.extra_vram[mirror_addr - 0x0800],
+                }
             }
             0x3F00..=0x3FFF => {
                 let palette_addr = (addr - 0x3F00) as usize & 0x1F;
-                let palette_addr = if palette_addr >= 0x10 && palette_addr % 4 == 0 {
+                let palette_addr = if palette_addr >= 0x10 && palette_addr.is_multiple_of(4) {
                     palette_addr & 0x0F
                 } else {
                     palette_addr
@@ -393,11 +565,14 @@ impl Ppu {
             0x0000..=0x1FFF => self.write_chr(addr, value),
             0x2000..=0x3EFF => {
                 let mirror_addr = self.mirror_vram_addr(addr);
-                self.renderer.vram[mirror_addr] = value;
+                match mirror_addr {
+                    0x0000..=0x07FF => self.renderer.vram[mirror_addr] = value,
+                    _ => self.renderer.extra_vram[mirror_addr - 0x0800] = value,
+                }
             }
             0x3F00..=0x3FFF => {
                 let palette_addr = (addr - 0x3F00) as usize & 0x1F;
-                let palette_addr = if palette_addr >= 0x10 && palette_addr % 4 == 0 {
+                let palette_addr = if palette_addr >= 0x10 && palette_addr.is_multiple_of(4) {
                     palette_addr & 0x0F
                 } else {
                     palette_addr
@@ -445,6 +620,8 @@ impl Ppu {
                 // All nametables map to the second one
                 (vram_index % 0x400 + 0x400) as usize
             }
+            // $2800-$2FFF are backed by the cartridge's extra 2KB
+            Mirroring::FourScreen => vram_index as usize,
         }
     }
 
@@ -466,9 +643,10 @@ impl Ppu {
             }
             0x2007 => {
                 let addr = self.registers.v;
-                let increment = if self.registers.ctrl & 0x04 != 0 { 32 } else { 1 };
-                self.registers.v = self.registers.v.wrapping_add(increment);
+                self.increment_vram_addr();
+                self.set_bus_address(addr);
 
+                self.watch.record(Access::Read, addr, self.read_vram(addr));
                 match addr {
                     0x0000..=0x3EFF => {
                         let result = self.registers.data_buffer;
@@ -506,13 +684,11 @@ impl Ppu {
                     // x:              FGH <- d: .....FGH
                     self.registers.t = (self.registers.t & 0xFFE0) | ((value as u16) >> 3);
                     self.registers.x = value & 0x07;
-                    self.registers.scroll_x = value;
                 } else {
                     // Second write (Y scroll)
                     // t: FGH..AB CDE..... <- d: ABCDEFGH
                     self.registers.t = (self.registers.t & 0x8FFF) | (((value as u16) & 0x07) << 12);
                     self.registers.t = (self.registers.t & 0xFC1F) | (((value as u16) & 0xF8) << 2);
-                    self.registers.scroll_y = value;
                 }
                 self.registers.w = !self.registers.w;
             }
@@ -528,24 +704,65 @@ impl Ppu {
                     // v: <...all bits...> <- t: <...all bits...>
                     self.registers.t = (self.registers.t & 0xFF00) | (value as u16);
                     self.registers.v = self.registers.t;
+                    self.set_bus_address(self.registers.v);
                 }
                 self.registers.w = !self.registers.w;
             }
             0x2007 => {
                 let addr = self.registers.v;
-                let increment = if self.registers.ctrl & 0x04 != 0 { 32 } else { 1 };
-                self.registers.v = self.registers.v.wrapping_add(increment);
+                self.increment_vram_addr();
+                self.set_bus_address(addr);
+                self.watch.record(Access::Write, addr, value);
                 self.write_vram(addr, value);
             }
             _ => {}
         }
     }
 
+    /// $2007アクセス後のVRAMアドレスのインクリメント
+    ///
+    /// レンダリング中はフェッチパイプラインと同じcoarse X / Yのインクリメントが起きる。
+    fn increment_vram_addr(&mut self) {
+        let scanline = self.renderer.scanline;
+        let rendering_line = scanline < 240 || scanline == self.region.pre_render_scanline();
+        if self.rendering_enabled() && rendering_line {
+            self.increment_coarse_x();
+            self.increment_y();
+        } else {
+            let increment = if self.registers.ctrl & 0x04 != 0 {
+                32
+            } else {
+                1
+            };
+            self.registers.v = self.registers.v.wrapping_add(increment) & 0x7FFF;
+        }
+    }
+
     pub fn write_oam_data(&mut self, value: u8) {
         self.renderer.oam[self.registers.oam_addr as usize] = value;
         self.registers.oam_addr = self.registers.oam_addr.wrapping_add(1);
     }
 
+    /// 状態を保存（フレームバッファは次のフレームで再生成されるため含めない）
+    pub fn save_state(&self, w: &mut StateWriter) {
+        self.registers.save_state(w);
+        self.renderer.save_state(w);
+    }
+
+    /// 状態を復元
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.registers.load_state(r)?;
+        self.renderer.load_state(r)?;
+        Ok(())
+    }
+
+    /// NMI出力のレベル（VBlankフラグとPPUCTRLのNMI有効ビットのAND）
+    ///
+    /// VBlank中にNMIを有効にすると、その時点で立ち上がりエッジが生じて再びNMIが発生する。
+    pub fn nmi_line(&self) -> bool {
+        self.registers.status & 0x80 != 0 && self.registers.ctrl & 0x80 != 0
+    }
+
     pub fn frame_buffer(&self) -> &[u8] {
         &self.renderer.frame_buffer
     }
@@ -570,8 +787,6 @@ impl Registers {
             mask: 0,
             status: 0,
             oam_addr: 0,
-            scroll_x: 0,
-            scroll_y: 0,
             addr: 0,
             data_buffer: 0,
             v: 0,
@@ -582,6 +797,35 @@ impl Registers {
     }
 }
 
+impl Registers {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u8(self.ctrl);
+        w.write_u8(self.mask);
+        w.write_u8(self.status);
+        w.write_u8(self.oam_addr);
+        w.write_u16(self.addr);
+        w.write_u8(self.data_buffer);
+        w.write_u16(self.v);
+        w.write_u16(self.t);
+        w.write_u8(self.x);
+        w.write_bool(self.w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.ctrl = r.read_u8()?;
+        self.mask = r.read_u8()?;
+        self.status = r.read_u8()?;
+        self.oam_addr = r.read_u8()?;
+        self.addr = r.read_u16()?;
+        self.data_buffer = r.read_u8()?;
+        self.v = r.read_u16()?;
+        self.t = r.read_u16()?;
+        self.x = r.read_u8()?;
+        self.w = r.read_bool()?;
+        Ok(())
+    }
+}
+
 impl Renderer {
     fn new() -> Self {
         Renderer {
@@ -591,16 +835,85 @@ impl Renderer {
             frame_buffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT * 4],
             palette: [0; 32],
             vram: [0; 2048],
+            extra_vram: [0; 2048],
             oam: [0; 256],
+            bg_next_tile: 0,
+            bg_next_attr: 0,
+            bg_next_lo: 0,
+            bg_next_hi: 0,
+            bg_shift_lo: 0,
+            bg_shift_hi: 0,
+            bg_attr_shift_lo: 0,
+            bg_attr_shift_hi: 0,
+            line_sprites: Vec::with_capacity(64),
+        }
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u16(self.scanline);
+        w.write_u16(self.cycle);
+        w.write_u64(self.frame);
+        w.write_bytes(&self.palette);
+        w.write_bytes(&self.vram);
+        w.write_bytes(&self.extra_vram);
+        w.write_bytes(&self.oam);
+        w.write_u8(self.bg_next_tile);
+        w.write_u8(self.bg_next_attr);
+        w.write_u8(self.bg_next_lo);
+        w.write_u8(self.bg_next_hi);
+        w.write_u16(self.bg_shift_lo);
+        w.write_u16(self.bg_shift_hi);
+        w.write_u16(self.bg_attr_shift_lo);
+        w.write_u16(self.bg_attr_shift_hi);
+        w.write_u8(self.line_sprites.len() as u8);
+        for sprite in &self.line_sprites {
+            w.write_u8(sprite.x);
+            w.write_u8(sprite.attr);
+            w.write_u16(sprite.addr);
+            w.write_u8(sprite.lo);
+            w.write_u8(sprite.hi);
+            w.write_bool(sprite.zero);
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<()> {
+        self.scanline = r.read_u16()?;
+        self.cycle = r.read_u16()?;
+        self.frame = r.read_u64()?;
+        r.read_bytes_into(&mut self.palette)?;
+        r.read_bytes_into(&mut self.vram)?;
+        r.read_bytes_into(&mut self.extra_vram)?;
+        r.read_bytes_into(&mut self.oam)?;
+        self.bg_next_tile = r.read_u8()?;
+        self.bg_next_attr = r.read_u8()?;
+        self.bg_next_lo = r.read_u8()?;
+        self.bg_next_hi = r.read_u8()?;
+        self.bg_shift_lo = r.read_u16()?;
+        self.bg_shift_hi = r.read_u16()?;
+        self.bg_attr_shift_lo = r.read_u16()?;
+        self.bg_attr_shift_hi = r.read_u16()?;
+        let count = r.read_u8()?;
+        self.line_sprites.clear();
+        for _ in 0..count {
+            self.line_sprites.push(LineSprite {
+                x: r.read_u8()?,
+                attr: r.read_u8()?,
+                addr: r.read_u16()?,
+                lo: r.read_u8()?,
+                hi: r.read_u8()?,
+                zero: r.read_bool()?,
+            });
         }
+        Ok(())
     }
 
-    fn tick(&mut self) {
+    /// 1ドット進める（`scanlines`は1フレームのスキャンライン数）
+    fn tick(&mut self, scanlines: u16) {
         self.cycle += 1;
         if self.cycle > 340 {
             self.cycle = 0;
             self.scanline += 1;
-            if self.scanline > 261 {
+            if self.scanline >= scanlines {
                 self.scanline = 0;
                 self.frame += 1;
             }
@@ -613,3 +926,223 @@ impl Default for Ppu {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: [u8; 3] = PALETTE[0x30];
+    const BLACK: [u8; 3] = PALETTE[0x0F];
+
+    /// CHR RAMのNROM（垂直ミラーリング）を接続し、タイル1を色1で塗りつぶしたPPU
+    fn setup() -> Ppu {
+        let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 1, 0, 0x01, 0];
+        rom.resize(16 + 16384, 0);
+        let cartridge = Cartridge::from_bytes(&rom).unwrap();
+
+        let mut ppu = Ppu::new();
+        ppu.set_cartridge(Rc::new(RefCell::new(cartridge)));
+        for row in 0..8 {
+            ppu.write_vram(0x0010 + row, 0xFF);
+        }
+        ppu.write_vram(0x3F00, 0x0F);
+        ppu.write_vram(0x3F01, 0x30);
+        ppu.write_vram(0x3F11, 0x30);
+        run_until(&mut ppu, 261, 0);
+        ppu
+    }
+
+    fn run_until(ppu: &mut Ppu, scanline: u16, cycle: u16) {
+        while ppu.scanline() != scanline || ppu.cycle() != cycle {
+            ppu.tick();
+        }
+    }
+
+    fn set_scroll(ppu: &mut Ppu, x: u8, y: u8) {
+        ppu.write_register(0x2005, x);
+        ppu.write_register(0x2005, y);
+    }
+
+    fn pixel(ppu: &Ppu, x: usize, y: usize) -> [u8; 3] {
+        let idx = (y * SCREEN_WIDTH + x) * 4;
+        let fb = ppu.frame_buffer();
+        [fb[idx], fb[idx + 1], fb[idx + 2]]
+    }
+
+    #[test]
+    fn test_mid_frame_scroll_split() {
+        let mut ppu = setup();
+        // ネームテーブル0の左端の列だけタイル1
+        for row in 0..30 {
+            ppu.write_vram(0x2000 + row * 32, 0x01);
+        }
+        ppu.write_register(0x2001, 0x0A);
+        set_scroll(&mut ppu, 3, 0);
+
+        // スキャンライン100から水平スクロールを0に戻す
+        run_until(&mut ppu, 99, 200);
+        set_scroll(&mut ppu, 0, 0);
+        run_until(&mut ppu, 241, 0);
+
+        assert_eq!(pixel(&ppu, 4, 50), WHITE);
+        assert_eq!(pixel(&ppu, 5, 50), BLACK);
+        assert_eq!(pixel(&ppu, 7, 150), WHITE);
+        assert_eq!(pixel(&ppu, 8, 150), BLACK);
+    }
+
+    /// X = 0, 16, 32, ... にY=49のスプライトを`count`個並べる
+    fn place_sprites(ppu: &mut Ppu, count: usize) {
+        ppu.renderer.oam = [0xFF; 256];
+        for i in 0..count {
+            ppu.renderer.oam[i * 4..i * 4 + 4].copy_from_slice(&[49, 0x01, 0x00, (i * 16) as u8]);
+        }
+    }
+
+    #[test]
+    fn test_sprite_limit_and_overflow() {
+        let mut ppu = setup();
+        ppu.write_register(0x2001, 0x14);
+        place_sprites(&mut ppu, 9);
+        run_until(&mut ppu, 51, 0);
+        assert_ne!(ppu.registers.status & 0x20, 0);
+        assert_eq!(pixel(&ppu, 7 * 16, 50), WHITE);
+        assert_eq!(pixel(&ppu, 8 * 16, 50), BLACK);
+
+        let mut ppu = setup();
+        ppu.set_sprite_limit(false);
+        ppu.write_register(0x2001, 0x14);
+        place_sprites(&mut ppu, 9);
+        run_until(&mut ppu, 51, 0);
+        assert_ne!(ppu.registers.status & 0x20, 0);
+        assert_eq!(pixel(&ppu, 8 * 16, 50), WHITE);
+    }
+
+    #[test]
+    fn test_sprite_overflow_hardware_bug() {
+        // 8個が同じラインにあり、9個目以降は範囲外。
+        // 9個目の判定ではY、10個目ではタイル番号 (m=1) を比較してしまう
+        let mut ppu = setup();
+        ppu.write_register(0x2001, 0x14);
+        place_sprites(&mut ppu, 8);
+        ppu.renderer.oam[32..36].copy_from_slice(&[0xF0, 0xF0, 49, 0xF0]);
+        ppu.renderer.oam[36..40].copy_from_slice(&[0xF0, 45, 0xF0, 0xF0]);
+        run_until(&mut ppu, 51, 0);
+        assert_ne!(ppu.registers.status & 0x20, 0);
+
+        // 9個目のYが範囲内でも、バグにより見逃されるケース（m=1のバイトが範囲外）
+        let mut ppu = setup();
+        ppu.write_register(0x2001, 0x14);
+        place_sprites(&mut ppu, 8);
+        ppu.renderer.oam[36..40].copy_from_slice(&[49, 0xF0, 0xF0, 0xF0]);
+        run_until(&mut ppu, 51, 0);
+        assert_eq!(ppu.registers.status & 0x20, 0);
+    }
+
+    #[test]
+    fn test_sprite_priority_and_left_clipping() {
+        let mut ppu = setup();
+        ppu.write_vram(0x3F12, 0x16);
+        // 背景: ネームテーブル0の行6の左から2タイル目だけ不透明
+        ppu.write_vram(0x2000 + 6 * 32 + 1, 0x01);
+        ppu.write_register(0x2001, 0x1A);
+        set_scroll(&mut ppu, 0, 0);
+        ppu.renderer.oam = [0xFF; 256];
+        // スプライト0: 背景の後ろ、X=8（背景タイルと重なる）、パレット1 (0x16)
+        ppu.renderer.oam[..4].copy_from_slice(&[49, 0x01, 0x20, 8]);
+        // スプライト1: X=0（左端8ピクセルのクリッピングで隠れる）
+        ppu.renderer.oam[4..8].copy_from_slice(&[49, 0x01, 0x00, 0]);
+        run_until(&mut ppu, 51, 0);
+
+        assert_eq!(pixel(&ppu, 10, 50), WHITE);
+        assert_eq!(pixel(&ppu, 4, 50), BLACK);
+        // 背景の後ろでもSprite 0 Hitは発生する
+        assert_ne!(ppu.registers.status & 0x40, 0);
+    }
+
+    #[test]
+    fn test_sprite_zero_hit_uses_scrolled_background() {
+        let mut ppu = setup();
+        // ネームテーブル1の行6 (Y=48-55) だけタイル1
+        for col in 0..32 {
+            ppu.write_vram(0x2400 + 6 * 32 + col, 0x01);
+        }
+        ppu.write_register(0x2000, 0x01);
+        ppu.write_register(0x2001, 0x1E);
+        set_scroll(&mut ppu, 0, 0);
+        // スプライト0: Y=49（スキャンライン50から表示）、X=100、タイル1
+        ppu.renderer.oam[..4].copy_from_slice(&[49, 0x01, 0x00, 100]);
+
+        run_until(&mut ppu, 50, 100);
+        assert_eq!(ppu.registers.status & 0x40, 0);
+        run_until(&mut ppu, 50, 103);
+        assert_ne!(ppu.registers.status & 0x40, 0);
+        assert_eq!(pixel(&ppu, 100, 50), WHITE);
+    }
+
+    #[test]
+    fn test_mmc3_irq_from_sprite_fetches() {
+        // MMC3（CHR ROM）: 背景は$0000、スプライトは$1000なので、A12は各ラインのドット261付近で立ち上がる
+        let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 2, 1, 0x40, 0];
+        rom.resize(16 + 2 * 16384 + 8192, 0);
+        let cartridge = Rc::new(RefCell::new(Cartridge::from_bytes(&rom).unwrap()));
+        let mut ppu = Ppu::new();
+        ppu.set_cartridge(cartridge.clone());
+        ppu.write_register(0x2000, 0x08);
+        ppu.write_register(0x2001, 0x18);
+        {
+            let mut c = cartridge.borrow_mut();
+            c.write_prg_byte(0xC000, 10);
+            c.write_prg_byte(0xC001, 0);
+            c.write_prg_byte(0xE001, 0);
+        }
+        run_until(&mut ppu, 261, 0);
+
+        // CPUサイクル（3ドットごと）を通知しながら進める
+        let run_with_cpu = |ppu: &mut Ppu, scanline: u16, cycle: u16| {
+            let mut dots = 0;
+            while ppu.scanline() != scanline || ppu.cycle() != cycle {
+                ppu.tick();
+                dots += 1;
+                if dots % 3 == 0 {
+                    cartridge.borrow_mut().notify_cpu_cycle();
+                }
+            }
+        };
+        // プリレンダーラインのスプライトフェッチでリロードされ、スキャンライン0-9でデクリメントして0になる
+        run_with_cpu(&mut ppu, 9, 256);
+        assert!(!cartridge.borrow().irq_pending());
+        run_with_cpu(&mut ppu, 9, 270);
+        assert!(cartridge.borrow().irq_pending());
+    }
+
+    #[test]
+    fn test_four_screen_nametables() {
+        let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 1, 0, 0x08, 0];
+        rom.resize(16 + 16384, 0);
+        let mut ppu = Ppu::new();
+        ppu.set_cartridge(Rc::new(RefCell::new(Cartridge::from_bytes(&rom).unwrap())));
+        for (i, addr) in [0x2000, 0x2400, 0x2800, 0x2C00].into_iter().enumerate() {
+            ppu.write_vram(addr, i as u8 + 1);
+        }
+        for (i, addr) in [0x2000, 0x2400, 0x2800, 0x2C00].into_iter().enumerate() {
+            assert_eq!(ppu.read_vram(addr), i as u8 + 1);
+        }
+        assert_eq!(ppu.renderer.extra_vram[0x400], 4);
+    }
+
+    #[test]
+    fn test_dendy_vblank_timing() {
+        let mut ppu = setup();
+        ppu.set_region(Region::Dendy);
+        run_until(&mut ppu, 241, 2);
+        assert_eq!(ppu.registers.status & 0x80, 0);
+        run_until(&mut ppu, 291, 2);
+        assert_eq!(ppu.registers.status & 0x80, 0x80);
+        // プリレンダーライン (311) でクリアされ、スキャンライン0に戻る
+        run_until(&mut ppu, 311, 2);
+        assert_eq!(ppu.registers.status & 0x80, 0);
+        let frame = ppu.frame();
+        run_until(&mut ppu, 0, 0);
+        assert_eq!(ppu.frame(), frame + 1);
+    }
+}
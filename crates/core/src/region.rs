@@ -0,0 +1,101 @@
+//! # Region
+//! NTSC / PAL / Dendy のタイミングの違い
+//!
+//! 参考: https://www.nesdev.org/wiki/Cycle_reference_chart
+
+use crate::rom::Timing;
+
+/// 本体の地域（CPU/PPUのクロックとフレーム構成）
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    /// 北米・日本 (RP2A03/RP2C02)
+    #[default]
+    Ntsc,
+    /// 欧州 (RP2A07/RP2C07)
+    Pal,
+    /// 旧ソ連圏の互換機（PALのフレーム構成とNTSCのクロック比を持つ）
+    Dendy,
+}
+
+impl Region {
+    /// ヘッダーのタイミング情報から地域を選ぶ（両対応のROMはNTSCとして扱う）
+    pub fn from_timing(timing: Timing) -> Self {
+        match timing {
+            Timing::Ntsc | Timing::MultiRegion => Region::Ntsc,
+            Timing::Pal => Region::Pal,
+            Timing::Dendy => Region::Dendy,
+        }
+    }
+
+    /// CPUのクロック周波数 (Hz)
+    pub fn cpu_clock_rate(self) -> f64 {
+        match self {
+            Region::Ntsc => 1_789_773.0,
+            Region::Pal => 1_662_607.0,
+            Region::Dendy => 1_773_448.0,
+        }
+    }
+
+    /// CPU 1サイクルあたりのPPUドット数（分子, 分母）
+    pub fn ppu_dots_per_cpu_cycle(self) -> (u32, u32) {
+        match self {
+            Region::Ntsc | Region::Dendy => (3, 1),
+            Region::Pal => (16, 5),
+        }
+    }
+
+    /// 1フレームのスキャンライン数（プリレンダーラインを含む）
+    pub fn scanlines(self) -> u16 {
+        match self {
+            Region::Ntsc => 262,
+            Region::Pal | Region::Dendy => 312,
+        }
+    }
+
+    /// VBlankフラグが立つスキャンライン
+    pub fn vblank_scanline(self) -> u16 {
+        match self {
+            Region::Ntsc | Region::Pal => 241,
+            // Dendyはポストレンダー期間が長く、VBlankはNTSCと同じ20ライン
+            Region::Dendy => 291,
+        }
+    }
+
+    /// プリレンダーライン
+    pub fn pre_render_scanline(self) -> u16 {
+        self.scanlines() - 1
+    }
+
+    /// 奇数フレームでプリレンダーラインの最後のドットを飛ばすか
+    pub fn skips_odd_frame_dot(self) -> bool {
+        self == Region::Ntsc
+    }
+
+    /// 1フレームあたりのCPUサイクル数（平均）
+    pub fn cpu_cycles_per_frame(self) -> f64 {
+        let (num, den) = self.ppu_dots_per_cpu_cycle();
+        let dots =
+            self.scanlines() as f64 * 341.0 - if self.skips_odd_frame_dot() { 0.5 } else { 0.0 };
+        dots * den as f64 / num as f64
+    }
+
+    /// フレームレート (Hz)
+    pub fn frame_rate(self) -> f64 {
+        self.cpu_clock_rate() / self.cpu_cycles_per_frame()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_timing() {
+        assert!((Region::Ntsc.cpu_cycles_per_frame() - 29780.5).abs() < 1e-9);
+        assert!((Region::Pal.cpu_cycles_per_frame() - 33247.5).abs() < 1e-9);
+        assert!((Region::Dendy.cpu_cycles_per_frame() - 35464.0).abs() < 1e-9);
+        assert!((Region::Ntsc.frame_rate() - 60.0988).abs() < 1e-3);
+        assert!((Region::Pal.frame_rate() - 50.0070).abs() < 1e-3);
+        assert_eq!(Region::from_timing(Timing::MultiRegion), Region::Ntsc);
+    }
+}
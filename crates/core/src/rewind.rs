@@ -0,0 +1,305 @@
+//! # Rewind
+//! フレームごとのセーブステートを差分圧縮して保持し、巻き戻しに使うリングバッファ
+//!
+//! 一定間隔のフレームはキーフレームとしてそのまま保存し、それ以外のフレームは直前のキーフレームとの
+//! XORをランレングス圧縮した差分として保存する。差分は1つ前のフレームではなくキーフレームに対して取るので、
+//! どのフレームもキーフレームと自分の差分だけで復元できる。
+//!
+//! 差分の形式（数値はLEB128の可変長整数）:
+//! ```text
+//! 状態の長さ | (0の連続数, リテラルの長さ, リテラル...)の繰り返し
+//! ```
+//! メモリの上限を超えると、最も古いキーフレームをその差分ごと捨てる。
+
+use std::collections::VecDeque;
+
+/// 巻き戻しバッファの設定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RewindConfig {
+    /// 保存したステートの合計サイズの上限（バイト）
+    pub memory_budget: usize,
+    /// キーフレームを保存する間隔（フレーム数）
+    pub keyframe_interval: usize,
+}
+
+impl Default for RewindConfig {
+    /// 16MiB、1秒ごとのキーフレーム
+    fn default() -> Self {
+        Self {
+            memory_budget: 16 * 1024 * 1024,
+            keyframe_interval: 60,
+        }
+    }
+}
+
+/// キーフレームとそれに対する差分
+#[derive(Debug)]
+struct Group {
+    keyframe: Vec<u8>,
+    deltas: Vec<Vec<u8>>,
+}
+
+impl Group {
+    fn size(&self) -> usize {
+        self.keyframe.len() + self.deltas.iter().map(Vec::len).sum::<usize>()
+    }
+
+    fn len(&self) -> usize {
+        1 + self.deltas.len()
+    }
+
+    /// グループ内の`index`番目（0はキーフレーム）のステート
+    fn state(&self, index: usize) -> Vec<u8> {
+        match index {
+            0 => self.keyframe.clone(),
+            _ => decode_delta(&self.keyframe, &self.deltas[index - 1]),
+        }
+    }
+}
+
+/// フレームごとのステートを保持するリングバッファ
+#[derive(Debug)]
+pub struct RewindBuffer {
+    config: RewindConfig,
+    groups: VecDeque<Group>,
+    /// 保存しているデータの合計サイズ
+    size: usize,
+}
+
+impl RewindBuffer {
+    pub fn new(config: RewindConfig) -> Self {
+        Self {
+            config: RewindConfig {
+                keyframe_interval: config.keyframe_interval.max(1),
+                ..config
+            },
+            groups: VecDeque::new(),
+            size: 0,
+        }
+    }
+
+    pub fn config(&self) -> RewindConfig {
+        self.config
+    }
+
+    /// 保存しているフレーム数
+    pub fn len(&self) -> usize {
+        self.groups.iter().map(Group::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// 保存しているデータの合計サイズ（バイト）
+    pub fn memory_usage(&self) -> usize {
+        self.size
+    }
+
+    pub fn clear(&mut self) {
+        self.groups.clear();
+        self.size = 0;
+    }
+
+    /// 最新のフレームとしてステートを追加する
+    pub fn push(&mut self, state: Vec<u8>) {
+        let interval = self.config.keyframe_interval;
+        match self.groups.back_mut() {
+            Some(group) if group.len() < interval => {
+                let delta = encode_delta(&group.keyframe, &state);
+                self.size += delta.len();
+                group.deltas.push(delta);
+            }
+            _ => {
+                self.size += state.len();
+                self.groups.push_back(Group {
+                    keyframe: state,
+                    deltas: Vec::new(),
+                });
+            }
+        }
+
+        // 最新のグループは上限を超えていても残す
+        while self.size > self.config.memory_budget && self.groups.len() > 1 {
+            if let Some(group) = self.groups.pop_front() {
+                self.size -= group.size();
+            }
+        }
+    }
+
+    /// 最新のフレームを取り除く
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let group = self.groups.back_mut()?;
+        match group.deltas.pop() {
+            Some(delta) => {
+                self.size -= delta.len();
+                Some(decode_delta(&group.keyframe, &delta))
+            }
+            None => {
+                let group = self.groups.pop_back()?;
+                self.size -= group.size();
+                Some(group.keyframe)
+            }
+        }
+    }
+
+    /// 古い方から`index`番目のフレームのステート
+    pub fn get(&self, mut index: usize) -> Option<Vec<u8>> {
+        for group in &self.groups {
+            if index < group.len() {
+                return Some(group.state(index));
+            }
+            index -= group.len();
+        }
+        None
+    }
+
+    /// 最新のフレームのステート
+    pub fn latest(&self) -> Option<Vec<u8>> {
+        let group = self.groups.back()?;
+        Some(group.state(group.deltas.len()))
+    }
+}
+
+/// `state`と`keyframe`のXORをランレングス圧縮する（長さが違う部分は`keyframe`を0とみなす）
+fn encode_delta(keyframe: &[u8], state: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_varint(&mut out, state.len());
+
+    let xor = |i: usize| state[i] ^ keyframe.get(i).copied().unwrap_or(0);
+    let mut i = 0;
+    while i < state.len() {
+        let zeros_start = i;
+        while i < state.len() && xor(i) == 0 {
+            i += 1;
+        }
+        let literal_start = i;
+        // 短い0の連続はリテラルに含めた方が小さい
+        while i < state.len()
+            && (xor(i) != 0 || (i + 2 < state.len() && (xor(i + 1) != 0 || xor(i + 2) != 0)))
+        {
+            i += 1;
+        }
+        write_varint(&mut out, literal_start - zeros_start);
+        write_varint(&mut out, i - literal_start);
+        out.extend((literal_start..i).map(xor));
+    }
+    out
+}
+
+fn decode_delta(keyframe: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut pos = 0;
+    let len = read_varint(delta, &mut pos);
+    let mut state: Vec<u8> = keyframe
+        .iter()
+        .copied()
+        .chain(std::iter::repeat(0))
+        .take(len)
+        .collect();
+
+    let mut i = 0;
+    while pos < delta.len() {
+        i += read_varint(delta, &mut pos);
+        let literal = read_varint(delta, &mut pos);
+        for (byte, &x) in state[i..i + literal]
+            .iter_mut()
+            .zip(&delta[pos..pos + literal])
+        {
+            *byte ^= x;
+        }
+        i += literal;
+        pos += literal;
+    }
+    state
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*pos];
+        *pos += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delta_round_trip() {
+        let keyframe: Vec<u8> = (0..1000).map(|i| (i * 7) as u8).collect();
+        let mut state = keyframe.clone();
+        state[3] ^= 0xFF;
+        state[4] ^= 0x01;
+        state[500] = 0;
+        state[999] = 0x42;
+        let delta = encode_delta(&keyframe, &state);
+        assert!(delta.len() < 20);
+        assert_eq!(decode_delta(&keyframe, &delta), state);
+
+        // 長さが違うステート
+        let longer: Vec<u8> = keyframe.iter().copied().chain([1, 2, 3]).collect();
+        assert_eq!(
+            decode_delta(&keyframe, &encode_delta(&keyframe, &longer)),
+            longer
+        );
+        let shorter = keyframe[..10].to_vec();
+        assert_eq!(
+            decode_delta(&keyframe, &encode_delta(&keyframe, &shorter)),
+            shorter
+        );
+        assert_eq!(
+            decode_delta(&keyframe, &encode_delta(&keyframe, &[])),
+            Vec::<u8>::new()
+        );
+    }
+
+    #[test]
+    fn test_push_pop_and_budget() {
+        let frame = |n: u8| {
+            let mut state = vec![0u8; 256];
+            state[n as usize] = n;
+            state
+        };
+        let mut buffer = RewindBuffer::new(RewindConfig {
+            memory_budget: 1024,
+            keyframe_interval: 4,
+        });
+        for n in 0..10 {
+            buffer.push(frame(n));
+        }
+        assert_eq!(buffer.len(), 10);
+        assert_eq!(buffer.get(5), Some(frame(5)));
+        assert_eq!(buffer.latest(), Some(frame(9)));
+        assert_eq!(buffer.pop(), Some(frame(9)));
+        assert_eq!(buffer.pop(), Some(frame(8)));
+        assert_eq!(buffer.latest(), Some(frame(7)));
+
+        // キーフレーム3つ（768バイト）と差分で上限を超えると古いグループから捨てる
+        for n in 8..20 {
+            buffer.push(frame(n));
+        }
+        assert!(buffer.memory_usage() <= 1024);
+        assert_eq!(buffer.latest(), Some(frame(19)));
+        assert_eq!(buffer.get(0), Some(frame(20 - buffer.len() as u8)));
+
+        buffer.clear();
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.memory_usage(), 0);
+        assert_eq!(buffer.pop(), None);
+    }
+}
@@ -0,0 +1,317 @@
+//! # ROMヘッダー
+//! iNES / NES 2.0 形式のヘッダーを解析する
+//!
+//! 参考: https://www.nesdev.org/wiki/INES, https://www.nesdev.org/wiki/NES_2.0
+
+use crate::cartridge::Mirroring;
+use crate::{NesError, Result};
+
+/// ヘッダーのサイズ
+pub const HEADER_SIZE: usize = 16;
+
+/// トレーナーのサイズ
+pub const TRAINER_SIZE: usize = 512;
+
+/// ヘッダー形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomFormat {
+    /// 旧来のiNES形式
+    INes,
+    /// NES 2.0形式
+    Nes20,
+}
+
+/// CPU/PPUのタイミング（地域）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timing {
+    Ntsc,
+    Pal,
+    /// NTSC/PAL両対応
+    MultiRegion,
+    Dendy,
+}
+
+/// 本体の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleType {
+    /// 通常のNES/ファミコン
+    Nes,
+    VsSystem,
+    Playchoice10,
+    /// 拡張コンソールタイプ（NES 2.0 byte 13）
+    Extended(u8),
+}
+
+/// 解析済みのROMヘッダー
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomHeader {
+    pub format: RomFormat,
+    /// マッパー番号（NES 2.0では12ビット）
+    pub mapper: u16,
+    /// サブマッパー番号（NES 2.0のみ、iNESでは0）
+    pub submapper: u8,
+    /// PRG ROMのサイズ（バイト）
+    pub prg_rom_size: usize,
+    /// CHR ROMのサイズ（バイト、0ならCHR RAMを使用）
+    pub chr_rom_size: usize,
+    /// 揮発性PRG RAMのサイズ（バイト）
+    pub prg_ram_size: usize,
+    /// バッテリーバックアップされたPRG RAMのサイズ（バイト）
+    pub prg_nvram_size: usize,
+    /// 揮発性CHR RAMのサイズ（バイト）
+    pub chr_ram_size: usize,
+    /// バッテリーバックアップされたCHR RAMのサイズ（バイト）
+    pub chr_nvram_size: usize,
+    /// ハードワイヤードのミラーリング
+    pub mirroring: Mirroring,
+    /// 4画面VRAM
+    pub four_screen: bool,
+    /// バッテリーバックアップの有無 (flags6 bit 1)
+    pub has_battery: bool,
+    /// 512バイトのトレーナーの有無 (flags6 bit 2)
+    pub has_trainer: bool,
+    pub timing: Timing,
+    pub console_type: ConsoleType,
+}
+
+impl RomHeader {
+    /// ROMデータの先頭16バイトからヘッダーを解析
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < HEADER_SIZE {
+            return Err(NesError::InvalidRom(format!(
+                "file too small for header ({} bytes)",
+                data.len()
+            )));
+        }
+        if &data[0..4] != b"NES\x1A" {
+            return Err(NesError::InvalidRom("missing iNES signature".to_string()));
+        }
+
+        let flags6 = data[6];
+        let flags7 = data[7];
+        let format = if flags7 & 0x0C == 0x08 {
+            RomFormat::Nes20
+        } else {
+            RomFormat::INes
+        };
+
+        let has_battery = flags6 & 0x02 != 0;
+        let has_trainer = flags6 & 0x04 != 0;
+        let four_screen = flags6 & 0x08 != 0;
+        let mirroring = if four_screen {
+            Mirroring::FourScreen
+        } else if flags6 & 0x01 != 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        };
+
+        match format {
+            RomFormat::Nes20 => {
+                let mapper =
+                    (flags6 >> 4) as u16 | (flags7 & 0xF0) as u16 | ((data[8] & 0x0F) as u16) << 8;
+                let console_type = match flags7 & 0x03 {
+                    0 => ConsoleType::Nes,
+                    1 => ConsoleType::VsSystem,
+                    2 => ConsoleType::Playchoice10,
+                    _ => ConsoleType::Extended(data[13] & 0x0F),
+                };
+                let timing = match data[12] & 0x03 {
+                    0 => Timing::Ntsc,
+                    1 => Timing::Pal,
+                    2 => Timing::MultiRegion,
+                    _ => Timing::Dendy,
+                };
+
+                Ok(RomHeader {
+                    format,
+                    mapper,
+                    submapper: data[8] >> 4,
+                    prg_rom_size: Self::nes20_rom_size(data[4], data[9] & 0x0F, 16384)?,
+                    chr_rom_size: Self::nes20_rom_size(data[5], data[9] >> 4, 8192)?,
+                    prg_ram_size: Self::nes20_ram_size(data[10] & 0x0F),
+                    prg_nvram_size: Self::nes20_ram_size(data[10] >> 4),
+                    chr_ram_size: Self::nes20_ram_size(data[11] & 0x0F),
+                    chr_nvram_size: Self::nes20_ram_size(data[11] >> 4),
+                    mirroring,
+                    four_screen,
+                    has_battery,
+                    has_trainer,
+                    timing,
+                    console_type,
+                })
+            }
+            RomFormat::INes => {
+                // "DiskDude!"などのゴミが書かれた古いヘッダーでは上位ニブルを無視する
+                let dirty = data[12..16].iter().any(|&b| b != 0);
+                let mapper_hi = if dirty { 0 } else { flags7 & 0xF0 };
+                let mapper = ((flags6 >> 4) | mapper_hi) as u16;
+
+                let chr_rom_size = data[5] as usize * 8192;
+                // byte 8: 8KB単位のPRG RAMサイズ（0は8KBとみなす）
+                let prg_ram_units = if dirty { 0 } else { data[8] as usize };
+                let prg_ram_size = prg_ram_units.max(1) * 8192;
+                let timing = if !dirty && data[9] & 0x01 != 0 {
+                    Timing::Pal
+                } else {
+                    Timing::Ntsc
+                };
+                let console_type = match flags7 & 0x03 {
+                    1 => ConsoleType::VsSystem,
+                    2 => ConsoleType::Playchoice10,
+                    _ => ConsoleType::Nes,
+                };
+
+                Ok(RomHeader {
+                    format,
+                    mapper,
+                    submapper: 0,
+                    prg_rom_size: data[4] as usize * 16384,
+                    chr_rom_size,
+                    prg_ram_size: if has_battery { 0 } else { prg_ram_size },
+                    prg_nvram_size: if has_battery { prg_ram_size } else { 0 },
+                    chr_ram_size: if chr_rom_size == 0 { 8192 } else { 0 },
+                    chr_nvram_size: 0,
+                    mirroring,
+                    four_screen,
+                    has_battery,
+                    has_trainer,
+                    timing,
+                    console_type,
+                })
+            }
+        }
+    }
+
+    /// NES 2.0のROMサイズ（LSBとMSBニブル、または指数表記）
+    fn nes20_rom_size(lsb: u8, msb: u8, unit: usize) -> Result<usize> {
+        if msb == 0x0F {
+            // 指数表記: EEEEEEMM -> 2^E * (MM * 2 + 1)
+            let exponent = (lsb >> 2) as u32;
+            let multiplier = (lsb & 0x03) as usize * 2 + 1;
+            1usize
+                .checked_shl(exponent)
+                .and_then(|size| size.checked_mul(multiplier))
+                .ok_or_else(|| {
+                    NesError::InvalidRom(format!("ROM size exponent {} too large", exponent))
+                })
+        } else {
+            Ok(((msb as usize) << 8 | lsb as usize) * unit)
+        }
+    }
+
+    /// NES 2.0のRAMサイズ（64 << shift、0はRAMなし）
+    fn nes20_ram_size(shift: u8) -> usize {
+        if shift == 0 {
+            0
+        } else {
+            64 << shift
+        }
+    }
+
+    /// PRG ROMの開始オフセット
+    pub fn prg_rom_offset(&self) -> usize {
+        HEADER_SIZE + if self.has_trainer { TRAINER_SIZE } else { 0 }
+    }
+
+    /// CHR ROMの開始オフセット
+    pub fn chr_rom_offset(&self) -> usize {
+        self.prg_rom_offset().saturating_add(self.prg_rom_size)
+    }
+
+    /// PRG RAMの合計サイズ（揮発性 + 不揮発性）
+    pub fn total_prg_ram_size(&self) -> usize {
+        self.prg_ram_size + self.prg_nvram_size
+    }
+
+    /// CHR RAMの合計サイズ（揮発性 + 不揮発性）
+    pub fn total_chr_ram_size(&self) -> usize {
+        self.chr_ram_size + self.chr_nvram_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(bytes: [u8; 12]) -> Vec<u8> {
+        let mut data = b"NES\x1A".to_vec();
+        data.extend_from_slice(&bytes);
+        data
+    }
+
+    #[test]
+    fn test_rejects_bad_input() {
+        assert!(matches!(
+            RomHeader::parse(b"NES"),
+            Err(NesError::InvalidRom(_))
+        ));
+        assert!(matches!(
+            RomHeader::parse(&[0u8; 16]),
+            Err(NesError::InvalidRom(_))
+        ));
+    }
+
+    #[test]
+    fn test_ines_header() {
+        let h = RomHeader::parse(&header([2, 1, 0x13, 0x40, 0, 0, 0, 0, 0, 0, 0, 0])).unwrap();
+        assert_eq!(h.format, RomFormat::INes);
+        assert_eq!(h.mapper, 0x41);
+        assert_eq!(h.prg_rom_size, 32768);
+        assert_eq!(h.chr_rom_size, 8192);
+        assert_eq!(h.mirroring, Mirroring::Vertical);
+        assert!(h.has_battery);
+        assert_eq!(h.prg_nvram_size, 8192);
+        assert_eq!(h.timing, Timing::Ntsc);
+    }
+
+    #[test]
+    fn test_ines_dirty_header_ignores_upper_mapper() {
+        let mut data = header([1, 1, 0x10, 0x40, 0, 0, 0, 0, 0, 0, 0, 0]);
+        data[7..16].copy_from_slice(b"DiskDude!");
+        let h = RomHeader::parse(&data).unwrap();
+        assert_eq!(h.mapper, 1);
+    }
+
+    #[test]
+    fn test_nes20_header() {
+        // mapper 0x11A / submapper 3, PRG 0x102 * 16KB, CHR-RAM 8KB, PRG-NVRAM 8KB, PAL
+        let h = RomHeader::parse(&header([
+            0x02, 0x00, 0xA2, 0x18, 0x31, 0x01, 0x70, 0x07, 0x01, 0, 0, 0,
+        ]))
+        .unwrap();
+        assert_eq!(h.format, RomFormat::Nes20);
+        assert_eq!(h.mapper, 0x11A);
+        assert_eq!(h.submapper, 3);
+        assert_eq!(h.prg_rom_size, 0x102 * 16384);
+        assert_eq!(h.chr_rom_size, 0);
+        assert_eq!(h.prg_ram_size, 0);
+        assert_eq!(h.prg_nvram_size, 8192);
+        assert_eq!(h.chr_ram_size, 8192);
+        assert!(h.has_battery);
+        assert_eq!(h.timing, Timing::Pal);
+        assert_eq!(h.console_type, ConsoleType::Nes);
+    }
+
+    #[test]
+    fn test_nes20_exponent_size() {
+        // PRG: E=10, MM=1 -> 1024 * 3
+        let h = RomHeader::parse(&header([
+            0b0010_1001,
+            0,
+            0,
+            0x08,
+            0,
+            0x0F,
+            0,
+            0,
+            0x03,
+            0,
+            0,
+            0,
+        ]))
+        .unwrap();
+        assert_eq!(h.prg_rom_size, 3072);
+        assert_eq!(h.timing, Timing::Dendy);
+    }
+}
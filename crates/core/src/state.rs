@@ -0,0 +1,178 @@
+//! # Save State
+//! マシン全体のスナップショットを保存・復元するためのバイナリ形式
+//!
+//! フォーマット:
+//! ```text
+//! "HNST" (4バイト) | バージョン (u32) | ROMチェックサム (u32) | 各コンポーネントのデータ...
+//! ```
+//! 数値はすべてリトルエンディアン。可変長データは長さ(u32)を前置する。
+
+use crate::{NesError, Result};
+
+/// セーブステートのマジックナンバー
+pub const STATE_MAGIC: &[u8; 4] = b"HNST";
+
+/// セーブステートのフォーマットバージョン
+pub const STATE_VERSION: u32 = 10;
+
+/// セーブステートの書き込み
+#[derive(Debug, Default)]
+pub struct StateWriter {
+    buf: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        Self { buf: Vec::new() }
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.buf.push(value as u8);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// 長さ付きでバイト列を書き込む
+    pub fn write_bytes(&mut self, data: &[u8]) {
+        self.write_u32(data.len() as u32);
+        self.buf.extend_from_slice(data);
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+/// セーブステートの読み込み
+#[derive(Debug)]
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() - self.pos < len {
+            return Err(NesError::InvalidState(format!(
+                "unexpected end of data at offset {}",
+                self.pos
+            )));
+        }
+        let slice = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(slice)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool> {
+        Ok(self.read_u8()? != 0)
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16> {
+        let b = self.take(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64> {
+        let b = self.take(8)?;
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(b);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    /// 長さ付きのバイト列を読み込む
+    pub fn read_bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.read_u32()? as usize;
+        self.take(len)
+    }
+
+    /// 長さ付きのバイト列を固定長バッファへ読み込む（長さが一致しない場合はエラー）
+    pub fn read_bytes_into(&mut self, dest: &mut [u8]) -> Result<()> {
+        let data = self.read_bytes()?;
+        if data.len() != dest.len() {
+            return Err(NesError::InvalidState(format!(
+                "size mismatch: expected {} bytes, found {}",
+                dest.len(),
+                data.len()
+            )));
+        }
+        dest.copy_from_slice(data);
+        Ok(())
+    }
+
+    /// 全データを読み終えたか
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+}
+
+/// CRC-32 (IEEE) チェックサム
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut w = StateWriter::new();
+        w.write_u8(0x12);
+        w.write_bool(true);
+        w.write_u16(0x3456);
+        w.write_u32(0x789A_BCDE);
+        w.write_u64(0x0123_4567_89AB_CDEF);
+        w.write_bytes(&[1, 2, 3]);
+        let data = w.into_inner();
+
+        let mut r = StateReader::new(&data);
+        assert_eq!(r.read_u8().unwrap(), 0x12);
+        assert!(r.read_bool().unwrap());
+        assert_eq!(r.read_u16().unwrap(), 0x3456);
+        assert_eq!(r.read_u32().unwrap(), 0x789A_BCDE);
+        assert_eq!(r.read_u64().unwrap(), 0x0123_4567_89AB_CDEF);
+        assert_eq!(r.read_bytes().unwrap(), &[1, 2, 3]);
+        assert!(r.is_empty());
+        assert!(r.read_u8().is_err());
+    }
+}
@@ -0,0 +1,31 @@
+//! # Test ROM
+//! ユニットテスト用に、手で組んだプログラムからNROMのiNESイメージを作る
+
+use crate::Nes;
+
+/// $0300をインクリメントし続けるプログラム
+pub(crate) const COUNTER: &[u8] = &[
+    0xEE, 0x00, 0x03, // INC $0300
+    0xA5, 0x10, // LDA $10
+    0x4C, 0x00, 0x80, // JMP $8000
+];
+
+/// 32KBのPRGを持つNROMイメージ（`program`は$8000、`data`は$C000に配置し、リセットベクタは$8000）
+pub(crate) fn nrom(program: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 2, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    let mut prg = vec![0u8; 32768];
+    prg[..program.len()].copy_from_slice(program);
+    prg[0x4000..0x4000 + data.len()].copy_from_slice(data);
+    prg[0x7FFC] = 0x00;
+    prg[0x7FFD] = 0x80;
+    rom.extend_from_slice(&prg);
+    rom.extend_from_slice(&[0u8; 8192]);
+    rom
+}
+
+/// `program`を読み込んだNes
+pub(crate) fn nes(program: &[u8]) -> Nes {
+    let mut nes = Nes::new();
+    nes.load_rom(&nrom(program, &[])).unwrap();
+    nes
+}
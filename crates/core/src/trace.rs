@@ -0,0 +1,193 @@
+//! # Trace
+//! nestest.log（Nintendulator形式）と同じ書式の命令トレース
+//!
+//! 1行は命令の実行直前の状態を表す:
+//! ```text
+//! C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+//! ```
+//! 非公式命令はニーモニックの前に`*`を付ける。メモリを参照する命令には
+//! 実効アドレスとその時点の値を付記する（`LDA ($80,X) @ 80 = 0200 = 5A`）。
+//!
+//! 参考: https://www.qmtpro.com/~nes/misc/nestest.log
+
+use crate::memory_editor::{AddressingMode, MemoryEditor};
+use crate::Nes;
+
+/// nestestの自動実行モードの開始アドレス
+pub const NESTEST_START: u16 = 0xC000;
+
+/// 現在のPCの命令をnestest.log形式の1行にする
+pub fn trace_line(nes: &Nes) -> String {
+    let cpu = &nes.cpu;
+    let pc = cpu.pc();
+    let op = MemoryEditor::decode(nes.peek_memory(pc));
+    let bytes = nes.read_memory_range(pc, op.mode.size());
+
+    let hex: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    let (_, text) = MemoryEditor::disassemble(&bytes, pc, 1).remove(0);
+    let disassembly = format!("{}{}", text, annotation(nes, op.mnemonic, op.mode, &bytes));
+
+    format!(
+        "{:04X}  {:<8} {}{:<32}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:>3},{:>3} CYC:{}",
+        pc,
+        hex.join(" "),
+        if op.official { ' ' } else { '*' },
+        disassembly,
+        cpu.a,
+        cpu.x,
+        cpu.y,
+        cpu.p,
+        cpu.sp(),
+        cpu.bus.ppu.scanline(),
+        cpu.bus.ppu.cycle(),
+        cpu.bus.cycles,
+    )
+}
+
+/// オペランドが参照するアドレスと値の注記
+fn annotation(nes: &Nes, mnemonic: &str, mode: AddressingMode, bytes: &[u8]) -> String {
+    let cpu = &nes.cpu;
+    let peek = |address: u16| nes.peek_memory(address);
+    // ゼロページ内で折り返す16ビットポインタ
+    let peek_zp_word =
+        |zp: u8| u16::from_le_bytes([peek(zp as u16), peek(zp.wrapping_add(1) as u16)]);
+
+    match mode {
+        AddressingMode::ZeroPage => format!(" = {:02X}", peek(bytes[1] as u16)),
+        AddressingMode::ZeroPageX | AddressingMode::ZeroPageY => {
+            let index = if mode == AddressingMode::ZeroPageX {
+                cpu.x
+            } else {
+                cpu.y
+            };
+            let address = bytes[1].wrapping_add(index);
+            format!(" @ {:02X} = {:02X}", address, peek(address as u16))
+        }
+        AddressingMode::Absolute => {
+            if mnemonic == "JMP" || mnemonic == "JSR" {
+                String::new()
+            } else {
+                let address = u16::from_le_bytes([bytes[1], bytes[2]]);
+                format!(" = {:02X}", peek(address))
+            }
+        }
+        AddressingMode::AbsoluteX | AddressingMode::AbsoluteY => {
+            let index = if mode == AddressingMode::AbsoluteX {
+                cpu.x
+            } else {
+                cpu.y
+            };
+            let address = u16::from_le_bytes([bytes[1], bytes[2]]).wrapping_add(index as u16);
+            format!(" @ {:04X} = {:02X}", address, peek(address))
+        }
+        AddressingMode::Indirect => {
+            // JMP ($xxFF)は上位バイトを同じページの$xx00から読む（6502のバグ）
+            let low = u16::from_le_bytes([bytes[1], bytes[2]]);
+            let high = (low & 0xFF00) | (low.wrapping_add(1) & 0x00FF);
+            format!(" = {:04X}", u16::from_le_bytes([peek(low), peek(high)]))
+        }
+        AddressingMode::IndirectX => {
+            let pointer = bytes[1].wrapping_add(cpu.x);
+            let address = peek_zp_word(pointer);
+            format!(
+                " @ {:02X} = {:04X} = {:02X}",
+                pointer,
+                address,
+                peek(address)
+            )
+        }
+        AddressingMode::IndirectY => {
+            let base = peek_zp_word(bytes[1]);
+            let address = base.wrapping_add(cpu.y as u16);
+            format!(" = {:04X} @ {:04X} = {:02X}", base, address, peek(address))
+        }
+        AddressingMode::Implied
+        | AddressingMode::Accumulator
+        | AddressingMode::Immediate
+        | AddressingMode::Relative => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// nestest.logの冒頭と同じ命令を同じアドレスに置いたNROMイメージ
+    fn nestest_like_rom() -> Vec<u8> {
+        let mut prg = vec![0u8; 16384];
+        let mut put = |address: u16, code: &[u8]| {
+            let offset = (address - 0xC000) as usize;
+            prg[offset..offset + code.len()].copy_from_slice(code);
+        };
+        put(0xC000, &[0x4C, 0xF5, 0xC5]); // JMP $C5F5
+        put(
+            0xC5F5,
+            &[
+                0xA2, 0x00, // LDX #$00
+                0x86, 0x00, // STX $00
+                0x86, 0x10, // STX $10
+                0x86, 0x11, // STX $11
+                0x20, 0x2D, 0xC7, // JSR $C72D
+            ],
+        );
+        put(
+            0xC72D,
+            &[
+                0xEA, // NOP
+                0x38, // SEC
+                0xB0, 0x04, // BCS $C735
+                0xA2, 0x00, 0xA2, 0x00, // (読み飛ばされる)
+                0xEA, // NOP
+                0x18, // CLC
+                0xB0, 0x03, // BCS $C73C
+                0x4C, 0x3D, 0xC7, // JMP $C73D
+                0x60, // (読み飛ばされる)
+                0xEA, // NOP
+            ],
+        );
+        put(0xFFFC, &[0x00, 0xC0]);
+
+        let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        rom.extend_from_slice(&prg);
+        rom.extend_from_slice(&[0u8; 8192]);
+        rom
+    }
+
+    #[test]
+    fn test_matches_golden_log() {
+        let golden = include_str!("../tests/fixtures/nestest_head.log");
+        let mut nes = Nes::new();
+        nes.load_rom(&nestest_like_rom()).unwrap();
+        nes.start_nestest_automation();
+
+        for (i, expected) in golden.lines().enumerate() {
+            assert_eq!(nes.trace_line(), expected, "line {}", i + 1);
+            nes.step().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_annotations() {
+        let mut nes = Nes::new();
+        nes.load_rom(&nestest_like_rom()).unwrap();
+        nes.poke_memory(0x0080, 0x00);
+        nes.poke_memory(0x0081, 0x02);
+        nes.poke_memory(0x0200, 0x5A);
+        nes.cpu.x = 0x00;
+
+        let annotate = |bytes: &[u8]| {
+            let op = MemoryEditor::decode(bytes[0]);
+            format!(
+                "{}{}",
+                MemoryEditor::disassemble(bytes, 0x8000, 1)[0].1,
+                annotation(&nes, op.mnemonic, op.mode, bytes)
+            )
+        };
+        assert_eq!(annotate(&[0xA1, 0x80]), "LDA ($80,X) @ 80 = 0200 = 5A");
+        assert_eq!(annotate(&[0xB1, 0x80]), "LDA ($80),Y = 0200 @ 0200 = 5A");
+        assert_eq!(annotate(&[0xBD, 0x00, 0x02]), "LDA $0200,X @ 0200 = 5A");
+        assert_eq!(annotate(&[0x6C, 0x80, 0x00]), "JMP ($0080) = 0200");
+        assert_eq!(annotate(&[0x20, 0x00, 0x02]), "JSR $0200");
+        assert_eq!(annotate(&[0x0A]), "ASL A");
+    }
+}
@@ -0,0 +1,201 @@
+//! # Frame Hash Tests
+//! 手元のROMを一定フレーム実行し、画面と音声のハッシュを期待値ファイルと比較する回帰テスト
+//!
+//! ROMは著作権の都合でリポジトリに含めないので、`NES_ROM_CORPUS`（デフォルトは`tests/roms`）の
+//! ディレクトリに置いた`.nes`ファイルを対象にする。ROMと同じ名前の`.fm2`があれば、その入力を再生する。
+//! 期待値は`tests/fixtures/frame_hashes.txt`に保存し、`NES_BLESS=1`で実行すると手元のROMの分を更新する。
+//!
+//! ```text
+//! NES_ROM_CORPUS=~/roms NES_BLESS=1 cargo test -p nes_core --test frame_hash
+//! ```
+//!
+//! 実行するフレーム数は`NES_FRAME_HASH_FRAMES`（デフォルト600、ムービーの方が長ければムービーの長さ）。
+//! ハッシュは全フレームの`frame_buffer`と全オーディオサンプルを順に流し込んだFNV-1a (64bit)。
+
+use nes_core::movie::Movie;
+use nes_core::Nes;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+const DEFAULT_FRAMES: usize = 600;
+
+/// FNV-1a (64bit)
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Fnv(0xCBF2_9CE4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0100_0000_01B3);
+        }
+    }
+}
+
+fn manifest_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+fn expectations_path() -> PathBuf {
+    manifest_dir().join("tests/fixtures/frame_hashes.txt")
+}
+
+/// コーパスの`.nes`ファイル（名前順）
+fn corpus_roms() -> Vec<PathBuf> {
+    let dir = std::env::var_os("NES_ROM_CORPUS")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| manifest_dir().join("tests/roms"));
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<_> = entries
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("nes"))
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// 1つのROMを実行した結果（`フレーム数\t映像\t音声`、エラーの場合は`error\tメッセージ`）
+fn run_rom(path: &Path, frames: usize) -> String {
+    let mut nes = Nes::new();
+    if let Err(e) = nes.load_rom(&std::fs::read(path).unwrap()) {
+        return format!("error\t{}", e);
+    }
+    let mut frames = frames;
+    let movie_path = path.with_extension("fm2");
+    if movie_path.exists() {
+        let movie = Movie::from_fm2(&std::fs::read_to_string(&movie_path).unwrap()).unwrap();
+        frames = frames.max(movie.frames.len());
+        nes.play_movie(movie).unwrap();
+    }
+
+    let mut video = Fnv::new();
+    let mut audio = Fnv::new();
+    for frame in 0..frames {
+        if let Err(e) = nes.step_frame() {
+            return format!("error\tframe {}: {}", frame, e);
+        }
+        video.write(nes.frame_buffer());
+        for sample in nes.get_audio_samples() {
+            audio.write(&sample.to_bits().to_le_bytes());
+        }
+    }
+    format!("{}\t{:016x}\t{:016x}", frames, video.0, audio.0)
+}
+
+/// 期待値ファイルを読む（ROMのファイル名 → 結果）
+fn read_expectations(path: &Path) -> BTreeMap<String, String> {
+    let text = std::fs::read_to_string(path).unwrap_or_default();
+    text.lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(name, outcome)| (name.to_string(), outcome.to_string()))
+        .collect()
+}
+
+fn write_expectations(path: &Path, expectations: &BTreeMap<String, String>) {
+    let mut text = String::from(
+        "# frame_hash.rsの期待値（NES_BLESS=1で更新）\n# ROM\tフレーム数\t映像ハッシュ\t音声ハッシュ\n",
+    );
+    for (name, outcome) in expectations {
+        text.push_str(&format!("{}\t{}\n", name, outcome));
+    }
+    std::fs::write(path, text).unwrap();
+}
+
+#[test]
+fn test_frame_hashes() {
+    let roms = corpus_roms();
+    if roms.is_empty() {
+        eprintln!("frame_hash: no ROMs in the corpus, skipping");
+        return;
+    }
+    let frames = std::env::var("NES_FRAME_HASH_FRAMES")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(DEFAULT_FRAMES);
+
+    // ROMごとに独立しているので並列に実行する
+    let next = AtomicUsize::new(0);
+    let outcomes = Mutex::new(BTreeMap::new());
+    let workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+    std::thread::scope(|scope| {
+        for _ in 0..workers.min(roms.len()) {
+            scope.spawn(|| {
+                while let Some(path) = roms.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                    let outcome = run_rom(path, frames);
+                    outcomes.lock().unwrap().insert(name, outcome);
+                }
+            });
+        }
+    });
+    let outcomes = outcomes.into_inner().unwrap();
+
+    let path = expectations_path();
+    let mut expectations = read_expectations(&path);
+    if std::env::var_os("NES_BLESS").is_some() {
+        // 手元にないROMの期待値は残す
+        expectations.extend(outcomes);
+        write_expectations(&path, &expectations);
+        return;
+    }
+
+    let mut failures = Vec::new();
+    for (name, outcome) in &outcomes {
+        match expectations.get(name) {
+            None => failures.push(format!("{}: no expectation (got {})", name, outcome)),
+            Some(expected) if expected != outcome => {
+                failures.push(format!("{}: got {}, expected {}", name, outcome, expected))
+            }
+            Some(_) => {}
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{} of {} ROMs changed output (run with NES_BLESS=1 to accept):\n{}",
+        failures.len(),
+        outcomes.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn test_expectations_round_trip() {
+    let dir = std::env::temp_dir().join(format!("nes_frame_hash_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("frame_hashes.txt");
+
+    let mut expectations = BTreeMap::new();
+    expectations.insert(
+        "Super Game (J).nes".to_string(),
+        "600\t0123456789abcdef\tfedcba9876543210".to_string(),
+    );
+    expectations.insert(
+        "broken.nes".to_string(),
+        "error\tUnsupported mapper: 999".to_string(),
+    );
+    write_expectations(&path, &expectations);
+    assert_eq!(read_expectations(&path), expectations);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // チェックインした期待値はすべて3列の結果かエラー
+    for (name, outcome) in read_expectations(&expectations_path()) {
+        let fields: Vec<_> = outcome.split('\t').collect();
+        assert!(
+            fields[0] == "error" || fields.len() == 3,
+            "{}: malformed entry {:?}",
+            name,
+            outcome
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! # Single Step Tests
+//! Tom HarteのSingleStepTests (https://github.com/SingleStepTests/65x02) と同じJSON形式で
+//! 1命令ずつ実行結果を検証する
+//!
+//! 各テストの初期状態をセットして1命令実行し、最終状態（レジスタとメモリ）と、サイクルごとの
+//! バスアクセス（アドレス, 値, 読み取り/書き込み）の列を比較する。CPUのバスには`FlatMemory`を
+//! 取り付けて、64KBすべてを平坦なRAMとして扱う。
+//!
+//! `tests/fixtures/single_step`には回帰テスト用に手で書いた少数のケースだけを置いている。
+//! 本物のテスト（オペコードごとに10000ケース）は、`NES_SINGLE_STEP_TESTS`に`nes6502/v1`の
+//! ディレクトリを指定すると実行する（なければスキップ）。
+//!
+//! ```text
+//! NES_SINGLE_STEP_TESTS=~/65x02/nes6502/v1 cargo test -p nes_core --test single_step
+//! ```
+//!
+//! KIL/JAM命令はCPUが止まった後のバスの動きをエミュレートしないので対象外。
+
+use nes_core::bus::FlatMemory;
+use nes_core::cpu::{Cpu, InvalidOpcodePolicy};
+use nes_core::debugger::Access;
+use nes_core::NesError;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Deserialize)]
+struct State {
+    pc: u16,
+    s: u8,
+    a: u8,
+    x: u8,
+    y: u8,
+    p: u8,
+    ram: Vec<(u16, u8)>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Test {
+    name: String,
+    initial: State,
+    #[serde(rename = "final")]
+    expected: State,
+    /// 各サイクルのバスアクセス (アドレス, 値, "read"/"write")
+    cycles: Vec<(u16, u8, String)>,
+}
+
+/// KIL/JAM命令
+const JAM_OPCODES: [u8; 12] = [
+    0x02, 0x12, 0x22, 0x32, 0x42, 0x52, 0x62, 0x72, 0x92, 0xB2, 0xD2, 0xF2,
+];
+
+/// 平坦なメモリを取り付けたCPU
+fn flat_cpu() -> Cpu {
+    let mut cpu = Cpu::new();
+    cpu.bus.flat_memory = Some(FlatMemory::new());
+    // 1つのCPUで多数のテストを続けて実行するので、フレームカウンタのIRQを止めておく
+    cpu.bus.apu.write_register(0x4017, 0x40);
+    cpu
+}
+
+fn run_test(cpu: &mut Cpu, test: &Test) -> Result<(), String> {
+    let init = &test.initial;
+    cpu.set_pc(init.pc);
+    cpu.set_sp(init.s);
+    cpu.a = init.a;
+    cpu.x = init.x;
+    cpu.y = init.y;
+    cpu.p = init.p;
+    let memory = cpu.bus.flat_memory.as_mut().unwrap();
+    for &(address, value) in &init.ram {
+        memory.data[address as usize] = value;
+    }
+    memory.accesses.clear();
+
+    let start = cpu.bus.cycles;
+    cpu.step().map_err(|e| e.to_string())?;
+    let cycles = (cpu.bus.cycles - start) as usize;
+
+    let expected = &test.expected;
+    let actual = (cpu.pc(), cpu.sp(), cpu.a, cpu.x, cpu.y, cpu.p);
+    let wanted = (
+        expected.pc,
+        expected.s,
+        expected.a,
+        expected.x,
+        expected.y,
+        expected.p,
+    );
+    if actual != wanted {
+        return Err(format!(
+            "registers (pc, s, a, x, y, p): got {:02x?}, expected {:02x?}",
+            actual, wanted
+        ));
+    }
+    let memory = cpu.bus.flat_memory.as_ref().unwrap();
+    for &(address, value) in &expected.ram {
+        let actual = memory.data[address as usize];
+        if actual != value {
+            return Err(format!(
+                "ram[{:#06x}]: got {:#04x}, expected {:#04x}",
+                address, actual, value
+            ));
+        }
+    }
+    let accesses: Vec<_> = memory
+        .accesses
+        .iter()
+        .map(|&(address, value, access)| {
+            let kind = if access == Access::Write {
+                "write"
+            } else {
+                "read"
+            };
+            (address, value, kind)
+        })
+        .collect();
+    let wanted: Vec<_> = test
+        .cycles
+        .iter()
+        .map(|(a, v, kind)| (*a, *v, kind.as_str()))
+        .collect();
+    if accesses != wanted {
+        return Err(format!(
+            "bus accesses: got {:02x?}, expected {:02x?}",
+            accesses, wanted
+        ));
+    }
+    if cycles != wanted.len() {
+        return Err(format!("cycles: got {}, expected {}", cycles, wanted.len()));
+    }
+    Ok(())
+}
+
+/// ディレクトリ内の`<オペコード>.json`（KIL/JAMを除く、名前順）
+fn test_files(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<_> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter(|path| {
+            let stem = path.file_stem().unwrap().to_string_lossy();
+            u8::from_str_radix(&stem, 16).is_ok_and(|opcode| !JAM_OPCODES.contains(&opcode))
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// すべてのファイルのテストを実行し、失敗を返す（表示が長くなりすぎないようにファイルごとに最初の3件まで）
+fn run_files(paths: &[PathBuf]) -> Vec<String> {
+    let mut cpu = flat_cpu();
+    let mut failures = Vec::new();
+    for path in paths {
+        let tests: Vec<Test> =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        let name = path.file_name().unwrap().to_string_lossy();
+        let errors: Vec<_> = tests
+            .iter()
+            .filter_map(|test| {
+                run_test(&mut cpu, test)
+                    .err()
+                    .map(|e| format!("{} [{}]: {}", name, test.name, e))
+            })
+            .collect();
+        if errors.len() > 3 {
+            failures.push(format!(
+                "{}: {} of {} tests failed",
+                name,
+                errors.len(),
+                tests.len()
+            ));
+        }
+        failures.extend(errors.into_iter().take(3));
+    }
+    failures
+}
+
+#[test]
+fn test_single_step_fixtures() {
+    let paths =
+        test_files(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/single_step"));
+    assert!(!paths.is_empty());
+    let failures = run_files(&paths);
+    assert!(
+        failures.is_empty(),
+        "{} failures:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn test_single_step_suite() {
+    let Some(dir) = std::env::var_os("NES_SINGLE_STEP_TESTS") else {
+        eprintln!("single_step: NES_SINGLE_STEP_TESTS is not set, skipping");
+        return;
+    };
+    let paths = test_files(Path::new(&dir));
+    assert!(!paths.is_empty(), "no <opcode>.json files in {:?}", dir);
+    let failures = run_files(&paths);
+    assert!(
+        failures.is_empty(),
+        "{} failures:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn test_jam_policy() {
+    let mut cpu = Cpu::new();
+    cpu.bus.ram[0x0200] = 0x02; // KIL
+    cpu.set_pc(0x0200);
+
+    // 停止: PCはKIL命令に留まり、何度実行してもエラーになる
+    assert!(matches!(
+        cpu.step(),
+        Err(NesError::InvalidInstruction(0x02))
+    ));
+    assert_eq!(cpu.pc(), 0x0200);
+    assert!(cpu.step().is_err());
+
+    // 続行: 1バイトのNOPとして進む
+    cpu.set_invalid_opcode_policy(InvalidOpcodePolicy::Continue);
+    let start = cpu.bus.cycles;
+    cpu.step().unwrap();
+    assert_eq!(cpu.pc(), 0x0201);
+    assert_eq!(cpu.bus.cycles - start, 2);
+}
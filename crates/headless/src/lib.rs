@@ -0,0 +1,4 @@
+//! # HackNES.rs Headless
+//! ウィンドウなしでテストROMを実行して結果を判定する（`nes_headless`バイナリと統合テストで共有）
+
+pub mod runner;
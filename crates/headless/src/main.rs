@@ -0,0 +1,115 @@
+//! # HackNES.rs Headless Runner
+//! ウィンドウなしでROMを実行するバッチランナー（CIでのテストROM実行用）
+//!
+//! 終了コード: 0 = 成功, 1 = 失敗, 2 = タイムアウト, 3 = ROMの読み込み/実行エラー,
+//! 4 = ステータスの報告なし（`--frames-only`を付けると0）
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use nes_core::Nes;
+use nes_headless::runner::{self, Outcome};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// 画面のサイズ
+const SCREEN_WIDTH: u32 = 256;
+const SCREEN_HEIGHT: u32 = 240;
+
+/// NESエミュレータ ヘッドレスランナー
+#[derive(Parser, Debug)]
+#[command(name = "nes_headless")]
+#[command(about = "Run a ROM without a window and report test results", long_about = None)]
+struct Args {
+    /// ROMファイルのパス
+    #[arg(value_name = "ROM")]
+    rom_path: PathBuf,
+
+    /// 実行する最大フレーム数（$6000のステータスが完了を示せばそこで終了）
+    #[arg(short, long, default_value = "3600")]
+    frames: u32,
+
+    /// 最後のフレームをPNGで保存するパス
+    #[arg(long, value_name = "PATH")]
+    png: Option<PathBuf>,
+
+    /// 命令トレース（nestest.log形式）を書き出すパス
+    #[arg(long, value_name = "PATH")]
+    trace: Option<PathBuf>,
+
+    /// nestestの自動実行モード（$C000から開始）で実行する
+    #[arg(long)]
+    nestest: bool,
+
+    /// $6000のステータスを報告しないROMでも、指定フレーム数を実行できれば成功とする
+    /// （スクリーンショットやトレースを取るためだけに実行する場合）
+    #[arg(long)]
+    frames_only: bool,
+}
+
+fn main() -> ExitCode {
+    env_logger::init();
+    let args = Args::parse();
+
+    match run(&args) {
+        Ok(Outcome::Completed) if args.frames_only => ExitCode::SUCCESS,
+        Ok(outcome) => ExitCode::from(outcome.exit_code()),
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            ExitCode::from(3)
+        }
+    }
+}
+
+fn run(args: &Args) -> Result<Outcome> {
+    let rom_data = std::fs::read(&args.rom_path)
+        .with_context(|| format!("failed to read {}", args.rom_path.display()))?;
+    let mut nes = Nes::new();
+    nes.load_rom(&rom_data)?;
+    if args.nestest {
+        nes.start_nestest_automation();
+    }
+    if let Some(path) = &args.trace {
+        let file =
+            File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+        nes.set_tracer(Some(Box::new(BufWriter::new(file))));
+    }
+
+    let outcome = runner::run(&mut nes, args.frames)?;
+    // BufWriterをフラッシュする
+    nes.set_tracer(None);
+
+    if !outcome.text().is_empty() {
+        println!("{}", outcome.text().trim_end());
+    }
+    match &outcome {
+        Outcome::Finished { code: 0, .. } => println!("PASSED"),
+        Outcome::Finished { code, .. } => println!("FAILED (code {})", code),
+        Outcome::TimedOut { .. } => println!("TIMED OUT after {} frames", args.frames),
+        Outcome::Completed if args.frames_only => println!("Completed {} frames", args.frames),
+        Outcome::Completed => println!(
+            "NO STATUS after {} frames (pass --frames-only to accept)",
+            args.frames
+        ),
+    }
+
+    if let Some(path) = &args.png {
+        save_png(path, nes.ppu_state().frame_buffer())
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        log::info!("Saved frame to {:?}", path);
+    }
+
+    Ok(outcome)
+}
+
+/// RGBAのフレームバッファをPNGで保存
+fn save_png(path: &Path, frame_buffer: &[u8]) -> Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, SCREEN_WIDTH, SCREEN_HEIGHT);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(frame_buffer)?;
+    Ok(())
+}
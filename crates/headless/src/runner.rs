@@ -0,0 +1,225 @@
+//! # Test ROM Runner
+//! blargg形式のテストROMをウィンドウなしで実行し、結果を判定する
+//!
+//! テストROMは$6000-$7FFFのPRG RAMに次の形式で結果を書き込む:
+//! ```text
+//! $6000     : ステータス ($80 = 実行中, $81 = リセット要求, $00-$7F = 結果コード、0なら成功)
+//! $6001-6003: シグネチャ $DE $B0 $61（書き込まれるまでステータスは無効）
+//! $6004-    : 結果のテキスト（NUL終端）
+//! ```
+//!
+//! 参考: https://github.com/christopherpow/nes-test-roms (blargg's test ROMs)
+
+use nes_core::{Nes, Result};
+
+/// ステータスバイトのアドレス
+const STATUS_ADDR: u16 = 0x6000;
+
+/// シグネチャのアドレスと内容
+const SIGNATURE_ADDR: u16 = 0x6001;
+const SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+
+/// 結果テキストのアドレスと最大長
+const TEXT_ADDR: u16 = 0x6004;
+const TEXT_MAX_LEN: usize = 0x1FFC;
+
+const STATUS_RUNNING: u8 = 0x80;
+const STATUS_RESET_REQUESTED: u8 = 0x81;
+
+/// リセット要求からリセットまで待つフレーム数（テストROMは100ms以上を要求する）
+const RESET_DELAY_FRAMES: u32 = 8;
+
+/// $6000のステータス
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestStatus {
+    /// シグネチャがまだ書き込まれていない
+    NotStarted,
+    Running,
+    ResetRequested,
+    /// 結果コード（0なら成功）
+    Finished(u8),
+}
+
+/// 実行結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// テストROMが結果コードを書き込んだ
+    Finished { code: u8, text: String },
+    /// テストの途中でフレーム数の上限に達した
+    TimedOut { text: String },
+    /// ステータスを報告しないROMを指定フレーム数だけ実行した
+    Completed,
+}
+
+impl Outcome {
+    /// プロセスの終了コード（0: 成功, 1: 失敗, 2: タイムアウト, 4: ステータスの報告なし）
+    ///
+    /// シグネチャを一度も書かなかったROMはテストとして成功とみなさない。
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Outcome::Finished { code: 0, .. } => 0,
+            Outcome::Finished { .. } => 1,
+            Outcome::TimedOut { .. } => 2,
+            Outcome::Completed => 4,
+        }
+    }
+
+    /// 結果のテキスト
+    pub fn text(&self) -> &str {
+        match self {
+            Outcome::Finished { text, .. } | Outcome::TimedOut { text } => text,
+            Outcome::Completed => "",
+        }
+    }
+}
+
+/// $6000のステータスを読み取る
+pub fn read_status(nes: &Nes) -> TestStatus {
+    let signature = nes.read_memory_range(SIGNATURE_ADDR, SIGNATURE.len());
+    if signature != SIGNATURE {
+        return TestStatus::NotStarted;
+    }
+    match nes.peek_memory(STATUS_ADDR) {
+        STATUS_RUNNING => TestStatus::Running,
+        STATUS_RESET_REQUESTED => TestStatus::ResetRequested,
+        code => TestStatus::Finished(code),
+    }
+}
+
+/// $6004からのNUL終端テキストを読み取る
+pub fn read_text(nes: &Nes) -> String {
+    let bytes: Vec<u8> = (0..TEXT_MAX_LEN)
+        .map(|i| nes.peek_memory(TEXT_ADDR + i as u16))
+        .take_while(|&b| b != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// 完了するか`max_frames`フレームに達するまで実行
+pub fn run(nes: &mut Nes, max_frames: u32) -> Result<Outcome> {
+    let mut started = false;
+    let mut reset_countdown: Option<u32> = None;
+
+    for _ in 0..max_frames {
+        nes.step_frame()?;
+
+        match read_status(nes) {
+            TestStatus::NotStarted => {}
+            TestStatus::Running => started = true,
+            TestStatus::ResetRequested => {
+                started = true;
+                match reset_countdown {
+                    Some(0) => {
+                        log::info!("Resetting at the test ROM's request");
+                        nes.reset();
+                        reset_countdown = None;
+                    }
+                    Some(ref mut frames) => *frames -= 1,
+                    None => reset_countdown = Some(RESET_DELAY_FRAMES),
+                }
+            }
+            TestStatus::Finished(code) => {
+                return Ok(Outcome::Finished {
+                    code,
+                    text: read_text(nes),
+                });
+            }
+        }
+    }
+
+    if started {
+        Ok(Outcome::TimedOut {
+            text: read_text(nes),
+        })
+    } else {
+        Ok(Outcome::Completed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// $6000に結果を書き込むNROMのテストROM（`wait`に比例した時間待ってから`code`を書き込む）
+    fn status_rom(code: u8, text: &[u8], wait: u8) -> Vec<u8> {
+        let mut program = vec![
+            0xA9, 0x80, 0x8D, 0x00, 0x60, // LDA #$80; STA $6000
+            0xA9, 0xDE, 0x8D, 0x01, 0x60, // シグネチャ
+            0xA9, 0xB0, 0x8D, 0x02, 0x60, 0xA9, 0x61, 0x8D, 0x03, 0x60,
+        ];
+        for (i, &b) in text.iter().chain(&[0]).enumerate() {
+            let addr = 0x6004 + i as u16;
+            program.extend_from_slice(&[0xA9, b, 0x8D, addr as u8, (addr >> 8) as u8]);
+        }
+        // 約wait * 256 * 5サイクル待つ
+        program.extend_from_slice(&[
+            0xA0, wait, // LDY #wait
+            0xA2, 0x00, // LDX #0
+            0xCA, 0xD0, 0xFD, // DEX; BNE
+            0x88, 0xD0, 0xF8, // DEY; BNE
+        ]);
+        program.extend_from_slice(&[0xA9, code, 0x8D, 0x00, 0x60]); // LDA #code; STA $6000
+        let jmp = 0x8000 + program.len() as u16;
+        program.extend_from_slice(&[0x4C, jmp as u8, (jmp >> 8) as u8]);
+
+        let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 1, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0];
+        let mut prg = vec![0u8; 16384];
+        prg[..program.len()].copy_from_slice(&program);
+        prg[0x3FFC] = 0x00;
+        prg[0x3FFD] = 0x80;
+        rom.extend_from_slice(&prg);
+        rom.extend_from_slice(&[0u8; 8192]);
+        rom
+    }
+
+    fn load(rom: &[u8]) -> Nes {
+        let mut nes = Nes::new();
+        nes.load_rom(rom).unwrap();
+        nes
+    }
+
+    #[test]
+    fn test_passing_rom() {
+        let mut nes = load(&status_rom(0, b"Passed", 1));
+        let outcome = run(&mut nes, 60).unwrap();
+        assert_eq!(
+            outcome,
+            Outcome::Finished {
+                code: 0,
+                text: "Passed".to_string()
+            }
+        );
+        assert_eq!(outcome.exit_code(), 0);
+    }
+
+    #[test]
+    fn test_failing_and_timed_out_rom() {
+        let mut nes = load(&status_rom(3, b"Failed #3", 1));
+        let outcome = run(&mut nes, 60).unwrap();
+        assert_eq!(outcome.exit_code(), 1);
+        assert_eq!(outcome.text(), "Failed #3");
+
+        // 結果を書き込む前に上限に達する
+        let mut nes = load(&status_rom(0, b"wait", 255));
+        let outcome = run(&mut nes, 2).unwrap();
+        assert_eq!(
+            outcome,
+            Outcome::TimedOut {
+                text: "wait".to_string()
+            }
+        );
+        assert_eq!(outcome.exit_code(), 2);
+    }
+
+    #[test]
+    fn test_rom_without_status_is_not_a_pass() {
+        // シグネチャを書かずに無限ループするだけのROM
+        let mut rom = status_rom(0, b"", 1);
+        rom[16..19].copy_from_slice(&[0x4C, 0x00, 0x80]); // JMP $8000
+        let mut nes = load(&rom);
+        let outcome = run(&mut nes, 10).unwrap();
+        assert_eq!(outcome, Outcome::Completed);
+        assert_eq!(outcome.exit_code(), 4);
+        assert_eq!(outcome.text(), "");
+    }
+}
@@ -0,0 +1,121 @@
+//! blarggのテストROMを`runner::run`で実行し、すべて結果コード0で終わることを確認する
+//!
+//! テストROMはリポジトリに含めないため、`NES_TEST_ROMS`に
+//! https://github.com/christopherpow/nes-test-roms のチェックアウトを指定したときだけ実行する。
+//! ```text
+//! NES_TEST_ROMS=path/to/nes-test-roms cargo test -p nes_headless --test blargg -- --nocapture
+//! ```
+
+use nes_core::Nes;
+use nes_headless::runner::{self, Outcome};
+use std::path::{Path, PathBuf};
+
+/// 1つのROMに許すフレーム数（最も遅い`cpu_interrupts.nes`でも数秒で終わる）
+const MAX_FRAMES: u32 = 60 * 60;
+
+/// テストROMのディレクトリ（未指定なら`None`）
+fn test_rom_dir(name: &str) -> Option<PathBuf> {
+    let Some(root) = std::env::var_os("NES_TEST_ROMS") else {
+        eprintln!("NES_TEST_ROMS is not set, skipping {name}");
+        return None;
+    };
+    Some(PathBuf::from(root))
+}
+
+/// ROMを実行して結果を表示し、成功しなかったものの説明を返す
+fn run_rom(path: &Path, patch: impl FnOnce(&mut [u8])) -> Option<String> {
+    let failure = match std::fs::read(path) {
+        Ok(mut rom) => {
+            patch(&mut rom);
+            let mut nes = Nes::new();
+            match nes
+                .load_rom(&rom)
+                .and_then(|_| runner::run(&mut nes, MAX_FRAMES))
+            {
+                Ok(Outcome::Finished { code: 0, .. }) => None,
+                Ok(Outcome::Finished { code, text }) => {
+                    Some(format!("failed with code {code}: {}", text.trim()))
+                }
+                Ok(Outcome::TimedOut { text }) => Some(format!("timed out: {}", text.trim())),
+                Ok(Outcome::Completed) => Some("never reported a status".to_string()),
+                Err(e) => Some(format!("emulation error: {e}")),
+            }
+        }
+        Err(e) => Some(e.to_string()),
+    };
+    let failure = failure.map(|failure| format!("{}: {failure}", path.display()));
+    eprintln!(
+        "{}",
+        failure
+            .clone()
+            .unwrap_or_else(|| format!("{}: passed", path.display()))
+    );
+    failure
+}
+
+/// iNESヘッダーをNES 2.0のMMC3サブマッパー4（NEC/MMC6のIRQ）に書き換える
+///
+/// テストROMはiNESヘッダーでリビジョンを区別できないので、NEC版を要求するものだけ書き換える。
+/// $6000にステータスを書けるように8KBのPRG RAMも指定する。
+fn use_nec_revision(rom: &mut [u8]) {
+    rom[7] = (rom[7] & 0xF3) | 0x08;
+    rom[8] = (rom[8] & 0x0F) | 0x40;
+    rom[9] = 0;
+    rom[10] = 0x07;
+    // CHR ROMがなければ8KBのCHR RAM
+    rom[11] = if rom[5] == 0 { 0x07 } else { 0 };
+    rom[12..16].fill(0);
+}
+
+fn assert_all_passed(failures: Vec<String>) {
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn test_cpu_interrupts_v2() {
+    let Some(root) = test_rom_dir("cpu_interrupts_v2") else {
+        return;
+    };
+    let dir = root.join("cpu_interrupts_v2");
+    let roms = [
+        "cpu_interrupts.nes",
+        "rom_singles/1-cli_latency.nes",
+        "rom_singles/2-nmi_and_brk.nes",
+        "rom_singles/3-nmi_and_irq.nes",
+        "rom_singles/4-irq_and_dma.nes",
+        "rom_singles/5-branch_delays_irq.nes",
+    ];
+    let failures = roms
+        .iter()
+        .filter_map(|rom| run_rom(&dir.join(rom), |_| {}))
+        .collect();
+    assert_all_passed(failures);
+}
+
+#[test]
+fn test_mmc3_test() {
+    let Some(root) = test_rom_dir("mmc3_test") else {
+        return;
+    };
+    let dir = root.join("mmc3_test");
+    // 5-MMC3はSharp版、6-MMC6はNEC版（MMC6）のIRQの挙動を要求する
+    let roms: [(&str, bool); 6] = [
+        ("1-clocking.nes", false),
+        ("2-details.nes", false),
+        ("3-A12_clocking.nes", false),
+        ("4-scanline_timing.nes", false),
+        ("5-MMC3.nes", false),
+        ("6-MMC6.nes", true),
+    ];
+    let failures = roms
+        .iter()
+        .filter_map(|&(rom, nec)| {
+            run_rom(&dir.join(rom), |rom| {
+                if nec {
+                    use_nec_revision(rom);
+                }
+            })
+        })
+        .collect();
+    assert_all_passed(failures);
+}
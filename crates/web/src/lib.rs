@@ -6,13 +6,17 @@ use wasm_bindgen::prelude::*;
 use wasm_bindgen::Clamped;
 use web_sys::{CanvasRenderingContext2d, ImageData};
 
+use nes_core::controller::{Button, Controller};
+use nes_core::input::{ArkanoidPaddle, InputDevice, PowerPad, Zapper};
+use nes_core::rewind::RewindConfig;
 use nes_core::Nes;
-use nes_core::controller::Button;
 
 /// WebAssembly用のNESエミュレータラッパー
 #[wasm_bindgen]
 pub struct NesWeb {
     nes: Nes,
+    /// プレイヤーごとのキー割り当て（KeyboardEvent.key → ボタン）
+    keymaps: Vec<Vec<(String, Button)>>,
 }
 
 #[wasm_bindgen]
@@ -31,7 +35,10 @@ impl NesWeb {
 
         log::info!("NES Web initialized");
 
-        Self { nes: Nes::new() }
+        Self {
+            nes: Nes::new(),
+            keymaps: vec![default_keymap(PLAYER1_KEYS), default_keymap(PLAYER2_KEYS)],
+        }
     }
 
     /// ROMをロード
@@ -68,6 +75,11 @@ impl NesWeb {
         Ok(())
     }
 
+    /// 1スキャンライン8個のスプライト制限の有効/無効を切り替える
+    pub fn set_sprite_limit(&mut self, enabled: bool) {
+        self.nes.set_sprite_limit(enabled);
+    }
+
     /// CPU状態の取得（デバッグ用）
     pub fn get_cpu_state(&self) -> String {
         let cpu = self.nes.cpu_state();
@@ -101,36 +113,188 @@ impl NesWeb {
     /// オーディオサンプル数を取得
     pub fn get_audio_sample_count(&self) -> usize {
         // APU sample buffer is drained by get_audio_samples, return expected samples per frame
-        (44100.0 / 60.0) as usize
+        (self.nes.sample_rate() as f64 / 60.0) as usize
+    }
+
+    /// オーディオの出力サンプルレートを設定（AudioContext.sampleRateに合わせる）
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.nes.set_sample_rate(sample_rate);
+    }
+
+    // ========== 巻き戻し API ==========
+
+    /// 巻き戻しを有効化（`budget_mb`はステートを保持するメモリの上限、0で無効化）
+    pub fn enable_rewind(&mut self, budget_mb: usize) {
+        if budget_mb == 0 {
+            self.nes.disable_rewind();
+        } else {
+            self.nes.enable_rewind(RewindConfig {
+                memory_budget: budget_mb * 1024 * 1024,
+                ..RewindConfig::default()
+            });
+        }
+    }
+
+    /// `frames`フレーム巻き戻し、実際に戻ったフレーム数を返す
+    pub fn rewind(&mut self, frames: usize) -> Result<usize, JsValue> {
+        self.nes
+            .rewind(frames)
+            .map_err(|e| JsValue::from_str(&format!("Rewind error: {}", e)))
+    }
+
+    // ========== バッテリーセーブ API ==========
+
+    /// カートリッジにバッテリーバックアップされたPRG RAMがあるか
+    pub fn has_battery(&self) -> bool {
+        self.nes.battery_ram().is_some()
+    }
+
+    /// バッテリーバックアップされたPRG RAMを取得（.savファイルの内容）
+    pub fn battery_ram(&self) -> Option<Vec<u8>> {
+        self.nes.battery_ram()
+    }
+
+    /// .savファイルの内容をバッテリーバックアップされたPRG RAMに読み込む
+    pub fn load_battery_ram(&mut self, data: &[u8]) -> Result<(), JsValue> {
+        self.nes
+            .load_battery_ram(data)
+            .map_err(|e| JsValue::from_str(&format!("Failed to load battery save: {}", e)))
+    }
+
+    /// バッテリーRAMをlocalStorageに保存（バッテリーがなければfalse）
+    pub fn save_battery_to_storage(&self) -> Result<bool, JsValue> {
+        let (Some(key), Some(ram)) = (self.battery_storage_key(), self.nes.battery_ram()) else {
+            return Ok(false);
+        };
+        let hex: String = ram.iter().map(|b| format!("{:02x}", b)).collect();
+        local_storage()?.set_item(&key, &hex)?;
+        Ok(true)
+    }
+
+    /// localStorageからバッテリーRAMを復元（保存データがなければfalse）
+    pub fn load_battery_from_storage(&mut self) -> Result<bool, JsValue> {
+        if !self.has_battery() {
+            return Ok(false);
+        }
+        let Some(key) = self.battery_storage_key() else {
+            return Ok(false);
+        };
+        let Some(hex) = local_storage()?.get_item(&key)? else {
+            return Ok(false);
+        };
+        let data = (0..hex.len())
+            .step_by(2)
+            .map(|i| {
+                hex.get(i..i + 2)
+                    .and_then(|s| u8::from_str_radix(s, 16).ok())
+            })
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(|| JsValue::from_str("Corrupted battery save in storage"))?;
+        self.load_battery_ram(&data)?;
+        Ok(true)
+    }
+
+    /// ROMごとのlocalStorageキー（ROMのCRC32で区別する）
+    fn battery_storage_key(&self) -> Option<String> {
+        self.nes
+            .rom_checksum()
+            .map(|crc| format!("hacknes.sav.{:08x}", crc))
     }
 
     /// キーボード入力を処理（キーダウン）
-    /// キーコード: ArrowUp, ArrowDown, ArrowLeft, ArrowRight, a, s, d, f
+    /// キー割り当ては`set_key`で変更できる（デフォルトはP1: 矢印/a/s/d/f、P2: i/k/j/l/h/g/t/y）
     pub fn key_down(&mut self, key: &str) {
-        if let Some(button) = self.key_to_button(key) {
-            self.nes.cpu_state_mut().bus.controller.press(button);
+        for (player, button) in self.key_to_buttons(key) {
+            self.nes.press_button(player, button);
         }
     }
 
     /// キーボード入力を処理（キーアップ）
     pub fn key_up(&mut self, key: &str) {
-        if let Some(button) = self.key_to_button(key) {
-            self.nes.cpu_state_mut().bus.controller.release(button);
+        for (player, button) in self.key_to_buttons(key) {
+            self.nes.release_button(player, button);
+        }
+    }
+
+    /// プレイヤー (0-3) のボタンにキーを割り当てる
+    /// ボタン名: up, down, left, right, a, b, select, start
+    pub fn set_key(&mut self, player: usize, button: &str, key: &str) -> Result<(), JsValue> {
+        if player >= 4 {
+            return Err(JsValue::from_str("Player must be 0-3"));
+        }
+        let button = parse_button(button)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown button: {}", button)))?;
+        if self.keymaps.len() <= player {
+            self.keymaps.resize_with(player + 1, Vec::new);
+        }
+        let keymap = &mut self.keymaps[player];
+        keymap.retain(|(_, b)| *b != button);
+        keymap.push((key.to_string(), button));
+        Ok(())
+    }
+
+    /// プレイヤーのキー割り当てをすべて解除
+    pub fn clear_keymap(&mut self, player: usize) {
+        if let Some(keymap) = self.keymaps.get_mut(player) {
+            keymap.clear();
         }
     }
 
-    fn key_to_button(&self, key: &str) -> Option<Button> {
-        match key {
-            "ArrowUp" => Some(Button::Up),
-            "ArrowDown" => Some(Button::Down),
-            "ArrowLeft" => Some(Button::Left),
-            "ArrowRight" => Some(Button::Right),
-            "a" | "A" => Some(Button::A),
-            "s" | "S" => Some(Button::B),
-            "d" | "D" => Some(Button::Select),
-            "f" | "F" => Some(Button::Start),
-            _ => None,
+    /// Four Score（4人用アダプタ）の接続/取り外し
+    pub fn set_four_score(&mut self, enabled: bool) {
+        self.nes.set_four_score(enabled);
+    }
+
+    /// ポート2に接続するデバイスを選択
+    /// デバイス名: controller, zapper, arkanoid, powerpad
+    pub fn set_port2_device(&mut self, name: &str) -> Result<(), JsValue> {
+        let device: Box<dyn InputDevice> = match name {
+            "controller" => Box::new(Controller::new()),
+            "zapper" => Box::new(Zapper::new()),
+            "arkanoid" => Box::new(ArkanoidPaddle::new()),
+            "powerpad" => Box::new(PowerPad::new()),
+            _ => return Err(JsValue::from_str(&format!("Unknown device: {}", name))),
+        };
+        self.nes.connect_input(1, device);
+        Ok(())
+    }
+
+    /// Canvas上のポインタ位置（表示サイズ基準の座標）をポート2のデバイスに送る
+    pub fn pointer_move(&mut self, x: f64, y: f64, display_width: f64, display_height: f64) {
+        if display_width <= 0.0 || display_height <= 0.0 {
+            return;
         }
+        let x = (x * 256.0 / display_width).floor() as i32;
+        let y = (y * 240.0 / display_height).floor() as i32;
+        self.nes.set_pointer(1, x, y);
+    }
+
+    /// ポインタのボタンを押した（Zapperのトリガー/Arkanoidのファイア）
+    pub fn pointer_down(&mut self) {
+        self.nes.set_trigger(1, true);
+    }
+
+    /// ポインタのボタンを離した
+    pub fn pointer_up(&mut self) {
+        self.nes.set_trigger(1, false);
+    }
+
+    /// Power Padのパネル (0-11) の状態を設定
+    pub fn set_power_pad_panel(&mut self, panel: usize, pressed: bool) {
+        self.nes.set_switch(1, panel, pressed);
+    }
+
+    fn key_to_buttons(&self, key: &str) -> Vec<(usize, Button)> {
+        self.keymaps
+            .iter()
+            .enumerate()
+            .flat_map(|(player, keymap)| {
+                keymap
+                    .iter()
+                    .filter(move |(k, _)| k.eq_ignore_ascii_case(key))
+                    .map(move |(_, button)| (player, *button))
+            })
+            .collect()
     }
 
     // ========== メモリエディタ API ==========
@@ -284,6 +448,63 @@ impl NesWeb {
     }
 }
 
+impl Default for NesWeb {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// プレイヤー1のデフォルトのキー割り当て
+const PLAYER1_KEYS: [(&str, Button); 8] = [
+    ("ArrowUp", Button::Up),
+    ("ArrowDown", Button::Down),
+    ("ArrowLeft", Button::Left),
+    ("ArrowRight", Button::Right),
+    ("a", Button::A),
+    ("s", Button::B),
+    ("d", Button::Select),
+    ("f", Button::Start),
+];
+
+/// プレイヤー2のデフォルトのキー割り当て
+const PLAYER2_KEYS: [(&str, Button); 8] = [
+    ("i", Button::Up),
+    ("k", Button::Down),
+    ("j", Button::Left),
+    ("l", Button::Right),
+    ("h", Button::A),
+    ("g", Button::B),
+    ("t", Button::Select),
+    ("y", Button::Start),
+];
+
+fn default_keymap(keys: [(&str, Button); 8]) -> Vec<(String, Button)> {
+    keys.iter()
+        .map(|(key, button)| (key.to_string(), *button))
+        .collect()
+}
+
+fn parse_button(name: &str) -> Option<Button> {
+    match name.to_ascii_lowercase().as_str() {
+        "up" => Some(Button::Up),
+        "down" => Some(Button::Down),
+        "left" => Some(Button::Left),
+        "right" => Some(Button::Right),
+        "a" => Some(Button::A),
+        "b" => Some(Button::B),
+        "select" => Some(Button::Select),
+        "start" => Some(Button::Start),
+        _ => None,
+    }
+}
+
+fn local_storage() -> Result<web_sys::Storage, JsValue> {
+    web_sys::window()
+        .ok_or_else(|| JsValue::from_str("No window"))?
+        .local_storage()?
+        .ok_or_else(|| JsValue::from_str("localStorage is not available"))
+}
+
 /// JavaScriptのコンソールにログを出力（初期化）
 #[wasm_bindgen]
 pub fn init_logger() {